@@ -0,0 +1,78 @@
+//! How the reader copes with a very large book: opening it, showing the
+//! first chapter, searching it and drawing frames while scrolling.
+//!
+//! The book is generated on the first run (see `synthetic`), which takes a
+//! little while. Run with `cargo bench --bench large_book`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+mod synthetic;
+
+use catatau::search::SearchIndex;
+use catatau::{App, DisplayOptions, EpubReader};
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::backend::TestBackend;
+use std::hint::black_box;
+use synthetic::NEEDLE;
+
+fn opening(c: &mut Criterion) {
+    let path = synthetic::large_book();
+    let mut group = c.benchmark_group("open");
+    group.sample_size(10);
+    group.bench_function("book", |b| b.iter(|| EpubReader::new(&path).unwrap()));
+    group.bench_function("first chapter", |b| {
+        b.iter_batched(
+            || EpubReader::new(&path).unwrap(),
+            |epub| epub.get_chapter(0).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+fn searching(c: &mut Criterion) {
+    let epub = EpubReader::new(&synthetic::large_book()).unwrap();
+    let index = SearchIndex::build(&epub);
+    let mut group = c.benchmark_group("search");
+    group.sample_size(10);
+    group.bench_function("every chapter", |b| {
+        b.iter(|| {
+            let results = epub.search(black_box(NEEDLE));
+            assert_eq!(results.len(), 1);
+        })
+    });
+    group.bench_function("indexed", |b| {
+        b.iter(|| {
+            let chapters = index.candidates(black_box(NEEDLE)).unwrap();
+            let results = epub.search_chapters(NEEDLE, chapters);
+            assert_eq!(results.len(), 1);
+        })
+    });
+    group.finish();
+}
+
+fn drawing(c: &mut Criterion) {
+    let epub = EpubReader::new(&synthetic::large_book()).unwrap();
+    let backend = TestBackend::new(120, 40);
+    let mut app = App::with_backend(epub, DisplayOptions::default(), backend).unwrap();
+    let mut group = c.benchmark_group("draw");
+    group.bench_function("frame", |b| b.iter(|| app.render_to_buffer().unwrap()));
+    // A line down each frame, going back to the top at the end of the
+    // chapter so every frame has a line not drawn before
+    group.bench_function("scrolling frame", |b| {
+        b.iter(|| {
+            let before = app.scroll_offset();
+            app.handle_key(KeyEvent::from(KeyCode::Char('j'))).unwrap();
+            if app.scroll_offset() == before {
+                app.handle_key(KeyEvent::from(KeyCode::Char('g'))).unwrap();
+                app.handle_key(KeyEvent::from(KeyCode::Char('g'))).unwrap();
+            }
+            app.render_to_buffer().unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, opening, searching, drawing);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+//! Books made up for benchmarking: as many chapters of generated prose as
+//! asked for, the same every time, with one rare word near the end for
+//! searches to find.
+
+use crate::common::EpubBuilder;
+use std::path::{Path, PathBuf};
+
+/// A word that appears once, in the last chapter.
+pub const NEEDLE: &str = "quincunx";
+
+/// How big a book to make.
+#[derive(Debug, Clone, Copy)]
+pub struct Shape {
+    pub chapters: usize,
+    /// Roughly how much text each chapter has, in bytes.
+    pub chapter_bytes: usize,
+}
+
+/// Ten thousand chapters and about 50MB of text.
+pub const LARGE: Shape = Shape {
+    chapters: 10_000,
+    chapter_bytes: 50 * 1024 * 1024 / 10_000,
+};
+
+const WORDS: &[&str] = &[
+    "the", "sea", "was", "calm", "and", "grey", "under", "a", "low", "sky", "ship", "sailed",
+    "north", "through", "long", "night", "while", "crew", "slept", "below", "deck", "captain",
+    "watched", "stars", "turn", "slowly", "over", "harbour", "lights", "far", "behind", "them",
+    "wind", "rose", "before", "dawn", "carrying", "salt", "smell", "of", "distant", "rain",
+    "letters", "from", "home", "lay", "unopened", "on", "table", "beside", "charts", "lamp",
+];
+
+/// The large book, made the first time it's asked for and kept in the
+/// target directory after that, since writing it takes a while.
+pub fn large_book() -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("synthetic-large.epub");
+    if !path.is_file() {
+        let partial = path.with_extension("epub.partial");
+        write_epub(&partial, LARGE);
+        std::fs::rename(&partial, &path).expect("Failed to move the synthetic book into place");
+    }
+    path
+}
+
+/// Write a book of the given shape to `path`.
+pub fn write_epub(path: &Path, shape: Shape) {
+    let mut book = EpubBuilder::new("Synthetic")
+        .opf_path("OEBPS/content.opf")
+        .metadata("<dc:creator>Benchmark</dc:creator>");
+    let mut words = Words(0x2545_f491_4f6c_dd1d);
+    for index in 0..shape.chapters {
+        let last = index + 1 == shape.chapters;
+        let html = chapter(index, shape.chapter_bytes, last, &mut words);
+        book = book.chapter(&format!("c{index}.xhtml"), &html);
+    }
+    book.write(path);
+}
+
+// A chapter with a heading and paragraphs of a few sentences each, the
+// last of them ending with the needle in the book's last chapter
+fn chapter(index: usize, bytes: usize, last: bool, words: &mut Words) -> String {
+    let mut html = format!(
+        "<html><head><title>Chapter {0}</title></head><body><h1>Chapter {0}</h1>",
+        index + 1
+    );
+    let start = html.len();
+    while html.len() - start < bytes {
+        html.push_str("<p>");
+        for _ in 0..4 {
+            let sentence: Vec<&str> = (0..12).map(|_| words.next()).collect();
+            html.push_str(&sentence.join(" "));
+            html.push_str(". ");
+        }
+        html.push_str("</p>");
+    }
+    if last {
+        html.push_str(&format!("<p>The last word is {NEEDLE}.</p>"));
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+// Words picked with xorshift, so every book of a shape is the same
+struct Words(u64);
+
+impl Words {
+    fn next(&mut self) -> &'static str {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        WORDS[(self.0 % WORDS.len() as u64) as usize]
+    }
+}
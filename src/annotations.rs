@@ -0,0 +1,273 @@
+use crate::epub::EpubReader;
+use crate::progress::Highlight;
+use crate::search;
+use serde_json::{Value, json};
+use std::path::Path;
+use std::str::FromStr;
+
+/// What highlights and notes are written out as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Markdown,
+    /// Readwise's CSV import layout.
+    Csv,
+    /// The body of a request to Readwise's highlights API.
+    Json,
+    /// Tab-separated flashcards for Anki to import.
+    Anki,
+}
+
+impl Format {
+    /// The format a file's extension calls for, Markdown unless it's
+    /// `.csv`, `.json` or `.tsv`.
+    pub fn for_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "csv" | "readwise" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "anki" | "tsv" => Ok(Self::Anki),
+            other => Err(format!(
+                "unknown annotation format '{other}' (expected markdown, csv, json or anki)"
+            )),
+        }
+    }
+}
+
+/// A book's highlights and notes written out as `format`.
+pub fn export(epub: &EpubReader, highlights: &[Highlight], format: Format) -> String {
+    match format {
+        Format::Markdown => markdown(epub, highlights),
+        Format::Csv => readwise_csv(epub, highlights),
+        Format::Json => format!("{:#}\n", readwise_json(epub, highlights)),
+        Format::Anki => anki_cards(epub, highlights),
+    }
+}
+
+/// A book's highlights and notes as Markdown, grouped under their chapters:
+/// each passage is quoted, followed by its note and where it is.
+pub fn markdown(epub: &EpubReader, highlights: &[Highlight]) -> String {
+    let mut text = format!("# {}\n\n{}\n", epub.title, epub.author);
+    let mut chapter = None;
+    let mut chapter_length = 0;
+    for highlight in highlights {
+        if chapter != Some(highlight.chapter) {
+            chapter = Some(highlight.chapter);
+            chapter_length = epub
+                .get_chapter(highlight.chapter)
+                .map(|chapter| chapter.content.chars().filter(|c| !c.is_whitespace()).count())
+                .unwrap_or(0);
+            text.push_str(&format!("\n## {}\n", epub.chapter_title(highlight.chapter)));
+        }
+
+        text.push_str(&format!("\n> {}\n", highlight.excerpt));
+        if let Some(note) = &highlight.note {
+            text.push_str(&format!("\n{note}\n"));
+        }
+        let percent = (highlight.start * 100).checked_div(chapter_length).unwrap_or(0);
+        text.push_str(&format!(
+            "\n*Chapter {}, {}% of the way through*\n",
+            highlight.chapter + 1,
+            percent.min(100)
+        ));
+    }
+    text
+}
+
+/// A book's highlights as a CSV file for Readwise to import, one row each
+/// with its note, numbered in the order they come in the book.
+pub fn readwise_csv(epub: &EpubReader, highlights: &[Highlight]) -> String {
+    let mut text = String::from("Highlight,Title,Author,URL,Note,Location,Date\n");
+    for (index, highlight) in highlights.iter().enumerate() {
+        let fields = [
+            highlight.excerpt.as_str(),
+            &epub.title,
+            &epub.author,
+            "",
+            highlight.note.as_deref().unwrap_or_default(),
+            &(index + 1).to_string(),
+            "",
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        text.push_str(&fields.join(","));
+        text.push('\n');
+    }
+    text
+}
+
+/// A book's highlights as Readwise's highlights API takes them, located by
+/// their order in the book.
+pub fn readwise_json(epub: &EpubReader, highlights: &[Highlight]) -> Value {
+    let highlights: Vec<Value> = highlights
+        .iter()
+        .enumerate()
+        .map(|(index, highlight)| {
+            let mut json = json!({
+                "text": highlight.excerpt,
+                "title": epub.title,
+                "author": epub.author,
+                "source_type": "catatau",
+                "category": "books",
+                "location": index + 1,
+                "location_type": "order",
+            });
+            if let Some(note) = &highlight.note {
+                json["note"] = json!(note);
+            }
+            json
+        })
+        .collect();
+    json!({ "highlights": highlights })
+}
+
+/// Flashcards, as a tab-separated file Anki imports, from the highlights
+/// whose notes are tagged `#vocab` or `#qa`. A vocabulary note reads
+/// `word: meaning`, and its card asks for the word and answers with the
+/// meaning; a Q&A card asks the note and answers with the passage. Either
+/// way the back has the sentence the passage comes from, for context.
+pub fn anki_cards(epub: &EpubReader, highlights: &[Highlight]) -> String {
+    let mut text = String::from("#separator:tab\n#html:true\n#tags column:3\n");
+    let mut chapter = None;
+    let mut content = String::new();
+    for highlight in highlights {
+        let Some(note) = &highlight.note else {
+            continue;
+        };
+        let words: Vec<&str> = note.split_whitespace().collect();
+        let (kind, tag) = if words.contains(&"#vocab") {
+            (CardKind::Vocabulary, "vocab")
+        } else if words.contains(&"#qa") {
+            (CardKind::Question, "qa")
+        } else {
+            continue;
+        };
+        let note: Vec<&str> = words.into_iter().filter(|word| !word.starts_with('#')).collect();
+        let note = note.join(" ");
+
+        if chapter != Some(highlight.chapter) {
+            chapter = Some(highlight.chapter);
+            content = epub
+                .get_chapter(highlight.chapter)
+                .map(|chapter| chapter.content.clone())
+                .unwrap_or_default();
+        }
+        let mut context = sentence_around(&content, highlight.start..highlight.end);
+        if context.is_empty() {
+            context = highlight.excerpt.clone();
+        }
+
+        let (front, back) = match kind {
+            CardKind::Vocabulary => {
+                let (word, meaning) = note
+                    .split_once(':')
+                    .map_or((note.as_str(), ""), |(word, meaning)| (word.trim(), meaning.trim()));
+                let context = emphasize(&context, word);
+                let back = match meaning {
+                    "" => context,
+                    meaning => format!("{}<br><br>{context}", html_escape(meaning)),
+                };
+                (html_escape(word), back)
+            }
+            CardKind::Question => (html_escape(&note), html_escape(&context)),
+        };
+        if front.is_empty() {
+            continue;
+        }
+        let source = format!(
+            "<i>{}, {}</i>",
+            html_escape(&epub.title),
+            html_escape(&epub.chapter_title(highlight.chapter))
+        );
+        text.push_str(&format!(
+            "{}\t{}<br><br>{}\tcatatau {tag}\n",
+            tsv_field(&front),
+            tsv_field(&back),
+            tsv_field(&source)
+        ));
+    }
+    text
+}
+
+enum CardKind {
+    Vocabulary,
+    Question,
+}
+
+// The sentence, or sentences, holding the non-whitespace characters
+// numbered `characters` in `text`, on one line
+fn sentence_around(text: &str, characters: std::ops::Range<usize>) -> String {
+    let positions: Vec<(usize, char)> =
+        text.char_indices().filter(|(_, c)| !c.is_whitespace()).collect();
+    let Some(&(start, _)) = positions.get(characters.start) else {
+        return String::new();
+    };
+    let end = positions[..characters.end.min(positions.len())]
+        .last()
+        .map_or(start, |&(byte, c)| byte + c.len_utf8())
+        .max(start);
+
+    let ends_sentence = |c: char| matches!(c, '.' | '!' | '?' | '。' | '！' | '？');
+    let before = &text[..start];
+    let sentence_start = before
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| ends_sentence(c))
+        .map_or(0, |(byte, c)| byte + c.len_utf8());
+    let sentence_start = before[sentence_start..]
+        .rfind("\n\n")
+        .map_or(sentence_start, |blank| sentence_start + blank + 2);
+    let after = &text[end..];
+    let mut sentence_end = after
+        .char_indices()
+        .find(|&(_, c)| ends_sentence(c))
+        .map_or(after.len(), |(byte, c)| byte + c.len_utf8());
+    if let Some(blank) = after[..sentence_end].find("\n\n") {
+        sentence_end = blank;
+    }
+    text[sentence_start..end + sentence_end].split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// The text as HTML with each appearance of `word` in bold
+fn emphasize(text: &str, word: &str) -> String {
+    if word.is_empty() {
+        return html_escape(text);
+    }
+    let mut html = String::new();
+    let mut end = 0;
+    for found in search::find_matches(text, word) {
+        html.push_str(&html_escape(&text[end..found.start]));
+        html.push_str(&format!("<b>{}</b>", html_escape(&text[found.clone()])));
+        end = found.end;
+    }
+    html.push_str(&html_escape(&text[end..]));
+    html
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Tabs and line breaks would split the card, so they become spaces
+fn tsv_field(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+// A field quoted if it holds anything that would end it early
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
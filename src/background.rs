@@ -0,0 +1,90 @@
+/// Ask the terminal for its background colour with an OSC 11 query. Returns
+/// `None` when output isn't a terminal or the terminal doesn't answer.
+#[cfg(unix)]
+pub fn query() -> Option<(u8, u8, u8)> {
+    use crate::constants::BACKGROUND_QUERY_TIMEOUT_MS;
+    use crossterm::terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled};
+    use std::io::{IsTerminal, Read, Write};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let mut tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let mut reader = tty.try_clone().ok()?;
+
+    let was_raw = is_raw_mode_enabled().unwrap_or(false);
+    enable_raw_mode().ok()?;
+
+    // Every terminal answers the device attributes query that follows the
+    // colour query, so the reader stops there even when the first is ignored
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut response = Vec::new();
+        let mut byte = [0];
+        while let Ok(1) = reader.read(&mut byte) {
+            response.push(byte[0]);
+            if ends_with_device_attributes(&response) {
+                break;
+            }
+        }
+        let _ = sender.send(response);
+    });
+    let response = tty
+        .write_all(b"\x1b]11;?\x07\x1b[c")
+        .and_then(|_| tty.flush())
+        .ok()
+        .and_then(|_| {
+            receiver.recv_timeout(Duration::from_millis(BACKGROUND_QUERY_TIMEOUT_MS)).ok()
+        });
+
+    if !was_raw {
+        let _ = disable_raw_mode();
+    }
+    parse_response(&String::from_utf8_lossy(&response?))
+}
+
+#[cfg(not(unix))]
+pub fn query() -> Option<(u8, u8, u8)> {
+    None
+}
+
+#[cfg(unix)]
+fn ends_with_device_attributes(response: &[u8]) -> bool {
+    response.ends_with(b"c")
+        && response
+            .windows(3)
+            .rposition(|window| window == b"\x1b[?")
+            .is_some_and(|start| {
+                response[start + 3..response.len() - 1]
+                    .iter()
+                    .all(|&b| b.is_ascii_digit() || b == b';')
+            })
+}
+
+/// Pull the colour out of an OSC 11 reply such as
+/// `ESC ]11;rgb:ffff/ffff/dddd BEL`. Each channel has one to four hex digits.
+pub fn parse_response(response: &str) -> Option<(u8, u8, u8)> {
+    let start = response.find("]11;rgb:")? + "]11;rgb:".len();
+    let end = response[start..]
+        .find(|c: char| !(c.is_ascii_hexdigit() || c == '/'))
+        .map_or(response.len(), |end| start + end);
+
+    let mut channels = response[start..end].split('/').map(|channel| {
+        let digits = channel.len();
+        if !(1..=4).contains(&digits) {
+            return None;
+        }
+        let value = u32::from_str_radix(channel, 16).ok()?;
+        let max = (1u32 << (4 * digits)) - 1;
+        Some((value * 255 / max) as u8)
+    });
+    let color = (channels.next()??, channels.next()??, channels.next()??);
+    channels.next().is_none().then_some(color)
+}
+
+/// Whether a background colour is light enough to want dark text.
+pub fn is_light((r, g, b): (u8, u8, u8)) -> bool {
+    299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b) > 500 * 255
+}
@@ -0,0 +1,96 @@
+use ratatui::{
+    layout::Alignment,
+    style::Style,
+    text::{Line, Span},
+};
+use unicode_bidi::{BidiClass, BidiInfo, Direction, Level, bidi_class, get_base_direction};
+
+// Primary subtags of languages normally written right to left
+const RTL_LANGUAGES: [&str; 13] = [
+    "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ps", "sd", "syr", "ug", "ur", "yi",
+];
+
+/// Whether a BCP 47 language tag (`he`, `ar-EG`, `az-Arab`…) names a
+/// right-to-left language or script.
+pub fn is_rtl_language(tag: &str) -> bool {
+    let mut subtags = tag.split(['-', '_']).map(|subtag| subtag.to_ascii_lowercase());
+    let primary = subtags.next().unwrap_or_default();
+    RTL_LANGUAGES.contains(&primary.as_str())
+        || subtags.any(|subtag| matches!(subtag.as_str(), "arab" | "hebr" | "syrc" | "thaa"))
+}
+
+/// Whether the text has any right-to-left characters, and so needs
+/// reordering before it's drawn.
+pub fn has_rtl(text: &str) -> bool {
+    text.chars()
+        .any(|c| matches!(bidi_class(c), BidiClass::R | BidiClass::AL))
+}
+
+/// Direction of a paragraph given its lines: right to left if the first
+/// strongly directional character is, `None` if there isn't one.
+pub fn paragraph_is_rtl<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<bool> {
+    lines.into_iter().find_map(|line| match get_base_direction(line) {
+        Direction::Ltr => Some(false),
+        Direction::Rtl => Some(true),
+        Direction::Mixed => None,
+    })
+}
+
+/// Reorder one styled line of logically ordered text into display order,
+/// keeping each character's style. Lines of right-to-left paragraphs are
+/// right-aligned.
+pub fn reorder_line(line: Line<'static>, rtl: bool) -> Line<'static> {
+    let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+    let styles: Vec<Style> = line
+        .spans
+        .iter()
+        .flat_map(|span| std::iter::repeat_n(span.style, span.content.len()))
+        .collect();
+
+    let level = if rtl { Level::rtl() } else { Level::ltr() };
+    let info = BidiInfo::new(&text, Some(level));
+    let mut pieces: Vec<(Style, String)> = Vec::new();
+    if let Some(paragraph) = info.paragraphs.first() {
+        let (levels, runs) = info.visual_runs(paragraph, paragraph.range.clone());
+        for run in runs {
+            let reversed = levels[run.start].is_rtl();
+            let mut chars: Vec<(usize, char)> = text[run.clone()]
+                .char_indices()
+                .map(|(offset, c)| (run.start + offset, if reversed { mirrored(c) } else { c }))
+                .collect();
+            if reversed {
+                chars.reverse();
+            }
+            for (index, c) in chars {
+                match pieces.last_mut() {
+                    Some((style, piece)) if *style == styles[index] => piece.push(c),
+                    _ => pieces.push((styles[index], c.to_string())),
+                }
+            }
+        }
+    }
+
+    let spans: Vec<Span> = pieces
+        .into_iter()
+        .map(|(style, piece)| Span::styled(piece, style))
+        .collect();
+    let alignment = if rtl { Alignment::Right } else { Alignment::Left };
+    Line::from(spans).style(line.style).alignment(alignment)
+}
+
+// Terminals draw brackets as they are, so swap them inside reversed runs
+fn mirrored(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        other => other,
+    }
+}
@@ -0,0 +1,127 @@
+use crate::error::CalibreError;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use std::path::{Path, PathBuf};
+
+const METADATA_DB: &str = "metadata.db";
+
+/// An EPUB kept in a Calibre library.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibreBook {
+    pub id: i64,
+    pub title: String,
+    /// Its authors, joined with " & " as Calibre shows them.
+    pub authors: String,
+    pub path: PathBuf,
+}
+
+/// Where the Calibre library is when none is given: `$CALIBRE_LIBRARY`, or
+/// Calibre's own default of `~/Calibre Library`.
+pub fn library_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("CALIBRE_LIBRARY").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    Some(PathBuf::from(std::env::var_os("HOME")?).join("Calibre Library"))
+}
+
+/// The book `wanted` names in the library at `library`: its Calibre id, or
+/// "Author/Title", or just a title, matched ignoring case against part of
+/// each. A title matched exactly wins over ones only containing it.
+pub fn find_book(library: &Path, wanted: &str) -> Result<CalibreBook, CalibreError> {
+    let db = Connection::open_with_flags(
+        library.join(METADATA_DB),
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    let mut statement = db.prepare(
+        "SELECT books.id, books.title, books.path, data.name,
+                (SELECT group_concat(authors.name, ' & ')
+                 FROM books_authors_link
+                 JOIN authors ON authors.id = books_authors_link.author
+                 WHERE books_authors_link.book = books.id)
+         FROM books JOIN data ON data.book = books.id AND data.format = 'EPUB'
+         ORDER BY books.id",
+    )?;
+    let books = statement
+        .query_map([], |row| {
+            let dir: String = row.get(2)?;
+            let name: String = row.get(3)?;
+            Ok(CalibreBook {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                authors: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                path: library.join(dir).join(format!("{name}.epub")),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut found: Vec<CalibreBook> = match wanted.trim().parse::<i64>() {
+        Ok(id) => books.into_iter().filter(|book| book.id == id).collect(),
+        Err(_) => {
+            let (author, title) = wanted.split_once('/').unwrap_or(("", wanted));
+            let (author, title) = (author.trim().to_lowercase(), title.trim().to_lowercase());
+            books
+                .into_iter()
+                .filter(|book| {
+                    book.authors.to_lowercase().contains(&author)
+                        && book.title.to_lowercase().contains(&title)
+                })
+                .collect()
+        }
+    };
+    if found.len() > 1 {
+        let title = wanted.rsplit('/').next().unwrap_or(wanted).trim();
+        let exact: Vec<CalibreBook> = found
+            .iter()
+            .filter(|book| book.title.to_lowercase() == title.to_lowercase())
+            .cloned()
+            .collect();
+        if !exact.is_empty() {
+            found = exact;
+        }
+    }
+    match found.len() {
+        0 => Err(CalibreError::BookNotFound(wanted.to_string())),
+        1 => Ok(found.remove(0)),
+        _ => Err(CalibreError::AmbiguousBook(
+            found
+                .iter()
+                .map(|book| format!("{} by {} (id {})", book.title, book.authors, book.id))
+                .collect(),
+        )),
+    }
+}
+
+/// Set the book's value in the library's numeric custom column `label`
+/// (without its `#`) to how far through it the reader is, as a percentage,
+/// the way Calibre's reading progress plugins do.
+pub fn save_progress(
+    library: &Path,
+    book_id: i64,
+    label: &str,
+    percent: f64,
+) -> Result<(), CalibreError> {
+    let label = label.trim_start_matches('#');
+    let db = Connection::open(library.join(METADATA_DB))?;
+    let (column, datatype): (i64, String) = db
+        .query_row(
+            "SELECT id, datatype FROM custom_columns WHERE label = ?1",
+            params![label],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| CalibreError::ColumnNotFound(label.to_string()))?;
+    let sql = format!(
+        "INSERT INTO custom_column_{column} (book, value) VALUES (?1, ?2)
+         ON CONFLICT(book) DO UPDATE SET value = excluded.value"
+    );
+    match datatype.as_str() {
+        "int" => db.execute(&sql, params![book_id, percent.round() as i64])?,
+        "float" => db.execute(&sql, params![book_id, percent])?,
+        _ => {
+            return Err(CalibreError::UnsupportedColumn {
+                label: label.to_string(),
+                datatype,
+            });
+        }
+    };
+    Ok(())
+}
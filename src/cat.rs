@@ -0,0 +1,90 @@
+use crate::epub::EpubReader;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::str::FromStr;
+use tracing::warn;
+
+/// Which chapters to print, counting from one with both ends included:
+/// `3`, `3..7`, `3..` or `..7`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChapterRange {
+    pub first: Option<usize>,
+    pub last: Option<usize>,
+}
+
+impl FromStr for ChapterRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let number = |text: &str| match text.trim() {
+            "" => Ok(None),
+            text => match text.parse::<usize>() {
+                Ok(0) | Err(_) => Err(format!("not a chapter number: {text}")),
+                Ok(number) => Ok(Some(number)),
+            },
+        };
+        let range = match value.split_once("..") {
+            Some((first, last)) => Self {
+                first: number(first)?,
+                last: number(last)?,
+            },
+            None => {
+                let chapter = number(value)?.ok_or("no chapter given")?;
+                Self {
+                    first: Some(chapter),
+                    last: Some(chapter),
+                }
+            }
+        };
+        if let (Some(first), Some(last)) = (range.first, range.last)
+            && first > last
+        {
+            return Err(format!("chapter {first} comes after {last}"));
+        }
+        Ok(range)
+    }
+}
+
+impl ChapterRange {
+    /// The indices of the chapters in a book of `count` of them.
+    pub fn indices(&self, count: usize) -> Result<Range<usize>, String> {
+        let first = self.first.unwrap_or(1);
+        let last = self.last.unwrap_or(count);
+        if first > count || last > count {
+            return Err(format!("No chapter {}: the book has {count}", first.max(last)));
+        }
+        Ok(first - 1..last)
+    }
+}
+
+/// Write the text of `chapters` as the reader shows it, wrapped to the
+/// book's text width, with a blank line between chapters. Chapters that
+/// can't be read are left out with a warning.
+pub fn write_text(
+    epub: &EpubReader,
+    chapters: Range<usize>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut first = true;
+    for index in chapters {
+        let chapter = match epub.read_chapter_uncached(index) {
+            Ok(chapter) => chapter,
+            Err(e) => {
+                warn!("Could not read chapter {}: {}", index + 1, e);
+                continue;
+            }
+        };
+        let text = chapter.content.trim_matches('\n');
+        if text.trim().is_empty() {
+            continue;
+        }
+        if !first {
+            writeln!(out)?;
+        }
+        first = false;
+        for line in text.lines() {
+            writeln!(out, "{}", line.trim_end())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,385 @@
+use crate::{
+    epub::{Archive, ArchiveIndex, EpubReader, EpubReaderOptions, OpfData, open_archive},
+    error::EpubError,
+};
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+use zip::CompressionMethod;
+
+const EPUB_MIMETYPE: &str = "application/epub+zip";
+const XHTML_MEDIA_TYPE: &str = "application/xhtml+xml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckIssue {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub location: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CheckReport {
+    pub path: PathBuf,
+    pub issues: Vec<CheckIssue>,
+}
+
+impl CheckReport {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            issues: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, code: &'static str, message: String, location: Option<&str>) {
+        self.push(Severity::Error, code, message, location);
+    }
+
+    fn warning(&mut self, code: &'static str, message: String, location: Option<&str>) {
+        self.push(Severity::Warning, code, message, location);
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        code: &'static str,
+        message: String,
+        location: Option<&str>,
+    ) {
+        self.issues.push(CheckIssue {
+            severity,
+            code,
+            message,
+            location: location.map(str::to_string),
+        });
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "file": self.path.to_string_lossy(),
+            "valid": !self.has_errors(),
+            "errors": self.error_count(),
+            "warnings": self.warning_count(),
+            "issues": self.issues.iter().map(|issue| json!({
+                "severity": issue.severity.as_str(),
+                "code": issue.code,
+                "message": issue.message,
+                "location": issue.location,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Validate an EPUB beyond what opening it requires: container layout,
+/// manifest and spine consistency, internal links, and whether every chapter
+/// actually loads under the given limits.
+pub fn check_epub(path: &Path, options: &EpubReaderOptions) -> CheckReport {
+    let mut report = CheckReport::new(path);
+
+    let mut archive = match File::open(path)
+        .map_err(EpubError::from)
+        .and_then(open_archive)
+    {
+        Ok(archive) => archive,
+        Err(e) => {
+            report.error("unreadable-archive", e.to_string(), None);
+            return report;
+        }
+    };
+    let archive_index = ArchiveIndex::new(&archive);
+
+    check_mimetype(&mut archive, &mut report);
+    check_compression_ratios(&mut archive, options, &mut report);
+
+    let opf_path = match EpubReader::find_opf_path(&mut archive) {
+        Ok(opf_path) => opf_path,
+        Err(e) => {
+            report.error("missing-package", e.to_string(), Some("META-INF/container.xml"));
+            return report;
+        }
+    };
+    match EpubReader::parse_opf(&mut archive, &opf_path) {
+        Ok(opf_data) => {
+            check_manifest(&archive_index, &opf_data, &mut report);
+            check_internal_links(&mut archive, &archive_index, &opf_data, &mut report);
+        }
+        Err(e) => {
+            report.error("invalid-package", e.to_string(), Some(&opf_path));
+            return report;
+        }
+    }
+
+    check_chapters(path, options, &mut report);
+
+    report
+}
+
+fn check_mimetype(archive: &mut Archive, report: &mut CheckReport) {
+    if archive.name_for_index(0) != Some("mimetype") {
+        report.warning(
+            "mimetype-not-first",
+            "the mimetype file should be the first entry in the archive".to_string(),
+            Some("mimetype"),
+        );
+    }
+
+    let mut file = match archive.by_name("mimetype") {
+        Ok(file) => file,
+        Err(_) => {
+            report.error(
+                "missing-mimetype",
+                "archive has no mimetype file".to_string(),
+                None,
+            );
+            return;
+        }
+    };
+
+    if file.compression() != CompressionMethod::Stored {
+        report.warning(
+            "compressed-mimetype",
+            "the mimetype file should be stored uncompressed".to_string(),
+            Some("mimetype"),
+        );
+    }
+
+    let mut content = String::new();
+    if file.read_to_string(&mut content).is_err() || content.trim() != EPUB_MIMETYPE {
+        report.error(
+            "bad-mimetype",
+            format!("expected \"{}\", found \"{}\"", EPUB_MIMETYPE, content.trim()),
+            Some("mimetype"),
+        );
+    }
+}
+
+fn check_compression_ratios(
+    archive: &mut Archive,
+    options: &EpubReaderOptions,
+    report: &mut CheckReport,
+) {
+    for i in 0..archive.len() {
+        let Ok(file) = archive.by_index_raw(i) else {
+            continue;
+        };
+        let Some(ratio) = file.size().checked_div(file.compressed_size()) else {
+            continue;
+        };
+        if ratio as usize > options.max_decompressed_ratio {
+            report.error(
+                "decompression-bomb",
+                format!(
+                    "{}x compression ratio exceeds the limit of {}x",
+                    ratio, options.max_decompressed_ratio
+                ),
+                Some(file.name()),
+            );
+        }
+    }
+}
+
+fn check_manifest(archive_index: &ArchiveIndex, opf_data: &OpfData, report: &mut CheckReport) {
+    for item in &opf_data.manifest {
+        if resolve_exact(archive_index, &item.href, &opf_data.opf_path).is_none() {
+            let message = format!("manifest item \"{}\" is not in the archive", item.id);
+            report.error(
+                "missing-manifest-item",
+                near_miss(archive_index, &item.href, &opf_data.opf_path, message),
+                Some(&item.href),
+            );
+        }
+    }
+
+    let manifest_ids: HashSet<&str> = opf_data
+        .manifest
+        .iter()
+        .map(|item| item.id.as_str())
+        .collect();
+    for idref in &opf_data.spine_ids {
+        if !manifest_ids.contains(idref.as_str()) {
+            report.error(
+                "missing-spine-item",
+                format!("spine references unknown manifest id \"{}\"", idref),
+                Some(&opf_data.opf_path),
+            );
+        }
+    }
+}
+
+fn check_internal_links(
+    archive: &mut Archive,
+    archive_index: &ArchiveIndex,
+    opf_data: &OpfData,
+    report: &mut CheckReport,
+) {
+    let link_re = Regex::new(r#"(?i)\b(?:href|src)\s*=\s*["']([^"']+)["']"#).unwrap();
+    let id_re = Regex::new(r#"\bid\s*=\s*["']([^"']+)["']"#).unwrap();
+    let mut anchors: HashMap<String, HashSet<String>> = HashMap::new();
+
+    let documents: Vec<String> = opf_data
+        .manifest
+        .iter()
+        .filter(|item| item.media_type == XHTML_MEDIA_TYPE)
+        .filter_map(|item| resolve_exact(archive_index, &item.href, &opf_data.opf_path))
+        .collect();
+
+    for document in &documents {
+        let Ok(content) = EpubReader::read_archive_entry(archive, document) else {
+            continue;
+        };
+
+        for captures in link_re.captures_iter(&content) {
+            let link = &captures[1];
+            if EpubReader::is_external_link(link) {
+                continue;
+            }
+
+            let (target_href, fragment) = match link.split_once('#') {
+                Some((href, fragment)) => (href, Some(fragment)),
+                None => (link, None),
+            };
+
+            let target = if target_href.is_empty() {
+                document.clone()
+            } else {
+                match resolve_exact(archive_index, target_href, document) {
+                    Some(target) => target,
+                    None => {
+                        let message = format!("link target \"{}\" is not in the archive", link);
+                        report.error(
+                            "broken-link",
+                            near_miss(archive_index, target_href, document, message),
+                            Some(document),
+                        );
+                        continue;
+                    }
+                }
+            };
+
+            let Some(fragment) = fragment.filter(|f| !f.is_empty()) else {
+                continue;
+            };
+            let fragment = percent_decode_str(fragment).decode_utf8_lossy();
+            if !anchors.contains_key(&target) {
+                let ids = EpubReader::read_archive_entry(archive, &target)
+                    .map(|html| {
+                        id_re
+                            .captures_iter(&html)
+                            .map(|c| c[1].to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                anchors.insert(target.clone(), ids);
+            }
+            if !anchors[&target].contains(fragment.as_ref()) {
+                report.warning(
+                    "missing-anchor",
+                    format!("link target \"{}\" has no element with that id", link),
+                    Some(document),
+                );
+            }
+        }
+    }
+}
+
+// The archive entry `href` names, read as a URL relative to the document
+// `base`: without its query or fragment, percent-decoded, with `.` and `..`
+// segments applied and matching an entry's name exactly. The reader forgives
+// hrefs that only match ignoring case or with folders left out, but a book
+// relying on that won't open elsewhere.
+fn resolve_exact(archive_index: &ArchiveIndex, href: &str, base: &str) -> Option<String> {
+    let path = href.split(['#', '?']).next().unwrap_or(href);
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let mut segments: Vec<&str> = match (path.starts_with('/'), base.rsplit_once('/')) {
+        (false, Some((dir, _))) => dir.split('/').collect(),
+        _ => Vec::new(),
+    };
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let resolved = segments.join("/");
+    archive_index.contains(&resolved).then_some(resolved)
+}
+
+// A message about a missing file, naming the entry the reader would have
+// settled for instead if there is one
+fn near_miss(archive_index: &ArchiveIndex, href: &str, base: &str, message: String) -> String {
+    match EpubReader::resolve_archive_path(archive_index, href, base) {
+        Some(found) => format!("{message} (only \"{found}\", which differs in case or folder)"),
+        None => message,
+    }
+}
+
+fn check_chapters(path: &Path, options: &EpubReaderOptions, report: &mut CheckReport) {
+    let epub = match EpubReader::with_options(path, options.clone().eager_scan(true)) {
+        Ok(epub) => epub,
+        Err(e) => {
+            report.error("open-failed", e.to_string(), None);
+            return;
+        }
+    };
+
+    if (0..epub.chapter_count()).all(|index| epub.is_skippable(index)) {
+        report.error(
+            "no-readable-chapters",
+            "no spine item produced readable text".to_string(),
+            None,
+        );
+    }
+
+    for index in 0..epub.chapter_count() {
+        if let Err(e) = epub.get_chapter(index) {
+            report.error(
+                "chapter-unreadable",
+                e.to_string(),
+                Some(&format!("chapter {}", index + 1)),
+            );
+        }
+    }
+}
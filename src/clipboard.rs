@@ -0,0 +1,44 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::io::{self, Write};
+
+/// Copies text for pasting elsewhere. The text goes through the terminal
+/// with OSC 52, which reaches the clipboard of the machine the terminal runs
+/// on even over SSH, and, when catatau runs on that machine too, straight to
+/// the system clipboard for terminals that ignore OSC 52.
+#[derive(Default)]
+pub struct Clipboard {
+    /// Kept open since on X11 the copied text is only there while its owner
+    /// is.
+    #[cfg(feature = "clipboard")]
+    local: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn copy(&mut self, text: &str) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(osc52(text).as_bytes())?;
+        stdout.flush()?;
+
+        #[cfg(feature = "clipboard")]
+        if !over_ssh() {
+            if self.local.is_none() {
+                self.local = arboard::Clipboard::new().ok();
+            }
+            // OSC 52 has been sent either way, so a failure here isn't fatal
+            if let Some(local) = &mut self.local {
+                let _ = local.set_text(text);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The escape sequence asking the terminal to put `text` on the clipboard.
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+#[cfg(feature = "clipboard")]
+fn over_ssh() -> bool {
+    ["SSH_CONNECTION", "SSH_TTY"].iter().any(|variable| std::env::var_os(variable).is_some())
+}
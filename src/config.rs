@@ -0,0 +1,289 @@
+use crate::constants::{CONFIG_FILE, MAX_TEXT_INDENT, MIN_TEXT_WIDTH, PARAGRAPH_INDENT};
+use crate::convert::ParagraphStyle;
+use crate::error::ConfigError;
+use crate::keymap::{self, Action, KeyChord, Keymap, Preset};
+use crate::theme::Theme;
+use crate::translate::Backend;
+use crate::ui::{LineSpacing, Profile};
+use serde::{Deserialize, Deserializer, de::Error as _};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::fmt::Display;
+use std::str::FromStr;
+use tracing::level_filters::LevelFilter;
+
+/// Settings from the config file, which the command line's flags override:
+///
+/// ```toml
+/// theme = "sepia"
+/// width = 72
+/// line-spacing = 1.5
+/// profile = "eink"
+/// confirm-quit = true
+/// resume = true
+/// cache-mb = 32
+/// library = ["~/Books", "~/Documents/papers"]
+/// translator = "deepl"
+/// translate-to = "pt-BR"
+/// keymap = "vim"
+/// log-file = "~/catatau.log"
+/// log-level = "debug"
+///
+/// [scroll]
+/// paged = false
+/// lines = 3
+///
+/// [paragraphs]
+/// style = "indented"
+/// indent = 4
+///
+/// [keys]
+/// n = "scroll-down"
+/// e = "scroll-up"
+/// "<C-d>" = "page-down"
+/// j = "none"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Colour theme, by name as for `--theme`.
+    #[serde(deserialize_with = "parsed")]
+    pub theme: Option<Theme>,
+    /// Maximum text column width.
+    #[serde(deserialize_with = "text_width")]
+    pub width: Option<usize>,
+    /// Rows each line of text takes, as for `--line-spacing`.
+    #[serde(deserialize_with = "line_spacing")]
+    pub line_spacing: Option<LineSpacing>,
+    /// The kind of screen to draw for, as for `--profile`.
+    #[serde(deserialize_with = "parsed")]
+    pub profile: Option<Profile>,
+    pub confirm_quit: bool,
+    /// Go back to where each book was left.
+    pub resume: bool,
+    /// Megabytes of converted chapters to keep in memory.
+    pub cache_mb: Option<NonZeroUsize>,
+    /// Directories of books to choose from when none is given, unless
+    /// `$CATATAU_LIBRARY` is set.
+    pub library: Vec<PathBuf>,
+    pub scroll: ScrollConfig,
+    pub paragraphs: ParagraphConfig,
+    #[serde(deserialize_with = "parsed")]
+    pub translator: Option<Backend>,
+    pub translate_to: Option<String>,
+    /// The built-in key bindings to start from.
+    #[serde(deserialize_with = "parsed")]
+    pub keymap: Option<Preset>,
+    /// Where to write the log, as for `--log-file`.
+    pub log_file: Option<PathBuf>,
+    /// The least severe messages to log: error, warn, info, debug or trace.
+    #[serde(deserialize_with = "parsed")]
+    pub log_level: Option<LevelFilter>,
+    // The `[keys]` table, unbindings first
+    #[serde(rename = "keys", deserialize_with = "key_bindings")]
+    bindings: Bindings,
+    /// The keymap's bindings, with the file's changes to them.
+    #[serde(skip)]
+    pub keys: Keymap,
+}
+
+// Keys and what they're bound to, or `None` to unbind them
+type Bindings = Vec<(Vec<KeyChord>, Option<Action>)>;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScrollConfig {
+    /// Start books in paged mode.
+    pub paged: bool,
+    /// How many lines `j` and `k` scroll by.
+    pub lines: NonZeroUsize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParagraphConfig {
+    /// Blank lines between paragraphs or indents, as `book`, `spaced` or
+    /// `indented`.
+    #[serde(deserialize_with = "parsed")]
+    pub style: Option<ParagraphStyle>,
+    /// Columns an indented paragraph's first line is indented by.
+    #[serde(deserialize_with = "paragraph_indent")]
+    pub indent: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: None,
+            width: None,
+            line_spacing: None,
+            profile: None,
+            confirm_quit: false,
+            resume: true,
+            cache_mb: None,
+            library: Vec::new(),
+            scroll: ScrollConfig::default(),
+            paragraphs: ParagraphConfig::default(),
+            translator: None,
+            translate_to: None,
+            keymap: None,
+            log_file: None,
+            log_level: None,
+            bindings: Vec::new(),
+            keys: Keymap::default(),
+        }
+    }
+}
+
+impl Default for ScrollConfig {
+    fn default() -> Self {
+        Self {
+            paged: false,
+            lines: NonZeroUsize::MIN,
+        }
+    }
+}
+
+impl Default for ParagraphConfig {
+    fn default() -> Self {
+        Self {
+            style: None,
+            indent: PARAGRAPH_INDENT,
+        }
+    }
+}
+
+impl Config {
+    /// Read the config file at `path`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&text).map_err(|message| ConfigError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// The config file in the usual place, or the defaults if there isn't
+    /// one.
+    pub fn load_default() -> Result<Self, ConfigError> {
+        match config_dir().map(|dir| dir.join(CONFIG_FILE)) {
+            Some(path) if path.is_file() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Parse a config file's text, with `~` in library and log paths
+    /// standing for the home directory.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        config.library = config.library.iter().map(|dir| expand_home(dir)).collect();
+        config.log_file = config.log_file.as_deref().map(expand_home);
+        config.use_keymap(config.keymap.unwrap_or_default())?;
+        Ok(config)
+    }
+
+    /// Start the key bindings from `preset` instead, as with `--keymap`,
+    /// keeping the file's changes to them.
+    pub fn use_keymap(&mut self, preset: Preset) -> Result<(), String> {
+        let mut keys = Keymap::preset(preset);
+        for (chords, action) in &self.bindings {
+            keys.bind(chords.clone(), *action)?;
+        }
+        self.keys = keys;
+        Ok(())
+    }
+}
+
+/// Where catatau's config file and themes live: `$XDG_CONFIG_HOME/catatau`,
+/// or `~/.config/catatau`.
+pub fn config_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("catatau"))
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+// A setting written the way its command line flag takes it
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| value.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+// Keys mapped to the names of actions, or to "none" to unbind them;
+// unbinding comes first so a key can take over from a sequence starting
+// with it
+fn key_bindings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bindings, D::Error> {
+    let bindings = BTreeMap::<String, String>::deserialize(deserializer)?;
+    let (unbound, bound): (Vec<_>, Vec<_>) =
+        bindings.iter().partition(|(_, action)| action.as_str() == "none");
+    unbound
+        .into_iter()
+        .chain(bound)
+        .map(|(keys, action)| {
+            let parsed = keymap::parse_keys(keys)
+                .map_err(|e| D::Error::custom(format!("can't bind '{keys}': {e}")))?;
+            let action = match action.as_str() {
+                "none" => None,
+                name => Some(name.parse().map_err(D::Error::custom)?),
+            };
+            Ok((parsed, action))
+        })
+        .collect()
+}
+
+fn text_width<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    let width = Option::<usize>::deserialize(deserializer)?;
+    match width {
+        Some(width) if width < MIN_TEXT_WIDTH => Err(D::Error::custom(format!(
+            "width must be at least {MIN_TEXT_WIDTH} columns"
+        ))),
+        _ => Ok(width),
+    }
+}
+
+// A line spacing written as a number, or by name as for the flag
+fn line_spacing<'de, D>(deserializer: D) -> Result<Option<LineSpacing>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Spacing {
+        Rows(f64),
+        Name(String),
+    }
+    let spacing = match Option::<Spacing>::deserialize(deserializer)? {
+        Some(Spacing::Rows(rows)) => rows.to_string(),
+        Some(Spacing::Name(name)) => name,
+        None => return Ok(None),
+    };
+    spacing.parse().map(Some).map_err(D::Error::custom)
+}
+
+fn paragraph_indent<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let indent = usize::deserialize(deserializer)?;
+    if indent > MAX_TEXT_INDENT {
+        return Err(D::Error::custom(format!(
+            "paragraph indent must be at most {MAX_TEXT_INDENT} columns"
+        )));
+    }
+    Ok(indent)
+}
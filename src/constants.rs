@@ -1,6 +1,14 @@
 // EPUB parsing
 pub const MIN_CONTENT_LENGTH: usize = 50;
 pub const HTML_TEXT_WIDTH: usize = 80;
+pub const MIN_TEXT_WIDTH: usize = 20;
+pub const TABLE_MAX_WIDTH: usize = 240;
+pub const MIN_TABLE_COLUMN_WIDTH: usize = 8;
+pub const CODE_TAB_WIDTH: usize = 4;
+pub const TEXT_INDENT_COLUMNS_PER_EM: usize = 2; // For a book's `text-indent`
+pub const MAX_TEXT_INDENT: usize = 8;
+pub const PARAGRAPH_INDENT: usize = 2; // First-line indent of indented paragraphs
+pub const READING_WORDS_PER_MINUTE: usize = 250; // For reading time estimates
 
 // Search and display
 pub const MIN_SEARCH_LINE_LENGTH: usize = 10;
@@ -9,18 +17,51 @@ pub const SEARCH_CONTEXT_LINES: usize = 1;
 pub const SEARCH_CONTEXT_AFTER_LINES: usize = 2;
 
 // UI
-pub const HEADER_HEIGHT: usize = 3;
-pub const FOOTER_HEIGHT: usize = 1;
-pub const UI_RESERVED_HEIGHT: usize = HEADER_HEIGHT + FOOTER_HEIGHT + 1;
+pub const HEADER_HEIGHT: usize = 4;
+pub const FOOTER_HEIGHT: usize = 3; // Progress bar plus key help
+pub const CONTENT_VERTICAL_CHROME: usize = 2; // Top and bottom borders
+pub const UI_RESERVED_HEIGHT: usize = HEADER_HEIGHT + FOOTER_HEIGHT + CONTENT_VERTICAL_CHROME;
 pub const DEFAULT_TERMINAL_HEIGHT: usize = 24;
+pub const CONTENT_HORIZONTAL_CHROME: usize = 5; // Borders plus left/right padding
+pub const MAX_LINE_SPACING: f64 = 3.0; // Rows a line of text can take, blank ones included
+pub const BACKGROUND_QUERY_TIMEOUT_MS: u64 = 200;
+pub const EVENT_POLL_INTERVAL_MS: u64 = 250; // How often to check for missed resizes
+pub const SEARCH_POLL_INTERVAL_MS: u64 = 50; // Redraw rate while a search runs
+pub const EINK_REFRESH_MS: u64 = 1000; // The same with the e-ink profile
+pub const WATCH_SETTLE_MS: u64 = 300; // Quiet after a watched book changes before reloading
+pub const READING_IDLE_TIMEOUT_SECS: u64 = 5 * 60; // Longer gaps between keys are breaks
 
 // Navigation
 pub const SEARCH_RESULT_TOP_OFFSET: usize = 2;
+pub const TABLE_SCROLL_STEP: usize = 8;
+pub const LINK_HINT_KEYS: &str = "asdfghjklqwertyuiopzxcvbnm";
+pub const JUMP_LIST_SIZE: usize = 100;
+pub const MAX_COUNT: usize = 9999; // Largest count prefix, as in 10j
+pub const MAX_REMOTE_REQUEST_BYTES: usize = 64 * 1024; // Longest remote control request line
 
 // Validation and limits
 pub const MAX_EPUB_SIZE: u64 = 100 * 1024 * 1024; // 100MB
 pub const MAX_CHAPTER_SIZE: usize = 5 * 1024 * 1024; // 5MB
 pub const MAX_DECOMPRESSED_RATIO: usize = 100; // Max 100x compression ratio
+pub const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024; // 10MB
+
+// Settings, in config::config_dir()
+pub const CONFIG_FILE: &str = "config.toml";
+
+// Saved state, in progress::state_dir()
+pub const PROGRESS_FILE: &str = "progress.json";
+pub const STATS_FILE: &str = "stats.json";
+pub const LOG_FILE: &str = "catatau.log";
+pub const CRASH_REPORTS_DIR: &str = "crash-reports";
+pub const CRASH_REPORT_KEYS: usize = 20; // Last keys pressed, kept for crash reports
+pub const SEARCH_INDEX_DIR: &str = "index"; // In progress::data_dir()
+pub const MAX_SAVED_SEARCH_INDEXES: usize = 200; // The most recently used are kept
+pub const LIBRARY_CACHE_FILE: &str = "library.json"; // In progress::data_dir()
+pub const COVERS_DIR: &str = "covers"; // In progress::data_dir()
+pub const HASH_CHUNK_BYTES: usize = 64 * 1024; // Read at a time when hashing a book
 
 // Caching
-pub const CHAPTER_CACHE_SIZE: usize = 5;
+pub const CHAPTER_CACHE_MB: usize = 16; // Converted chapters kept in memory
+pub const CHAPTER_CACHE_BYTES: usize = CHAPTER_CACHE_MB * 1024 * 1024;
+pub const PREFETCH_SCREENS: usize = 2; // Load the next chapter this close to the end of one
+pub const RENDER_CACHE_SIZE: usize = 4; // Chapters' styled lines, as for both views of a split
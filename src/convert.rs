@@ -0,0 +1,1472 @@
+use crate::constants::{CODE_TAB_WIDTH, MIN_TABLE_COLUMN_WIDTH, TABLE_MAX_WIDTH};
+use crate::css::{Style, Stylesheet, TextAlign};
+use html2text::render::text_renderer::{TaggedLine, TaggedLineElement, TextDecorator};
+use quick_xml::escape::{resolve_predefined_entity, unescape_with};
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use unicode_width::UnicodeWidthStr;
+
+// Private-use character marking where an extracted block belongs in the
+// converted text; it never appears in real book content.
+const PLACEHOLDER_MARK: char = '\u{E000}';
+/// Bullets for successive levels of nested unordered lists.
+pub const LIST_BULLETS: [char; 3] = ['•', '◦', '▪'];
+// html2text sizes hanging indents by byte length, so single-byte control
+// characters stand in for the bullets during rendering.
+const BULLET_MARKS: [char; 3] = ['\u{1}', '\u{2}', '\u{3}'];
+// Stands in for one column of intentional indentation, which html2text would
+// otherwise strip from the start of a line.
+const INDENT_MARK: char = '\u{E001}';
+// Placed before the text of a centred or right-aligned block and after it.
+// Tag characters have no width, so they don't change how the text wraps.
+const ALIGN_CENTER_MARK: char = '\u{E0043}';
+const ALIGN_RIGHT_MARK: char = '\u{E0052}';
+const ALIGN_END_MARK: char = '\u{E007F}';
+// Elements whose text a stylesheet can align and indent.
+const STYLED_BLOCKS: [&str; 16] = [
+    "p", "div", "h1", "h2", "h3", "h4", "h5", "h6", "li", "blockquote", "dd", "dt",
+    "figcaption", "section", "header", "footer",
+];
+// Elements a heading can be cut out of without changing how the text
+// around it is laid out. Quotes are opened again around the heading and
+// the text after it.
+const HEADING_CONTAINERS: [&str; 12] = [
+    "html", "body", "div", "section", "article", "header", "footer", "main", "aside", "nav",
+    "hgroup", "blockquote",
+];
+// Elements with no end tag.
+const VOID_ELEMENTS: [&str; 10] =
+    ["area", "base", "br", "col", "hr", "img", "input", "link", "meta", "wbr"];
+// Columns of indentation per level of the `i1`, `i2`… verse classes used by
+// Standard Ebooks and similar producers.
+const VERSE_CLASS_INDENT: usize = 2;
+// Characters with a Unicode superscript or subscript form. Q and most
+// subscript letters have none, so text using them is left inline.
+const SUPERSCRIPTS: [(char, char); 39] = [
+    ('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'),
+    ('5', '⁵'), ('6', '⁶'), ('7', '⁷'), ('8', '⁸'), ('9', '⁹'),
+    ('+', '⁺'), ('-', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'),
+    ('a', 'ᵃ'), ('b', 'ᵇ'), ('c', 'ᶜ'), ('d', 'ᵈ'), ('e', 'ᵉ'),
+    ('f', 'ᶠ'), ('g', 'ᵍ'), ('h', 'ʰ'), ('i', 'ⁱ'), ('j', 'ʲ'),
+    ('k', 'ᵏ'), ('l', 'ˡ'), ('m', 'ᵐ'), ('n', 'ⁿ'), ('o', 'ᵒ'),
+    ('p', 'ᵖ'), ('r', 'ʳ'), ('s', 'ˢ'), ('t', 'ᵗ'), ('u', 'ᵘ'),
+    ('v', 'ᵛ'), ('w', 'ʷ'), ('x', 'ˣ'), ('y', 'ʸ'),
+];
+const SUBSCRIPTS: [(char, char); 32] = [
+    ('0', '₀'), ('1', '₁'), ('2', '₂'), ('3', '₃'), ('4', '₄'),
+    ('5', '₅'), ('6', '₆'), ('7', '₇'), ('8', '₈'), ('9', '₉'),
+    ('+', '₊'), ('-', '₋'), ('=', '₌'), ('(', '₍'), (')', '₎'),
+    ('a', 'ₐ'), ('e', 'ₑ'), ('h', 'ₕ'), ('i', 'ᵢ'), ('j', 'ⱼ'),
+    ('k', 'ₖ'), ('l', 'ₗ'), ('m', 'ₘ'), ('n', 'ₙ'), ('o', 'ₒ'),
+    ('p', 'ₚ'), ('r', 'ᵣ'), ('s', 'ₛ'), ('t', 'ₜ'), ('u', 'ᵤ'),
+    ('v', 'ᵥ'), ('x', 'ₓ'),
+];
+
+/// Chapter text after conversion, along with the blocks that keep their own
+/// layout instead of being wrapped to the text column.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertedText {
+    pub text: String,
+    pub blocks: Vec<Block>,
+    pub links: Vec<Link>,
+    /// Line on which each element `id` (or `<a name>`) ends up.
+    pub anchors: HashMap<String, usize>,
+    pub headings: Vec<HeadingLines>,
+    /// Emphasised, strong and code text, and the alt text of images, in
+    /// the order it appears.
+    pub spans: Vec<TextSpan>,
+    /// Lines the book's styles centre or align right.
+    pub aligned: Vec<AlignedLines>,
+}
+
+/// A run of converted lines laid out at their natural width. Lines in a block
+/// may be wider than the text column and are scrolled horizontally.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub lines: Range<usize>,
+    pub kind: BlockKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockKind {
+    Table,
+    Code { language: Option<String> },
+    Image,
+}
+
+/// A hyperlink in the converted text, positioned at the start of its text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub line: usize,
+    /// Character columns covered by the link text on `line`.
+    pub columns: Range<usize>,
+    /// The `href` exactly as written in the chapter.
+    pub target: String,
+}
+
+/// A heading in the converted text, which may have been wrapped onto
+/// several lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadingLines {
+    pub lines: Range<usize>,
+    /// 1 for `<h1>` down to 6 for `<h6>`.
+    pub level: usize,
+}
+
+/// Lines of a block aligned other than to the left. Blocks inside another
+/// come before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignedLines {
+    pub lines: Range<usize>,
+    pub align: TextAlign,
+}
+
+/// A run of text on one line that is set differently from the text around
+/// it. Runs may overlap, as with emphasis inside strong text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub line: usize,
+    /// Character columns covered by the run on `line`.
+    pub columns: Range<usize>,
+    pub style: SpanStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanStyle {
+    /// `<em>` or `<i>`.
+    Emphasis,
+    /// `<strong>` or `<b>`.
+    Strong,
+    /// `<code>` outside a listing.
+    Code,
+    /// The alt text standing in for an image.
+    Image,
+}
+
+/// A part of a chapter that html2text renders on its own.
+#[derive(Debug, Clone, PartialEq)]
+enum Section {
+    /// The text between headings.
+    Flow(String),
+    /// A heading, inside any quotes it's in.
+    Heading { level: usize, html: String },
+}
+
+/// Markup that html2text carries along with the text inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Markup {
+    /// The `href` of an `<a>`.
+    Link(String),
+    Style(SpanStyle),
+}
+
+/// How pictures are shown in chapter text. With art turned off (or when an
+/// image can't be loaded) only the alt text is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageArt {
+    #[default]
+    Off,
+    /// `▀`/`▄` half blocks, two pixels to a cell.
+    HalfBlock,
+    /// Braille patterns, eight dots to a cell.
+    Braille,
+}
+
+impl FromStr for ImageArt {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" | "none" => Ok(Self::Off),
+            "halfblock" | "half-block" | "blocks" => Ok(Self::HalfBlock),
+            "braille" => Ok(Self::Braille),
+            other => Err(format!(
+                "unknown image style '{other}' (expected off, halfblock or braille)"
+            )),
+        }
+    }
+}
+
+/// How `<ruby>` annotations, such as furigana, are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RubyStyle {
+    /// The reading in brackets after its base text: `漢字(かんじ)`.
+    #[default]
+    Inline,
+    /// Only the base text.
+    Strip,
+}
+
+impl FromStr for RubyStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "inline" => Ok(Self::Inline),
+            "strip" | "off" => Ok(Self::Strip),
+            other => Err(format!("unknown ruby style '{other}' (expected inline or strip)")),
+        }
+    }
+}
+
+/// How paragraphs are set apart from one another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParagraphStyle {
+    /// A blank line between paragraphs, with first lines indented as the
+    /// book's styles have them.
+    #[default]
+    Book,
+    /// A blank line between paragraphs and no indents.
+    Spaced,
+    /// No blank line between paragraphs, and the first line of each
+    /// indented, as in print. The first paragraph after a heading, and
+    /// paragraphs the book's styles set flush, are left flush.
+    Indented,
+}
+
+impl FromStr for ParagraphStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "book" => Ok(Self::Book),
+            "spaced" | "blank-line" => Ok(Self::Spaced),
+            "indented" | "indent" => Ok(Self::Indented),
+            other => Err(format!(
+                "unknown paragraph style '{other}' (expected book, spaced or indented)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    pub image_art: ImageArt,
+    pub ruby: RubyStyle,
+    /// Clean up leftovers of the book's own conversion: soft hyphens,
+    /// doubled spaces, stray entities, and `--` for em dashes.
+    pub typography: bool,
+    /// The stylesheets the chapter links to. Its own `<style>` elements and
+    /// `style` attributes are followed as well.
+    pub stylesheet: Stylesheet,
+    pub paragraphs: ParagraphStyle,
+    /// Columns the first line of a paragraph is indented by, with
+    /// [`ParagraphStyle::Indented`].
+    pub paragraph_indent: usize,
+}
+
+/// An `<img>` pulled out of the chapter to be drawn as text art.
+struct Picture {
+    src: String,
+    alt: String,
+}
+
+/// Convert chapter XHTML into the plain text shown in the reader, wrapped to
+/// `width` columns.
+pub fn html_to_text(html: &str, width: usize) -> String {
+    convert_html(html, width).text
+}
+
+/// Like [`html_to_text`], but also reports which lines belong to tables and
+/// code listings, and where the links and anchors are.
+pub fn convert_html(html: &str, width: usize) -> ConvertedText {
+    convert_html_with(html, width, &ConvertOptions::default(), &|_| None)
+}
+
+/// Like [`convert_html`], with pictures drawn as text art if `options` ask
+/// for it. `load_image` returns the bytes of the image an `src` refers to.
+pub fn convert_html_with(
+    html: &str,
+    width: usize,
+    options: &ConvertOptions,
+    load_image: &dyn Fn(&str) -> Option<Vec<u8>>,
+) -> ConvertedText {
+    let html = annotate_ruby(html, options.ruby);
+    let html = raise_scripts(&html, "sup", &SUPERSCRIPTS);
+    let html = raise_scripts(&html, "sub", &SUBSCRIPTS);
+    let html = apply_styles(&html, options);
+    let html = move_anchors_to_text(&html);
+    let mut html = preserve_line_layout(&html);
+    if options.paragraphs == ParagraphStyle::Indented {
+        html = join_paragraphs(&html);
+    }
+    let (html, mut tables) = extract_elements(&html, "table", 'T');
+    let (mut html, listings) = extract_elements(&html, "pre", 'P');
+    // After the listings are out of the way, since code is kept verbatim
+    if options.typography {
+        html = tidy_typography(&html);
+        tables = tables.iter().map(|table| tidy_typography(table)).collect();
+    }
+    let (html, pictures) = match options.image_art {
+        ImageArt::Off => (html, Vec::new()),
+        _ => extract_pictures(&html),
+    };
+    let rendered = render_html(&html, width);
+    if tables.is_empty() && listings.is_empty() && pictures.is_empty() {
+        return rendered;
+    }
+
+    let mut converted = ConvertedText {
+        text: String::with_capacity(rendered.text.len()),
+        ..ConvertedText::default()
+    };
+    let mut links = rendered.links.into_iter().peekable();
+    // Output line for each line of the main rendering, for placing anchors
+    let mut line_map = Vec::new();
+    let mut line_count = 0;
+    for (index, line) in rendered.text.lines().enumerate() {
+        line_map.push(line_count);
+        let (block, kind) = match placeholder(line) {
+            Some(('T', index)) if index < tables.len() => {
+                (render_table(&tables[index], width), BlockKind::Table)
+            }
+            Some(('P', index)) if index < listings.len() => {
+                let listing = &listings[index];
+                (
+                    ConvertedText {
+                        text: render_listing(listing),
+                        anchors: element_ids(listing).map(|id| (id, 0)).collect(),
+                        ..ConvertedText::default()
+                    },
+                    BlockKind::Code {
+                        language: listing_language(listing),
+                    },
+                )
+            }
+            Some(('I', index)) if index < pictures.len() => {
+                let picture = &pictures[index];
+                let art = load_image(&picture.src)
+                    .and_then(|bytes| render_image_art(&bytes, options.image_art, width));
+                match art {
+                    Some(art) => (
+                        ConvertedText {
+                            text: art,
+                            ..ConvertedText::default()
+                        },
+                        BlockKind::Image,
+                    ),
+                    None => {
+                        if !picture.alt.is_empty() {
+                            let alt = format!("[{}]", picture.alt);
+                            converted.spans.push(TextSpan {
+                                line: line_count,
+                                columns: 0..alt.chars().count(),
+                                style: SpanStyle::Image,
+                            });
+                            converted.text.push_str(&alt);
+                            converted.text.push('\n');
+                            line_count += 1;
+                        }
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                while let Some(link) = links.next_if(|link| link.line == index) {
+                    converted.links.push(Link {
+                        line: line_count,
+                        ..link
+                    });
+                }
+                converted.text.push_str(line);
+                converted.text.push('\n');
+                line_count += 1;
+                continue;
+            }
+        };
+
+        let start = line_count;
+        converted
+            .links
+            .extend(block.links.into_iter().map(|link| Link {
+                line: start + link.line,
+                ..link
+            }));
+        for (id, line) in block.anchors {
+            converted.anchors.entry(id).or_insert(start + line);
+        }
+        for line in block.text.lines() {
+            converted.text.push_str(line);
+            converted.text.push('\n');
+            line_count += 1;
+        }
+        converted.blocks.push(Block {
+            lines: start..line_count,
+            kind,
+        });
+    }
+
+    for (id, line) in rendered.anchors {
+        if let Some(&output_line) = line_map.get(line) {
+            converted.anchors.entry(id).or_insert(output_line);
+        }
+    }
+    // Ranges of lines take in the whole of any block in them
+    let output_lines = |lines: &Range<usize>| {
+        let start = *line_map.get(lines.start)?;
+        Some(start..line_map.get(lines.end).copied().unwrap_or(line_count))
+    };
+    converted.headings = rendered
+        .headings
+        .into_iter()
+        .filter_map(|heading| {
+            Some(HeadingLines {
+                lines: output_lines(&heading.lines)?,
+                ..heading
+            })
+        })
+        .collect();
+    converted.aligned = rendered
+        .aligned
+        .into_iter()
+        .filter_map(|aligned| {
+            Some(AlignedLines {
+                lines: output_lines(&aligned.lines)?,
+                ..aligned
+            })
+        })
+        .collect();
+    converted.spans.extend(rendered.spans.into_iter().filter_map(|span| {
+        Some(TextSpan {
+            line: *line_map.get(span.line)?,
+            ..span
+        })
+    }));
+    converted.spans.sort_by_key(|span| span.line);
+    converted
+}
+
+/// First line of each page when `text` is shown `page_size` lines at a time.
+/// A page that would end mid-paragraph ends at the last blank line instead,
+/// as long as that keeps it at least half full.
+pub fn page_starts(text: &str, page_size: usize) -> Vec<usize> {
+    let lines: Vec<&str> = text.lines().collect();
+    let blank = |index: usize| lines[index].trim().is_empty();
+    let page_size = page_size.max(1);
+
+    let mut starts = vec![0];
+    let mut start = 0;
+    while start + page_size < lines.len() {
+        let end = start + page_size;
+        let mut next = if blank(end) || blank(end - 1) {
+            end
+        } else {
+            (start + page_size / 2..end)
+                .rev()
+                .find(|&index| blank(index))
+                .map_or(end, |index| index + 1)
+        };
+        // Don't open a page on the blank lines between paragraphs
+        while next < lines.len() && blank(next) {
+            next += 1;
+        }
+        if next >= lines.len() {
+            break;
+        }
+        starts.push(next);
+        start = next;
+    }
+    starts
+}
+
+fn render_html(html: &str, width: usize) -> ConvertedText {
+    let mut converted = ConvertedText::default();
+    let mut lines: Vec<TaggedLine<Vec<Option<Markup>>>> = Vec::new();
+    for section in sections(html) {
+        let (html, level) = match section {
+            Section::Flow(html) => (html, None),
+            Section::Heading { level, html } => (html, Some(level)),
+        };
+        let mut rendered = html2text::config::with_decorator(ReaderDecorator::new())
+            .lines_from_read(html.as_bytes(), width)
+            .unwrap_or_default();
+        while rendered.last().is_some_and(is_blank) {
+            rendered.pop();
+        }
+        let leading = rendered.iter().take_while(|line| is_blank(line)).count();
+        if leading == rendered.len() {
+            continue;
+        }
+        // Sections are set apart like the blocks html2text renders
+        // together, inside whatever quotes they share
+        if let Some(last) = lines.last() {
+            let depth = quote_depth(last).min(quote_depth(&rendered[leading]));
+            lines.push(TaggedLine::from_string("> ".repeat(depth), &Vec::new()));
+        }
+        let start = lines.len();
+        lines.extend(rendered.into_iter().skip(leading));
+        if let Some(level) = level {
+            converted.headings.push(HeadingLines {
+                lines: start..lines.len(),
+                level,
+            });
+        }
+    }
+
+    // Where each aligned block still open began
+    let mut aligning: Vec<(TextAlign, usize)> = Vec::new();
+    for (line_number, line) in lines.iter().enumerate() {
+        let mut column = 0;
+        for element in line.iter() {
+            let piece = match element {
+                TaggedLineElement::Str(piece) => piece,
+                TaggedLineElement::FragmentStart(id) => {
+                    converted.anchors.entry(id.clone()).or_insert(line_number);
+                    continue;
+                }
+            };
+            let start = column;
+            for c in piece.s.chars() {
+                match c {
+                    ALIGN_CENTER_MARK => aligning.push((TextAlign::Center, line_number)),
+                    ALIGN_RIGHT_MARK => aligning.push((TextAlign::Right, line_number)),
+                    ALIGN_END_MARK => {
+                        if let Some((align, start)) = aligning.pop() {
+                            converted.aligned.push(AlignedLines {
+                                lines: start..line_number + 1,
+                                align,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+                if matches!(c, ALIGN_CENTER_MARK | ALIGN_RIGHT_MARK | ALIGN_END_MARK) {
+                    continue;
+                }
+                converted.text.push(match BULLET_MARKS.iter().position(|&mark| mark == c) {
+                    Some(level) => LIST_BULLETS[level],
+                    None if c == INDENT_MARK => ' ',
+                    None => c,
+                });
+                column += 1;
+            }
+            if start == column {
+                continue;
+            }
+
+            for style in piece.tag.iter().filter_map(|markup| match markup {
+                Some(Markup::Style(style)) => Some(*style),
+                _ => None,
+            }) {
+                add_span(&mut converted.spans, line_number, start..column, style);
+            }
+            let Some(target) = piece.tag.iter().rev().find_map(|markup| match markup {
+                Some(Markup::Link(target)) => Some(target),
+                _ => None,
+            }) else {
+                continue;
+            };
+            // Emphasis inside a link splits it into pieces, and a link wrapped
+            // onto a new line is still the same link
+            if let Some(last) = converted.links.last_mut().filter(|last| last.target == *target) {
+                if last.line == line_number && last.columns.end == start {
+                    last.columns.end = column;
+                    continue;
+                }
+                if last.line + 1 == line_number && start == 0 {
+                    continue;
+                }
+            }
+            if !piece.s.trim().is_empty() {
+                converted.links.push(Link {
+                    line: line_number,
+                    columns: start..column,
+                    target: target.clone(),
+                });
+            }
+        }
+
+        let trimmed = converted.text.trim_end_matches([' ', '\t']).len();
+        converted.text.truncate(trimmed);
+        converted.text.push('\n');
+    }
+    converted
+}
+
+/// Cut the chapter around each heading, so that which lines a heading
+/// takes up and its level come from the element itself. Headings inside
+/// lists, tables and other elements that set out their text are left in
+/// the flow, where they read as ordinary text.
+fn sections(html: &str) -> Vec<Section> {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]*>").unwrap());
+
+    let lower = html.to_ascii_lowercase();
+    let mut sections = Vec::new();
+    // The elements open, with the tags that opened them
+    let mut open: Vec<(String, &str)> = Vec::new();
+    let mut flow = String::new();
+    let mut cursor = 0;
+    let mut flow_start = 0;
+    while let Some(found) = tag.find_at(html, cursor) {
+        let markup = found.as_str();
+        cursor = found.end();
+        let name = lower[found.start()..found.end()]
+            .trim_start_matches(['<', '/'])
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        if markup.starts_with("</") {
+            if let Some(at) = open.iter().rposition(|(open, _)| *open == name) {
+                open.truncate(at);
+            }
+            continue;
+        }
+        if markup.starts_with("<!")
+            || markup.starts_with("<?")
+            || markup.ends_with("/>")
+            || VOID_ELEMENTS.contains(&name.as_str())
+        {
+            continue;
+        }
+        if matches!(name.as_str(), "title" | "style" | "script") {
+            cursor = lower[cursor..].find(&format!("</{name}")).map_or(html.len(), |i| cursor + i);
+            continue;
+        }
+
+        let level = match name.as_bytes() {
+            [b'h', digit @ b'1'..=b'6'] => Some(usize::from(digit - b'0')),
+            _ => None,
+        };
+        if let Some(level) = level
+            && open.iter().all(|(open, _)| HEADING_CONTAINERS.contains(&open.as_str()))
+            && let Some(end) = element_end(&lower, &name, found.start())
+        {
+            let quotes: String = open
+                .iter()
+                .filter(|(open, _)| open == "blockquote")
+                .map(|(_, markup)| *markup)
+                .collect();
+            flow.push_str(&html[flow_start..found.start()]);
+            sections.push(Section::Flow(std::mem::replace(&mut flow, quotes.clone())));
+            sections.push(Section::Heading {
+                level,
+                html: quotes + &html[found.start()..end],
+            });
+            flow_start = end;
+            cursor = end;
+            continue;
+        }
+        open.push((name, markup));
+    }
+
+    flow.push_str(&html[flow_start..]);
+    sections.push(Section::Flow(flow));
+    sections
+}
+
+fn is_blank(line: &TaggedLine<Vec<Option<Markup>>>) -> bool {
+    line.iter().all(|element| match element {
+        TaggedLineElement::Str(piece) => piece.s.trim().is_empty(),
+        TaggedLineElement::FragmentStart(_) => false,
+    })
+}
+
+/// How many quotes deep a rendered line is.
+fn quote_depth(line: &TaggedLine<Vec<Option<Markup>>>) -> usize {
+    let text: String = line.chars().collect();
+    (text.len() - text.trim_start_matches("> ").len()) / 2
+}
+
+// Text in one style split into pieces, say by a link inside it, is one run
+fn add_span(spans: &mut Vec<TextSpan>, line: usize, columns: Range<usize>, style: SpanStyle) {
+    let continued = spans.iter_mut().rev().take_while(|span| span.line == line).find(|span| {
+        span.style == style && span.columns.end == columns.start
+    });
+    match continued {
+        Some(span) => span.columns.end = columns.end,
+        None => spans.push(TextSpan {
+            line,
+            columns,
+            style,
+        }),
+    }
+}
+
+/// Keep the line structure of verse and other deliberately laid out text.
+/// html2text already honours `<br/>`, but drops the leading non-breaking
+/// spaces poems use for indentation and ignores `white-space: pre`.
+fn preserve_line_layout(html: &str) -> String {
+    static PRE_STYLE: OnceLock<Regex> = OnceLock::new();
+    static LEADING_SPACE: OnceLock<Regex> = OnceLock::new();
+    static INDENT_CLASS: OnceLock<Regex> = OnceLock::new();
+    let pre_style = PRE_STYLE.get_or_init(|| {
+        Regex::new(r#"(?i)<([a-z][a-z0-9]*)\b[^>]*\bstyle\s*=\s*["'][^"']*white-space\s*:\s*pre"#)
+            .unwrap()
+    });
+    let leading_space = LEADING_SPACE.get_or_init(|| {
+        Regex::new(r"(?i)(<br\s*/?>|<p\b[^>]*>)(\s*)((?:&#160;|&nbsp;|&#xa0;|\x{a0}|\x{3000})+)")
+            .unwrap()
+    });
+    let indent_class = INDENT_CLASS.get_or_init(|| {
+        Regex::new(r#"(?i)<span\b[^>]*\bclass\s*=\s*["'](?:[^"']*\s)?i(\d)(?:\s[^"']*)?["'][^>]*>"#)
+            .unwrap()
+    });
+
+    // Elements styled `white-space: pre` keep their newlines and spacing.
+    // The lowercased copy is edited alongside, which keeps its offsets the
+    // same as the markup's, since ASCII lowercasing keeps every length.
+    let mut html = html.to_string();
+    let mut lower = html.to_ascii_lowercase();
+    let mut cursor = 0;
+    while let Some(captures) = pre_style.captures_at(&html, cursor) {
+        let start = captures.get(0).map_or(0, |m| m.start());
+        let tag = captures[1].to_ascii_lowercase();
+        let (Some(content_start), Some(end)) = (
+            lower[start..].find('>').map(|i| start + i + 1),
+            element_end(&lower, &tag, start),
+        ) else {
+            break;
+        };
+        let content_end = lower[..end].rfind("</").unwrap_or(end).max(content_start);
+
+        let content = html[content_start..content_end].trim_start_matches(['\r', '\n']);
+        let laid_out = content
+            .lines()
+            .map(|line| {
+                let text = line.trim_start_matches([' ', '\t']);
+                let indent: usize = line[..line.len() - text.len()]
+                    .chars()
+                    .map(|c| if c == '\t' { CODE_TAB_WIDTH } else { 1 })
+                    .sum();
+                format!("{}{}", String::from(INDENT_MARK).repeat(indent), text)
+            })
+            .collect::<Vec<_>>()
+            .join("<br/>\n");
+
+        html.replace_range(content_start..content_end, &laid_out);
+        lower.replace_range(content_start..content_end, &laid_out.to_ascii_lowercase());
+        cursor = content_start + laid_out.len();
+    }
+
+    let html = leading_space.replace_all(&html, |captures: &regex::Captures| {
+        let run = &captures[3];
+        let columns = run.matches('&').count()
+            + run.matches('\u{a0}').count()
+            + 2 * run.matches('\u{3000}').count();
+        format!(
+            "{}{}{}",
+            &captures[1],
+            &captures[2],
+            String::from(INDENT_MARK).repeat(columns)
+        )
+    });
+
+    indent_class
+        .replace_all(&html, |captures: &regex::Captures| {
+            let level: usize = captures[1].parse().unwrap_or(0);
+            format!(
+                "{}{}",
+                &captures[0],
+                String::from(INDENT_MARK).repeat(level * VERSE_CLASS_INDENT)
+            )
+        })
+        .into_owned()
+}
+
+/// Decorator used for chapter text. Rather than writing markers into the
+/// text, it tags link text with its target and emphasis, strong text, code
+/// and image alt text with their style, and marks list items with bullets
+/// that change with nesting depth.
+#[derive(Clone, Debug)]
+struct ReaderDecorator {
+    depth: usize,
+}
+
+impl ReaderDecorator {
+    fn new() -> Self {
+        Self { depth: 0 }
+    }
+}
+
+impl TextDecorator for ReaderDecorator {
+    type Annotation = Option<Markup>;
+
+    fn decorate_link_start(&mut self, url: &str) -> (String, Self::Annotation) {
+        (String::new(), Some(Markup::Link(url.to_string())))
+    }
+
+    fn decorate_link_end(&mut self) -> String {
+        String::new()
+    }
+
+    fn decorate_em_start(&self) -> (String, Self::Annotation) {
+        (String::new(), Some(Markup::Style(SpanStyle::Emphasis)))
+    }
+
+    fn decorate_em_end(&self) -> String {
+        String::new()
+    }
+
+    fn decorate_strong_start(&self) -> (String, Self::Annotation) {
+        (String::new(), Some(Markup::Style(SpanStyle::Strong)))
+    }
+
+    fn decorate_strong_end(&self) -> String {
+        String::new()
+    }
+
+    fn decorate_strikeout_start(&self) -> (String, Self::Annotation) {
+        (String::new(), None)
+    }
+
+    fn decorate_strikeout_end(&self) -> String {
+        String::new()
+    }
+
+    fn decorate_code_start(&self) -> (String, Self::Annotation) {
+        (String::new(), Some(Markup::Style(SpanStyle::Code)))
+    }
+
+    fn decorate_code_end(&self) -> String {
+        String::new()
+    }
+
+    fn decorate_preformat_first(&self) -> Self::Annotation {
+        None
+    }
+
+    fn decorate_preformat_cont(&self) -> Self::Annotation {
+        None
+    }
+
+    fn decorate_image(&mut self, _src: &str, title: &str) -> (String, Self::Annotation) {
+        (format!("[{}]", title), Some(Markup::Style(SpanStyle::Image)))
+    }
+
+    fn header_prefix(&self, _level: usize) -> String {
+        String::new()
+    }
+
+    fn quote_prefix(&self) -> String {
+        "> ".to_string()
+    }
+
+    fn unordered_item_prefix(&self) -> String {
+        format!("{} ", BULLET_MARKS[self.depth % BULLET_MARKS.len()])
+    }
+
+    fn ordered_item_prefix(&self, i: i64) -> String {
+        format!("{}. ", i)
+    }
+
+    fn make_subblock_decorator(&self) -> Self {
+        Self {
+            depth: self.depth + 1,
+        }
+    }
+
+    fn finalise(&mut self, _links: Vec<String>) -> Vec<TaggedLine<Self::Annotation>> {
+        Vec::new()
+    }
+}
+
+fn placeholder(line: &str) -> Option<(char, usize)> {
+    let inner = line
+        .trim()
+        .strip_prefix(PLACEHOLDER_MARK)?
+        .strip_suffix(PLACEHOLDER_MARK)?;
+    let mut chars = inner.chars();
+    let kind = chars.next()?;
+    Some((kind, chars.as_str().parse().ok()?))
+}
+
+/// Pull every top-level `<tag>` element out of the markup, leaving a
+/// placeholder paragraph in its place.
+fn extract_elements(html: &str, tag: &str, kind: char) -> (String, Vec<String>) {
+    let open_tag = format!("<{tag}");
+    let lower = html.to_ascii_lowercase();
+    let mut output = String::with_capacity(html.len());
+    let mut elements = Vec::new();
+    let mut cursor = 0;
+
+    while let Some(start) = find_tag(&lower, &open_tag, cursor) {
+        let Some(end) = element_end(&lower, tag, start) else {
+            break;
+        };
+
+        output.push_str(&html[cursor..start]);
+        output.push_str(&format!(
+            "<p>{mark}{kind}{index}{mark}</p>",
+            mark = PLACEHOLDER_MARK,
+            index = elements.len()
+        ));
+        elements.push(html[start..end].to_string());
+        cursor = end;
+    }
+
+    output.push_str(&html[cursor..]);
+    (output, elements)
+}
+
+/// Rewrite the text of each `<tag>` element (`sup` or `sub`) with the
+/// matching Unicode characters, so `x<sup>2</sup>` reads as `x²` rather than
+/// `x2`. Elements with any character lacking a form in `forms` are left for
+/// html2text to run inline. Markup inside, such as a footnote link, is kept.
+fn raise_scripts(html: &str, tag: &str, forms: &[(char, char)]) -> String {
+    static MARKUP: OnceLock<Regex> = OnceLock::new();
+    let markup = MARKUP.get_or_init(|| Regex::new(r"<[^>]*>").unwrap());
+    let form = |c: char| {
+        forms
+            .iter()
+            .find(|(plain, _)| *plain == c)
+            .map(|&(_, raised)| raised)
+            .or(c.is_whitespace().then_some(c))
+    };
+
+    let lower = html.to_ascii_lowercase();
+    let open_tag = format!("<{tag}");
+    let mut output = String::with_capacity(html.len());
+    let mut cursor = 0;
+    while let Some(start) = find_tag(&lower, &open_tag, cursor) {
+        let (Some(content_start), Some(end)) = (
+            lower[start..].find('>').map(|i| start + i + 1),
+            element_end(&lower, tag, start),
+        ) else {
+            break;
+        };
+        let content_end = lower[..end].rfind("</").unwrap_or(end).max(content_start);
+        let content = &html[content_start..content_end];
+
+        // Text between the tags, which must all have raised forms
+        let mut text = Vec::new();
+        let mut text_start = 0;
+        for found in markup.find_iter(content) {
+            text.push(text_start..found.start());
+            text_start = found.end();
+        }
+        text.push(text_start..content.len());
+        let raisable = text
+            .iter()
+            .all(|range| content[range.clone()].chars().all(|c| form(c).is_some()))
+            && text.iter().any(|range| !content[range.clone()].trim().is_empty());
+
+        output.push_str(&html[cursor..start]);
+        if raisable {
+            let mut previous_end = 0;
+            for range in text {
+                output.push_str(&content[previous_end..range.start]);
+                output.extend(content[range.clone()].chars().filter_map(form));
+                previous_end = range.end;
+            }
+        } else {
+            output.push_str(&html[start..end]);
+        }
+        cursor = end;
+    }
+
+    output.push_str(&html[cursor..]);
+    output
+}
+
+/// Fix common artifacts of careless conversion in the text of the markup,
+/// leaving tags alone: soft hyphens are dropped, runs of spaces and
+/// non-breaking spaces become one space, double-escaped entities that would
+/// show as a literal `&nbsp;` are unescaped once, and `--` becomes an em dash.
+fn tidy_typography(html: &str) -> String {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    static SOFT_HYPHEN: OnceLock<Regex> = OnceLock::new();
+    static SPACES: OnceLock<Regex> = OnceLock::new();
+    static ESCAPED_ENTITY: OnceLock<Regex> = OnceLock::new();
+    static DASH: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]*>").unwrap());
+    let soft_hyphen =
+        SOFT_HYPHEN.get_or_init(|| Regex::new(r"(?i)\x{ad}|&shy;|&#173;|&#xad;").unwrap());
+    let spaces = SPACES.get_or_init(|| {
+        Regex::new(r"(?i)(?:[ \t\r\n]|\x{a0}|&nbsp;|&#160;|&#xa0;){2,}").unwrap()
+    });
+    let escaped_entity = ESCAPED_ENTITY.get_or_init(|| {
+        Regex::new(r"(?i)&amp;((?:nbsp|amp|lt|gt|quot|apos|shy|mdash|ndash|hellip)|#[0-9]+|#x[0-9a-f]+);")
+            .unwrap()
+    });
+    let dash = DASH.get_or_init(|| Regex::new(r"-{2,3}").unwrap());
+
+    let tidy = |text: &str| {
+        let text = escaped_entity.replace_all(text, "&$1;");
+        let text = soft_hyphen.replace_all(&text, "");
+        let text = spaces.replace_all(&text, " ");
+        dash.replace_all(&text, "—").into_owned()
+    };
+
+    let mut output = String::with_capacity(html.len());
+    let mut cursor = 0;
+    for tag in tag.find_iter(html) {
+        output.push_str(&tidy(&html[cursor..tag.start()]));
+        output.push_str(tag.as_str());
+        cursor = tag.end();
+    }
+    output.push_str(&tidy(&html[cursor..]));
+    output
+}
+
+/// Flatten `<ruby>` markup, which html2text runs together with its base
+/// text, into either `base(reading)` or the base alone. `<rp>` fallback
+/// brackets are dropped either way.
+fn annotate_ruby(html: &str, style: RubyStyle) -> String {
+    static FALLBACK: OnceLock<Regex> = OnceLock::new();
+    static READING: OnceLock<Regex> = OnceLock::new();
+    static CONTAINER_TAG: OnceLock<Regex> = OnceLock::new();
+    let fallback = FALLBACK.get_or_init(|| Regex::new(r"(?is)<rp\b[^>]*>.*?</rp\s*>").unwrap());
+    let reading = READING.get_or_init(|| Regex::new(r"(?is)<rt\b[^>]*>(.*?)</rt\s*>").unwrap());
+    let container_tag =
+        CONTAINER_TAG.get_or_init(|| Regex::new(r"(?i)</?(?:rb|rtc)\b[^>]*>").unwrap());
+
+    let lower = html.to_ascii_lowercase();
+    let mut output = String::with_capacity(html.len());
+    let mut cursor = 0;
+    while let Some(start) = find_tag(&lower, "<ruby", cursor) {
+        let (Some(content_start), Some(end)) = (
+            lower[start..].find('>').map(|i| start + i + 1),
+            element_end(&lower, "ruby", start),
+        ) else {
+            break;
+        };
+        let content_end = lower[..end].rfind("</").unwrap_or(end).max(content_start);
+
+        let content = fallback.replace_all(&html[content_start..content_end], "");
+        let content = container_tag.replace_all(&content, "");
+        let content = reading.replace_all(&content, |captures: &regex::Captures| {
+            match (style, captures[1].trim()) {
+                (RubyStyle::Inline, text) if !text.is_empty() => format!("({text})"),
+                _ => String::new(),
+            }
+        });
+
+        output.push_str(&html[cursor..start]);
+        output.push_str(&content);
+        cursor = end;
+    }
+
+    output.push_str(&html[cursor..]);
+    output
+}
+
+/// Pull every `<img>` out of the markup, leaving a placeholder paragraph in
+/// its place.
+fn extract_pictures(html: &str) -> (String, Vec<Picture>) {
+    static IMG: OnceLock<Regex> = OnceLock::new();
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let img = IMG.get_or_init(|| Regex::new(r"(?i)<img\b[^>]*>").unwrap());
+    let attr = ATTR.get_or_init(|| {
+        Regex::new(r#"(?i)\s(src|alt)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap()
+    });
+
+    let mut pictures = Vec::new();
+    let html = img.replace_all(html, |captures: &regex::Captures| {
+        let mut picture = Picture {
+            src: String::new(),
+            alt: String::new(),
+        };
+        for attribute in attr.captures_iter(&captures[0]) {
+            let raw = attribute.get(2).or(attribute.get(3)).map_or("", |m| m.as_str());
+            let value = unescape_with(raw, resolve_predefined_entity)
+                .map_or_else(|_| raw.to_string(), |value| value.into_owned());
+            match attribute[1].to_ascii_lowercase().as_str() {
+                "src" => picture.src = value,
+                _ => picture.alt = value.trim().to_string(),
+            }
+        }
+        if picture.src.is_empty() {
+            return captures[0].to_string();
+        }
+        pictures.push(picture);
+        format!(
+            "<p>{mark}I{index}{mark}</p>",
+            mark = PLACEHOLDER_MARK,
+            index = pictures.len() - 1
+        )
+    });
+    (html.into_owned(), pictures)
+}
+
+#[cfg(feature = "image-art")]
+fn render_image_art(bytes: &[u8], style: ImageArt, width: usize) -> Option<String> {
+    crate::image_art::render(bytes, style, width)
+}
+
+#[cfg(not(feature = "image-art"))]
+fn render_image_art(_: &[u8], _: ImageArt, _: usize) -> Option<String> {
+    None
+}
+
+/// Follow the book's styles by rewriting the markup. Text a stylesheet or
+/// `style` attribute sets in italic or bold is wrapped in `<em>` or
+/// `<strong>`, the text of a centred or right-aligned block is marked, and
+/// the first line of an indented block starts with indent marks.
+fn apply_styles(html: &str, options: &ConvertOptions) -> String {
+    static STYLE_ELEMENT: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let style_element = STYLE_ELEMENT
+        .get_or_init(|| Regex::new(r"(?is)<style\b[^>]*>(.*?)</style\s*>").unwrap());
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]*>").unwrap());
+    let attr = ATTR.get_or_init(|| {
+        Regex::new(r#"(?i)\s(class|style)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap()
+    });
+
+    let lower = html.to_ascii_lowercase();
+    let mut stylesheet = options.stylesheet.clone();
+    for captures in style_element.captures_iter(html) {
+        stylesheet.extend(&Stylesheet::parse(&captures[1]));
+    }
+    if stylesheet.is_empty()
+        && !lower.contains("style")
+        && options.paragraphs != ParagraphStyle::Indented
+    {
+        return html.to_string();
+    }
+
+    let mut open: Vec<StyledElement> = Vec::new();
+    // As in print, the paragraph opening a chapter or section isn't indented
+    let mut after_heading = true;
+    let mut output = String::with_capacity(html.len() + html.len() / 20);
+    let mut cursor = 0;
+    while let Some(found) = tag.find_at(html, cursor) {
+        add_styled_text(&mut output, &mut open, &html[cursor..found.start()]);
+        let markup = found.as_str();
+        cursor = found.end();
+        let name = lower[found.start()..found.end()]
+            .trim_start_matches(['<', '/'])
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        if markup.starts_with("</") {
+            if let Some(at) = open.iter().rposition(|element| element.name == name) {
+                for element in open.drain(at..).rev() {
+                    element.close(&mut output);
+                }
+            }
+            output.push_str(markup);
+            continue;
+        }
+        output.push_str(markup);
+        if matches!(name.as_str(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "hr") {
+            after_heading = true;
+        }
+        if markup.starts_with("<!")
+            || markup.starts_with("<?")
+            || markup.ends_with("/>")
+            || VOID_ELEMENTS.contains(&name.as_str())
+        {
+            continue;
+        }
+        // Text in these elements isn't rendered as part of the chapter
+        if matches!(name.as_str(), "title" | "style" | "script") {
+            let end = lower[cursor..].find(&format!("</{name}")).map_or(html.len(), |i| cursor + i);
+            output.push_str(&html[cursor..end]);
+            cursor = end;
+            continue;
+        }
+
+        let (mut classes, mut inline) = ("", "");
+        for attribute in attr.captures_iter(markup) {
+            let value = attribute.get(2).or(attribute.get(3)).map_or("", |m| m.as_str());
+            match attribute[1].to_ascii_lowercase().as_str() {
+                "class" => classes = value,
+                _ => inline = value,
+            }
+        }
+        let classes: Vec<&str> = classes.split_whitespace().collect();
+        let inherited = open.last().map(|element| element.style).unwrap_or_default();
+        let mut style = Style::parse(inline)
+            .or(stylesheet.style_for(&name, &classes))
+            .or(inherited);
+        style.indent = match options.paragraphs {
+            ParagraphStyle::Book => style.indent,
+            ParagraphStyle::Spaced => None,
+            ParagraphStyle::Indented if name == "p" && after_heading => None,
+            ParagraphStyle::Indented if name == "p" => {
+                style.indent.filter(|&indent| indent == 0).or(Some(options.paragraph_indent))
+            }
+            ParagraphStyle::Indented => style.indent,
+        };
+        if name == "p" {
+            after_heading = false;
+        }
+        open.push(StyledElement {
+            style,
+            block: STYLED_BLOCKS.contains(&name.as_str()),
+            name,
+            text_end: None,
+        });
+    }
+
+    add_styled_text(&mut output, &mut open, &html[cursor..]);
+    for element in open.into_iter().rev() {
+        element.close(&mut output);
+    }
+    output
+}
+
+/// Turn paragraphs into plain blocks, which html2text starts on a new line
+/// rather than after a blank one.
+fn join_paragraphs(html: &str) -> String {
+    static PARAGRAPH_TAG: OnceLock<Regex> = OnceLock::new();
+    let paragraph_tag =
+        PARAGRAPH_TAG.get_or_init(|| Regex::new(r"(?i)<(/?)p(\s[^>]*)?>").unwrap());
+    paragraph_tag.replace_all(html, "<${1}div${2}>").into_owned()
+}
+
+/// An element open at some point of [`apply_styles`].
+struct StyledElement {
+    name: String,
+    /// The element's own style over what it inherits.
+    style: Style,
+    block: bool,
+    /// For a block with text of its own, where that text ends.
+    text_end: Option<usize>,
+}
+
+impl StyledElement {
+    fn close(self, output: &mut String) {
+        if let Some(end) = self.text_end
+            && matches!(self.style.align, Some(TextAlign::Center | TextAlign::Right))
+        {
+            output.insert(end, ALIGN_END_MARK);
+        }
+    }
+}
+
+// Text is marked for the innermost block it's in, unless that's inside a
+// table, and listings are left verbatim
+fn add_styled_text(output: &mut String, open: &mut [StyledElement], text: &str) {
+    let content = text.trim();
+    let inside = |names: &[&str]| open.iter().any(|element| names.contains(&element.name.as_str()));
+    if content.is_empty() || inside(&["pre"]) {
+        output.push_str(text);
+        return;
+    }
+    let style = open.last().map(|element| element.style).unwrap_or_default();
+    let italic = style.italic == Some(true) && !inside(&["em", "i"]);
+    let bold = style.bold == Some(true) && !inside(&["strong", "b"]);
+    let block = if inside(&["table"]) {
+        None
+    } else {
+        open.iter_mut().rev().find(|element| element.block)
+    };
+
+    let leading = text.len() - text.trim_start().len();
+    output.push_str(&text[..leading]);
+    if let Some(block) = &block
+        && block.text_end.is_none()
+    {
+        match block.style.align {
+            Some(TextAlign::Center) => output.push(ALIGN_CENTER_MARK),
+            Some(TextAlign::Right) => output.push(ALIGN_RIGHT_MARK),
+            _ => {}
+        }
+        output.extend(std::iter::repeat_n(INDENT_MARK, block.style.indent.unwrap_or(0)));
+    }
+    output.push_str(match (italic, bold) {
+        (true, true) => "<em><strong>",
+        (true, false) => "<em>",
+        (false, true) => "<strong>",
+        (false, false) => "",
+    });
+    output.push_str(content);
+    output.push_str(match (italic, bold) {
+        (true, true) => "</strong></em>",
+        (true, false) => "</em>",
+        (false, true) => "</strong>",
+        (false, false) => "",
+    });
+    if let Some(block) = block {
+        block.text_end = Some(output.len());
+    }
+    output.push_str(&text[leading + content.len()..]);
+}
+
+/// html2text records an element's `id` where the previous block ended, and
+/// drops ids on containers whose first child is another block. Move each id
+/// onto an empty `<span>` placed where the element's text begins instead.
+fn move_anchors_to_text(html: &str) -> String {
+    static ID_ATTR: OnceLock<Regex> = OnceLock::new();
+    let id_attr = ID_ATTR.get_or_init(|| {
+        Regex::new(r#"(?i)^(<[a-z][a-z0-9]*\b[^>]*?\s)id\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap()
+    });
+
+    let lower = html.to_ascii_lowercase();
+    let mut output = String::with_capacity(html.len() + html.len() / 20);
+    let mut pending: Vec<String> = Vec::new();
+    let mut pos = 0;
+
+    while pos < html.len() {
+        if html[pos..].starts_with('<') {
+            let Some(tag_end) = html[pos..].find('>').map(|i| pos + i + 1) else {
+                output.push_str(&html[pos..]);
+                break;
+            };
+            let tag = &html[pos..tag_end];
+            let name = lower[pos + 1..tag_end]
+                .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .next()
+                .unwrap_or_default();
+
+            // Text in these elements isn't rendered as part of the chapter
+            if matches!(name, "title" | "style" | "script") {
+                let end = lower[tag_end..]
+                    .find(&format!("</{name}"))
+                    .map_or(html.len(), |i| tag_end + i);
+                output.push_str(&html[pos..end]);
+                pos = end;
+                continue;
+            }
+
+            match id_attr.captures(tag) {
+                Some(captures) if !matches!(name, "html" | "head" | "body") => {
+                    let id = captures.get(2).or(captures.get(3)).map_or("", |m| m.as_str());
+                    output.push_str(&captures[1]);
+                    output.push_str("data-anchor");
+                    output.push_str(&tag[captures[1].len() + "id".len()..]);
+                    pending.push(id.to_string());
+                }
+                _ => output.push_str(tag),
+            }
+            pos = tag_end;
+            continue;
+        }
+
+        let c = html[pos..].chars().next().unwrap_or_default();
+        if !c.is_whitespace() {
+            for id in pending.drain(..) {
+                output.push_str(&format!(r#"<span id="{id}"></span>"#));
+            }
+        }
+        output.push(c);
+        pos += c.len_utf8();
+    }
+    output
+}
+
+/// Every `id` attribute in a fragment of markup.
+fn element_ids(html: &str) -> impl Iterator<Item = String> + '_ {
+    static ID: OnceLock<Regex> = OnceLock::new();
+    let id = ID.get_or_init(|| Regex::new(r#"(?i)\bid\s*=\s*["']([^"']+)["']"#).unwrap());
+    id.captures_iter(html).map(|captures| captures[1].to_string())
+}
+
+/// Byte offset just past the close of the `<tag>` element opening at `start`,
+/// allowing for nested elements of the same name.
+fn element_end(lower: &str, tag: &str, start: usize) -> Option<usize> {
+    let open_tag = format!("<{tag}");
+    let close_tag = format!("</{tag}");
+    let mut depth = 0;
+    let mut pos = start;
+    loop {
+        let next_open = find_tag(lower, &open_tag, pos + 1);
+        let next_close = lower[pos + 1..].find(&close_tag).map(|i| i + pos + 1)?;
+        match next_open {
+            Some(open) if open < next_close => {
+                depth += 1;
+                pos = open;
+            }
+            _ if depth > 0 => {
+                depth -= 1;
+                pos = next_close;
+            }
+            _ => return lower[next_close..].find('>').map(|i| next_close + i + 1),
+        }
+    }
+}
+
+fn find_tag(lower: &str, tag: &str, from: usize) -> Option<usize> {
+    let mut from = from;
+    while let Some(offset) = lower.get(from..)?.find(tag) {
+        let start = from + offset;
+        let next = lower[start + tag.len()..].chars().next();
+        if matches!(next, Some(c) if c.is_whitespace() || c == '>' || c == '/') {
+            return Some(start);
+        }
+        from = start + tag.len();
+    }
+    None
+}
+
+fn render_table(table_html: &str, width: usize) -> ConvertedText {
+    let natural = render_html(table_html, TABLE_MAX_WIDTH.max(width));
+    if max_line_width(&natural.text) <= width {
+        return natural;
+    }
+
+    // Squeezing a wide table into the column is fine while every column stays
+    // readable; otherwise keep the natural layout and let the reader scroll.
+    let squeezed = render_html(table_html, width);
+    let readable = match (column_widths(&natural.text), column_widths(&squeezed.text)) {
+        (Some(natural), Some(squeezed)) => {
+            squeezed.len() == natural.len()
+                && squeezed.iter().all(|&width| width >= MIN_TABLE_COLUMN_WIDTH)
+        }
+        _ => false,
+    };
+
+    if readable && max_line_width(&squeezed.text) <= width {
+        squeezed
+    } else {
+        natural
+    }
+}
+
+/// Column widths read from the first horizontal rule of a rendered table.
+fn column_widths(table: &str) -> Option<Vec<usize>> {
+    let rule = table.lines().find(|line| line.starts_with('─'))?;
+    Some(
+        rule.split(['┬', '┼', '┴'])
+            .map(|column| column.width())
+            .collect(),
+    )
+}
+
+/// Render a `<pre>` element verbatim: markup is dropped, entities decoded
+/// and tabs expanded, but lines are never re-wrapped.
+fn render_listing(pre_html: &str) -> String {
+    static LINE_BREAK: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let line_break = LINE_BREAK.get_or_init(|| Regex::new(r"(?i)<br\s*/?>").unwrap());
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]*>").unwrap());
+
+    let raw = line_break.replace_all(pre_html, "\n");
+    let raw = tag.replace_all(&raw, "");
+    let decoded = unescape_with(&raw, |entity| {
+        resolve_predefined_entity(entity).or(match entity {
+            "nbsp" => Some(" "),
+            _ => None,
+        })
+    })
+    .map(|text| text.into_owned())
+    .unwrap_or_else(|_| raw.into_owned());
+
+    let tab = " ".repeat(CODE_TAB_WIDTH);
+    let body = decoded.strip_prefix('\n').unwrap_or(&decoded).trim_end();
+    let mut output = String::with_capacity(body.len());
+    for line in body.lines() {
+        output.push_str(line.replace('\t', &tab).trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+/// Language named by a `language-*`/`lang-*` class on the `<pre>` or its
+/// `<code>` child, or by a SyntaxHighlighter-style `brush:` class.
+fn listing_language(pre_html: &str) -> Option<String> {
+    static CLASS: OnceLock<Regex> = OnceLock::new();
+    let class = CLASS.get_or_init(|| {
+        Regex::new(r#"(?i)class\s*=\s*["'][^"']*?\b(?:language-|lang-|brush:\s*)([\w+#-]+)"#)
+            .unwrap()
+    });
+
+    let opening_tags = &pre_html[..pre_html.find("</").unwrap_or(pre_html.len())];
+    class
+        .captures(opening_tags)
+        .map(|captures| captures[1].to_ascii_lowercase())
+}
+
+fn max_line_width(text: &str) -> usize {
+    text.lines().map(|line| line.width()).max().unwrap_or(0)
+}
@@ -0,0 +1,115 @@
+use crate::constants::{CRASH_REPORT_KEYS, CRASH_REPORTS_DIR};
+use std::collections::VecDeque;
+use std::fmt::{Display, Write};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What the reader was doing, kept up to date as it runs so that a crash
+/// report can say.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub book: Option<PathBuf>,
+    pub chapter: usize,
+    /// The line at the top of the screen.
+    pub offset: usize,
+    /// The last keys pressed, oldest first.
+    pub keys: VecDeque<String>,
+}
+
+static SNAPSHOT: Mutex<Snapshot> = Mutex::new(Snapshot {
+    book: None,
+    chapter: 0,
+    offset: 0,
+    keys: VecDeque::new(),
+});
+
+/// Remember where the reader is in which book.
+pub fn note_position(book: &Path, chapter: usize, offset: usize) {
+    let mut snapshot = SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner());
+    if snapshot.book.as_deref() != Some(book) {
+        snapshot.book = Some(book.to_path_buf());
+    }
+    snapshot.chapter = chapter;
+    snapshot.offset = offset;
+}
+
+/// Remember a key pressed, forgetting the oldest once there are more than
+/// [`CRASH_REPORT_KEYS`].
+pub fn note_key(key: impl Display) {
+    let mut snapshot = SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner());
+    if snapshot.keys.len() == CRASH_REPORT_KEYS {
+        snapshot.keys.pop_front();
+    }
+    snapshot.keys.push_back(key.to_string());
+}
+
+/// Remember that a key was typed into a note or a query, without saying
+/// which, so that what was written stays out of the report.
+pub fn note_typed_key() {
+    note_key("<typed>");
+}
+
+/// What's been remembered so far. A panic while it was being noted leaves
+/// nothing to give, rather than waiting on itself.
+pub fn snapshot() -> Option<Snapshot> {
+    match SNAPSHOT.try_lock() {
+        Ok(snapshot) => Some(snapshot.clone()),
+        Err(std::sync::TryLockError::Poisoned(e)) => Some(e.into_inner().clone()),
+        Err(std::sync::TryLockError::WouldBlock) => None,
+    }
+}
+
+/// A crash report for a panic, given as the hook shows it, with the
+/// backtrace and the reader's state when it happened.
+pub fn report(panic: &str, backtrace: &dyn Display, snapshot: Option<&Snapshot>) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "catatau {} crashed", env!("CARGO_PKG_VERSION"));
+    let thread = std::thread::current();
+    let _ = writeln!(report, "\nThread '{}' {panic}", thread.name().unwrap_or("<unnamed>"));
+    match snapshot {
+        Some(snapshot) => {
+            if let Some(book) = &snapshot.book {
+                let _ = writeln!(report, "\nBook:      {}", book.display());
+                let _ = writeln!(
+                    report,
+                    "Position:  chapter {}, line {}",
+                    snapshot.chapter + 1,
+                    snapshot.offset + 1
+                );
+            }
+            let keys: Vec<&str> = snapshot.keys.iter().map(String::as_str).collect();
+            let _ = writeln!(report, "Last keys: {}", keys.join(" "));
+        }
+        None => report.push_str("\nThe reader's state couldn't be read.\n"),
+    }
+    let _ = write!(report, "\nBacktrace:\n{backtrace}");
+    report
+}
+
+/// Write a report into `crash-reports/` in `dir`, named for when it was
+/// written and by which process, and give its path. A report already
+/// there is never written over.
+pub fn write_report(dir: &Path, report: &str) -> io::Result<PathBuf> {
+    let dir = dir.join(CRASH_REPORTS_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let stem = format!("crash-{seconds}-{}", std::process::id());
+    let mut attempt = 0;
+    loop {
+        let name = match attempt {
+            0 => format!("{stem}.txt"),
+            n => format!("{stem}-{n}.txt"),
+        };
+        let path = dir.join(name);
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(report.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::constants::{MAX_TEXT_INDENT, TEXT_INDENT_COLUMNS_PER_EM};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// How the lines of a block are placed across the text column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// The part of a book's styles the reader follows. Each property is `None`
+/// where nothing sets it, so that it's inherited from the parent element.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub italic: Option<bool>,
+    pub bold: Option<bool>,
+    pub align: Option<TextAlign>,
+    /// Columns the first line of a block is indented by.
+    pub indent: Option<usize>,
+}
+
+impl Style {
+    /// Read the properties the reader follows from a declaration block, as
+    /// in `font-style: italic; text-indent: 1em`, ignoring the rest.
+    pub fn parse(declarations: &str) -> Self {
+        let mut style = Self::default();
+        for declaration in declarations.split(';') {
+            let Some((name, value)) = declaration.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().to_ascii_lowercase();
+            let value = value.trim_end_matches("!important").trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "font-style" => {
+                    style.italic = match value {
+                        "italic" | "oblique" => Some(true),
+                        "normal" => Some(false),
+                        _ => style.italic,
+                    }
+                }
+                "font-weight" => {
+                    style.bold = match value {
+                        "bold" | "bolder" => Some(true),
+                        "normal" | "lighter" => Some(false),
+                        weight => weight.parse::<u16>().map(|weight| weight >= 600).ok(),
+                    }
+                    .or(style.bold)
+                }
+                "text-align" => {
+                    style.align = match value {
+                        "left" | "start" | "justify" => Some(TextAlign::Left),
+                        "center" => Some(TextAlign::Center),
+                        "right" | "end" => Some(TextAlign::Right),
+                        _ => style.align,
+                    }
+                }
+                "text-indent" => style.indent = indent_columns(value).or(style.indent),
+                _ => {}
+            }
+        }
+        style
+    }
+
+    /// This style with anything it leaves unset taken from `other`.
+    pub fn or(self, other: Style) -> Style {
+        Style {
+            italic: self.italic.or(other.italic),
+            bold: self.bold.or(other.bold),
+            align: self.align.or(other.align),
+            indent: self.indent.or(other.indent),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Style::default()
+    }
+}
+
+/// A rule's selector: an element name, classes, or both, as in `p.centre`.
+#[derive(Debug, Clone, PartialEq)]
+struct Selector {
+    tag: Option<String>,
+    classes: Vec<String>,
+}
+
+impl Selector {
+    // Anything with combinators, ids, attributes or pseudo-classes in it is
+    // passed over rather than matched too widely
+    fn parse(selector: &str) -> Option<Self> {
+        static SIMPLE: OnceLock<Regex> = OnceLock::new();
+        let simple = SIMPLE.get_or_init(|| {
+            Regex::new(r"^([a-zA-Z][a-zA-Z0-9]*)?((?:\.[-_a-zA-Z0-9]+)*)$").unwrap()
+        });
+        let captures = simple.captures(selector.trim())?;
+        let tag = captures.get(1).map(|tag| tag.as_str().to_ascii_lowercase());
+        let classes: Vec<String> =
+            captures[2].split('.').filter(|class| !class.is_empty()).map(str::to_string).collect();
+        (tag.is_some() || !classes.is_empty()).then_some(Self { tag, classes })
+    }
+
+    fn matches(&self, tag: &str, classes: &[&str]) -> bool {
+        self.tag.as_deref().is_none_or(|name| name == tag)
+            && self.classes.iter().all(|class| classes.contains(&class.as_str()))
+    }
+
+    // Classes outweigh element names, as CSS has it
+    fn specificity(&self) -> (usize, bool) {
+        (self.classes.len(), self.tag.is_some())
+    }
+}
+
+/// The rules of a book's stylesheets that the reader can follow.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stylesheet {
+    rules: Vec<(Selector, Style)>,
+}
+
+impl Stylesheet {
+    /// Parse a stylesheet, keeping rules with simple selectors that set a
+    /// property the reader follows. At-rules, such as `@media` blocks, are
+    /// skipped.
+    pub fn parse(css: &str) -> Self {
+        static COMMENT: OnceLock<Regex> = OnceLock::new();
+        let comment = COMMENT.get_or_init(|| Regex::new(r"(?s)/\*.*?\*/").unwrap());
+        let css = comment.replace_all(css, "");
+
+        let mut rules = Vec::new();
+        let mut rest = css.trim_start();
+        while !rest.is_empty() {
+            if rest.starts_with('@') {
+                rest = skip_at_rule(rest).trim_start();
+                continue;
+            }
+            let Some((selectors, after)) = rest.split_once('{') else {
+                break;
+            };
+            let (declarations, after) = after.split_once('}').unwrap_or((after, ""));
+            let style = Style::parse(declarations);
+            if !style.is_empty() {
+                let selectors = selectors.split(',').filter_map(Selector::parse);
+                rules.extend(selectors.map(|selector| (selector, style)));
+            }
+            rest = after.trim_start();
+        }
+        Self { rules }
+    }
+
+    /// Add the rules of a stylesheet read after this one.
+    pub fn extend(&mut self, other: &Stylesheet) {
+        self.rules.extend(other.rules.iter().cloned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The style the rules give an element named `tag` with `classes`, with
+    /// more specific rules, then later ones, taking precedence.
+    pub fn style_for(&self, tag: &str, classes: &[&str]) -> Style {
+        let mut matching: Vec<(usize, &(Selector, Style))> = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, (selector, _))| selector.matches(tag, classes))
+            .collect();
+        matching.sort_by_key(|(order, (selector, _))| (selector.specificity(), *order));
+        matching
+            .into_iter()
+            .rev()
+            .fold(Style::default(), |style, (_, (_, rule))| style.or(*rule))
+    }
+}
+
+// The rest of the stylesheet after an at-rule, whether it ends with `;` or
+// a block, which may hold blocks of its own
+fn skip_at_rule(css: &str) -> &str {
+    let mut depth = 0;
+    for (at, c) in css.char_indices() {
+        match c {
+            ';' if depth == 0 => return &css[at + 1..],
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth <= 0 {
+                    return &css[at + 1..];
+                }
+            }
+            _ => {}
+        }
+    }
+    ""
+}
+
+// A cell is about half as wide as it is tall, so an em is two columns. An
+// indent in percent of the page, or a negative one, is left alone.
+fn indent_columns(value: &str) -> Option<usize> {
+    let number_end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let number: f64 = value[..number_end].parse().ok()?;
+    let ems = match value[number_end..].trim() {
+        "em" | "rem" => number,
+        "" if number == 0.0 => 0.0,
+        "px" => number / 16.0,
+        "pt" => number / 12.0,
+        _ => return None,
+    };
+    (ems >= 0.0).then(|| {
+        ((ems * TEXT_INDENT_COLUMNS_PER_EM as f64).round() as usize).min(MAX_TEXT_INDENT)
+    })
+}
@@ -1,21 +1,34 @@
 use crate::{
     constants::{
-        CHAPTER_CACHE_SIZE, HTML_TEXT_WIDTH, MAX_CHAPTER_SIZE, MAX_DECOMPRESSED_RATIO,
-        MAX_EPUB_SIZE, MIN_CONTENT_LENGTH, SEARCH_CONTEXT_AFTER_LINES, SEARCH_CONTEXT_LINES,
+        CHAPTER_CACHE_BYTES, HTML_TEXT_WIDTH, MAX_CHAPTER_SIZE, MAX_DECOMPRESSED_RATIO,
+        MAX_EPUB_SIZE, MAX_IMAGE_SIZE, MIN_CONTENT_LENGTH, MIN_TEXT_WIDTH, PARAGRAPH_INDENT,
+        SEARCH_CONTEXT_AFTER_LINES, SEARCH_CONTEXT_LINES,
     },
+    convert::{
+        self, AlignedLines, Block, ConvertOptions, HeadingLines, ImageArt, Link, ParagraphStyle,
+        RubyStyle, TextSpan,
+    },
+    css::{Stylesheet, TextAlign},
     error::EpubError,
+    narration::{self, Clip},
+    search,
 };
 use lru::LruCache;
+use percent_encoding::percent_decode_str;
 use quick_xml::{Reader, events::Event};
+use rayon::prelude::*;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fs::File,
     io::Read,
-    num::NonZeroUsize,
-    path::Path,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    },
 };
 use tracing::{debug, info, warn};
+use unicode_width::UnicodeWidthStr;
 use zip::ZipArchive;
 
 #[derive(Debug, Clone)]
@@ -23,6 +36,117 @@ pub struct Chapter {
     pub content: String,
     pub id: String,
     pub title: String,
+    /// Tables and code listings, which are not wrapped to the text width.
+    pub blocks: Vec<Block>,
+    pub links: Vec<Link>,
+    /// Output line of each element id, for jumping to `chapter.xhtml#id`.
+    pub anchors: HashMap<String, usize>,
+    /// Emphasis, strong text, code and image alt text, by line.
+    pub spans: Vec<TextSpan>,
+    heading_lines: Vec<HeadingLines>,
+    aligned: Vec<AlignedLines>,
+    /// Where each line of `content` starts, so lines can be reached
+    /// without walking the text from the top.
+    line_starts: Vec<usize>,
+}
+
+/// A heading in a chapter's converted text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub line: usize,
+    /// 1 for `<h1>` down to 6 for `<h6>`.
+    pub level: usize,
+    pub text: String,
+}
+
+impl Chapter {
+    /// Lines in the converted text, as `content.lines()` counts them.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Line `index` of the converted text, counting from zero.
+    pub fn line(&self, index: usize) -> Option<&str> {
+        self.lines_from(index).next()
+    }
+
+    /// The line holding byte `offset` of the converted text.
+    fn line_at(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset).saturating_sub(1)
+    }
+
+    /// The lines of the converted text from line `index` to the end.
+    pub fn lines_from(&self, index: usize) -> std::str::Lines<'_> {
+        let start = self.line_starts.get(index).copied().unwrap_or(self.content.len());
+        self.content[start..].lines()
+    }
+
+    /// Roughly how many bytes the chapter takes in memory.
+    pub fn memory_size(&self) -> usize {
+        let links: usize = self.links.iter().map(|link| link.target.len()).sum();
+        let anchors: usize = self.anchors.keys().map(String::len).sum();
+        self.content.len()
+            + self.line_starts.len() * std::mem::size_of::<usize>()
+            + self.blocks.len() * std::mem::size_of::<Block>()
+            + self.links.len() * std::mem::size_of::<Link>()
+            + links
+            + self.anchors.len() * std::mem::size_of::<(String, usize)>()
+            + anchors
+            + self.spans.len() * std::mem::size_of::<TextSpan>()
+            + self.heading_lines.len() * std::mem::size_of::<HeadingLines>()
+            + self.aligned.len() * std::mem::size_of::<AlignedLines>()
+    }
+
+    /// The table or code listing containing `line`, if any.
+    pub fn block_at(&self, line: usize) -> Option<&Block> {
+        self.blocks.iter().find(|block| block.lines.contains(&line))
+    }
+
+    /// The chapter's headings in order, with the text of headings wrapped
+    /// onto several lines joined back together.
+    pub fn headings(&self) -> Vec<Heading> {
+        self.heading_lines
+            .iter()
+            .map(|heading| {
+                let lines = self.lines_from(heading.lines.start).take(heading.lines.len());
+                let words: Vec<&str> = lines.flat_map(str::split_whitespace).collect();
+                Heading {
+                    line: heading.lines.start,
+                    level: heading.level,
+                    text: words.join(" "),
+                }
+            })
+            .collect()
+    }
+
+    /// The level of the heading `line` is part of, if it's part of one.
+    pub fn heading_level(&self, line: usize) -> Option<usize> {
+        self.heading_lines
+            .iter()
+            .find(|heading| heading.lines.contains(&line))
+            .map(|heading| heading.level)
+    }
+
+    /// How the book's styles align `line`, where they set it apart from
+    /// the left.
+    pub fn alignment(&self, line: usize) -> Option<TextAlign> {
+        self.aligned
+            .iter()
+            .find(|aligned| aligned.lines.contains(&line))
+            .map(|aligned| aligned.align)
+    }
+
+    /// The styled runs of text on `line`.
+    pub fn spans_on(&self, line: usize) -> &[TextSpan] {
+        let start = self.spans.partition_point(|span| span.line < line);
+        let end = self.spans.partition_point(|span| span.line <= line);
+        &self.spans[start..end]
+    }
+
+    /// The line an element id was rendered on.
+    pub fn anchor_line(&self, anchor: &str) -> Option<usize> {
+        self.anchors.get(anchor).copied()
+    }
 }
 
 impl std::ops::Deref for Chapter {
@@ -33,6 +157,14 @@ impl std::ops::Deref for Chapter {
     }
 }
 
+/// Where an internal link leads: a chapter, and optionally an element id
+/// within it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkTarget {
+    pub chapter: usize,
+    pub anchor: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct SearchResult {
@@ -42,78 +174,795 @@ pub struct SearchResult {
     pub position: usize,
 }
 
+/// Series membership as recorded by calibre (`calibre:series` and
+/// `calibre:series_index`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub name: String,
+    pub index: Option<f64>,
+}
+
+impl std::fmt::Display for Series {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) if index.fract() == 0.0 => write!(f, "Book {} of {}", index, self.name),
+            Some(index) => write!(f, "Book {:.1} of {}", index, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ManifestItem {
+    pub(crate) id: String,
+    pub(crate) href: String,
+    pub(crate) media_type: String,
+}
+
+#[derive(Debug)]
+pub(crate) struct OpfData {
+    pub(crate) metadata: HashMap<String, String>,
+    /// Every `dc:creator` and `dc:identifier`, in order.
+    pub(crate) creators: Vec<String>,
+    pub(crate) identifiers: Vec<String>,
+    pub(crate) manifest: Vec<ManifestItem>,
+    pub(crate) spine: Vec<String>,
+    pub(crate) spine_ids: Vec<String>,
+    pub(crate) opf_path: String,
+    /// The SMIL media overlay narrating each content document, by href.
+    pub(crate) media_overlays: HashMap<String, String>,
+}
+
+/// Names of every entry in the archive, built once so that href resolution can
+/// fall back to case-insensitive and suffix matches without rescanning the zip.
+#[derive(Debug, Default)]
+pub(crate) struct ArchiveIndex {
+    names: HashSet<String>,
+    lowercase: HashMap<String, String>,
+}
+
+impl ArchiveIndex {
+    pub(crate) fn new(archive: &Archive) -> Self {
+        let mut index = Self::default();
+        for name in archive.file_names() {
+            index
+                .lowercase
+                .entry(name.to_lowercase())
+                .or_insert_with(|| name.to_string());
+            index.names.insert(name.to_string());
+        }
+        index
+    }
+
+    /// Whether an entry is named exactly `path`.
+    pub(crate) fn contains(&self, path: &str) -> bool {
+        self.names.contains(path)
+    }
+
+    pub(crate) fn lookup(&self, path: &str) -> Option<String> {
+        if self.names.contains(path) {
+            return Some(path.to_string());
+        }
+        self.lowercase.get(&path.to_lowercase()).cloned()
+    }
+
+    fn lookup_by_suffix(&self, path: &str) -> Option<String> {
+        // Malformed EPUBs often omit the content directory (OEBPS/, OPS/, Text/...)
+        // from their hrefs; prefer the shallowest entry that ends with the href.
+        let suffix = format!("/{}", path.to_lowercase());
+        self.lowercase
+            .iter()
+            .filter(|(lower, _)| lower.ends_with(&suffix))
+            .min_by_key(|(lower, _)| lower.len())
+            .map(|(_, name)| name.clone())
+    }
+}
+
+/// The book's bytes as the zip reader reads them: mapped into memory with
+/// the `mmap` feature, so that reading an entry is a copy rather than a
+/// seek and a read on the file each time, or straight from the file.
+/// Either way clones share the one open book, each reading from its own
+/// position, so threads can read at once without waiting on each other.
+#[cfg(feature = "mmap")]
+pub(crate) type BookData = std::io::Cursor<SharedMap>;
+#[cfg(not(feature = "mmap"))]
+pub(crate) type BookData = SharedFile;
+
+/// A zip archive of the book. Cloning it is cheap: the clone shares the
+/// parsed directory of entries as well as the file.
+pub(crate) type Archive = ZipArchive<BookData>;
+
+/// Read `file` as a zip archive, mapping it into memory first with the
+/// `mmap` feature.
+pub(crate) fn open_archive(file: File) -> Result<Archive, EpubError> {
+    #[cfg(feature = "mmap")]
+    let data = {
+        // SAFETY: the map is only ever read. A book truncated in place
+        // while it's open is the one case this can't guard against; tools
+        // that replace the file, as pandoc and most editors do, leave the
+        // map be.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        std::io::Cursor::new(SharedMap(Arc::new(map)))
+    };
+    #[cfg(not(feature = "mmap"))]
+    let data = SharedFile {
+        len: file.metadata()?.len(),
+        file: Arc::new(BookFile::from(file)),
+        position: 0,
+    };
+    Ok(ZipArchive::new(data)?)
+}
+
+/// A book mapped into memory, shared between clones of the archive.
+#[cfg(feature = "mmap")]
+#[derive(Debug, Clone)]
+pub(crate) struct SharedMap(Arc<memmap2::Mmap>);
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for SharedMap {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A book file shared between clones of the archive, each reading at its
+/// own position with positioned reads rather than moving the file's.
+#[cfg(not(feature = "mmap"))]
+#[derive(Debug, Clone)]
+pub(crate) struct SharedFile {
+    file: Arc<BookFile>,
+    len: u64,
+    position: u64,
+}
+
+#[cfg(all(not(feature = "mmap"), any(unix, windows)))]
+type BookFile = File;
+// Without positioned reads, clones take turns to seek the one file and
+// read from it
+#[cfg(all(not(feature = "mmap"), not(any(unix, windows))))]
+type BookFile = Mutex<File>;
+
+#[cfg(not(feature = "mmap"))]
+impl Read for SharedFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let read = std::os::unix::fs::FileExt::read_at(&*self.file, buf, self.position)?;
+        #[cfg(windows)]
+        let read = std::os::windows::fs::FileExt::seek_read(&*self.file, buf, self.position)?;
+        #[cfg(not(any(unix, windows)))]
+        let read = {
+            use std::io::Seek;
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            file.seek(std::io::SeekFrom::Start(self.position))?;
+            file.read(buf)?
+        };
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+impl std::io::Seek for SharedFile {
+    fn seek(&mut self, to: std::io::SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match to {
+            std::io::SeekFrom::Start(position) => (position, 0),
+            std::io::SeekFrom::End(offset) => (self.len, offset),
+            std::io::SeekFrom::Current(offset) => (self.position, offset),
+        };
+        self.position = base.checked_add_signed(offset).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before the start")
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// Converted chapters keyed by (chapter index, wrap width), forgetting the
+/// least recently read once together they take more than a budget of
+/// memory. The last chapter put in is kept whatever its size.
 #[derive(Debug)]
-struct OpfData {
-    metadata: HashMap<String, String>,
-    spine: Vec<String>,
-    opf_path: String,
+struct ChapterCache {
+    chapters: LruCache<(usize, usize), Arc<Chapter>>,
+    /// Roughly how much memory the cached chapters take.
+    bytes: usize,
+    budget: usize,
+}
+
+impl ChapterCache {
+    fn new(budget: usize) -> Self {
+        Self {
+            chapters: LruCache::unbounded(),
+            bytes: 0,
+            budget,
+        }
+    }
+
+    fn get(&mut self, key: &(usize, usize)) -> Option<&Arc<Chapter>> {
+        self.chapters.get(key)
+    }
+
+    fn contains(&self, key: &(usize, usize)) -> bool {
+        self.chapters.contains(key)
+    }
+
+    fn put(&mut self, key: (usize, usize), chapter: Arc<Chapter>) {
+        self.bytes += chapter.memory_size();
+        if let Some(old) = self.chapters.put(key, chapter) {
+            self.bytes -= old.memory_size();
+        }
+        while self.bytes > self.budget && self.chapters.len() > 1 {
+            let Some((_, evicted)) = self.chapters.pop_lru() else {
+                break;
+            };
+            self.bytes -= evicted.memory_size();
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 struct ChapterInfo {
     href: String,
+    path: String,
+    /// Archive path of the media overlay narrating the chapter, if any.
+    overlay: Option<String>,
+    summary: OnceLock<ChapterSummary>,
+}
+
+/// Title and length classification of a chapter, worked out the first time
+/// the chapter (or the table of contents) is needed rather than at open.
+#[derive(Debug, Clone)]
+struct ChapterSummary {
     title: String,
+    skippable: bool,
+    word_count: usize,
+}
+
+/// Safety limits applied while opening and reading an EPUB.
+///
+/// The defaults guard against oversized files and zip bombs; raise them for
+/// legitimately large books (image-heavy editions, omnibuses).
+#[derive(Debug, Clone)]
+pub struct EpubReaderOptions {
+    pub max_file_size: u64,
+    pub max_chapter_size: usize,
+    pub max_decompressed_ratio: usize,
+    pub eager_scan: bool,
+    pub image_art: ImageArt,
+    pub ruby: RubyStyle,
+    pub typography: bool,
+    pub paragraphs: ParagraphStyle,
+    /// Columns a paragraph's first line is indented by, with
+    /// [`ParagraphStyle::Indented`].
+    pub paragraph_indent: usize,
+    /// Roughly how many bytes of converted chapters are kept in memory.
+    pub cache_budget: usize,
+}
+
+impl Default for EpubReaderOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: MAX_EPUB_SIZE,
+            max_chapter_size: MAX_CHAPTER_SIZE,
+            max_decompressed_ratio: MAX_DECOMPRESSED_RATIO,
+            eager_scan: false,
+            image_art: ImageArt::Off,
+            ruby: RubyStyle::Inline,
+            typography: false,
+            paragraphs: ParagraphStyle::Book,
+            paragraph_indent: PARAGRAPH_INDENT,
+            cache_budget: CHAPTER_CACHE_BYTES,
+        }
+    }
+}
+
+impl EpubReaderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    pub fn max_chapter_size(mut self, bytes: usize) -> Self {
+        self.max_chapter_size = bytes;
+        self
+    }
+
+    pub fn max_decompressed_ratio(mut self, ratio: usize) -> Self {
+        self.max_decompressed_ratio = ratio;
+        self
+    }
+
+    /// Work out every chapter's title and length while opening, instead of
+    /// on first use.
+    pub fn eager_scan(mut self, eager: bool) -> Self {
+        self.eager_scan = eager;
+        self
+    }
+
+    /// Draw pictures in chapters as half-block or braille art, at the text
+    /// width, instead of showing only their alt text.
+    pub fn image_art(mut self, style: ImageArt) -> Self {
+        self.image_art = style;
+        self
+    }
+
+    /// Show ruby readings (furigana) after their base text, or leave them out.
+    pub fn ruby(mut self, style: RubyStyle) -> Self {
+        self.ruby = style;
+        self
+    }
+
+    /// Tidy up typographic artifacts (soft hyphens, doubled spaces, literal
+    /// `&nbsp;`, `--`) while converting chapters.
+    pub fn typography(mut self, tidy: bool) -> Self {
+        self.typography = tidy;
+        self
+    }
+
+    /// Set paragraphs apart with blank lines or with indents, and how many
+    /// columns an indent is.
+    pub fn paragraphs(mut self, style: ParagraphStyle, indent: usize) -> Self {
+        self.paragraphs = style;
+        self.paragraph_indent = indent;
+        self
+    }
+
+    /// Keep up to about this many bytes of converted chapters in memory,
+    /// for moving back and forth between them without converting them
+    /// again. The chapter being read is kept however large it is.
+    pub fn cache_budget(mut self, bytes: usize) -> Self {
+        self.cache_budget = bytes;
+        self
+    }
+
+    pub fn open(self, path: &Path) -> Result<EpubReader, EpubError> {
+        EpubReader::with_options(path, self)
+    }
 }
 
 #[derive(Debug)]
 pub struct EpubReader {
-    archive: Arc<Mutex<ZipArchive<File>>>,
-    chapter_cache: Arc<Mutex<LruCache<usize, Chapter>>>,
+    path: PathBuf,
+    archive: Archive,
+    chapter_cache: Arc<Mutex<ChapterCache>>,
     chapter_info: Vec<ChapterInfo>,
-    opf_path: String,
+    /// Stylesheets read so far, by archive path.
+    stylesheets: Mutex<HashMap<String, Arc<Stylesheet>>>,
+    text_width: AtomicUsize,
+    options: EpubReaderOptions,
     pub title: String,
     pub author: String,
+    /// Every author named, where `author` is the last.
+    pub authors: Vec<String>,
+    /// ISBNs, UUIDs and the like, as the book gives them.
+    pub identifiers: Vec<String>,
+    pub publisher: Option<String>,
+    /// When the book was published (`dc:date`), as the book writes it.
+    pub published: Option<String>,
+    /// The book's main language (`dc:language`), as a BCP 47 tag.
+    pub language: Option<String>,
+    pub series: Option<Series>,
+    pub custom_columns: BTreeMap<String, String>,
+    /// Archive path of the cover image, if the book names one.
+    cover: Option<String>,
+    file_hash: OnceLock<String>,
 }
 
 impl EpubReader {
+    /// The file the book was opened from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The [`file_hash`](crate::progress::file_hash) of the book, read
+    /// through once however often it's asked for.
+    pub fn file_hash(&self) -> std::io::Result<String> {
+        if let Some(hash) = self.file_hash.get() {
+            return Ok(hash.clone());
+        }
+        let hash = crate::progress::file_hash(&self.path)?;
+        Ok(self.file_hash.get_or_init(|| hash).clone())
+    }
+
+    pub fn options(&self) -> &EpubReaderOptions {
+        &self.options
+    }
+
     pub fn chapter_count(&self) -> usize {
         self.chapter_info.len()
     }
 
-    pub fn get_chapter(&self, index: usize) -> Result<Chapter, EpubError> {
+    /// Column width chapter text is wrapped to.
+    pub fn text_width(&self) -> usize {
+        self.text_width.load(Ordering::Relaxed)
+    }
+
+    /// Re-wrap chapter text at a new width. Chapters already converted at
+    /// other widths stay cached and simply age out.
+    pub fn set_text_width(&self, width: usize) {
+        self.text_width
+            .store(width.max(MIN_TEXT_WIDTH), Ordering::Relaxed);
+    }
+
+    pub fn get_chapter(&self, index: usize) -> Result<Arc<Chapter>, EpubError> {
         if index >= self.chapter_info.len() {
             return Err(EpubError::InvalidChapterIndex(index));
         }
 
+        let width = self.text_width();
+        let key = (index, width);
+
         {
             let mut cache = self
                 .chapter_cache
                 .lock()
                 .map_err(|_| EpubError::CacheLockError)?;
 
-            if let Some(chapter) = cache.get(&index) {
+            if let Some(chapter) = cache.get(&key) {
                 debug!("Chapter {} loaded from cache", index);
-                return Ok(chapter.clone());
+                return Ok(Arc::clone(chapter));
             }
         }
 
-        debug!("Loading chapter {} from archive", index);
-        let chapter = self.load_chapter(index)?;
+        debug!("Loading chapter {} from archive at width {}", index, width);
+        let chapter = Arc::new(self.load_chapter(index, width)?);
 
         {
             let mut cache = self
                 .chapter_cache
                 .lock()
                 .map_err(|_| EpubError::CacheLockError)?;
-            cache.put(index, chapter.clone());
+            cache.put(key, Arc::clone(&chapter));
         }
 
         Ok(chapter)
     }
 
-    fn load_chapter(&self, index: usize) -> Result<Chapter, EpubError> {
+    /// Whether the chapter is cached at the current text width, so that
+    /// [`EpubReader::get_chapter`] won't have to convert it.
+    pub fn is_chapter_cached(&self, index: usize) -> bool {
+        let key = (index, self.text_width());
+        self.chapter_cache.lock().is_ok_and(|cache| cache.contains(&key))
+    }
+
+    /// Convert a chapter without going through the cache, for passes over
+    /// the whole book that would otherwise push out the chapters being read.
+    pub fn read_chapter_uncached(&self, index: usize) -> Result<Chapter, EpubError> {
+        if index >= self.chapter_info.len() {
+            return Err(EpubError::InvalidChapterIndex(index));
+        }
+        self.load_chapter(index, self.text_width())
+    }
+
+    /// A chapter's text as the search index sees it: converted at the
+    /// standard width, with pictures left as their captions.
+    pub fn chapter_index_text(&self, index: usize) -> Result<String, EpubError> {
+        if index >= self.chapter_info.len() {
+            return Err(EpubError::InvalidChapterIndex(index));
+        }
+        let content = self.read_chapter_html(index)?;
+        let options = ConvertOptions {
+            image_art: ImageArt::Off,
+            ruby: self.options.ruby,
+            typography: self.options.typography,
+            ..ConvertOptions::default()
+        };
+        Ok(convert::convert_html_with(&content, HTML_TEXT_WIDTH, &options, &|_| None).text)
+    }
+
+    /// Title of a chapter, extracted from its markup on first request.
+    pub fn chapter_title(&self, index: usize) -> String {
+        self.chapter_summary(index)
+            .map(|summary| summary.title.clone())
+            .unwrap_or_else(|| format!("Chapter {}", index + 1))
+    }
+
+    /// Whether a spine document has too little text to be worth stopping on
+    /// (cover pages, title pages, blank separators) or could not be read.
+    pub fn is_skippable(&self, index: usize) -> bool {
+        self.chapter_summary(index)
+            .map(|summary| summary.skippable)
+            .unwrap_or(true)
+    }
+
+    /// Number of words in a chapter's text, not counting stray punctuation.
+    pub fn chapter_word_count(&self, index: usize) -> usize {
+        self.chapter_summary(index)
+            .map(|summary| summary.word_count)
+            .unwrap_or(0)
+    }
+
+    /// Summarize every chapter not yet seen, spreading the work across the
+    /// rayon pool with one archive handle per worker thread.
+    pub fn scan_chapters(&self) {
+        let pending: Vec<usize> = (0..self.chapter_info.len())
+            .filter(|&index| self.chapter_info[index].summary.get().is_none())
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+
+        debug!("Scanning {} chapters", pending.len());
+        pending.par_iter().for_each_init(
+            || self.archive.clone(),
+            |archive, &index| {
+                let info = &self.chapter_info[index];
+                match Self::read_archive_entry(archive, &info.path) {
+                    Ok(html) => {
+                        Self::summarize_chapter(info, index, &html);
+                    }
+                    Err(e) => warn!("Could not load chapter {}: {}", info.href, e),
+                }
+            },
+        );
+    }
+
+    fn chapter_summary(&self, index: usize) -> Option<&ChapterSummary> {
+        let info = self.chapter_info.get(index)?;
+        if let Some(summary) = info.summary.get() {
+            return Some(summary);
+        }
+
+        match self.read_chapter_html(index) {
+            Ok(html) => Some(Self::summarize_chapter(info, index, &html)),
+            Err(e) => {
+                warn!("Could not load chapter {}: {}", info.href, e);
+                None
+            }
+        }
+    }
+
+    // Summaries are made from the plain text at a fixed width, whichever way
+    // the chapter is first read, so they don't depend on the screen
+    fn summarize_chapter<'a>(
+        info: &'a ChapterInfo,
+        index: usize,
+        html: &str,
+    ) -> &'a ChapterSummary {
+        info.summary.get_or_init(|| {
+            let text = convert::html_to_text(html, HTML_TEXT_WIDTH);
+            let skippable = text.trim().len() < MIN_CONTENT_LENGTH;
+            if skippable {
+                debug!("Chapter {} is too short to stop on: {}", index, info.href);
+            }
+            ChapterSummary {
+                title: Self::extract_chapter_title(html, &text, index + 1),
+                skippable,
+                word_count: text
+                    .split_whitespace()
+                    .filter(|word| word.chars().any(char::is_alphanumeric))
+                    .count(),
+            }
+        })
+    }
+
+    /// Resolve an `href` found in a chapter to the spine entry it points at.
+    /// External links, and links to documents outside the spine, give `None`.
+    pub fn resolve_link(&self, from_chapter: usize, href: &str) -> Option<LinkTarget> {
+        if Self::is_external_link(href) {
+            return None;
+        }
+
+        let (path, anchor) = href.split_once('#').unwrap_or((href, ""));
+        let anchor = (!anchor.is_empty())
+            .then(|| percent_decode_str(anchor).decode_utf8_lossy().into_owned());
+        let from = self.chapter_info.get(from_chapter)?;
+
+        let chapter = if path.trim().is_empty() {
+            from_chapter
+        } else {
+            let base = Path::new(&from.path).parent().unwrap_or(Path::new(""));
+            Self::href_variants(path).iter().find_map(|candidate| {
+                let resolved = Self::normalize_archive_path(&base.join(candidate));
+                self.chapter_info
+                    .iter()
+                    .position(|info| info.path.eq_ignore_ascii_case(&resolved))
+            })?
+        };
+
+        Some(LinkTarget { chapter, anchor })
+    }
+
+    /// Whether an `href` carries a URL scheme (`https:`, `mailto:`, …) and so
+    /// points outside the book.
+    pub fn is_external_link(href: &str) -> bool {
+        href.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        })
+    }
+
+    /// The chapter's XHTML exactly as stored in the book.
+    pub fn get_chapter_html(&self, index: usize) -> Result<String, EpubError> {
+        if index >= self.chapter_info.len() {
+            return Err(EpubError::InvalidChapterIndex(index));
+        }
+        self.read_chapter_html(index)
+    }
+
+    /// Uncompressed size of each chapter's XHTML, a rough measure of how much
+    /// of the book it makes up. Chapters missing from the archive count as empty.
+    pub fn chapter_sizes(&self) -> Vec<u64> {
+        let mut archive = self.archive.clone();
+        self.chapter_info
+            .iter()
+            .map(|info| archive.by_name(&info.path).map_or(0, |file| file.size()))
+            .collect()
+    }
+
+    fn read_chapter_html(&self, index: usize) -> Result<String, EpubError> {
         let info = &self.chapter_info[index];
-        let mut archive = self
-            .archive
-            .lock()
-            .map_err(|_| EpubError::CacheLockError)?;
+        Self::read_archive_entry(&mut self.archive.clone(), &info.path)
+    }
+
+    /// Bytes of an image referenced from a chapter, if it's in the book and
+    /// not unreasonably large.
+    fn read_image(&self, chapter_path: &str, src: &str) -> Option<Vec<u8>> {
+        let path = self.image_path(chapter_path, src)?;
+        let mut archive = self.archive.clone();
+        let file = archive.by_name(&path).ok()?;
+        if file.size() > MAX_IMAGE_SIZE {
+            warn!("Skipping image {}: {} bytes", path, file.size());
+            return None;
+        }
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.take(MAX_IMAGE_SIZE).read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    // The archive path of an image referenced from a chapter, if it's there
+    fn image_path(&self, chapter_path: &str, src: &str) -> Option<String> {
+        if Self::is_external_link(src) {
+            return None;
+        }
+        let chapter_dir = Path::new(chapter_path).parent().unwrap_or(Path::new(""));
+        let mut archive = self.archive.clone();
+        Self::href_variants(src).iter().find_map(|candidate| {
+            let path = Self::normalize_archive_path(&chapter_dir.join(candidate));
+            archive.by_name(&path).is_ok().then_some(path)
+        })
+    }
+
+    /// The stylesheets a chapter links to, in the order it links them. Each
+    /// is read from the book once.
+    fn linked_stylesheet(&self, chapter_path: &str, html: &str) -> Stylesheet {
+        static LINK: OnceLock<regex::Regex> = OnceLock::new();
+        static HREF: OnceLock<regex::Regex> = OnceLock::new();
+        let link = LINK.get_or_init(|| regex::Regex::new(r"(?i)<link\b[^>]*>").unwrap());
+        let href = HREF.get_or_init(|| {
+            regex::Regex::new(r#"(?i)\shref\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap()
+        });
+
+        let chapter_dir = Path::new(chapter_path).parent().unwrap_or(Path::new(""));
+        let mut stylesheet = Stylesheet::default();
+        for tag in link.find_iter(html) {
+            let tag = tag.as_str();
+            let Some(captures) = href.captures(tag) else {
+                continue;
+            };
+            let src = captures.get(1).or(captures.get(2)).map_or("", |m| m.as_str());
+            if !tag.to_ascii_lowercase().contains("stylesheet") || Self::is_external_link(src) {
+                continue;
+            }
+            let path = Self::normalize_archive_path(&chapter_dir.join(src));
+            let mut stylesheets = self.stylesheets.lock().unwrap_or_else(|e| e.into_inner());
+            let sheet = stylesheets.entry(path).or_insert_with_key(|path| {
+                let mut archive = self.archive.clone();
+                match Self::read_archive_entry(&mut archive, path) {
+                    Ok(css) => Arc::new(Stylesheet::parse(&css)),
+                    Err(e) => {
+                        warn!("Could not read stylesheet {}: {}", path, e);
+                        Arc::default()
+                    }
+                }
+            });
+            stylesheet.extend(sheet);
+        }
+        stylesheet
+    }
+
+    /// The bytes of an image a chapter refers to by `src`, if it's in the
+    /// book and not unreasonably large.
+    pub fn chapter_image(&self, index: usize, src: &str) -> Option<Vec<u8>> {
+        let info = self.chapter_info.get(index)?;
+        self.read_image(&info.path, src)
+    }
+
+    /// Where in the book an image a chapter refers to by `src` is stored,
+    /// telling apart images that share a file name in different folders.
+    pub fn chapter_image_path(&self, index: usize, src: &str) -> Option<String> {
+        let info = self.chapter_info.get(index)?;
+        self.image_path(&info.path, src)
+    }
+
+    /// Whether the chapter has narration, from an EPUB 3 media overlay.
+    pub fn has_narration(&self, index: usize) -> bool {
+        self.chapter_info
+            .get(index)
+            .is_some_and(|info| info.overlay.is_some())
+    }
+
+    /// The clips of the chapter's media overlay that read out the chapter,
+    /// in order, with their text and audio as archive paths.
+    pub fn narration(&self, index: usize) -> Result<Vec<Clip>, EpubError> {
+        let info = self
+            .chapter_info
+            .get(index)
+            .ok_or(EpubError::InvalidChapterIndex(index))?;
+        let Some(smil_path) = &info.overlay else {
+            return Ok(Vec::new());
+        };
+        let smil = Self::read_archive_entry(&mut self.archive.clone(), smil_path)?;
+        let smil_dir = Path::new(smil_path).parent().unwrap_or(Path::new(""));
+        let resolve = |src: &str| {
+            let src = percent_decode_str(src).decode_utf8_lossy().into_owned();
+            Self::normalize_archive_path(&smil_dir.join(src))
+        };
+        Ok(narration::parse_smil(&smil)?
+            .into_iter()
+            .map(|clip| Clip {
+                document: resolve(&clip.document),
+                audio: resolve(&clip.audio),
+                ..clip
+            })
+            .filter(|clip| clip.document == info.path)
+            .collect())
+    }
+
+    /// The bytes of an entry of the book, such as a narration's audio, as
+    /// long as it's no bigger than a whole book is allowed to be.
+    pub fn read_entry(&self, path: &str) -> Result<Vec<u8>, EpubError> {
+        let max = self.options.max_file_size;
+        let mut archive = self.archive.clone();
+        let entry = archive.by_name(path)?;
+        let mut bytes = Vec::with_capacity(entry.size().min(max) as usize);
+        entry.take(max + 1).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > max {
+            return Err(EpubError::FileTooLarge {
+                size: bytes.len() as u64,
+                max,
+            });
+        }
+        Ok(bytes)
+    }
 
-        let content =
-            Self::resolve_and_read_file_from_archive(&mut archive, &info.href, &self.opf_path)?;
+    /// The cover image's bytes and the name it has in the book, if it names
+    /// one that's there and not unreasonably large.
+    pub fn cover_image(&self) -> Option<(String, Vec<u8>)> {
+        let path = self.cover.as_ref()?;
+        let mut archive = self.archive.clone();
+        let file = archive.by_name(path).ok()?;
+        if file.size() > MAX_IMAGE_SIZE {
+            warn!("Skipping cover {}: {} bytes", path, file.size());
+            return None;
+        }
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.take(MAX_IMAGE_SIZE).read_to_end(&mut bytes).ok()?;
+        Some((path.clone(), bytes))
+    }
 
-        let text_content = html2text::from_read(content.as_bytes(), HTML_TEXT_WIDTH);
+    fn load_chapter(&self, index: usize, width: usize) -> Result<Chapter, EpubError> {
+        let info = &self.chapter_info[index];
+        let content = self.read_chapter_html(index)?;
+
+        let options = ConvertOptions {
+            image_art: self.options.image_art,
+            ruby: self.options.ruby,
+            typography: self.options.typography,
+            stylesheet: self.linked_stylesheet(&info.path, &content),
+            paragraphs: self.options.paragraphs,
+            paragraph_indent: self.options.paragraph_indent,
+        };
+        let load_image = |src: &str| self.read_image(&info.path, src);
+        let converted = convert::convert_html_with(&content, width, &options, &load_image);
+        let text_content = converted.text;
+        let summary = Self::summarize_chapter(info, index, &content);
 
-        if text_content.len() > MAX_CHAPTER_SIZE {
+        if text_content.len() > self.options.max_chapter_size {
             warn!(
                 "Chapter {} exceeds size limit: {} bytes",
                 index,
@@ -121,54 +970,88 @@ impl EpubReader {
             );
             return Err(EpubError::ChapterTooLarge {
                 size: text_content.len(),
-                max: MAX_CHAPTER_SIZE,
+                max: self.options.max_chapter_size,
             });
         }
 
+        let line_starts = std::iter::once(0)
+            .chain(text_content.match_indices('\n').map(|(at, _)| at + 1))
+            .filter(|&start| start < text_content.len())
+            .collect();
         Ok(Chapter {
-            title: info.title.clone(),
+            title: summary.title.clone(),
+            line_starts,
             content: text_content,
             id: info.href.clone(),
+            blocks: converted.blocks,
+            links: converted.links,
+            anchors: converted.anchors,
+            spans: converted.spans,
+            heading_lines: converted.headings,
+            aligned: converted.aligned,
         })
     }
-
 }
 
 impl EpubReader {
+    #[allow(dead_code)]
     pub fn new(path: &Path) -> Result<Self, EpubError> {
+        Self::with_options(path, EpubReaderOptions::default())
+    }
+
+    pub fn with_options(path: &Path, options: EpubReaderOptions) -> Result<Self, EpubError> {
         info!("Opening EPUB file: {:?}", path);
 
-        let metadata = std::fs::metadata(path)?;
-        let file_size = metadata.len();
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
 
-        if file_size > MAX_EPUB_SIZE {
+        if file_size > options.max_file_size {
             return Err(EpubError::FileTooLarge {
                 size: file_size,
-                max: MAX_EPUB_SIZE,
+                max: options.max_file_size,
             });
         }
 
         debug!("EPUB file size: {} bytes", file_size);
 
-        let file = File::open(path)?;
-        let mut archive = ZipArchive::new(file)?;
+        let mut archive = open_archive(file)?;
+
+        let archive_index = ArchiveIndex::new(&archive);
 
         let opf_path = Self::find_opf_path(&mut archive)?;
         let opf_data = Self::parse_opf(&mut archive, &opf_path)?;
-        let chapter_info = Self::extract_chapter_info(&mut archive, opf_data.spine, &opf_data.opf_path)?;
+        let mut chapter_info = Self::extract_chapter_info(
+            &mut archive,
+            &archive_index,
+            opf_data.spine,
+            &opf_data.opf_path,
+            &options,
+        )?;
 
         info!("Loaded EPUB with {} chapters", chapter_info.len());
 
-        let file = File::open(path)?;
-        let archive = Arc::new(Mutex::new(ZipArchive::new(file)?));
-        let cache_size = NonZeroUsize::new(CHAPTER_CACHE_SIZE).unwrap();
-        let chapter_cache = Arc::new(Mutex::new(LruCache::new(cache_size)));
+        for info in &mut chapter_info {
+            info.overlay = opf_data.media_overlays.get(&info.href).and_then(|href| {
+                Self::resolve_archive_path(&archive_index, href, &opf_data.opf_path)
+            });
+        }
 
-        Ok(EpubReader {
+        let series = Self::parse_calibre_series(&opf_data.metadata);
+        let custom_columns = Self::parse_calibre_custom_columns(&opf_data.metadata);
+        let cover = opf_data.metadata.get("cover").and_then(|href| {
+            Self::resolve_archive_path(&archive_index, href, &opf_data.opf_path)
+        });
+
+        let chapter_cache = Arc::new(Mutex::new(ChapterCache::new(options.cache_budget)));
+
+        let reader = EpubReader {
+            path: path.to_path_buf(),
             archive,
             chapter_cache,
             chapter_info,
-            opf_path: opf_data.opf_path,
+            stylesheets: Mutex::new(HashMap::new()),
+            text_width: AtomicUsize::new(HTML_TEXT_WIDTH),
+            options,
             title: opf_data
                 .metadata
                 .get("title")
@@ -179,10 +1062,77 @@ impl EpubReader {
                 .get("creator")
                 .cloned()
                 .unwrap_or_else(|| "Unknown".to_string()),
+            authors: opf_data.creators,
+            identifiers: opf_data.identifiers,
+            publisher: opf_data.metadata.get("publisher").cloned(),
+            published: opf_data.metadata.get("date").cloned(),
+            language: opf_data.metadata.get("language").cloned(),
+            series,
+            custom_columns,
+            cover,
+            file_hash: OnceLock::new(),
+        };
+
+        if reader.options.eager_scan {
+            reader.scan_chapters();
+        }
+
+        Ok(reader)
+    }
+
+    fn parse_calibre_series(metadata: &HashMap<String, String>) -> Option<Series> {
+        let name = metadata.get("calibre:series")?.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        Some(Series {
+            name: name.to_string(),
+            index: metadata
+                .get("calibre:series_index")
+                .and_then(|index| index.trim().parse().ok()),
         })
     }
 
-    fn find_opf_path(archive: &mut ZipArchive<File>) -> Result<String, EpubError> {
+    fn parse_calibre_custom_columns(metadata: &HashMap<String, String>) -> BTreeMap<String, String> {
+        // calibre stores each custom column as a JSON blob describing the column,
+        // with the actual value under "#value#" and the display label under "name".
+        let mut columns = BTreeMap::new();
+
+        for (key, content) in metadata {
+            let Some(column) = key.strip_prefix("calibre:user_metadata:") else {
+                continue;
+            };
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(content) else {
+                warn!("Ignoring malformed calibre column {}", column);
+                continue;
+            };
+
+            let value = match &json["#value#"] {
+                serde_json::Value::Null => continue,
+                serde_json::Value::String(value) => value.clone(),
+                serde_json::Value::Array(values) => values
+                    .iter()
+                    .map(|v| v.as_str().map(str::to_string).unwrap_or_else(|| v.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                other => other.to_string(),
+            };
+            if value.is_empty() {
+                continue;
+            }
+
+            let label = json["name"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| column.trim_start_matches('#').to_string());
+            columns.insert(label, value);
+        }
+
+        columns
+    }
+
+    pub(crate) fn find_opf_path(archive: &mut Archive) -> Result<String, EpubError> {
         let mut container_file = match archive.by_name("META-INF/container.xml") {
             Ok(file) => file,
             Err(zip::result::ZipError::FileNotFound) => return Err(EpubError::ContainerNotFound),
@@ -215,7 +1165,10 @@ impl EpubReader {
         Err(EpubError::OpfNotFound)
     }
 
-    fn parse_opf(archive: &mut ZipArchive<File>, opf_path: &str) -> Result<OpfData, EpubError> {
+    pub(crate) fn parse_opf(
+        archive: &mut Archive,
+        opf_path: &str,
+    ) -> Result<OpfData, EpubError> {
         let mut opf_file = match archive.by_name(opf_path) {
             Ok(file) => file,
             Err(zip::result::ZipError::FileNotFound) => return Err(EpubError::OpfNotFound),
@@ -229,9 +1182,15 @@ impl EpubReader {
 
         let mut metadata = HashMap::new();
         let mut manifest = HashMap::new();
+        let mut manifest_items = Vec::new();
         let mut spine = Vec::new();
+        let mut spine_ids = Vec::new();
         let mut buf = Vec::new();
         let mut current_section = String::new();
+        let mut cover_href = None;
+        let mut overlay_ids = Vec::new();
+        let mut creators = Vec::new();
+        let mut identifiers = Vec::new();
 
         loop {
             match reader.read_event_into(&mut buf)? {
@@ -248,16 +1207,41 @@ impl EpubReader {
                     b"item" if current_section == "manifest" => {
                         let mut id = String::new();
                         let mut href = String::new();
+                        let mut media_type = String::new();
+                        let mut properties = String::new();
+                        let mut overlay = None;
                         for attr in e.attributes() {
                             let attr = attr?;
                             match attr.key.as_ref() {
                                 b"id" => id = String::from_utf8(attr.value.to_vec())?,
                                 b"href" => href = String::from_utf8(attr.value.to_vec())?,
+                                b"media-type" => {
+                                    media_type = String::from_utf8(attr.value.to_vec())?
+                                }
+                                b"properties" => {
+                                    properties = String::from_utf8(attr.value.to_vec())?
+                                }
+                                b"media-overlay" => {
+                                    overlay = Some(String::from_utf8(attr.value.to_vec())?)
+                                }
                                 _ => {}
                             }
                         }
+                        // EPUB 3 marks the cover in the manifest
+                        if properties.split_whitespace().any(|property| property == "cover-image")
+                        {
+                            cover_href = Some(href.clone());
+                        }
+                        if let Some(overlay) = overlay {
+                            overlay_ids.push((href.clone(), overlay));
+                        }
                         if !id.is_empty() && !href.is_empty() {
-                            manifest.insert(id, href);
+                            manifest.insert(id.clone(), href.clone());
+                            manifest_items.push(ManifestItem {
+                                id,
+                                href,
+                                media_type,
+                            });
                         }
                     }
                     b"itemref" if current_section == "spine" => {
@@ -268,8 +1252,26 @@ impl EpubReader {
                                 if let Some(href) = manifest.get(&idref) {
                                     spine.push(href.clone());
                                 }
+                                spine_ids.push(idref);
+                            }
+                        }
+                    }
+                    b"meta" if current_section == "metadata" => {
+                        let mut name = None;
+                        let mut content = None;
+                        for attr in e.attributes() {
+                            let attr = attr?;
+                            match attr.key.as_ref() {
+                                b"name" => name = Some(attr.unescape_value()?.to_string()),
+                                b"content" => content = Some(attr.unescape_value()?.to_string()),
+                                _ => {}
                             }
                         }
+                        if let (Some(name), Some(content)) = (name, content)
+                            && (name.starts_with("calibre:") || name == "cover")
+                        {
+                            metadata.insert(name, content);
+                        }
                     }
                     b"dc:title" if current_section == "metadata" => {
                         if let Ok(Event::Text(text)) = reader.read_event_into(&mut buf) {
@@ -278,7 +1280,32 @@ impl EpubReader {
                     }
                     b"dc:creator" if current_section == "metadata" => {
                         if let Ok(Event::Text(text)) = reader.read_event_into(&mut buf) {
-                            metadata.insert("creator".to_string(), text.unescape()?.to_string());
+                            let creator = text.unescape()?.to_string();
+                            creators.push(creator.clone());
+                            metadata.insert("creator".to_string(), creator);
+                        }
+                    }
+                    b"dc:identifier" if current_section == "metadata" => {
+                        if let Ok(Event::Text(text)) = reader.read_event_into(&mut buf) {
+                            identifiers.push(text.unescape()?.trim().to_string());
+                        }
+                    }
+                    b"dc:publisher" | b"dc:date" if current_section == "metadata" => {
+                        let key = match e.name().as_ref() {
+                            b"dc:date" => "date",
+                            _ => "publisher",
+                        };
+                        if let Ok(Event::Text(text)) = reader.read_event_into(&mut buf) {
+                            metadata
+                                .entry(key.to_string())
+                                .or_insert(text.unescape()?.trim().to_string());
+                        }
+                    }
+                    b"dc:language" if current_section == "metadata" => {
+                        if let Ok(Event::Text(text)) = reader.read_event_into(&mut buf) {
+                            metadata
+                                .entry("language".to_string())
+                                .or_insert(text.unescape()?.trim().to_string());
                         }
                     }
                     _ => {}
@@ -299,61 +1326,82 @@ impl EpubReader {
             return Err(EpubError::InvalidOpfStructure);
         }
 
+        // EPUB 2 names the cover's manifest id in a <meta name="cover">
+        if let Some(href) =
+            cover_href.or_else(|| manifest.get(metadata.get("cover")?).cloned())
+        {
+            metadata.insert("cover".to_string(), href);
+        } else {
+            metadata.remove("cover");
+        }
+
+        let media_overlays = overlay_ids
+            .into_iter()
+            .filter_map(|(href, id)| Some((href, manifest.get(&id)?.clone())))
+            .collect();
+
         Ok(OpfData {
             metadata,
+            creators,
+            identifiers,
+            manifest: manifest_items,
             spine,
+            spine_ids,
             opf_path: opf_path.to_string(),
+            media_overlays,
         })
     }
 
     fn extract_chapter_info(
-        archive: &mut ZipArchive<File>,
+        archive: &mut Archive,
+        archive_index: &ArchiveIndex,
         spine: Vec<String>,
         opf_path: &str,
+        options: &EpubReaderOptions,
     ) -> Result<Vec<ChapterInfo>, EpubError> {
         let mut chapter_info = Vec::new();
+        let mut seen_paths = HashSet::new();
 
         for (index, href) in spine.iter().enumerate() {
-            match Self::resolve_and_read_file_from_archive(archive, href, opf_path) {
-                Ok(content) => {
-                    Self::validate_decompression_ratio(archive, href)?;
-
-                    let text_content = html2text::from_read(content.as_bytes(), HTML_TEXT_WIDTH);
+            let Some(path) = Self::resolve_archive_path(archive_index, href, opf_path) else {
+                warn!(
+                    "Skipping spine entry {}: {}",
+                    index,
+                    EpubError::ChapterNotFound(href.clone())
+                );
+                continue;
+            };
 
-                    if text_content.trim().len() < MIN_CONTENT_LENGTH {
-                        debug!("Skipping chapter {} (too short): {}", index, href);
-                        continue;
-                    }
+            if !seen_paths.insert(path.clone()) {
+                warn!("Skipping duplicate spine entry {}: {}", index, href);
+                continue;
+            }
 
-                    let chapter_title =
-                        Self::extract_chapter_title(&content, &text_content, chapter_info.len() + 1);
+            Self::validate_decompression_ratio(archive, &path, options.max_decompressed_ratio)?;
 
-                    chapter_info.push(ChapterInfo {
-                        href: href.clone(),
-                        title: chapter_title,
-                    });
-                }
-                Err(e) => {
-                    warn!("Could not load chapter {}: {}", href, e);
-                    continue;
-                }
-            }
+            chapter_info.push(ChapterInfo {
+                href: href.clone(),
+                path,
+                overlay: None,
+                summary: OnceLock::new(),
+            });
         }
 
         Ok(chapter_info)
     }
 
     fn validate_decompression_ratio(
-        archive: &mut ZipArchive<File>,
+        archive: &mut Archive,
         filename: &str,
+        max_ratio: usize,
     ) -> Result<(), EpubError> {
         if let Ok(file) = archive.by_name(filename) {
             let compressed = file.compressed_size();
             let decompressed = file.size();
 
-            if compressed > 0 {
-                let ratio = (decompressed / compressed) as usize;
-                if ratio > MAX_DECOMPRESSED_RATIO {
+            if let Some(ratio) = decompressed.checked_div(compressed) {
+                let ratio = ratio as usize;
+                if ratio > max_ratio {
                     return Err(EpubError::DecompressionBomb {
                         compressed,
                         decompressed,
@@ -365,49 +1413,77 @@ impl EpubReader {
         Ok(())
     }
 
-    fn resolve_and_read_file_from_archive(
-        archive: &mut ZipArchive<File>,
+    pub(crate) fn read_archive_entry(
+        archive: &mut Archive,
+        path: &str,
+    ) -> Result<String, EpubError> {
+        let mut file = archive.by_name(path)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    pub(crate) fn resolve_archive_path(
+        archive_index: &ArchiveIndex,
         href: &str,
         opf_path: &str,
-    ) -> Result<String, EpubError> {
+    ) -> Option<String> {
         let opf_dir = Path::new(opf_path).parent().unwrap_or(Path::new(""));
-        let resolved_path = opf_dir.join(href);
-        let resolved_path_str = resolved_path.to_string_lossy();
 
-        if let Ok(mut file) = archive.by_name(&resolved_path_str) {
-            let mut content = String::new();
-            file.read_to_string(&mut content)?;
-            return Ok(content);
-        }
+        for candidate in Self::href_variants(href) {
+            let resolved_path = Self::normalize_archive_path(&opf_dir.join(&candidate));
+            if let Some(path) = archive_index.lookup(&resolved_path) {
+                return Some(path);
+            }
 
-        // Fallback for malformed EPUBs: try the original href as-is
-        if let Ok(mut file) = archive.by_name(href) {
-            let mut content = String::new();
-            file.read_to_string(&mut content)?;
-            return Ok(content);
-        }
+            // Fallback for malformed EPUBs: try the original href as-is
+            if let Some(path) = archive_index.lookup(&candidate) {
+                return Some(path);
+            }
 
-        let fallback_paths = Self::generate_fallback_paths(href);
-        for path in fallback_paths {
-            if let Ok(mut file) = archive.by_name(&path) {
-                let mut content = String::new();
-                file.read_to_string(&mut content)?;
-                return Ok(content);
+            if let Some(path) = archive_index.lookup_by_suffix(&candidate) {
+                return Some(path);
             }
         }
 
-        Err(EpubError::ChapterNotFound(href.to_string()))
+        None
+    }
+
+    fn normalize_archive_path(path: &Path) -> String {
+        // Zip entry names never contain `.` or `..` segments, so collapse them
+        // the way a browser would before looking the path up.
+        let mut segments: Vec<String> = Vec::new();
+        for component in path.components() {
+            match component {
+                std::path::Component::ParentDir => {
+                    segments.pop();
+                }
+                std::path::Component::Normal(segment) => {
+                    segments.push(segment.to_string_lossy().into_owned());
+                }
+                _ => {}
+            }
+        }
+        segments.join("/")
     }
 
-    fn generate_fallback_paths(href: &str) -> Vec<String> {
-        // Fallback paths for malformed EPUBs that don't follow spec (many exist)
-        vec![
-            format!("OEBPS/{}", href),
-            format!("OPS/{}", href),
-            format!("Text/{}", href),
-            format!("EPUB/{}", href),
-            format!("content/{}", href),
-        ]
+    fn href_variants(href: &str) -> Vec<String> {
+        // Packagers disagree on whether the zip entry or the href carries the
+        // percent-encoding, so try the href as written, decoded and re-encoded.
+        let href = href.split('#').next().unwrap_or(href).trim();
+        let mut variants = vec![href.to_string()];
+
+        let decoded = percent_decode_str(href).decode_utf8_lossy().into_owned();
+        if !variants.contains(&decoded) {
+            variants.push(decoded);
+        }
+
+        let encoded = href.replace(' ', "%20");
+        if !variants.contains(&encoded) {
+            variants.push(encoded);
+        }
+
+        variants
     }
 
     fn extract_chapter_title(
@@ -435,18 +1511,17 @@ impl EpubReader {
         ];
 
         for pattern in &title_patterns {
-            if let Ok(re) = regex::Regex::new(pattern) {
-                if let Some(captures) = re.captures(html_content) {
-                    if let Some(title) = captures.get(1) {
-                        let title_text = title.as_str().trim();
-                        if !title_text.is_empty() && title_text.len() < 100 {
-                            return Some(
-                                html2text::from_read(title_text.as_bytes(), 200)
-                                    .trim()
-                                    .to_string(),
-                            );
-                        }
-                    }
+            if let Ok(re) = regex::Regex::new(pattern)
+                && let Some(captures) = re.captures(html_content)
+                && let Some(title) = captures.get(1)
+            {
+                let title_text = title.as_str().trim();
+                if !title_text.is_empty() && title_text.width() < 100 {
+                    return Some(
+                        html2text::from_read(title_text.as_bytes(), 200)
+                            .trim()
+                            .to_string(),
+                    );
                 }
             }
         }
@@ -457,8 +1532,8 @@ impl EpubReader {
         let first_line = text_content.lines().next()?.trim();
 
         if !first_line.is_empty()
-            && first_line.len() < 100
-            && first_line.len() > 3
+            && first_line.width() < 100
+            && first_line.width() > 3
             && !first_line.ends_with('.')
             && first_line.chars().any(|c| c.is_alphabetic())
         {
@@ -468,12 +1543,22 @@ impl EpubReader {
         }
     }
 
-    #[allow(dead_code)]
+    /// Lines matching `query`, read as a [`search::Query`], with the lines
+    /// around them.
     pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        self.search_chapters(query, 0..self.chapter_count())
+    }
+
+    /// Like [`search`](Self::search), but only looking in `chapters`.
+    pub fn search_chapters(
+        &self,
+        query: &str,
+        chapters: impl IntoIterator<Item = usize>,
+    ) -> Vec<SearchResult> {
+        let query = search::Query::parse(query);
         let mut results = Vec::new();
-        let query_lower = query.to_lowercase();
 
-        for chapter_index in 0..self.chapter_count() {
+        for chapter_index in chapters {
             let chapter = match self.get_chapter(chapter_index) {
                 Ok(ch) => ch,
                 Err(e) => {
@@ -481,29 +1566,32 @@ impl EpubReader {
                     continue;
                 }
             };
+            if !query.allows_chapter(&chapter.content) {
+                continue;
+            }
 
-            let lines: Vec<&str> = chapter.content.lines().collect();
-
-            for (line_index, line) in lines.iter().enumerate() {
-                let line_lower = line.to_lowercase();
-                if line_lower.contains(&query_lower) {
-                    let position: usize = lines[..line_index]
-                        .iter()
-                        .map(|l| l.len() + 1)
-                        .sum();
-
-                    let start = line_index.saturating_sub(SEARCH_CONTEXT_LINES);
-                    let end = std::cmp::min(line_index + SEARCH_CONTEXT_AFTER_LINES, lines.len());
-                    let context_lines = &lines[start..end];
-                    let context = context_lines.join("\n");
-
-                    results.push(SearchResult {
-                        chapter_index,
-                        line_number: line_index,
-                        context: context.to_string(),
-                        position,
-                    });
+            // No term holds a newline, so matching the whole text finds the
+            // same lines as matching line by line
+            let mut last_line = None;
+            for found in query.find(&chapter.content) {
+                let line_index = chapter.line_at(found.start);
+                if last_line.replace(line_index) == Some(line_index) {
+                    continue;
                 }
+
+                let start = line_index.saturating_sub(SEARCH_CONTEXT_LINES);
+                let end = std::cmp::min(
+                    line_index + SEARCH_CONTEXT_AFTER_LINES,
+                    chapter.line_count(),
+                );
+                let context: Vec<&str> = chapter.lines_from(start).take(end - start).collect();
+
+                results.push(SearchResult {
+                    chapter_index,
+                    line_number: line_index,
+                    context: context.join("\n"),
+                    position: chapter.line_starts[line_index],
+                });
             }
         }
 
@@ -513,7 +1601,7 @@ impl EpubReader {
     #[allow(dead_code)]
     pub fn get_chapter_line_count(&self, chapter_index: usize) -> usize {
         match self.get_chapter(chapter_index) {
-            Ok(chapter) => chapter.content.lines().count(),
+            Ok(chapter) => chapter.line_count(),
             Err(_) => 0,
         }
     }
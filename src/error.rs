@@ -1,4 +1,5 @@
 use std::fmt;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum EpubError {
@@ -135,3 +136,116 @@ impl From<std::io::Error> for UiError {
     }
 }
 
+#[cfg(feature = "calibre")]
+#[derive(Debug)]
+pub enum CalibreError {
+    Database(rusqlite::Error),
+    BookNotFound(String),
+    /// The books matching, as "Title by Author (id N)".
+    AmbiguousBook(Vec<String>),
+    ColumnNotFound(String),
+    UnsupportedColumn { label: String, datatype: String },
+}
+
+#[cfg(feature = "calibre")]
+impl fmt::Display for CalibreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalibreError::Database(err) => write!(f, "Calibre database error: {}", err),
+            CalibreError::BookNotFound(book) => {
+                write!(f, "No EPUB in the Calibre library matches {}", book)
+            }
+            CalibreError::AmbiguousBook(books) => {
+                write!(f, "Several books match: {}", books.join("; "))
+            }
+            CalibreError::ColumnNotFound(label) => {
+                write!(f, "The Calibre library has no #{} column", label)
+            }
+            CalibreError::UnsupportedColumn { label, datatype } => {
+                write!(f, "Column #{} holds {}, not a number", label, datatype)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "calibre")]
+impl std::error::Error for CalibreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CalibreError::Database(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "calibre")]
+impl From<rusqlite::Error> for CalibreError {
+    fn from(err: rusqlite::Error) -> Self {
+        CalibreError::Database(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io { path: PathBuf, source: std::io::Error },
+    /// The file isn't TOML, or has a setting that's unknown or out of range.
+    Invalid { path: PathBuf, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => {
+                write!(f, "Can't read config file {}: {}", path.display(), source)
+            }
+            ConfigError::Invalid { path, message } => {
+                write!(f, "Bad config file {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Invalid { .. } => None,
+        }
+    }
+}
+
+/// Why a remote control request, as sent to `--listen`, wasn't carried out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteError {
+    /// The line sent wasn't a JSON-RPC request.
+    Parse(String),
+    UnknownMethod(String),
+    InvalidParams(String),
+    /// The reader couldn't do what was asked, as for a chapter it hasn't got.
+    Failed(String),
+}
+
+impl RemoteError {
+    /// The JSON-RPC 2.0 error code.
+    pub fn code(&self) -> i64 {
+        match self {
+            RemoteError::Parse(_) => -32700,
+            RemoteError::UnknownMethod(_) => -32601,
+            RemoteError::InvalidParams(_) => -32602,
+            RemoteError::Failed(_) => -32000,
+        }
+    }
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteError::Parse(message) => write!(f, "Bad request: {}", message),
+            RemoteError::UnknownMethod(method) => write!(f, "Unknown method '{}'", method),
+            RemoteError::InvalidParams(message) => write!(f, "Bad params: {}", message),
+            RemoteError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for RemoteError {}
@@ -0,0 +1,217 @@
+use crate::epub::EpubReader;
+use crate::markdown::{self, Reference};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::warn;
+
+/// Where images go, inside the directory a book is exported to.
+const IMAGES_DIR: &str = "images";
+
+/// What a book's chapters are written out as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Markdown converted from the chapters' markup, keeping headings,
+    /// emphasis, links and images.
+    #[default]
+    Markdown,
+    /// The text as the reader shows it.
+    Text,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Text => "txt",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "text" | "txt" => Ok(Self::Text),
+            other => Err(format!("unknown export format '{other}' (expected md or txt)")),
+        }
+    }
+}
+
+/// Write each chapter of the book to a file of its own in `dir`, named by
+/// its number and title, and give the files written. Links between
+/// chapters lead to their files and the places in them, and for Markdown
+/// the images are copied into `images/` beside them. Chapters with no text, such as blank
+/// separators, and chapters that can't be read are left out.
+pub fn export_book(epub: &EpubReader, format: Format, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let names = file_names(epub, format);
+    let targets = match format {
+        Format::Markdown => link_targets(epub),
+        Format::Text => HashSet::new(),
+    };
+    let mut images = Images::default();
+    let mut written = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        let text = match format {
+            Format::Markdown => match epub.get_chapter_html(index) {
+                Ok(html) => markdown::html_to_markdown(&html, &mut |reference, url| {
+                    match reference {
+                        Reference::Link => match epub.resolve_link(index, url) {
+                            Some(target) => match target.anchor {
+                                Some(anchor) => format!("{}#{anchor}", names[target.chapter]),
+                                None => names[target.chapter].clone(),
+                            },
+                            None => url.to_string(),
+                        },
+                        Reference::Image => copy_image(epub, index, url, dir, &mut images),
+                        Reference::Anchor if targets.contains(&(index, url.to_string())) => {
+                            url.to_string()
+                        }
+                        Reference::Anchor => String::new(),
+                    }
+                }),
+                Err(e) => {
+                    warn!("Could not read chapter {}: {}", index + 1, e);
+                    continue;
+                }
+            },
+            Format::Text => match epub.read_chapter_uncached(index) {
+                Ok(chapter) => {
+                    let lines: Vec<&str> = chapter.content.lines().map(str::trim_end).collect();
+                    format!("{}\n", lines.join("\n").trim_matches('\n'))
+                }
+                Err(e) => {
+                    warn!("Could not read chapter {}: {}", index + 1, e);
+                    continue;
+                }
+            },
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        let path = dir.join(name);
+        std::fs::write(&path, text)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+// The places in chapters that links in the book lead to, so that only
+// those get anchors
+fn link_targets(epub: &EpubReader) -> HashSet<(usize, String)> {
+    let mut targets = HashSet::new();
+    for index in 0..epub.chapter_count() {
+        let Ok(html) = epub.get_chapter_html(index) else {
+            continue;
+        };
+        for href in markdown::links(&html) {
+            if let Some(target) = epub.resolve_link(index, &href)
+                && let Some(anchor) = target.anchor
+            {
+                targets.insert((target.chapter, anchor));
+            }
+        }
+    }
+    targets
+}
+
+// `03-the-voyage-out.md`, numbered with as many digits as the last chapter
+// needs so the files sort in reading order
+fn file_names(epub: &EpubReader, format: Format) -> Vec<String> {
+    let count = epub.chapter_count();
+    let digits = count.to_string().len().max(2);
+    (0..count)
+        .map(|index| {
+            let slug = slug(&epub.chapter_title(index));
+            format!("{:0digits$}-{slug}.{}", index + 1, format.extension())
+        })
+        .collect()
+}
+
+fn slug(title: &str) -> String {
+    let words: Vec<String> = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut slug = String::new();
+    for word in words {
+        if slug.chars().count() + word.chars().count() > 40 {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+    if slug.is_empty() {
+        slug.push_str("chapter");
+    }
+    slug
+}
+
+// The images copied so far, by where they are in the book, and the file
+// names they were given
+#[derive(Default)]
+struct Images {
+    copied: HashMap<String, String>,
+    names: HashSet<String>,
+}
+
+// Copy an image out of the book once, giving where it was put relative to
+// the chapter files, or the `src` as it was if it isn't in the book
+fn copy_image(
+    epub: &EpubReader,
+    chapter: usize,
+    src: &str,
+    dir: &Path,
+    images: &mut Images,
+) -> String {
+    let Some(archive_path) = epub.chapter_image_path(chapter, src) else {
+        return src.to_string();
+    };
+    if let Some(path) = images.copied.get(&archive_path) {
+        return path.clone();
+    }
+    let Some(bytes) = epub.chapter_image(chapter, src) else {
+        return src.to_string();
+    };
+    let name = unused_name(&archive_path, &images.names);
+    let images_dir = dir.join(IMAGES_DIR);
+    let written = std::fs::create_dir_all(&images_dir)
+        .and_then(|()| std::fs::write(images_dir.join(&name), bytes));
+    if let Err(e) = written {
+        warn!("Could not write image {}: {}", name, e);
+        return src.to_string();
+    }
+    let path = format!("{IMAGES_DIR}/{name}");
+    images.names.insert(name);
+    images.copied.insert(archive_path, path.clone());
+    path
+}
+
+// The image's own file name, or `map-2.png` and so on when an image from
+// another folder of the book already has it
+fn unused_name(archive_path: &str, taken: &HashSet<String>) -> String {
+    let path = Path::new(archive_path);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "image".to_string());
+    if !taken.contains(&name) {
+        return name;
+    }
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| format!("{stem}-{n}{extension}"))
+        .find(|name| !taken.contains(name))
+        .unwrap()
+}
@@ -0,0 +1,60 @@
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+use std::sync::OnceLock;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::ThemeSet,
+    parsing::SyntaxSet,
+};
+
+// Used when the requested theme isn't one of syntect's bundled ones
+const THEME_NAME: &str = "base16-ocean.dark";
+
+struct Highlighter {
+    syntaxes: SyntaxSet,
+    themes: ThemeSet,
+}
+
+// Loading the bundled syntaxes takes a moment, so only do it once a listing
+// actually needs highlighting
+fn highlighter() -> &'static Highlighter {
+    static HIGHLIGHTER: OnceLock<Highlighter> = OnceLock::new();
+    HIGHLIGHTER.get_or_init(|| Highlighter {
+        syntaxes: SyntaxSet::load_defaults_newlines(),
+        themes: ThemeSet::load_defaults(),
+    })
+}
+
+/// Syntax-highlight the lines of a code listing with the named syntect
+/// theme. Returns `None` when the language is not recognised.
+pub fn highlight(language: &str, lines: &[&str], theme: &str) -> Option<Vec<Line<'static>>> {
+    let highlighter = highlighter();
+    let syntax = highlighter.syntaxes.find_syntax_by_token(language)?;
+    let theme = highlighter
+        .themes
+        .themes
+        .get(theme)
+        .or_else(|| highlighter.themes.themes.get(THEME_NAME))?;
+    let mut state = HighlightLines::new(syntax, theme);
+
+    lines
+        .iter()
+        .map(|line| {
+            let line = format!("{line}\n");
+            let ranges = state.highlight_line(&line, &highlighter.syntaxes).ok()?;
+            let spans: Vec<Span> = ranges
+                .into_iter()
+                .map(|(style, text)| {
+                    let color = style.foreground;
+                    Span::styled(
+                        text.trim_end_matches('\n').to_string(),
+                        Style::default().fg(Color::Rgb(color.r, color.g, color.b)),
+                    )
+                })
+                .collect();
+            Some(Line::from(spans))
+        })
+        .collect()
+}
@@ -0,0 +1,98 @@
+use crate::convert::ImageArt;
+use image::{GrayImage, Luma, imageops::FilterType};
+
+// Taller pictures are scaled down to fit in about a screenful
+const MAX_ROWS: u32 = 40;
+
+// Braille cells are two dots wide and four tall; bit for the dot at (x, y)
+const BRAILLE_DOTS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+const BRAILLE_BASE: u32 = 0x2800;
+
+/// Draw an image as rows of half-block or braille characters, at most
+/// `width` columns wide. Returns `None` if the image can't be decoded.
+pub fn render(bytes: &[u8], style: ImageArt, width: usize) -> Option<String> {
+    // Pixels covered by one character cell. Terminal cells are about twice as
+    // tall as they are wide, so both layouts keep the image's proportions.
+    let (cell_width, cell_height) = match style {
+        ImageArt::Off => return None,
+        ImageArt::HalfBlock => (1, 2),
+        ImageArt::Braille => (2, 4),
+    };
+
+    let image = flatten(&image::load_from_memory(bytes).ok()?.to_rgba8());
+    let (image_width, image_height) = image.dimensions();
+    if image_width == 0 || image_height == 0 || width == 0 {
+        return None;
+    }
+
+    // Never enlarge small images, and keep tall ones to a screenful or so
+    let mut target_width = (width as u32 * cell_width).min(image_width);
+    let mut target_height = scale(image_height, target_width, image_width);
+    let max_height = MAX_ROWS * cell_height;
+    if target_height > max_height {
+        target_height = max_height;
+        target_width = scale(image_width, target_height, image_height);
+    }
+    let resized =
+        image::imageops::resize(&image, target_width, target_height, FilterType::Triangle);
+
+    // Anything darker than average is drawn; that picks out the lines of a
+    // diagram on a light page and still gives a rough shape for photos
+    let total: u64 = resized.pixels().map(|pixel| u64::from(pixel[0])).sum();
+    let threshold = total / u64::from(target_width * target_height);
+    let inked = |x: u32, y: u32| {
+        x < target_width && y < target_height && u64::from(resized.get_pixel(x, y)[0]) < threshold
+    };
+
+    let columns = target_width.div_ceil(cell_width);
+    let rows = target_height.div_ceil(cell_height);
+    let mut output = String::new();
+    for row in 0..rows {
+        let mut line = String::new();
+        for column in 0..columns {
+            let (x, y) = (column * cell_width, row * cell_height);
+            line.push(match style {
+                ImageArt::HalfBlock => match (inked(x, y), inked(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                },
+                _ => {
+                    let mut dots = 0;
+                    for (dx, column_dots) in BRAILLE_DOTS.iter().enumerate() {
+                        for (dy, dot) in column_dots.iter().enumerate() {
+                            if inked(x + dx as u32, y + dy as u32) {
+                                dots |= dot;
+                            }
+                        }
+                    }
+                    match dots {
+                        0 => ' ',
+                        dots => char::from_u32(BRAILLE_BASE + u32::from(dots)).unwrap_or(' '),
+                    }
+                }
+            });
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    Some(output)
+}
+
+/// Greyscale copy of the image, with transparent areas shown as white paper.
+fn flatten(image: &image::RgbaImage) -> GrayImage {
+    GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, alpha] = image.get_pixel(x, y).0;
+        let luma = (299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b)) / 1000;
+        let alpha = u32::from(alpha);
+        Luma([((luma * alpha + 255 * (255 - alpha)) / 255) as u8])
+    })
+}
+
+/// `length * numerator / denominator`, rounded and never zero.
+fn scale(length: u32, numerator: u32, denominator: u32) -> u32 {
+    let scaled = (u64::from(length) * u64::from(numerator) + u64::from(denominator) / 2)
+        / u64::from(denominator);
+    scaled.max(1) as u32
+}
@@ -0,0 +1,231 @@
+use crate::constants::READING_WORDS_PER_MINUTE;
+use crate::epub::EpubReader;
+use crate::stats::format_duration;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::fmt::Write;
+use tracing::warn;
+
+/// Counts of the words and sentences in a book's text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextStats {
+    pub words: usize,
+    /// Distinct words, ignoring case.
+    pub unique_words: usize,
+    pub sentences: usize,
+    /// The same for each chapter, titled.
+    pub chapters: Vec<(String, TextStats)>,
+}
+
+impl TextStats {
+    /// Words a sentence on average.
+    pub fn average_sentence_length(&self) -> f64 {
+        match self.sentences {
+            0 => 0.0,
+            sentences => self.words as f64 / sentences as f64,
+        }
+    }
+
+    /// Roughly how long the text takes to read, in seconds.
+    pub fn reading_seconds(&self) -> u64 {
+        (self.words * 60 / READING_WORDS_PER_MINUTE) as u64
+    }
+}
+
+/// A book's details and chapters as `catatau info` prints them, with each
+/// chapter's word count.
+pub fn info_text(epub: &EpubReader) -> String {
+    epub.scan_chapters();
+    let mut text = String::new();
+    let mut field = |label: &str, value: &str| {
+        if !value.is_empty() {
+            let _ = writeln!(text, "{:<12} {value}", format!("{label}:"));
+        }
+    };
+    field("Title", &epub.title);
+    field("Authors", &authors(epub).join(", "));
+    field("Language", epub.language.as_deref().unwrap_or_default());
+    field("Identifiers", &epub.identifiers.join(", "));
+    field("Publisher", epub.publisher.as_deref().unwrap_or_default());
+    field("Published", epub.published.as_deref().unwrap_or_default());
+    if let Some(series) = &epub.series {
+        field("Series", &series.to_string());
+    }
+    field("Chapters", &epub.chapter_count().to_string());
+    field("Words", &total_words(epub).to_string());
+
+    let number_width = epub.chapter_count().to_string().len();
+    text.push('\n');
+    for index in 0..epub.chapter_count() {
+        let _ = writeln!(
+            text,
+            "{:>number_width$}  {:>7}  {}",
+            index + 1,
+            epub.chapter_word_count(index),
+            epub.chapter_title(index)
+        );
+    }
+    text
+}
+
+/// The same as `catatau info --json` prints it, with chapters numbered
+/// from one.
+pub fn info_json(epub: &EpubReader) -> Value {
+    epub.scan_chapters();
+    let chapters: Vec<Value> = (0..epub.chapter_count())
+        .map(|index| {
+            json!({
+                "number": index + 1,
+                "title": epub.chapter_title(index),
+                "words": epub.chapter_word_count(index),
+            })
+        })
+        .collect();
+    json!({
+        "path": epub.path().display().to_string(),
+        "title": epub.title,
+        "authors": authors(epub),
+        "language": epub.language,
+        "identifiers": epub.identifiers,
+        "publisher": epub.publisher,
+        "published": epub.published,
+        "series": epub.series.as_ref().map(|series| json!({
+            "name": series.name,
+            "index": series.index,
+        })),
+        "words": total_words(epub),
+        "chapters": chapters,
+    })
+}
+
+fn authors(epub: &EpubReader) -> Vec<String> {
+    epub.authors
+        .iter()
+        .map(|author| author.trim().to_string())
+        .filter(|author| !author.is_empty())
+        .collect()
+}
+
+fn total_words(epub: &EpubReader) -> usize {
+    (0..epub.chapter_count()).map(|index| epub.chapter_word_count(index)).sum()
+}
+
+/// Count the words and sentences of every chapter of the book.
+pub fn text_stats(epub: &EpubReader) -> TextStats {
+    let mut book = TextStats::default();
+    let mut book_words = HashSet::new();
+    for index in 0..epub.chapter_count() {
+        let text = match epub.chapter_index_text(index) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("Could not read chapter {}: {}", index + 1, e);
+                String::new()
+            }
+        };
+        let mut chapter_words = HashSet::new();
+        let mut chapter = count_text(&text, &mut chapter_words);
+        chapter.unique_words = chapter_words.len();
+        book.words += chapter.words;
+        book.sentences += chapter.sentences;
+        book_words.extend(chapter_words);
+        book.chapters.push((epub.chapter_title(index), chapter));
+    }
+    book.unique_words = book_words.len();
+    book
+}
+
+/// Statistics as `catatau stats` prints them, with a line for each
+/// chapter.
+pub fn stats_text(stats: &TextStats) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "Words:            {}", stats.words);
+    let _ = writeln!(text, "Unique words:     {}", stats.unique_words);
+    let _ = writeln!(text, "Sentences:        {}", stats.sentences);
+    let _ = writeln!(
+        text,
+        "Sentence length:  {:.1} words on average",
+        stats.average_sentence_length()
+    );
+    let _ = writeln!(
+        text,
+        "Reading time:     {} at {READING_WORDS_PER_MINUTE} words a minute",
+        format_duration(stats.reading_seconds())
+    );
+
+    let number_width = stats.chapters.len().to_string().len();
+    let _ = writeln!(
+        text,
+        "\n{:>number_width$}  {:>7}  {:>7}  {:>9}  {:>8}  Title",
+        "#", "Words", "Unique", "Sentences", "Time"
+    );
+    for (index, (title, chapter)) in stats.chapters.iter().enumerate() {
+        let _ = writeln!(
+            text,
+            "{:>number_width$}  {:>7}  {:>7}  {:>9}  {:>8}  {title}",
+            index + 1,
+            chapter.words,
+            chapter.unique_words,
+            chapter.sentences,
+            format_duration(chapter.reading_seconds()),
+        );
+    }
+    text
+}
+
+/// The same as `catatau stats --json` prints them, with chapters numbered
+/// from one and reading times in minutes.
+pub fn stats_json(stats: &TextStats) -> Value {
+    let counts = |stats: &TextStats| {
+        json!({
+            "words": stats.words,
+            "unique_words": stats.unique_words,
+            "sentences": stats.sentences,
+            "average_sentence_length": stats.average_sentence_length(),
+            "reading_minutes": stats.reading_seconds() / 60,
+        })
+    };
+    let chapters: Vec<Value> = stats
+        .chapters
+        .iter()
+        .enumerate()
+        .map(|(index, (title, chapter))| {
+            let mut json = counts(chapter);
+            json["number"] = json!(index + 1);
+            json["title"] = json!(title);
+            json
+        })
+        .collect();
+    let mut json = counts(stats);
+    json["chapters"] = json!(chapters);
+    json
+}
+
+// Words are runs of text with a letter or digit in them, and sentences end
+// at a word ending in `.`, `!`, `?` or `…`, or with the paragraph, so that
+// headings count as sentences of their own
+fn count_text(text: &str, unique: &mut HashSet<String>) -> TextStats {
+    let mut stats = TextStats::default();
+    let mut in_sentence = false;
+    for line in text.lines().chain([""]) {
+        if line.trim().is_empty() && in_sentence {
+            stats.sentences += 1;
+            in_sentence = false;
+        }
+        for word in line.split_whitespace() {
+            if !word.chars().any(char::is_alphanumeric) {
+                continue;
+            }
+            stats.words += 1;
+            in_sentence = true;
+            let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+            unique.insert(bare.to_lowercase());
+
+            let end = word.trim_end_matches(['"', '\'', '”', '’', ')', ']', '»']);
+            if end.ends_with(['.', '!', '?', '…']) {
+                stats.sentences += 1;
+                in_sentence = false;
+            }
+        }
+    }
+    stats
+}
@@ -0,0 +1,504 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+use std::str::FromStr;
+
+/// What a key does while reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    ScrollDown,
+    ScrollUp,
+    PageDown,
+    PageUp,
+    NextChapter,
+    PreviousChapter,
+    ChapterStart,
+    /// The end of the chapter, or with a count that far through it as a
+    /// percentage, as in `25G`.
+    ChapterEnd,
+    FirstChapter,
+    LastChapter,
+    NextHeading,
+    PreviousHeading,
+    ScrollRight,
+    ScrollLeft,
+    Search,
+    ClearSearch,
+    NextMatch,
+    PreviousMatch,
+    Command,
+    Contents,
+    Outline,
+    BookInfo,
+    Stats,
+    LinkHints,
+    ToggleSource,
+    Visual,
+    Highlights,
+    Notes,
+    Books,
+    NextBook,
+    PreviousBook,
+    CopyParagraph,
+    TranslateParagraph,
+    Narrate,
+    Zen,
+    Paged,
+    /// Open the book's file again, as after it's changed.
+    ReloadBook,
+    ReloadTheme,
+    ToggleTheme,
+    Suspend,
+    /// Quit, asking first with `--confirm-quit`.
+    Quit,
+    QuitNow,
+    JumpBack,
+    JumpForward,
+    /// Wait for a window command, as after `Ctrl-w` in vim.
+    Window,
+    /// Start or stop noting down the keys typed, to replay with `--keys`.
+    RecordKeys,
+}
+
+/// Every action by the name it's bound by in the config file.
+pub const ACTIONS: [(&str, Action); 46] = [
+    ("scroll-down", Action::ScrollDown),
+    ("scroll-up", Action::ScrollUp),
+    ("page-down", Action::PageDown),
+    ("page-up", Action::PageUp),
+    ("next-chapter", Action::NextChapter),
+    ("previous-chapter", Action::PreviousChapter),
+    ("chapter-start", Action::ChapterStart),
+    ("chapter-end", Action::ChapterEnd),
+    ("first-chapter", Action::FirstChapter),
+    ("last-chapter", Action::LastChapter),
+    ("next-heading", Action::NextHeading),
+    ("previous-heading", Action::PreviousHeading),
+    ("scroll-right", Action::ScrollRight),
+    ("scroll-left", Action::ScrollLeft),
+    ("search", Action::Search),
+    ("clear-search", Action::ClearSearch),
+    ("next-match", Action::NextMatch),
+    ("previous-match", Action::PreviousMatch),
+    ("command", Action::Command),
+    ("contents", Action::Contents),
+    ("outline", Action::Outline),
+    ("book-info", Action::BookInfo),
+    ("stats", Action::Stats),
+    ("link-hints", Action::LinkHints),
+    ("toggle-source", Action::ToggleSource),
+    ("visual", Action::Visual),
+    ("highlights", Action::Highlights),
+    ("notes", Action::Notes),
+    ("books", Action::Books),
+    ("next-book", Action::NextBook),
+    ("previous-book", Action::PreviousBook),
+    ("copy-paragraph", Action::CopyParagraph),
+    ("translate-paragraph", Action::TranslateParagraph),
+    ("narrate", Action::Narrate),
+    ("zen", Action::Zen),
+    ("paged", Action::Paged),
+    ("reload-book", Action::ReloadBook),
+    ("reload-theme", Action::ReloadTheme),
+    ("toggle-theme", Action::ToggleTheme),
+    ("suspend", Action::Suspend),
+    ("quit", Action::Quit),
+    ("quit-now", Action::QuitNow),
+    ("jump-back", Action::JumpBack),
+    ("jump-forward", Action::JumpForward),
+    ("window", Action::Window),
+    ("record-keys", Action::RecordKeys),
+];
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ACTIONS
+            .iter()
+            .find(|(action, _)| *action == name)
+            .map(|&(_, action)| action)
+            .ok_or_else(|| format!("unknown action '{name}'"))
+    }
+}
+
+/// A key with the modifiers held down with it, as in `Ctrl-w`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is already in the character typed, and terminals differ
+        // on whether they report it; Ctrl-O and Ctrl-o are the same key
+        let (code, modifiers) = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+                (KeyCode::Char(c.to_ascii_lowercase()), modifiers - KeyModifiers::SHIFT)
+            }
+            KeyCode::Char(_) | KeyCode::BackTab => (code, modifiers - KeyModifiers::SHIFT),
+            _ => (code, modifiers),
+        };
+        let held = KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT;
+        Self {
+            code,
+            modifiers: modifiers & held,
+        }
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl From<KeyChord> for KeyEvent {
+    fn from(key: KeyChord) -> Self {
+        KeyEvent::new(key.code, key.modifiers)
+    }
+}
+
+// Names of keys written in angle brackets, as in vim
+const KEY_NAMES: [(&str, KeyCode); 19] = [
+    ("space", KeyCode::Char(' ')),
+    ("lt", KeyCode::Char('<')),
+    ("gt", KeyCode::Char('>')),
+    ("tab", KeyCode::Tab),
+    ("enter", KeyCode::Enter),
+    ("cr", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("bs", KeyCode::Backspace),
+    ("backspace", KeyCode::Backspace),
+    ("del", KeyCode::Delete),
+    ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+/// Keys written the way vim writes them: characters as they are, and
+/// other keys and modifiers in angle brackets, as in `gg`, `<C-w>`,
+/// `<Space>`, `<PageDown>`, `<A-j>` or `<F5>`.
+pub fn parse_keys(text: &str) -> Result<Vec<KeyChord>, String> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '<'
+            && let Some(end) = rest.find('>')
+            && end > 1
+        {
+            keys.push(parse_key_name(&rest[1..end])?);
+            rest = &rest[end + 1..];
+        } else {
+            keys.push(KeyChord::new(KeyCode::Char(c), KeyModifiers::NONE));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if keys.is_empty() {
+        return Err("no keys given".to_string());
+    }
+    Ok(keys)
+}
+
+// What's inside `<...>`: modifiers such as `C-` and `A-`, then a key
+fn parse_key_name(name: &str) -> Result<KeyChord, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut key = name;
+    while let Some((modifier, rest)) = key.split_once('-')
+        && !rest.is_empty()
+    {
+        modifiers |= match modifier.to_ascii_lowercase().as_str() {
+            "c" | "ctrl" => KeyModifiers::CONTROL,
+            "a" | "m" | "alt" => KeyModifiers::ALT,
+            "s" | "shift" => KeyModifiers::SHIFT,
+            _ => return Err(format!("unknown modifier '{modifier}' in <{name}>")),
+        };
+        key = rest;
+    }
+    let lowercase = key.to_ascii_lowercase();
+    let code = if let Some(&(_, code)) = KEY_NAMES.iter().find(|(known, _)| *known == lowercase) {
+        code
+    } else if let Some(number) = lowercase.strip_prefix('f')
+        && let Ok(number @ 1..=12) = number.parse()
+    {
+        KeyCode::F(number)
+    } else if let [c] = key.chars().collect::<Vec<_>>()[..] {
+        KeyCode::Char(c)
+    } else {
+        return Err(format!("unknown key <{name}>"));
+    };
+    let code = match code {
+        KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+        code => code,
+    };
+    Ok(KeyChord::new(code, modifiers))
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char('<') => "lt".to_string(),
+            KeyCode::Char('>') if !self.modifiers.is_empty() => "gt".to_string(),
+            KeyCode::Char(c) if self.modifiers.is_empty() => return write!(f, "{c}"),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::BackTab => "S-Tab".to_string(),
+            KeyCode::F(number) => format!("F{number}"),
+            code => format!("{code:?}"),
+        };
+        f.write_str("<")?;
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(prefix)?;
+            }
+        }
+        write!(f, "{name}>")
+    }
+}
+
+/// Which keys do what in the reader.
+const DEFAULT_BINDINGS: [(&str, Action); 55] = [
+    ("j", Action::ScrollDown),
+    ("<Down>", Action::ScrollDown),
+    ("k", Action::ScrollUp),
+    ("<Up>", Action::ScrollUp),
+    ("<Space>", Action::PageDown),
+    ("<PageDown>", Action::PageDown),
+    ("b", Action::PageUp),
+    ("<PageUp>", Action::PageUp),
+    ("l", Action::NextChapter),
+    ("<Right>", Action::NextChapter),
+    ("h", Action::PreviousChapter),
+    ("<Left>", Action::PreviousChapter),
+    ("gg", Action::ChapterStart),
+    ("<Home>", Action::ChapterStart),
+    ("G", Action::ChapterEnd),
+    ("<End>", Action::ChapterEnd),
+    ("g0", Action::FirstChapter),
+    ("g$", Action::LastChapter),
+    ("}", Action::NextHeading),
+    ("{", Action::PreviousHeading),
+    (">", Action::ScrollRight),
+    ("<lt>", Action::ScrollLeft),
+    ("/", Action::Search),
+    ("<Esc>", Action::ClearSearch),
+    ("n", Action::NextMatch),
+    ("N", Action::PreviousMatch),
+    (":", Action::Command),
+    ("-", Action::Contents),
+    ("o", Action::Outline),
+    ("i", Action::BookInfo),
+    ("S", Action::Stats),
+    ("f", Action::LinkHints),
+    ("V", Action::ToggleSource),
+    ("v", Action::Visual),
+    ("a", Action::Highlights),
+    ("K", Action::Notes),
+    ("B", Action::Books),
+    ("gt", Action::NextBook),
+    ("gT", Action::PreviousBook),
+    ("y", Action::CopyParagraph),
+    ("T", Action::TranslateParagraph),
+    ("P", Action::Narrate),
+    ("z", Action::Zen),
+    ("p", Action::Paged),
+    ("R", Action::ReloadBook),
+    ("<C-r>", Action::ReloadTheme),
+    ("t", Action::ToggleTheme),
+    ("<C-z>", Action::Suspend),
+    ("q", Action::Quit),
+    ("ZZ", Action::QuitNow),
+    ("<C-o>", Action::JumpBack),
+    // Terminals send Tab for Ctrl-i
+    ("<Tab>", Action::JumpForward),
+    ("<C-i>", Action::JumpForward),
+    ("<C-w>", Action::Window),
+    ("<A-q>", Action::RecordKeys),
+];
+
+// Changes to the default bindings for readers used to less(1): its
+// scrolling and paging keys, with g and G for the start and end of the
+// chapter, and the arrows to scroll sideways
+const LESS_BINDINGS: [(&str, Option<Action>); 38] = [
+    ("gg", None),
+    ("g0", None),
+    ("g$", None),
+    ("gt", None),
+    ("gT", None),
+    ("e", Some(Action::ScrollDown)),
+    ("<C-e>", Some(Action::ScrollDown)),
+    ("<C-n>", Some(Action::ScrollDown)),
+    ("<Enter>", Some(Action::ScrollDown)),
+    ("y", Some(Action::ScrollUp)),
+    ("<C-y>", Some(Action::ScrollUp)),
+    ("<C-p>", Some(Action::ScrollUp)),
+    ("f", Some(Action::PageDown)),
+    ("<C-f>", Some(Action::PageDown)),
+    ("<C-v>", Some(Action::PageDown)),
+    ("z", Some(Action::PageDown)),
+    ("d", Some(Action::PageDown)),
+    ("<C-d>", Some(Action::PageDown)),
+    ("<C-b>", Some(Action::PageUp)),
+    ("w", Some(Action::PageUp)),
+    ("<A-v>", Some(Action::PageUp)),
+    ("u", Some(Action::PageUp)),
+    ("<C-u>", Some(Action::PageUp)),
+    ("g", Some(Action::ChapterStart)),
+    ("<lt>", Some(Action::ChapterStart)),
+    (">", Some(Action::ChapterEnd)),
+    ("<A-lt>", Some(Action::FirstChapter)),
+    ("<A-gt>", Some(Action::LastChapter)),
+    ("]", Some(Action::NextBook)),
+    ("[", Some(Action::PreviousBook)),
+    ("<Right>", Some(Action::ScrollRight)),
+    ("<Left>", Some(Action::ScrollLeft)),
+    ("=", Some(Action::BookInfo)),
+    ("<C-g>", Some(Action::BookInfo)),
+    ("F", Some(Action::LinkHints)),
+    ("Y", Some(Action::CopyParagraph)),
+    ("<A-z>", Some(Action::Zen)),
+    ("Q", Some(Action::QuitNow)),
+];
+
+// Emacs keys added to the defaults, mostly as in its view mode and with
+// Ctrl-x for books and chapters
+const EMACS_BINDINGS: [(&str, Option<Action>); 19] = [
+    ("<C-n>", Some(Action::ScrollDown)),
+    ("<C-p>", Some(Action::ScrollUp)),
+    ("<C-v>", Some(Action::PageDown)),
+    ("<A-v>", Some(Action::PageUp)),
+    ("<A-lt>", Some(Action::ChapterStart)),
+    ("<A-gt>", Some(Action::ChapterEnd)),
+    ("<C-x>]", Some(Action::NextChapter)),
+    ("<C-x>[", Some(Action::PreviousChapter)),
+    ("<A-}>", Some(Action::NextHeading)),
+    ("<A-{>", Some(Action::PreviousHeading)),
+    ("<C-s>", Some(Action::Search)),
+    ("<C-g>", Some(Action::ClearSearch)),
+    ("<A-x>", Some(Action::Command)),
+    ("<C-Space>", Some(Action::Visual)),
+    ("<A-w>", Some(Action::CopyParagraph)),
+    ("<C-x>b", Some(Action::Books)),
+    ("<C-x><Right>", Some(Action::NextBook)),
+    ("<C-x><Left>", Some(Action::PreviousBook)),
+    ("<C-x><C-c>", Some(Action::QuitNow)),
+];
+
+/// A built-in set of key bindings, which the config file's `[keys]` change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Preset {
+    /// vim-style keys, the default.
+    #[default]
+    Vim,
+    /// The vim keys plus emacs ones: `C-n`, `C-v`, `M-<`, `C-s` and so on.
+    Emacs,
+    /// less(1)'s keys, with the vim ones that don't clash with them.
+    Less,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "vim" => Ok(Self::Vim),
+            "emacs" => Ok(Self::Emacs),
+            "less" => Ok(Self::Less),
+            other => Err(format!("unknown keymap '{other}' (expected vim, emacs or less)")),
+        }
+    }
+}
+
+/// What the keys typed so far are bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Action(Action),
+    /// The start of a longer binding, waiting for the rest.
+    Prefix,
+    Unbound,
+}
+
+/// Key sequences and the actions they're bound to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyChord>, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset(Preset::Vim)
+    }
+}
+
+impl Keymap {
+    /// The bindings `preset` starts with.
+    pub fn preset(preset: Preset) -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .map(|&(keys, action)| (parse_keys(keys).expect("default key binding"), action))
+            .collect();
+        let mut keymap = Self { bindings };
+        let changes: &[(&str, Option<Action>)] = match preset {
+            Preset::Vim => &[],
+            Preset::Emacs => &EMACS_BINDINGS,
+            Preset::Less => &LESS_BINDINGS,
+        };
+        for &(keys, action) in changes {
+            let keys = parse_keys(keys).expect("preset key binding");
+            keymap.bind(keys, action).expect("preset key binding");
+        }
+        keymap
+    }
+
+    /// Bind `keys` to `action`, or unbind them with `None`. A sequence can't
+    /// be bound while a longer one starting with it is, or the other way
+    /// round, since one would hide the other.
+    pub fn bind(&mut self, keys: Vec<KeyChord>, action: Option<Action>) -> Result<(), String> {
+        self.bindings.retain(|(bound, _)| *bound != keys);
+        let Some(action) = action else {
+            return Ok(());
+        };
+        if let Some((other, _)) = self
+            .bindings
+            .iter()
+            .find(|(bound, _)| bound.starts_with(&keys) || keys.starts_with(bound))
+        {
+            return Err(format!(
+                "{} can't be bound while {} is; unbind it with \"none\"",
+                display_keys(&keys),
+                display_keys(other)
+            ));
+        }
+        self.bindings.push((keys, action));
+        Ok(())
+    }
+
+    /// What `typed` does.
+    pub fn lookup(&self, typed: &[KeyChord]) -> Binding {
+        let mut binding = Binding::Unbound;
+        for (keys, action) in &self.bindings {
+            if keys == typed {
+                return Binding::Action(*action);
+            }
+            if keys.starts_with(typed) {
+                binding = Binding::Prefix;
+            }
+        }
+        binding
+    }
+}
+
+/// Keys as they're written in the config file.
+pub fn display_keys(keys: &[KeyChord]) -> String {
+    keys.iter().map(KeyChord::to_string).collect()
+}
@@ -1,8 +1,48 @@
+pub mod annotations;
+#[cfg(feature = "tui")]
+pub mod background;
+#[cfg(feature = "tui")]
+pub mod bidi;
+#[cfg(feature = "calibre")]
+pub mod calibre;
+pub mod cat;
 pub mod epub;
+#[cfg(feature = "tui")]
 pub mod ui;
 pub mod error;
+pub mod export;
 pub mod constants;
+pub mod check;
+#[cfg(feature = "tui")]
+pub mod config;
+pub mod clipboard;
+pub mod convert;
+pub mod crash;
+pub mod css;
+#[cfg(feature = "tui")]
+pub mod keymap;
+pub mod info;
+#[cfg(feature = "tui")]
+pub mod library;
+pub mod lookup;
+pub mod markdown;
+pub mod narration;
+pub mod progress;
+pub mod remote;
+pub mod search;
+pub mod stats;
+#[cfg(feature = "tui")]
+pub mod theme;
+pub mod translate;
+pub mod watch;
+#[cfg(feature = "highlight")]
+pub mod highlight;
+#[cfg(feature = "image-art")]
+pub mod image_art;
 
-pub use epub::{EpubReader, Chapter};
-pub use ui::App;
-pub use error::{EpubError, UiError};
\ No newline at end of file
+pub use epub::{EpubReader, EpubReaderOptions, Chapter, Heading, LinkTarget, Series};
+#[cfg(feature = "tui")]
+pub use ui::{App, DisplayOptions, Exit, LineSpacing, Message, Profile};
+pub use error::{EpubError, UiError};
+#[cfg(feature = "tui")]
+pub use theme::Theme;
\ No newline at end of file
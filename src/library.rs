@@ -0,0 +1,758 @@
+use crate::constants::SEARCH_CONTEXT_LINES;
+use crate::epub::{EpubReader, EpubReaderOptions, Series};
+use crate::error::UiError;
+use crate::progress::{self, Progress};
+use crate::search::{self, SearchIndex};
+use crate::theme::Theme;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    Frame, Terminal,
+    backend::CrosstermBackend,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Padding, Paragraph},
+};
+use rayon::prelude::*;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::warn;
+
+// Covers are shrunk to fit in a square this many pixels wide
+#[cfg(feature = "image-art")]
+const COVER_THUMBNAIL_SIZE: u32 = 256;
+
+/// A book found in the library, with what's needed to list it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryBook {
+    pub path: PathBuf,
+    /// Key of the book in the reading progress.
+    pub key: String,
+    pub title: String,
+    pub author: String,
+    pub series: Option<Series>,
+    pub chapters: usize,
+    pub words: usize,
+    /// A small copy of the cover image, if the book has one.
+    pub cover: Option<PathBuf>,
+}
+
+impl LibraryBook {
+    /// How much of the book has been read, as a percentage of its chapters,
+    /// or `None` if it's never been opened.
+    pub fn percent_read(&self, progress: &Progress) -> Option<usize> {
+        let book = progress.book(&self.key)?;
+        Some((book.read_chapters.len() * 100).checked_div(self.chapters).unwrap_or(0).min(100))
+    }
+}
+
+/// Where books are looked for when none is given: the directories listed
+/// in `$CATATAU_LIBRARY`, separated as in `PATH`, or the `configured` ones,
+/// or `~/Books`.
+pub fn library_dirs(configured: &[PathBuf]) -> Vec<PathBuf> {
+    if let Some(dirs) = std::env::var_os("CATATAU_LIBRARY").filter(|dirs| !dirs.is_empty()) {
+        return std::env::split_paths(&dirs).collect();
+    }
+    if !configured.is_empty() {
+        return configured.to_vec();
+    }
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join("Books"))
+        .into_iter()
+        .collect()
+}
+
+/// EPUB files in the directories and all the directories under them.
+pub fn find_books(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut books = Vec::new();
+    let mut pending: Vec<PathBuf> = dirs.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("epub"))
+            {
+                books.push(path);
+            }
+        }
+    }
+    books.sort();
+    books
+}
+
+/// What was read from each book in the library the last time it was
+/// listed, kept in a JSON file so that only books added or changed since
+/// then need opening.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryCache {
+    /// Keyed by the path the book was found at.
+    books: BTreeMap<PathBuf, CachedBook>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CachedBook {
+    /// The file's size and modification time in milliseconds when it was
+    /// read; if either differs it's read again.
+    size: u64,
+    modified: u64,
+    book: LibraryBook,
+}
+
+impl LibraryCache {
+    /// Read the cache file, starting afresh if it's missing or can't be
+    /// understood.
+    pub fn load(path: &Path) -> Self {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<Value>(&text) {
+            Ok(json) => Self::from_json(&json),
+            Err(e) => {
+                warn!("Ignoring unreadable library cache {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        progress::write_atomically(path, serde_json::to_string_pretty(&self.to_json())?)
+    }
+
+    fn from_json(json: &Value) -> Self {
+        let books = json
+            .get("books")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(path, book)| {
+                let path = PathBuf::from(path);
+                Some((path.clone(), CachedBook::from_json(path, book)?))
+            })
+            .collect();
+        Self { books }
+    }
+
+    fn to_json(&self) -> Value {
+        let books: Map<String, Value> = self
+            .books
+            .iter()
+            .map(|(path, book)| (path.to_string_lossy().into_owned(), book.to_json()))
+            .collect();
+        json!({ "books": books })
+    }
+}
+
+impl CachedBook {
+    fn from_json(path: PathBuf, json: &Value) -> Option<Self> {
+        let text = |name: &str| Some(json.get(name)?.as_str()?.to_string());
+        let count = |name: &str| usize::try_from(json.get(name)?.as_u64()?).ok();
+        let series = json.get("series").and_then(|series| {
+            Some(Series {
+                name: series.get("name")?.as_str()?.to_string(),
+                index: series.get("index").and_then(Value::as_f64),
+            })
+        });
+        Some(Self {
+            size: json.get("size")?.as_u64()?,
+            modified: json.get("modified")?.as_u64()?,
+            book: LibraryBook {
+                key: progress::book_key(&path),
+                path,
+                title: text("title")?,
+                author: text("author")?,
+                series,
+                chapters: count("chapters")?,
+                words: count("words")?,
+                cover: text("cover").map(PathBuf::from),
+            },
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let book = &self.book;
+        let mut json = json!({
+            "size": self.size,
+            "modified": self.modified,
+            "title": book.title,
+            "author": book.author,
+            "chapters": book.chapters,
+            "words": book.words,
+        });
+        if let Some(series) = &book.series {
+            json["series"] = json!({ "name": series.name, "index": series.index });
+        }
+        if let Some(cover) = &book.cover {
+            json["cover"] = json!(cover.to_string_lossy());
+        }
+        json
+    }
+}
+
+/// The books' details, from the cache where the file hasn't changed since
+/// it was read and otherwise by opening it, leaving out any that can't be
+/// opened, sorted by title. Covers of newly read books are saved in
+/// `covers`. Books whose files have gone are dropped from the cache.
+pub fn load_books(
+    paths: &[PathBuf],
+    options: &EpubReaderOptions,
+    cache: &mut LibraryCache,
+    covers: Option<&Path>,
+) -> Vec<LibraryBook> {
+    let cached = &cache.books;
+    let found: Vec<CachedBook> = paths
+        .par_iter()
+        .filter_map(|path| {
+            let metadata = std::fs::metadata(path).ok()?;
+            let size = metadata.len();
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_millis() as u64);
+            if let Some(entry) = cached.get(path)
+                && entry.size == size
+                && entry.modified == modified
+            {
+                return Some(entry.clone());
+            }
+            match options.clone().open(path) {
+                Ok(epub) => Some(CachedBook {
+                    size,
+                    modified,
+                    book: read_book(path, &epub, covers),
+                }),
+                Err(e) => {
+                    warn!("Leaving {} out of the library: {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    cache.books.retain(|path, entry| {
+        let exists = path.exists();
+        if !exists && let Some(cover) = &entry.book.cover {
+            let _ = std::fs::remove_file(cover);
+        }
+        exists
+    });
+    let mut books = Vec::with_capacity(found.len());
+    for entry in found {
+        books.push(entry.book.clone());
+        cache.books.insert(entry.book.path.clone(), entry);
+    }
+    books.sort_by_cached_key(|book| book.title.to_lowercase());
+    books
+}
+
+fn read_book(path: &Path, epub: &EpubReader, covers: Option<&Path>) -> LibraryBook {
+    epub.scan_chapters();
+    let key = progress::book_key(path);
+    LibraryBook {
+        cover: covers.and_then(|dir| save_cover(epub, dir, &key)),
+        key,
+        title: epub.title.clone(),
+        author: epub.author.clone(),
+        series: epub.series.clone(),
+        chapters: epub.chapter_count(),
+        words: (0..epub.chapter_count()).map(|index| epub.chapter_word_count(index)).sum(),
+        path: path.to_path_buf(),
+    }
+}
+
+// A copy of the book's cover in the directory, named after the book's key,
+// shrunk to a thumbnail when it can be decoded
+fn save_cover(epub: &EpubReader, dir: &Path, key: &str) -> Option<PathBuf> {
+    let (name, bytes) = epub.cover_image()?;
+    let stem = progress::key_hash(key);
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        warn!("Could not create {}: {}", dir.display(), e);
+        return None;
+    }
+
+    #[cfg(feature = "image-art")]
+    if let Ok(image) = image::load_from_memory(&bytes) {
+        let path = dir.join(format!("{stem}.png"));
+        let thumbnail = image.thumbnail(COVER_THUMBNAIL_SIZE, COVER_THUMBNAIL_SIZE);
+        let mut png = io::Cursor::new(Vec::new());
+        if thumbnail.write_to(&mut png, image::ImageFormat::Png).is_ok()
+            && progress::write_atomically(&path, png.into_inner()).is_ok()
+        {
+            return Some(path);
+        }
+    }
+
+    // Without a way to shrink it, the cover is kept as it is
+    let extension = Path::new(&name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("img");
+    let path = dir.join(format!("{stem}.{extension}"));
+    match progress::write_atomically(&path, bytes) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            warn!("Could not save the cover of {}: {}", epub.title, e);
+            None
+        }
+    }
+}
+
+/// A line matching a search through the library's books.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryHit {
+    /// Index of the book in those searched.
+    pub book: usize,
+    pub chapter: usize,
+    pub chapter_title: String,
+    /// The matching line, counted in the chapter as it's first shown.
+    pub line: usize,
+    pub snippet: String,
+}
+
+/// Lines matching `query` in the books that have a search index saved in
+/// `index_dir`, opened with `options` as they're read, in the order of the
+/// books and then where the lines are in them. Books that haven't been
+/// indexed yet are left out, since searching them means reading them
+/// through.
+pub fn search_books(
+    books: &[LibraryBook],
+    query: &str,
+    options: &EpubReaderOptions,
+    index_dir: &Path,
+) -> Vec<LibraryHit> {
+    books
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(book, entry)| {
+            let hits = options
+                .clone()
+                .open(&entry.path)
+                .ok()
+                .and_then(|epub| {
+                    let index = SearchIndex::load_saved(&epub, index_dir)?;
+                    let chapters = index
+                        .candidates(query)
+                        .unwrap_or_else(|| (0..epub.chapter_count()).collect());
+                    let hits: Vec<LibraryHit> = epub
+                        .search_chapters(query, chapters)
+                        .into_iter()
+                        .map(|result| LibraryHit {
+                            book,
+                            chapter: result.chapter_index,
+                            chapter_title: epub.chapter_title(result.chapter_index),
+                            line: result.line_number,
+                            // The context starts a line or so before the match
+                            snippet: result
+                                .context
+                                .lines()
+                                .nth(result.line_number.min(SEARCH_CONTEXT_LINES))
+                                .unwrap_or_default()
+                                .trim()
+                                .to_string(),
+                        })
+                        .collect();
+                    Some(hits)
+                })
+                .unwrap_or_default();
+            hits.into_iter()
+        })
+        .collect()
+}
+
+/// What was picked in the library.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Choice {
+    Book(PathBuf),
+    /// A line found by searching the books, to open the book at.
+    Match {
+        path: PathBuf,
+        query: String,
+        chapter: usize,
+        line: usize,
+    },
+}
+
+/// A screen listing the library's books to choose one to read.
+pub struct Picker {
+    books: Vec<LibraryBook>,
+    /// Index into `books` of the one selected, whether or not the filter
+    /// hides it.
+    selected: usize,
+    /// Typed to narrow the list down to books whose title or author match
+    /// it fuzzily.
+    filter: String,
+    /// Shown in place of the key help, such as why a book didn't open.
+    status_message: Option<String>,
+    /// How books are opened, and where their search indexes are kept, for
+    /// searching through them.
+    search_in: Option<(EpubReaderOptions, PathBuf)>,
+    /// Typed, after Ctrl-f, to search the text of every indexed book
+    /// instead of filtering the list.
+    query: Option<String>,
+    /// What the query last found, with the one selected.
+    hits: Vec<LibraryHit>,
+    selected_hit: usize,
+}
+
+impl Picker {
+    pub fn new(books: Vec<LibraryBook>) -> Self {
+        Self {
+            books,
+            selected: 0,
+            filter: String::new(),
+            status_message: None,
+            search_in: None,
+            query: None,
+            hits: Vec::new(),
+            selected_hit: 0,
+        }
+    }
+
+    /// Let Ctrl-f search the text of the books that have a search index
+    /// saved in `index_dir`, opening them with `options`.
+    pub fn keep_search_index(&mut self, options: EpubReaderOptions, index_dir: PathBuf) {
+        self.search_in = Some((options, index_dir));
+    }
+
+    /// Show a message under the list the next time it's shown.
+    pub fn report(&mut self, message: String) {
+        self.status_message = Some(message);
+    }
+
+    /// Let the reader choose a book, or a place in one, returning `None` if
+    /// they quit instead.
+    pub fn run(&mut self, progress: &Progress, theme: &Theme) -> Result<Option<Choice>, UiError> {
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+        let chosen = self.choose(&mut terminal, progress, theme);
+
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal.show_cursor()?;
+        chosen
+    }
+
+    fn choose(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+        progress: &Progress,
+        theme: &Theme,
+    ) -> Result<Option<Choice>, UiError> {
+        loop {
+            terminal.draw(|f| self.draw(f, progress, theme))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            self.status_message = None;
+
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(None);
+            }
+            if self.query.is_some() {
+                if let Some(choice) = self.handle_search_key(key.code, terminal, progress, theme)?
+                {
+                    return Ok(Some(choice));
+                }
+                continue;
+            }
+
+            let entries = self.entries();
+            let position = entries.iter().position(|(index, _)| *index == self.selected);
+            match key.code {
+                KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if self.search_in.is_some() {
+                        self.query = Some(String::new());
+                    } else {
+                        self.status_message = Some("Books can't be searched".to_string());
+                    }
+                }
+                KeyCode::Esc if self.filter.is_empty() => return Ok(None),
+                KeyCode::Esc => self.filter.clear(),
+                KeyCode::Enter => {
+                    if let Some(position) = position {
+                        return Ok(Some(Choice::Book(self.books[entries[position].0].path.clone())));
+                    }
+                }
+                KeyCode::Up => {
+                    let previous = position.map_or(0, |position| position.saturating_sub(1));
+                    if let Some((index, _)) = entries.get(previous) {
+                        self.selected = *index;
+                    }
+                }
+                KeyCode::Down => {
+                    let next = position.map_or(0, |position| position + 1);
+                    if let Some((index, _)) = entries.get(next) {
+                        self.selected = *index;
+                    }
+                }
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                _ => {}
+            }
+            // A changed filter selects its best match
+            if matches!(key.code, KeyCode::Char(_) | KeyCode::Backspace)
+                && !self.filter.is_empty()
+                && let Some((index, _)) = self.entries().first()
+            {
+                self.selected = *index;
+            }
+        }
+    }
+
+    // Keys while searching the books' text: typing edits the query, Enter
+    // runs it or, once it's found something, opens the match selected
+    fn handle_search_key(
+        &mut self,
+        code: KeyCode,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+        progress: &Progress,
+        theme: &Theme,
+    ) -> Result<Option<Choice>, UiError> {
+        let Some(query) = &mut self.query else {
+            return Ok(None);
+        };
+        match code {
+            KeyCode::Esc if query.is_empty() => self.query = None,
+            KeyCode::Esc => {
+                query.clear();
+                self.hits.clear();
+            }
+            KeyCode::Enter if !self.hits.is_empty() => {
+                let hit = &self.hits[self.selected_hit];
+                return Ok(Some(Choice::Match {
+                    path: self.books[hit.book].path.clone(),
+                    query: query.clone(),
+                    chapter: hit.chapter,
+                    line: hit.line,
+                }));
+            }
+            KeyCode::Enter if !query.trim().is_empty() => {
+                let query = query.clone();
+                self.status_message = Some(format!("Searching {} books…", self.books.len()));
+                terminal.draw(|f| self.draw(f, progress, theme))?;
+                if let Some((options, index_dir)) = &self.search_in {
+                    self.hits = search_books(&self.books, &query, options, index_dir);
+                }
+                self.selected_hit = 0;
+                self.status_message = self.hits.is_empty().then(|| {
+                    format!("No matches for {query} in the books opened since they were added")
+                });
+            }
+            KeyCode::Up => self.selected_hit = self.selected_hit.saturating_sub(1),
+            KeyCode::Down => {
+                self.selected_hit = (self.selected_hit + 1).min(self.hits.len().saturating_sub(1));
+            }
+            KeyCode::Char(c) => {
+                query.push(c);
+                self.hits.clear();
+            }
+            KeyCode::Backspace => {
+                query.pop();
+                self.hits.clear();
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    // Books to list with the byte ranges of their labels matching the
+    // filter, best matches first while filtering
+    fn entries(&self) -> Vec<(usize, Vec<Range<usize>>)> {
+        if self.filter.trim().is_empty() {
+            return (0..self.books.len()).map(|index| (index, Vec::new())).collect();
+        }
+        let mut matches: Vec<(usize, search::Match)> = self
+            .books
+            .iter()
+            .enumerate()
+            .filter_map(|(index, book)| {
+                Some((index, search::fuzzy_match(&Self::label(book), &self.filter)?))
+            })
+            .collect();
+        matches.sort_by_key(|(_, found)| std::cmp::Reverse(found.score));
+        matches.into_iter().map(|(index, found)| (index, found.ranges)).collect()
+    }
+
+    fn label(book: &LibraryBook) -> String {
+        format!("{} by {}", book.title, book.author)
+    }
+
+    fn draw(&self, f: &mut Frame, progress: &Progress, theme: &Theme) {
+        f.render_widget(Block::default().style(theme.text.bg(theme.background)), f.area());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(f.area());
+
+        let (items, title, selected) = match &self.query {
+            Some(query) => self.hit_items(query, theme),
+            None => self.book_items(progress, theme),
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.border)
+                    .title(title)
+                    .title_style(theme.accent.add_modifier(Modifier::BOLD))
+                    .padding(Padding::horizontal(1)),
+            )
+            .style(theme.text)
+            .highlight_style(theme.selection.add_modifier(Modifier::BOLD))
+            .highlight_symbol("▶ ");
+        let mut list_state = ListState::default();
+        list_state.select(selected);
+        f.render_stateful_widget(list, chunks[0], &mut list_state);
+
+        let footer = match &self.status_message {
+            Some(message) => Line::from(Span::styled(message.as_str(), theme.warning)),
+            None if self.query.is_some() => Line::from(vec![
+                Span::styled("type", theme.special),
+                Span::styled(":query ", theme.muted),
+                Span::styled("⏎", theme.confirm),
+                Span::styled(":search/open ", theme.muted),
+                Span::styled("↑↓", theme.accent),
+                Span::styled(":choose ", theme.muted),
+                Span::styled("Esc", theme.danger),
+                Span::styled(":back", theme.muted),
+            ]),
+            None => Line::from(vec![
+                Span::styled("↑↓", theme.accent),
+                Span::styled(":choose ", theme.muted),
+                Span::styled("⏎", theme.confirm),
+                Span::styled(":read ", theme.muted),
+                Span::styled("type", theme.special),
+                Span::styled(":filter ", theme.muted),
+                Span::styled("^f", theme.special),
+                Span::styled(":search books ", theme.muted),
+                Span::styled("Esc", theme.danger),
+                Span::styled(":quit", theme.muted),
+            ]),
+        };
+        f.render_widget(Paragraph::new(footer).alignment(Alignment::Center), chunks[1]);
+    }
+
+    // The books, each with how much of it has been read, and which is
+    // selected
+    fn book_items(
+        &self,
+        progress: &Progress,
+        theme: &Theme,
+    ) -> (Vec<ListItem<'static>>, String, Option<usize>) {
+        let entries = self.entries();
+        let items = entries
+            .iter()
+            .map(|(index, ranges)| {
+                let book = &self.books[*index];
+                let read = match book.percent_read(progress) {
+                    Some(percent) => Span::styled(format!("{percent:>3}%  "), theme.accent),
+                    None => Span::styled("   ·  ", theme.muted),
+                };
+                let mut spans = vec![read];
+                spans.extend(Self::label_spans(book, ranges, theme));
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+
+        let title = if self.filter.is_empty() {
+            format!("📚 Library ({} books)", self.books.len())
+        } else {
+            format!("📚 Library · {} ({} of {})", self.filter, entries.len(), self.books.len())
+        };
+        let selected = entries.iter().position(|(index, _)| *index == self.selected);
+        (items, title, selected)
+    }
+
+    // Matches of a search through the books as "Book — Chapter — snippet",
+    // with the query highlighted in the snippet
+    fn hit_items(
+        &self,
+        query: &str,
+        theme: &Theme,
+    ) -> (Vec<ListItem<'static>>, String, Option<usize>) {
+        let parsed = search::Query::parse(query);
+        let items = self
+            .hits
+            .iter()
+            .map(|hit| {
+                let mut spans = vec![
+                    Span::styled(
+                        self.books[hit.book].title.clone(),
+                        theme.text.add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(format!(" — {} — ", hit.chapter_title), theme.muted),
+                ];
+                let mut end = 0;
+                for range in parsed.find(&hit.snippet) {
+                    spans.push(Span::styled(hit.snippet[end..range.start].to_string(), theme.text));
+                    let matched = hit.snippet[range.clone()].to_string();
+                    spans.push(Span::styled(matched, theme.highlight));
+                    end = range.end;
+                }
+                spans.push(Span::styled(hit.snippet[end..].to_string(), theme.text));
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+
+        let title = if self.hits.is_empty() {
+            format!("🔍 Search books · {query}")
+        } else {
+            format!("🔍 Search books · {query} ({} matches)", self.hits.len())
+        };
+        (items, title, (!self.hits.is_empty()).then_some(self.selected_hit))
+    }
+
+    // The title in bold and the author dimmed, with the characters matching
+    // the filter highlighted
+    fn label_spans(
+        book: &LibraryBook,
+        ranges: &[Range<usize>],
+        theme: &Theme,
+    ) -> Vec<Span<'static>> {
+        let label = Self::label(book);
+        let title_end = book.title.len();
+        let mut spans = Vec::new();
+        let mut boundaries = vec![0, title_end, label.len()];
+        for range in ranges {
+            boundaries.extend([range.start, range.end]);
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        for pair in boundaries.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            let base = if start < title_end {
+                theme.text.add_modifier(Modifier::BOLD)
+            } else {
+                theme.muted
+            };
+            let matched = ranges.iter().any(|range| range.start <= start && end <= range.end);
+            let style = if matched { base.patch(theme.highlight) } else { base };
+            spans.push(Span::styled(label[start..end].to_string(), style));
+        }
+        spans
+    }
+}
+
@@ -0,0 +1,159 @@
+use crate::convert;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde_json::Value;
+
+/// Where a word or phrase is looked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Wikipedia,
+    Wiktionary,
+}
+
+impl Source {
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Wikipedia => "Wikipedia",
+            Source::Wiktionary => "Wiktionary",
+        }
+    }
+}
+
+/// What a lookup found, with the page to read more on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub source: Source,
+    pub title: String,
+    pub text: String,
+    pub url: String,
+}
+
+/// Look `phrase` up, in the Wikipedia of `language` (a BCP 47 tag such as
+/// the book's) or, for Wiktionary, among the English Wiktionary's entries
+/// for words in that language. This waits on the network, so it's best
+/// called away from the UI thread.
+pub fn fetch(source: Source, phrase: &str, language: &str) -> Result<Summary, String> {
+    let phrase = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+    if phrase.is_empty() {
+        return Err("Nothing to look up".to_string());
+    }
+    let language = primary_language(language);
+    let language = language.as_str();
+    let title = page_title(&phrase);
+    match source {
+        Source::Wikipedia => {
+            let url = format!("https://{language}.wikipedia.org/api/rest_v1/page/summary/{title}");
+            let json = get(&url)?.ok_or_else(|| format!("No Wikipedia article on {phrase}"))?;
+            parse_wikipedia(&json).ok_or_else(|| format!("No Wikipedia article on {phrase}"))
+        }
+        Source::Wiktionary => {
+            let url = format!("https://en.wiktionary.org/api/rest_v1/page/definition/{title}");
+            let json = get(&url)?.ok_or_else(|| format!("No Wiktionary entry for {phrase}"))?;
+            parse_wiktionary(&json, &phrase, language)
+                .ok_or_else(|| format!("No Wiktionary entry for {phrase}"))
+        }
+    }
+}
+
+/// The article summary in a response from Wikipedia's page summary API.
+pub fn parse_wikipedia(json: &Value) -> Option<Summary> {
+    let extract = json.get("extract")?.as_str()?.trim();
+    if extract.is_empty() {
+        return None;
+    }
+    Some(Summary {
+        source: Source::Wikipedia,
+        title: json.get("title")?.as_str()?.to_string(),
+        text: extract.to_string(),
+        url: json.pointer("/content_urls/desktop/page")?.as_str()?.to_string(),
+    })
+}
+
+/// The definitions in a response from Wiktionary's definition API, for
+/// words in `language` if it has any and otherwise the first language
+/// listed, numbered under each part of speech.
+pub fn parse_wiktionary(json: &Value, word: &str, language: &str) -> Option<Summary> {
+    let entries = json
+        .get(language)
+        .or_else(|| json.as_object()?.values().next())?
+        .as_array()?;
+    let mut text = String::new();
+    for entry in entries {
+        let definitions: Vec<String> = entry
+            .get("definitions")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|definition| definition.get("definition")?.as_str())
+            .map(plain_text)
+            .filter(|definition| !definition.is_empty())
+            .collect();
+        if definitions.is_empty() {
+            continue;
+        }
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        if let Some(part) = entry.get("partOfSpeech").and_then(Value::as_str) {
+            text.push_str(part);
+            text.push('\n');
+        }
+        for (number, definition) in definitions.iter().enumerate() {
+            text.push_str(&format!("{}. {definition}\n", number + 1));
+        }
+    }
+    if text.is_empty() {
+        return None;
+    }
+    Some(Summary {
+        source: Source::Wiktionary,
+        title: word.to_string(),
+        text: text.trim_end().to_string(),
+        url: format!("https://en.wiktionary.org/wiki/{}", page_title(word)),
+    })
+}
+
+// A page's title as it goes in a URL, with underscores for spaces
+fn page_title(phrase: &str) -> String {
+    utf8_percent_encode(&phrase.replace(' ', "_"), NON_ALPHANUMERIC).to_string()
+}
+
+/// The language code a BCP 47 tag starts with, as `en` for `en-GB`, which
+/// names the Wikipedia to look in. Since the tag comes from the book, it's
+/// English unless the code is two or three letters, so that it can't
+/// point the lookup at some other host.
+pub fn primary_language(language: &str) -> String {
+    let primary = language.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+    if (2..=3).contains(&primary.len()) && primary.bytes().all(|b| b.is_ascii_lowercase()) {
+        primary
+    } else {
+        "en".to_string()
+    }
+}
+
+// Definitions come as HTML fragments with links and markup
+fn plain_text(html: &str) -> String {
+    convert::html_to_text(html, 10_000)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// The JSON at `url`, or `None` if there's nothing there
+#[cfg(feature = "online")]
+fn get(url: &str) -> Result<Option<Value>, String> {
+    let response = ureq::get(url)
+        .set("User-Agent", concat!("catatau/", env!("CARGO_PKG_VERSION")))
+        .timeout(std::time::Duration::from_secs(10))
+        .call();
+    let body = match response {
+        Ok(response) => response.into_string().map_err(|e| e.to_string())?,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(format!("Lookup failed: {e}")),
+    };
+    serde_json::from_str(&body).map(Some).map_err(|e| format!("Lookup failed: {e}"))
+}
+
+#[cfg(not(feature = "online"))]
+fn get(_url: &str) -> Result<Option<Value>, String> {
+    Err("Lookups need catatau built with the online feature".to_string())
+}
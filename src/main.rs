@@ -1,47 +1,853 @@
-use clap::Parser;
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use crossterm::{
     execute,
     terminal::{disable_raw_mode, LeaveAlternateScreen},
 };
-use std::{io, path::PathBuf};
+use std::{
+    backtrace::Backtrace,
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::level_filters::LevelFilter;
 
+mod annotations;
+mod background;
+mod bidi;
+mod cat;
+#[cfg(feature = "calibre")]
+mod calibre;
+mod check;
+mod config;
+mod clipboard;
 mod constants;
+mod convert;
+mod crash;
+mod css;
 mod epub;
 mod error;
+mod export;
+#[cfg(feature = "highlight")]
+mod highlight;
+#[cfg(feature = "image-art")]
+mod image_art;
+mod info;
+mod keymap;
+mod library;
+mod lookup;
+mod markdown;
+mod narration;
+mod progress;
+mod remote;
+mod search;
+mod stats;
+mod theme;
+mod translate;
+mod watch;
 mod ui;
 
-use epub::EpubReader;
-use ui::App;
+use config::Config;
+use constants::{
+    COVERS_DIR, HTML_TEXT_WIDTH, LIBRARY_CACHE_FILE, LOG_FILE, PROGRESS_FILE, SEARCH_INDEX_DIR,
+};
+use convert::{ImageArt, RubyStyle};
+use epub::EpubReaderOptions;
+use theme::Theme;
+use translate::Translator;
+use ui::{App, DisplayOptions, Exit, LineSpacing, Profile};
 
 #[derive(Parser)]
 #[command(name = "catatau")]
 #[command(about = "A terminal EPUB reader")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    epub_file: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The books to read, switched between with gt and gT, or a directory
+    /// of books to choose from. Without any, books are looked for in
+    /// $CATATAU_LIBRARY or ~/Books
+    epub_files: Vec<PathBuf>,
+
+    /// Maximum text column width; wider terminals centre the text
+    #[arg(long, value_name = "COLUMNS")]
+    width: Option<usize>,
+
+    /// Rows each line of text takes, leaving blank ones between lines:
+    /// single, double, or a number such as 1.5
+    #[arg(long, value_name = "ROWS")]
+    line_spacing: Option<LineSpacing>,
+
+    /// Draw pictures as text art: off, halfblock or braille
+    #[arg(long, value_name = "STYLE", default_value = "off")]
+    images: ImageArt,
+
+    /// Show ruby readings (furigana) inline after their base text, or strip them
+    #[arg(long, value_name = "STYLE", default_value = "inline")]
+    ruby: RubyStyle,
+
+    /// Tidy typography: drop soft hyphens and doubled spaces, fix literal
+    /// entities and turn -- into em dashes
+    #[arg(long)]
+    typography: bool,
+
+    /// Colour theme: dark, light, sepia, gruvbox, high-contrast, monochrome
+    /// or one of your own; light or dark to match the terminal if not given
+    #[arg(long, value_name = "NAME")]
+    theme: Option<Theme>,
+
+    /// Draw without colour, using only bold, underline and reverse video.
+    /// Also the default when NO_COLOR is set
+    #[arg(long, conflicts_with = "theme")]
+    no_color: bool,
+
+    /// Draw for a kind of screen: eink keeps the cursor steady, leaves out
+    /// the progress bar and spinners, opens books paged and, without a
+    /// theme, draws in monochrome
+    #[arg(long, value_name = "NAME")]
+    profile: Option<Profile>,
+
+    /// Ask before q quits (ZZ still quits straight away)
+    #[arg(long)]
+    confirm_quit: bool,
+
+    /// Start from these key bindings rather than the config file's: vim,
+    /// emacs or less
+    #[arg(long, value_name = "NAME")]
+    keymap: Option<keymap::Preset>,
+
+    /// Read settings from this file instead of ~/.config/catatau/config.toml
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Append the log to this file; otherwise it goes to stderr, or while
+    /// reading to catatau.log in ~/.local/state/catatau
+    #[arg(long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Log messages this severe or worse: off, error, warn, info, debug or
+    /// trace
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<LevelFilter>,
+
+    /// Start at the beginning rather than where the book was left
+    #[arg(long)]
+    no_resume: bool,
+
+    /// Type these keys once the book is open, written as in the config
+    /// file's [keys], as in "jjl/magic<Enter>"; Alt-q records keys to replay
+    #[arg(long, value_name = "KEYS")]
+    keys: Option<String>,
+
+    /// Take requests from other programs on a Unix socket at this path:
+    /// JSON-RPC, one request to a line, such as
+    /// {"jsonrpc": "2.0", "id": 1, "method": "goto-chapter", "params": {"chapter": 3}}
+    #[arg(long, value_name = "SOCKET")]
+    listen: Option<PathBuf>,
+
+    /// Reload the book whenever its file changes, keeping your place, as
+    /// when it's being written with pandoc or mdBook
+    #[arg(long)]
+    watch: bool,
+
+    /// Open the book at this chapter, counting from one
+    #[arg(long, value_name = "N", group = "start_at")]
+    chapter: Option<usize>,
+
+    /// Open the book this far through, as a percentage
+    #[arg(
+        long,
+        value_name = "P",
+        group = "start_at",
+        value_parser = clap::value_parser!(u8).range(..=100)
+    )]
+    percent: Option<u8>,
+
+    /// Open the book at the first line matching this, searched for as with /
+    #[arg(long, value_name = "PHRASE", group = "start_at")]
+    find: Option<String>,
+
+    /// Write the book's highlights and notes to a file instead of reading
+    /// it: Readwise CSV for .csv, Readwise JSON for .json, Anki cards for
+    /// .tsv, otherwise Markdown
+    #[arg(long, value_name = "FILE")]
+    export_annotations: Option<PathBuf>,
+
+    /// Translate passages selected with t, or the paragraph at the top of
+    /// the screen with T: argos (the argos-translate command), deepl
+    /// ($DEEPL_API_KEY) or google ($GOOGLE_TRANSLATE_API_KEY)
+    #[arg(long, value_name = "BACKEND")]
+    translator: Option<translate::Backend>,
+
+    /// The language to translate into; the locale's if not given
+    #[arg(long, value_name = "LANG")]
+    translate_to: Option<String>,
+
+    /// Read a book from a Calibre library, given by its id, "Author/Title"
+    /// or its title
+    #[cfg(feature = "calibre")]
+    #[arg(long, value_name = "BOOK", conflicts_with = "epub_files")]
+    calibre: Option<String>,
+
+    /// The Calibre library to look in; $CALIBRE_LIBRARY or ~/Calibre Library
+    /// if not given
+    #[cfg(feature = "calibre")]
+    #[arg(long, value_name = "DIR", requires = "calibre")]
+    calibre_library: Option<PathBuf>,
+
+    /// Write how far through the book you got, as a percentage, to this
+    /// numeric custom column of the Calibre library when you stop reading
+    #[cfg(feature = "calibre")]
+    #[arg(long, value_name = "LABEL", requires = "calibre")]
+    calibre_column: Option<String>,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+
+    /// Print the manual page, in roff, instead of reading
+    #[arg(long, conflicts_with = "epub_files")]
+    generate_man: bool,
+
+    /// Settings from the config file, for whatever isn't given above.
+    #[arg(skip)]
+    settings: Config,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_level(false)
-        .with_writer(std::io::stderr)
-        .init();
+#[derive(Subcommand)]
+enum Command {
+    /// Validate an EPUB and print a JSON report, exiting non-zero on errors
+    Check {
+        epub_file: PathBuf,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Print the lines of an EPUB matching a query, with the lines around
+    /// them, exiting non-zero if there are none
+    Search {
+        epub_file: PathBuf,
+
+        query: String,
+
+        /// Print the matches as JSON
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Print the highlights and notes made in a book as Markdown, grouped by
+    /// chapter, or for Readwise to import
+    Annotations {
+        epub_file: PathBuf,
+
+        /// Write them to a file instead
+        #[arg(long, value_name = "FILE")]
+        export: Option<PathBuf>,
+
+        /// markdown, csv or json (both laid out for Readwise), or anki for
+        /// flashcards. Taken from the file's extension if not given
+        #[arg(long, value_name = "FORMAT")]
+        format: Option<annotations::Format>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Print a book's text, as the reader shows it, without opening the
+    /// reader
+    Cat {
+        epub_file: PathBuf,
+
+        /// Only these chapters, counting from one: N, N..M, N.. or ..M
+        #[arg(long, value_name = "RANGE")]
+        chapter: Option<cat::ChapterRange>,
+
+        /// Wrap the text to this many columns
+        #[arg(long, value_name = "COLUMNS", default_value_t = HTML_TEXT_WIDTH)]
+        width: usize,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Print a book's details and its chapters with their word counts
+    Info {
+        epub_file: PathBuf,
+
+        /// Print them as JSON
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Print how many words and sentences a book has, how long it takes to
+    /// read, and the same for each chapter
+    Stats {
+        epub_file: PathBuf,
+
+        /// Print them as JSON
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Write each chapter of a book to a file of its own, as Markdown or as
+    /// plain text
+    Export {
+        epub_file: PathBuf,
+
+        /// md, for Markdown with the book's headings, emphasis, links and
+        /// images, or txt for the text as the reader shows it
+        #[arg(long, value_name = "FORMAT", default_value = "md")]
+        format: export::Format,
+
+        /// The directory to write the files to, made if it isn't there
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Print a script completing catatau's arguments in the given shell:
+    /// bash, zsh, fish, elvish or powershell
+    Completions { shell: clap_complete::Shell },
+}
+
+#[derive(Args)]
+struct LimitArgs {
+    /// Maximum EPUB file size in megabytes
+    #[arg(
+        long,
+        value_name = "MB",
+        value_parser = clap::value_parser!(u64).range(..=u64::MAX / MEGABYTE)
+    )]
+    max_size: Option<u64>,
+
+    /// Maximum size of a single converted chapter in megabytes
+    #[arg(
+        long,
+        value_name = "MB",
+        value_parser = clap::value_parser!(u64).range(..=usize::MAX as u64 / MEGABYTE)
+    )]
+    max_chapter_size: Option<u64>,
 
+    /// Maximum compression ratio accepted for archive entries
+    #[arg(long, value_name = "RATIO")]
+    max_ratio: Option<usize>,
+}
+
+// The sizes above are given in these, and kept small enough to count in
+// bytes
+const MEGABYTE: u64 = 1024 * 1024;
+
+impl LimitArgs {
+    fn reader_options(&self) -> EpubReaderOptions {
+        let mut options = EpubReaderOptions::new();
+        if let Some(mb) = self.max_size {
+            options = options.max_file_size(mb * MEGABYTE);
+        }
+        if let Some(mb) = self.max_chapter_size {
+            options = options.max_chapter_size((mb * MEGABYTE) as usize);
+        }
+        if let Some(ratio) = self.max_ratio {
+            options = options.max_decompressed_ratio(ratio);
+        }
+        options
+    }
+}
+
+fn run_check(epub_file: &Path, limits: &LimitArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let report = check::check_epub(epub_file, &limits.reader_options());
+    println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_search(
+    epub_file: &Path,
+    query: &str,
+    json: bool,
+    limits: &LimitArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let epub = limits
+        .reader_options()
+        .open(epub_file)
+        .map_err(|e| format!("Failed to open EPUB file: {}", e))?;
+    let results = epub.search(query);
+    if json {
+        let report = search::results_json(&epub, query, &results);
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", search::results_text(&results));
+    }
+
+    if results.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_cat(
+    epub_file: &Path,
+    chapters: Option<cat::ChapterRange>,
+    width: usize,
+    limits: &LimitArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let epub = limits
+        .reader_options()
+        .open(epub_file)
+        .map_err(|e| format!("Failed to open EPUB file: {}", e))?;
+    epub.set_text_width(width);
+    let chapters = chapters.unwrap_or_default().indices(epub.chapter_count())?;
+    match cat::write_text(&epub, chapters, &mut io::stdout().lock()) {
+        // Piped into head or less and closed early
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn run_info(
+    epub_file: &Path,
+    json: bool,
+    limits: &LimitArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let epub = limits
+        .reader_options()
+        .open(epub_file)
+        .map_err(|e| format!("Failed to open EPUB file: {}", e))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info::info_json(&epub))?);
+    } else {
+        print!("{}", info::info_text(&epub));
+    }
+    Ok(())
+}
+
+fn run_stats(
+    epub_file: &Path,
+    json: bool,
+    limits: &LimitArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let epub = limits
+        .reader_options()
+        .open(epub_file)
+        .map_err(|e| format!("Failed to open EPUB file: {}", e))?;
+    let stats = info::text_stats(&epub);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info::stats_json(&stats))?);
+    } else {
+        print!("{}", info::stats_text(&stats));
+    }
+    Ok(())
+}
+
+fn run_export(
+    epub_file: &Path,
+    format: export::Format,
+    out: &Path,
+    limits: &LimitArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let epub = limits
+        .reader_options()
+        .open(epub_file)
+        .map_err(|e| format!("Failed to open EPUB file: {}", e))?;
+    let written = export::export_book(&epub, format, out)
+        .map_err(|e| format!("Couldn't write to {}: {e}", out.display()))?;
+    println!("Wrote {} chapters to {}", written.len(), out.display());
+    Ok(())
+}
+
+fn run_annotations(
+    epub_file: &Path,
+    export: Option<&Path>,
+    format: Option<annotations::Format>,
+    limits: &LimitArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let epub = limits
+        .reader_options()
+        .open(epub_file)
+        .map_err(|e| format!("Failed to open EPUB file: {}", e))?;
+    let saved = progress::state_dir()
+        .map(|dir| progress::Progress::load(&dir.join(PROGRESS_FILE)))
+        .unwrap_or_default();
+    let highlights = saved
+        .book(&progress::book_key(epub_file))
+        .map(|book| book.highlights.as_slice())
+        .unwrap_or_default();
+    if highlights.is_empty() {
+        return Err(format!("No highlights in {}", epub_file.display()).into());
+    }
+
+    let format = format.or_else(|| export.map(annotations::Format::for_path)).unwrap_or_default();
+    let text = annotations::export(&epub, highlights, format);
+    match export {
+        Some(path) => std::fs::write(path, text)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+// Read a book kept in a Calibre library, writing back how far through it
+// the reader got if asked to
+#[cfg(feature = "calibre")]
+fn run_calibre(cli: &Cli, wanted: &str, theme: &Theme) -> Result<(), Box<dyn std::error::Error>> {
+    let library = cli
+        .calibre_library
+        .clone()
+        .or_else(calibre::library_dir)
+        .ok_or("No Calibre library found; give one with --calibre-library")?;
+    let book = calibre::find_book(&library, wanted)?;
+    read_books(cli, std::slice::from_ref(&book.path), theme)?;
+
+    if let Some(column) = &cli.calibre_column {
+        let epub = book_options(cli)
+            .open(&book.path)
+            .map_err(|e| format!("Failed to open {}: {}", book.path.display(), e))?;
+        let percent = progress::state_dir()
+            .map(|dir| progress::Progress::load(&dir.join(PROGRESS_FILE)))
+            .unwrap_or_default()
+            .book(&progress::book_key(&book.path))
+            .and_then(|progress| progress.percent_through(epub.chapter_count()));
+        if let Some(percent) = percent {
+            calibre::save_progress(&library, book.id, column, percent)?;
+        }
+    }
+    Ok(())
+}
+
+// Show the library's books to pick from, coming back to the list each time
+// a book is closed
+fn run_library(
+    cli: &Cli,
+    dir: Option<&Path>,
+    theme: &Theme,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dirs = match dir {
+        Some(dir) => vec![dir.to_path_buf()],
+        None => library::library_dirs(&cli.settings.library),
+    };
+    let paths = library::find_books(&dirs);
+    if paths.is_empty() {
+        let dirs: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+        return Err(format!(
+            "No EPUB files found in {}; give a book, or a directory of them, or set \
+             CATATAU_LIBRARY",
+            dirs.join(", ")
+        )
+        .into());
+    }
+
+    let data_dir = progress::data_dir();
+    let cache_file = data_dir.as_ref().map(|dir| dir.join(LIBRARY_CACHE_FILE));
+    let mut cache = cache_file.as_deref().map(library::LibraryCache::load).unwrap_or_default();
+    let covers = data_dir.as_ref().map(|dir| dir.join(COVERS_DIR));
+    let books =
+        library::load_books(&paths, &cli.limits.reader_options(), &mut cache, covers.as_deref());
+    if let Some(cache_file) = &cache_file
+        && let Err(e) = cache.save(cache_file)
+    {
+        tracing::warn!("Could not save the library cache to {}: {}", cache_file.display(), e);
+    }
+    let mut picker = library::Picker::new(books);
+    if let Some(dir) = &data_dir {
+        picker.keep_search_index(book_options(cli), dir.join(SEARCH_INDEX_DIR));
+    }
+    loop {
+        // Reading a book changes its progress, so it's loaded afresh each time
+        let progress = progress::state_dir()
+            .map(|dir| progress::Progress::load(&dir.join(PROGRESS_FILE)))
+            .unwrap_or_default();
+        let Some(choice) = picker.run(&progress, theme)? else {
+            return Ok(());
+        };
+        let read = match choice {
+            library::Choice::Book(epub_file) => read_books(cli, &[epub_file], theme),
+            library::Choice::Match {
+                path,
+                query,
+                chapter,
+                line,
+            } => open_book(cli, &path, theme.clone()).and_then(|mut app| {
+                app.show_match(&query, chapter, line);
+                read_apps(vec![app])
+            }),
+        };
+        if let Err(e) = read {
+            picker.report(e.to_string());
+        }
+    }
+}
+
+// Open the books and read them, switching between them like editor buffers
+fn read_books(
+    cli: &Cli,
+    epub_files: &[PathBuf],
+    theme: &Theme,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let keys = match &cli.keys {
+        Some(keys) => keymap::parse_keys(keys).map_err(|e| format!("Bad --keys: {e}"))?,
+        None => Vec::new(),
+    };
+    let mut apps = epub_files
+        .iter()
+        .map(|epub_file| open_book(cli, epub_file, theme.clone()))
+        .collect::<Result<Vec<App>, _>>()?;
+    if let Some(first) = apps.first_mut() {
+        first.play_keys(&keys);
+        if let Some(socket) = &cli.listen {
+            first
+                .listen(socket)
+                .map_err(|e| format!("Failed to listen on {}: {}", socket.display(), e))?;
+        }
+    }
+    read_apps(apps)
+}
+
+fn read_apps(mut apps: Vec<App>) -> Result<(), Box<dyn std::error::Error>> {
+    if apps.len() > 1 {
+        let titles: Vec<String> = apps.iter().map(|app| app.epub().title.clone()).collect();
+        for (index, app) in apps.iter_mut().enumerate() {
+            app.set_open_books(titles.clone(), index);
+        }
+    }
+
+    let mut current = 0;
+    loop {
+        match apps[current].run()? {
+            Exit::Quit => return Ok(()),
+            Exit::SwitchBook(next) => {
+                let (from, to) = if current < next {
+                    let (before, after) = apps.split_at_mut(next);
+                    (&mut before[current], &mut after[0])
+                } else {
+                    let (before, after) = apps.split_at_mut(current);
+                    (&mut after[0], &mut before[next])
+                };
+                from.hand_over_terminal(to);
+                current = next;
+            }
+        }
+    }
+}
+
+// How books are opened for reading, which a search through them should
+// match
+fn book_options(cli: &Cli) -> EpubReaderOptions {
+    let options = cli
+        .limits
+        .reader_options()
+        .image_art(cli.images)
+        .ruby(cli.ruby)
+        .typography(cli.typography)
+        .paragraphs(
+            cli.settings.paragraphs.style.unwrap_or_default(),
+            cli.settings.paragraphs.indent,
+        );
+    match cli.settings.cache_mb {
+        Some(mb) => options.cache_budget(mb.get() * 1024 * 1024),
+        None => options,
+    }
+}
+
+// The kind of screen to draw for, from the flag or the config file
+fn profile(cli: &Cli) -> Profile {
+    cli.profile.or(cli.settings.profile).unwrap_or_default()
+}
+
+fn open_book(
+    cli: &Cli,
+    epub_file: &Path,
+    theme: Theme,
+) -> Result<App, Box<dyn std::error::Error>> {
+    let epub = book_options(cli)
+        .open(epub_file)
+        .map_err(|e| format!("Failed to open {}: {}", epub_file.display(), e))?;
+    let display = DisplayOptions {
+        max_width: cli.width.or(cli.settings.width),
+        theme,
+        zen: false,
+        confirm_quit: cli.confirm_quit || cli.settings.confirm_quit,
+        paged: cli.settings.scroll.paged || profile(cli) == Profile::Eink,
+        scroll_lines: cli.settings.scroll.lines.get(),
+        line_spacing: cli.line_spacing.or(cli.settings.line_spacing).unwrap_or_default(),
+        profile: profile(cli),
+        keymap: cli.settings.keys.clone(),
+    };
+    let mut app = App::with_options(epub, display);
+    if let Some(dir) = progress::state_dir() {
+        app.keep_state(dir);
+        if !cli.no_resume && cli.settings.resume {
+            app.resume();
+        }
+    }
+    if let Some(chapter) = cli.chapter {
+        app.go_to_chapter(chapter)?;
+    } else if let Some(percent) = cli.percent {
+        app.go_to_book_percent(percent.into());
+    } else if let Some(phrase) = &cli.find {
+        app.find_first(phrase)?;
+    }
+    if let Some(dir) = progress::data_dir() {
+        app.keep_search_index(dir.join(SEARCH_INDEX_DIR));
+    }
+    if cli.watch {
+        app.watch()
+            .map_err(|e| format!("Failed to watch {}: {}", epub_file.display(), e))?;
+    }
+    if let Some(backend) = cli.translator.as_ref().or(cli.settings.translator.as_ref()) {
+        let target = cli
+            .translate_to
+            .clone()
+            .or_else(|| cli.settings.translate_to.clone())
+            .unwrap_or_else(translate::locale_language);
+        app.translate_with(Translator::new(backend.clone(), &target));
+    }
+    Ok(app)
+}
+
+// Log to the file asked for, or to stderr unless the reader is about to
+// take over the terminal, when the log goes to a fresh file in the state
+// directory so that nothing is written over the screen
+fn init_logging(cli: &Cli, reading: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let level = cli.log_level.or(cli.settings.log_level).unwrap_or(LevelFilter::INFO);
+    let file = match cli.log_file.as_ref().or(cli.settings.log_file.as_ref()) {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path);
+            Some(file.map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?)
+        }
+        None if reading => progress::state_dir().and_then(|dir| {
+            std::fs::create_dir_all(&dir).ok()?;
+            File::create(dir.join(LOG_FILE)).ok()
+        }),
+        None => None,
+    };
+    let logger = tracing_subscriber::fmt().with_target(false).with_max_level(level);
+    match file {
+        Some(file) => logger.with_ansi(false).with_writer(Mutex::new(file)).init(),
+        None if reading => logger.with_writer(io::sink).init(),
+        None => logger.with_level(false).with_writer(io::stderr).init(),
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let original_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
-        let _ = disable_raw_mode();
-        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        // A background thread's panic leaves the reader running, so the
+        // screen is only given back when the main thread goes down
+        if std::thread::current().name() == Some("main") {
+            let _ = disable_raw_mode();
+            let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        }
         original_hook(panic_info);
+
+        let backtrace = Backtrace::force_capture();
+        let report = crash::report(&panic_info.to_string(), &backtrace, crash::snapshot().as_ref());
+        match progress::state_dir().map(|dir| crash::write_report(&dir, &report)) {
+            Some(Ok(path)) => {
+                eprintln!("A crash report was written to {}", path.display());
+                eprintln!("Please attach it to a bug report");
+            }
+            Some(Err(e)) => eprintln!("Failed to write a crash report: {}", e),
+            None => {}
+        }
     }));
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    let reading = cli.command.is_none() && cli.export_annotations.is_none() && !cli.generate_man;
+    if reading {
+        cli.settings = match &cli.config {
+            Some(path) => Config::load(path)?,
+            None => Config::load_default()?,
+        };
+        if let Some(preset) = cli.keymap {
+            cli.settings.use_keymap(preset)?;
+        }
+    }
+    init_logging(&cli, reading)?;
 
-    let epub =
-        EpubReader::new(&cli.epub_file).map_err(|e| format!("Failed to open EPUB file: {}", e))?;
-    let mut app = App::new(epub);
+    match &cli.command {
+        Some(Command::Check { epub_file, limits }) => return run_check(epub_file, limits),
+        Some(Command::Search {
+            epub_file,
+            query,
+            json,
+            limits,
+        }) => return run_search(epub_file, query, *json, limits),
+        Some(Command::Annotations {
+            epub_file,
+            export,
+            format,
+            limits,
+        }) => return run_annotations(epub_file, export.as_deref(), *format, limits),
+        Some(Command::Cat {
+            epub_file,
+            chapter,
+            width,
+            limits,
+        }) => return run_cat(epub_file, *chapter, *width, limits),
+        Some(Command::Info {
+            epub_file,
+            json,
+            limits,
+        }) => return run_info(epub_file, *json, limits),
+        Some(Command::Stats {
+            epub_file,
+            json,
+            limits,
+        }) => return run_stats(epub_file, *json, limits),
+        Some(Command::Export {
+            epub_file,
+            format,
+            out,
+            limits,
+        }) => return run_export(epub_file, *format, out, limits),
+        Some(Command::Completions { shell }) => {
+            let bin_name = env!("CARGO_BIN_NAME");
+            clap_complete::generate(*shell, &mut Cli::command(), bin_name, &mut io::stdout());
+            return Ok(());
+        }
+        None => {}
+    }
+    if cli.generate_man {
+        let command = Cli::command().name(env!("CARGO_BIN_NAME"));
+        clap_mangen::Man::new(command).render(&mut io::stdout())?;
+        return Ok(());
+    }
+    if let Some(export) = &cli.export_annotations {
+        let [epub_file] = cli.epub_files.as_slice() else {
+            return Err("--export-annotations takes one book".into());
+        };
+        return run_annotations(epub_file, Some(export), None, &cli.limits);
+    }
 
-    app.run()
-        .map_err(|e| -> Box<dyn std::error::Error> { Box::new(e) })?;
+    // https://no-color.org: any non-empty value turns colour off
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let configured_theme = cli.settings.theme.clone().filter(|_| !cli.no_color);
+    let theme = match cli.theme.clone().or(configured_theme) {
+        Some(theme) => theme,
+        None if cli.no_color || no_color => Theme::monochrome(),
+        // E-ink shows colours as greys, if at all
+        None if profile(&cli) == Profile::Eink => Theme::monochrome(),
+        None => match background::query() {
+            Some(color) if background::is_light(color) => Theme::light(),
+            _ => Theme::dark(),
+        },
+    };
 
-    Ok(())
+    #[cfg(feature = "calibre")]
+    if let Some(wanted) = &cli.calibre {
+        return run_calibre(&cli, wanted, &theme);
+    }
+    match cli.epub_files.as_slice() {
+        [] => run_library(&cli, None, &theme),
+        [dir] if dir.is_dir() => run_library(&cli, Some(dir), &theme),
+        epub_files => read_books(&cli, epub_files, &theme),
+    }
 }
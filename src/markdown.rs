@@ -0,0 +1,586 @@
+use quick_xml::Reader;
+use quick_xml::escape::{resolve_predefined_entity, unescape_with};
+use quick_xml::events::{BytesStart, Event};
+
+/// What a URL in a chapter points at, for [`html_to_markdown`]'s caller to
+/// rewrite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    Link,
+    Image,
+    /// An element's `id`, marked in the Markdown as an anchor unless
+    /// `rewrite` gives back an empty string.
+    Anchor,
+}
+
+/// Convert chapter XHTML into Markdown, keeping its headings, emphasis,
+/// links, images, lists, quotations, code and tables. Every link's `href`
+/// and image's `src` goes through `rewrite`, so they can be pointed at
+/// wherever the book's other files end up, and so do the `id`s links can
+/// lead to. Ruby annotations are kept in brackets after their text.
+pub fn html_to_markdown(
+    html: &str,
+    rewrite: &mut dyn FnMut(Reference, &str) -> String,
+) -> String {
+    let mut reader = Reader::from_str(html);
+    reader.config_mut().check_end_names = false;
+    reader.config_mut().allow_unmatched_ends = true;
+
+    let mut writer = Writer::default();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => writer.start(&e, rewrite),
+            Ok(Event::Empty(e)) => {
+                writer.start(&e, rewrite);
+                writer.end(&tag_name(e.local_name().as_ref()));
+            }
+            Ok(Event::End(e)) => writer.end(&tag_name(e.local_name().as_ref())),
+            Ok(Event::Text(e)) => {
+                let raw = String::from_utf8_lossy(&e);
+                writer.text(&unescape(&raw));
+            }
+            Ok(Event::CData(e)) => writer.text(&String::from_utf8_lossy(&e)),
+            // Whatever could be read of a malformed chapter is kept
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    writer.finish()
+}
+
+/// The `href` of every link in chapter XHTML, in order.
+pub fn links(html: &str) -> Vec<String> {
+    let mut reader = Reader::from_str(html);
+    reader.config_mut().check_end_names = false;
+    reader.config_mut().allow_unmatched_ends = true;
+
+    let mut links = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e) | Event::Empty(e))
+                if tag_name(e.local_name().as_ref()) == "a"
+                    && let Some(href) = attribute(&e, "href") =>
+            {
+                links.push(href);
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    links
+}
+
+// Elements that start a block of their own
+const BLOCKS: [&str; 17] = [
+    "p", "div", "section", "article", "aside", "header", "footer", "nav", "main", "figure",
+    "figcaption", "address", "center", "body", "dl", "dt", "dd",
+];
+
+// Elements whose content isn't part of the text. The brackets `<rp>` holds
+// for readers without ruby are left out, as `<rt>` brings its own.
+const HIDDEN: [&str; 5] = ["head", "script", "style", "title", "rp"];
+
+// Markup wrapped around text once some turns up, so that empty elements and
+// the spaces at their edges don't leave stray markers
+struct Mark {
+    open: String,
+    close: String,
+    opened: bool,
+}
+
+enum Container {
+    Quote,
+    List { ordered: bool, next: usize },
+    Item { marker: String, used: bool },
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    // The block being written
+    inline: String,
+    marks: Vec<Mark>,
+    // Anchors waiting for the text they're on
+    anchors: String,
+    containers: Vec<Container>,
+    heading: Option<usize>,
+    hidden: usize,
+    code: usize,
+    // The text of a `<pre>`, and the language it's in if it says
+    listing: Option<(String, String)>,
+    // The rows of a table, and the cell being written
+    table: Option<Vec<Vec<String>>>,
+    last_in_list: bool,
+}
+
+impl Writer {
+    fn start(&mut self, e: &BytesStart, rewrite: &mut dyn FnMut(Reference, &str) -> String) {
+        let name = tag_name(e.local_name().as_ref());
+        if HIDDEN.contains(&name.as_str()) {
+            self.hidden += 1;
+            return;
+        }
+        if self.hidden > 0 {
+            return;
+        }
+        if self.listing.is_some() {
+            if name == "br" {
+                self.text("\n");
+            }
+            return;
+        }
+        let id = match name.as_str() {
+            "a" => attribute(e, "id").or_else(|| attribute(e, "name")),
+            _ => attribute(e, "id"),
+        };
+        if let Some(id) = id {
+            let id = rewrite(Reference::Anchor, &id);
+            if !id.is_empty() {
+                let id = quick_xml::escape::escape(&id);
+                self.anchors.push_str(&format!("<a id=\"{id}\"></a>"));
+            }
+        }
+        match name.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush();
+                self.heading = name[1..].parse().ok();
+            }
+            "blockquote" => {
+                self.flush();
+                self.containers.push(Container::Quote);
+            }
+            "ul" | "ol" => {
+                self.flush();
+                let next = attribute(e, "start").and_then(|start| start.parse().ok());
+                self.containers.push(Container::List {
+                    ordered: name == "ol",
+                    next: next.unwrap_or(1),
+                });
+            }
+            "li" => {
+                self.flush();
+                let marker = match self.containers.last_mut() {
+                    Some(Container::List { ordered: true, next }) => {
+                        *next += 1;
+                        format!("{}. ", *next - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.containers.push(Container::Item {
+                    marker,
+                    used: false,
+                });
+            }
+            "pre" => {
+                self.flush();
+                self.anchor_block();
+                let language = language(e).unwrap_or_default();
+                self.listing = Some((String::new(), language));
+            }
+            "table" => {
+                self.flush();
+                self.anchor_block();
+                self.table = Some(Vec::new());
+            }
+            "tr" => {
+                if let Some(rows) = &mut self.table {
+                    rows.push(Vec::new());
+                }
+            }
+            "td" | "th" => self.inline.clear(),
+            "hr" => {
+                self.flush();
+                self.anchor_block();
+                self.block("---".to_string());
+            }
+            "rt" => self.text("("),
+            "br" if self.heading.is_some() || self.table.is_some() => self.text(" "),
+            "br" if !self.inline.is_empty() && !self.inline.ends_with('\n') => {
+                self.open_marks();
+                self.inline.push_str("\\\n");
+            }
+            "em" | "i" | "cite" | "dfn" | "var" => self.mark("*", "*".to_string()),
+            "strong" | "b" => self.mark("**", "**".to_string()),
+            "code" | "kbd" | "samp" | "tt" => {
+                self.mark("`", "`".to_string());
+                self.code += 1;
+            }
+            "a" => {
+                let close = match attribute(e, "href") {
+                    Some(href) => {
+                        format!("]({})", link_target(&rewrite(Reference::Link, &href)))
+                    }
+                    None => String::new(),
+                };
+                let open = if close.is_empty() { "" } else { "[" };
+                self.mark(open, close);
+            }
+            "img" | "image" => {
+                let src = attribute(e, "src")
+                    .or_else(|| attribute(e, "href"))
+                    .unwrap_or_default();
+                if !src.is_empty() {
+                    let alt = collapse(&attribute(e, "alt").unwrap_or_default());
+                    let alt = escape(alt.trim());
+                    let src = link_target(&rewrite(Reference::Image, &src));
+                    self.open_marks();
+                    self.inline.push_str(&format!("![{alt}]({src})"));
+                }
+            }
+            name if BLOCKS.contains(&name) => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if HIDDEN.contains(&name) {
+            self.hidden = self.hidden.saturating_sub(1);
+            return;
+        }
+        if self.hidden > 0 {
+            return;
+        }
+        if self.listing.is_some() {
+            if name == "pre"
+                && let Some((text, language)) = self.listing.take()
+            {
+                let text = text.trim_start_matches('\n').trim_end();
+                let fence = if text.contains("```") { "~~~" } else { "```" };
+                self.block(format!("{fence}{language}\n{text}\n{fence}"));
+            }
+            return;
+        }
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush();
+                self.heading = None;
+            }
+            "blockquote" => {
+                self.flush();
+                if let Some(Container::Quote) = self.containers.last() {
+                    self.containers.pop();
+                }
+            }
+            "ul" | "ol" => {
+                self.flush();
+                if let Some(Container::List { .. }) = self.containers.last() {
+                    self.containers.pop();
+                }
+            }
+            "li" => {
+                self.flush();
+                if let Some(Container::Item { .. }) = self.containers.last() {
+                    self.containers.pop();
+                }
+            }
+            "td" | "th" => {
+                self.close_marks();
+                let cell = collapse(&std::mem::take(&mut self.inline)).replace('|', "\\|");
+                if let Some(row) = self.table.as_mut().and_then(|rows| rows.last_mut()) {
+                    row.push(cell.trim().to_string());
+                }
+            }
+            "table" => {
+                self.inline.clear();
+                if let Some(rows) = self.table.take() {
+                    self.block(table(&rows));
+                }
+            }
+            "rt" => self.text(")"),
+            "em" | "i" | "cite" | "dfn" | "var" | "strong" | "b" | "a" => self.close_mark(),
+            "code" | "kbd" | "samp" | "tt" => {
+                self.code = self.code.saturating_sub(1);
+                self.close_mark();
+            }
+            name if BLOCKS.contains(&name) => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.hidden > 0 {
+            return;
+        }
+        if let Some((listing, _)) = &mut self.listing {
+            listing.push_str(text);
+            return;
+        }
+        let text = collapse(text);
+        let (space, text) = match text.strip_prefix(' ') {
+            Some(rest) => (true, rest),
+            None => (false, text.as_str()),
+        };
+        if space && !self.inline.is_empty() && !self.inline.ends_with([' ', '\n']) {
+            self.inline.push(' ');
+        }
+        if text.is_empty() {
+            return;
+        }
+        self.open_marks();
+        if self.code > 0 {
+            self.inline.push_str(text);
+        } else {
+            self.inline.push_str(&escape(text));
+        }
+    }
+
+    fn mark(&mut self, open: &str, close: String) {
+        self.marks.push(Mark {
+            open: open.to_string(),
+            close,
+            opened: false,
+        });
+    }
+
+    fn open_marks(&mut self) {
+        self.inline.push_str(&std::mem::take(&mut self.anchors));
+        for mark in self.marks.iter_mut().filter(|mark| !mark.opened) {
+            self.inline.push_str(&mark.open);
+            mark.opened = true;
+        }
+    }
+
+    // Close the innermost mark, outside any space it ends with
+    fn close_mark(&mut self) {
+        let Some(mark) = self.marks.pop() else {
+            return;
+        };
+        if !mark.opened {
+            return;
+        }
+        let space = self.inline.ends_with(' ');
+        if space {
+            self.inline.pop();
+        }
+        self.inline.push_str(&mark.close);
+        if space {
+            self.inline.push(' ');
+        }
+    }
+
+    // Close the marks that have been opened, leaving them to open again
+    // when more text turns up
+    fn close_marks(&mut self) {
+        for index in (0..self.marks.len()).rev() {
+            if !self.marks[index].opened {
+                continue;
+            }
+            let space = self.inline.ends_with(' ');
+            if space {
+                self.inline.pop();
+            }
+            self.inline.push_str(&self.marks[index].close);
+            if space {
+                self.inline.push(' ');
+            }
+            self.marks[index].opened = false;
+        }
+    }
+
+    // Finish the block being written, if it has any text. Marks carry on
+    // into the next block, as when `<em>` holds several paragraphs.
+    fn flush(&mut self) {
+        if self.table.is_some() {
+            return;
+        }
+        self.close_marks();
+        let text = std::mem::take(&mut self.inline);
+        let text = text.trim_matches(|c: char| c == ' ' || c == '\n');
+        let text = text.strip_suffix('\\').unwrap_or(text).trim_end();
+        // An indent of non-breaking spaces would make a code block
+        let text: Vec<&str> = text.lines().map(str::trim_start).collect();
+        let text = text.join("\n");
+        if text.is_empty() {
+            return;
+        }
+        let text = match self.heading {
+            Some(level) => format!("{} {text}", "#".repeat(level)),
+            None => escape_block_start(&text),
+        };
+        self.block(text);
+    }
+
+    // Add a finished block, inside the quotations and list items it's in
+    fn block(&mut self, text: String) {
+        let first_of_item = matches!(
+            self.containers.last(),
+            Some(Container::Item { used: false, .. })
+        );
+        let in_list = self.containers.iter().any(|c| matches!(c, Container::Item { .. }));
+        if !self.out.is_empty() {
+            let tight = first_of_item && self.last_in_list;
+            self.out.push_str(if tight { "\n" } else { "\n\n" });
+        }
+        self.last_in_list = in_list;
+
+        let mut first_prefix = String::new();
+        let mut prefix = String::new();
+        for container in &mut self.containers {
+            match container {
+                Container::Quote => {
+                    first_prefix.push_str("> ");
+                    prefix.push_str("> ");
+                }
+                Container::List { .. } => {}
+                Container::Item { marker, used } => {
+                    let indent = " ".repeat(marker.len());
+                    if *used {
+                        first_prefix.push_str(&indent);
+                    } else {
+                        first_prefix.push_str(marker);
+                        *used = true;
+                    }
+                    prefix.push_str(&indent);
+                }
+            }
+        }
+        for (index, line) in text.lines().enumerate() {
+            if index > 0 {
+                self.out.push('\n');
+            }
+            let prefix = if index == 0 { &first_prefix } else { &prefix };
+            if line.is_empty() {
+                self.out.push_str(prefix.trim_end());
+            } else {
+                self.out.push_str(prefix);
+                self.out.push_str(line);
+            }
+        }
+    }
+
+    // Anchors with no text after them for their own block, before a
+    // listing, table or rule or at the end of the chapter
+    fn anchor_block(&mut self) {
+        if !self.anchors.is_empty() {
+            let anchors = std::mem::take(&mut self.anchors);
+            self.block(anchors);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        self.anchor_block();
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+}
+
+fn tag_name(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).to_ascii_lowercase()
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Option<String> {
+    e.attributes().flatten().find_map(|attr| {
+        (tag_name(attr.key.local_name().as_ref()) == name)
+            .then(|| unescape(&String::from_utf8_lossy(&attr.value)))
+    })
+}
+
+// The language of a code listing, from a `language-rust` or `lang-rust`
+// class on the `<pre>`
+fn language(e: &BytesStart) -> Option<String> {
+    let classes = attribute(e, "class")?;
+    classes.split_whitespace().find_map(|class| {
+        class
+            .strip_prefix("language-")
+            .or_else(|| class.strip_prefix("lang-"))
+            .map(str::to_string)
+    })
+}
+
+// Entities XHTML chapters use that XML itself doesn't define
+fn unescape(raw: &str) -> String {
+    unescape_with(raw, |entity| {
+        resolve_predefined_entity(entity).or(match entity {
+            "nbsp" => Some("\u{a0}"),
+            "shy" => Some("\u{ad}"),
+            "mdash" => Some("—"),
+            "ndash" => Some("–"),
+            "hellip" => Some("…"),
+            "lsquo" => Some("‘"),
+            "rsquo" => Some("’"),
+            "ldquo" => Some("“"),
+            "rdquo" => Some("”"),
+            "laquo" => Some("«"),
+            "raquo" => Some("»"),
+            "copy" => Some("©"),
+            "thinsp" => Some("\u{2009}"),
+            _ => None,
+        })
+    })
+    .map_or_else(|_| raw.to_string(), |text| text.into_owned())
+}
+
+// Runs of markup whitespace as one space; non-breaking spaces are kept
+fn collapse(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            collapsed.push(' ');
+            space = false;
+        }
+        collapsed.push(c);
+    }
+    if space {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+// Backslash the characters Markdown would take as markup
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// A paragraph starting like a heading, quotation or list item would be
+// taken for one
+fn escape_block_start(text: &str) -> String {
+    if text.starts_with(['#', '>', '-', '+', '=']) {
+        return format!("\\{text}");
+    }
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && text[digits..].starts_with(['.', ')']) {
+        return format!("{}\\{}", &text[..digits], &text[digits..]);
+    }
+    text.to_string()
+}
+
+// URLs with spaces or brackets go in angle brackets
+fn link_target(url: &str) -> String {
+    if url.contains([' ', '(', ')']) {
+        format!("<{url}>")
+    } else {
+        url.to_string()
+    }
+}
+
+// A GitHub-style table, with the first row as its header
+fn table(rows: &[Vec<String>]) -> String {
+    let rows: Vec<&Vec<String>> = rows.iter().filter(|row| !row.is_empty()).collect();
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut text = String::new();
+    for (index, row) in rows.iter().enumerate() {
+        let cells: Vec<&str> = (0..columns)
+            .map(|column| row.get(column).map_or("", String::as_str))
+            .collect();
+        text.push_str(&format!("| {} |\n", cells.join(" | ")));
+        if index == 0 {
+            text.push_str(&format!("|{}\n", " --- |".repeat(columns)));
+        }
+    }
+    text.trim_end().to_string()
+}
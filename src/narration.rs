@@ -0,0 +1,310 @@
+use crate::epub::EpubReader;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use speaker::{Playing, Speaker};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How far apart one clip's end and the next one's start can be and still
+/// be played straight through rather than skipped to.
+const CLIP_GAP: Duration = Duration::from_millis(250);
+
+/// A stretch of narration from an EPUB 3 media overlay: the part of the
+/// audio that reads out one fragment of the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    /// The text document read out, as its archive path once the book has
+    /// resolved it.
+    pub document: String,
+    /// The id of the element read out.
+    pub fragment: String,
+    /// The audio file, as its archive path once the book has resolved it.
+    pub audio: String,
+    pub begin: Duration,
+    /// Where the clip stops, or the end of the file if it doesn't say.
+    pub end: Option<Duration>,
+}
+
+/// The clips of a SMIL media overlay document in the order they're read,
+/// with their text and audio `src` as written.
+pub fn parse_smil(xml: &str) -> Result<Vec<Clip>, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut clips = Vec::new();
+    let mut text: Option<String> = None;
+    let mut audio: Option<(String, Duration, Option<Duration>)> = None;
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"par" => {
+                    text = None;
+                    audio = None;
+                }
+                b"text" => {
+                    text = e
+                        .try_get_attribute("src")?
+                        .map(|src| src.unescape_value().map(|src| src.to_string()))
+                        .transpose()?;
+                }
+                b"audio" => {
+                    let mut src = None;
+                    let mut begin = Duration::ZERO;
+                    let mut end = None;
+                    for attr in e.attributes().flatten() {
+                        let value = attr.unescape_value()?;
+                        match attr.key.local_name().as_ref() {
+                            b"src" => src = Some(value.to_string()),
+                            b"clipBegin" => begin = parse_clock(&value).unwrap_or_default(),
+                            b"clipEnd" => end = parse_clock(&value),
+                            _ => {}
+                        }
+                    }
+                    audio = src.map(|src| (src, begin, end));
+                }
+                _ => {}
+            },
+            Event::End(e) if e.local_name().as_ref() == b"par" => {
+                if let (Some(text), Some((audio, begin, end))) = (text.take(), audio.take())
+                    && let Some((document, fragment)) = text.split_once('#')
+                {
+                    clips.push(Clip {
+                        document: document.to_string(),
+                        fragment: fragment.to_string(),
+                        audio,
+                        begin,
+                        end,
+                    });
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(clips)
+}
+
+/// A SMIL clock value: `1:02:03.5` or `02:03.5`, or a count such as `3.5s`,
+/// `3500ms`, `2min`, `1h` or plain `3.5` seconds.
+pub fn parse_clock(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.contains(':') {
+        let mut seconds = 0.0;
+        for part in value.split(':') {
+            seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+        }
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+    let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+    let (count, metric) = value.split_at(split);
+    let count: f64 = count.parse().ok()?;
+    let seconds = match metric {
+        "h" => count * 3600.0,
+        "min" => count * 60.0,
+        "s" | "" => count,
+        "ms" => count / 1000.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// Plays a chapter's narration, keeping track of which clip is being read
+/// out.
+pub struct Narrator {
+    epub: Arc<EpubReader>,
+    clips: Vec<Clip>,
+    current: usize,
+    // Opened when there's first something to play
+    speaker: Option<Speaker>,
+    player: Option<Player>,
+    /// The audio file last read out of the book, with its archive path.
+    loaded: Option<(String, Arc<[u8]>)>,
+}
+
+struct Player {
+    playing: Playing,
+    audio: String,
+}
+
+impl Narrator {
+    /// Narrate `clips` from the book, starting at the one numbered `first`.
+    pub fn new(epub: Arc<EpubReader>, clips: Vec<Clip>, first: usize) -> Self {
+        Self {
+            epub,
+            clips,
+            current: first,
+            speaker: None,
+            player: None,
+            loaded: None,
+        }
+    }
+
+    pub fn clips(&self) -> &[Clip] {
+        &self.clips
+    }
+
+    /// Keep the audio playing, moving on through the clips as it does, and
+    /// say which clip is being read out, or `None` once they've all been.
+    pub fn update(&mut self) -> Result<Option<usize>, String> {
+        loop {
+            let Some(clip) = self.clips.get(self.current).cloned() else {
+                self.stop();
+                return Ok(None);
+            };
+            let end = self.clip_end(self.current);
+            let Some(player) = self.player.as_ref().filter(|player| player.audio == clip.audio)
+            else {
+                self.play(&clip.audio, clip.begin)?;
+                return Ok(Some(self.current));
+            };
+            let exited = player.playing.finished();
+            let played = match end {
+                Some(end) => exited || player.playing.position() >= end,
+                None => exited,
+            };
+            if !played {
+                return Ok(Some(self.current));
+            }
+
+            // Carry on playing into the next clip if it follows on, and
+            // otherwise start again where it begins
+            self.current += 1;
+            let follows_on = self.clips.get(self.current).is_some_and(|next| {
+                next.audio == clip.audio
+                    && end.is_some_and(|end| next.begin.abs_diff(end) <= CLIP_GAP)
+            });
+            if !follows_on || exited {
+                self.stop();
+            }
+        }
+    }
+
+    // Where the clip numbered `index` stops: where it says, or where the
+    // next one in the same file starts
+    fn clip_end(&self, index: usize) -> Option<Duration> {
+        let clip = &self.clips[index];
+        clip.end.or_else(|| {
+            let next = self.clips.get(index + 1)?;
+            (next.audio == clip.audio).then_some(next.begin)
+        })
+    }
+
+    fn play(&mut self, audio: &str, from: Duration) -> Result<(), String> {
+        self.stop();
+        let bytes = match &self.loaded {
+            Some((path, bytes)) if path == audio => Arc::clone(bytes),
+            _ => {
+                let bytes: Arc<[u8]> = self
+                    .epub
+                    .read_entry(audio)
+                    .map_err(|e| format!("Couldn't read the narration: {e}"))?
+                    .into();
+                self.loaded = Some((audio.to_string(), Arc::clone(&bytes)));
+                bytes
+            }
+        };
+        let speaker = match &mut self.speaker {
+            Some(speaker) => speaker,
+            speaker => speaker.insert(Speaker::open()?),
+        };
+        self.player = Some(Player {
+            playing: speaker.play(bytes, from)?,
+            audio: audio.to_string(),
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.player = None;
+    }
+}
+
+// The audio output, played through in the reader's own process
+#[cfg(feature = "narration")]
+mod speaker {
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    pub struct Speaker {
+        // Stops the output when dropped
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    }
+
+    /// An audio file playing, which stops when dropped.
+    pub struct Playing {
+        sink: Sink,
+        from: Duration,
+    }
+
+    impl Speaker {
+        pub fn open() -> Result<Self, String> {
+            let (stream, handle) = OutputStream::try_default()
+                .map_err(|e| format!("Couldn't open the audio output: {e}"))?;
+            Ok(Self {
+                _stream: stream,
+                handle,
+            })
+        }
+
+        /// Play an audio file from `from`.
+        pub fn play(&self, bytes: Arc<[u8]>, from: Duration) -> Result<Playing, String> {
+            let mut decoder = Decoder::new(Cursor::new(bytes))
+                .map_err(|e| format!("Couldn't play the narration: {e}"))?;
+            let sink = Sink::try_new(&self.handle)
+                .map_err(|e| format!("Couldn't play the narration: {e}"))?;
+            // Formats that can't seek are decoded up to where to start
+            if decoder.try_seek(from).is_ok() {
+                sink.append(decoder);
+            } else {
+                sink.append(decoder.skip_duration(from));
+            }
+            Ok(Playing { sink, from })
+        }
+    }
+
+    impl Playing {
+        /// Where in the audio file it has got to.
+        pub fn position(&self) -> Duration {
+            self.from + self.sink.get_pos()
+        }
+
+        pub fn finished(&self) -> bool {
+            self.sink.empty()
+        }
+    }
+}
+
+#[cfg(not(feature = "narration"))]
+mod speaker {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    pub enum Speaker {}
+
+    pub enum Playing {}
+
+    impl Speaker {
+        pub fn open() -> Result<Self, String> {
+            Err("Narration needs catatau built with the narration feature".to_string())
+        }
+
+        pub fn play(&self, _bytes: Arc<[u8]>, _from: Duration) -> Result<Playing, String> {
+            match *self {}
+        }
+    }
+
+    impl Playing {
+        pub fn position(&self) -> Duration {
+            match *self {}
+        }
+
+        pub fn finished(&self) -> bool {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::constants::HASH_CHUNK_BYTES;
+use serde_json::{Map, Value, json};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// How far the reader has got in each book, kept between runs in a small
+/// JSON file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    /// Keyed by [`book_key`].
+    books: BTreeMap<String, BookProgress>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookProgress {
+    /// Chapters whose end has been on screen.
+    pub read_chapters: BTreeSet<usize>,
+    /// Where the book was left, to pick up from next time.
+    pub position: Option<ReadingPosition>,
+    /// Passages marked in visual mode, in the order they come in the book.
+    pub highlights: Vec<Highlight>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadingPosition {
+    pub chapter: usize,
+    /// The line at the top of the screen, out of `lines` in the chapter as
+    /// it was wrapped then, so the place survives a different width.
+    pub line: usize,
+    pub lines: usize,
+    /// [`file_hash`] of the book, since a position in a changed file may
+    /// point anywhere.
+    pub file_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    pub chapter: usize,
+    /// Where the passage starts and ends, counted in non-whitespace
+    /// characters from the start of the chapter's text, so it stays put
+    /// however the text is wrapped.
+    pub start: usize,
+    pub end: usize,
+    /// The passage's text, for the list of highlights.
+    pub excerpt: String,
+    /// What the reader wrote about the passage.
+    pub note: Option<String>,
+}
+
+impl Progress {
+    /// Read the progress file, starting afresh if it's missing or can't be
+    /// understood. One that can't be is set aside rather than saved over.
+    pub fn load(path: &Path) -> Self {
+        read_state_file(path).map_or_else(Self::default, |json| Self::from_json(&json))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_atomically(path, serde_json::to_string_pretty(&self.to_json())?)
+    }
+
+    fn from_json(json: &Value) -> Self {
+        let books = json
+            .get("books")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .map(|(key, book)| (key.clone(), BookProgress::from_json(book)))
+            .collect();
+        Self { books }
+    }
+
+    fn to_json(&self) -> Value {
+        let books: Map<String, Value> = self
+            .books
+            .iter()
+            .map(|(key, book)| (key.clone(), book.to_json()))
+            .collect();
+        json!({ "books": books })
+    }
+
+    pub fn book(&self, key: &str) -> Option<&BookProgress> {
+        self.books.get(key)
+    }
+
+    pub fn book_mut(&mut self, key: &str) -> &mut BookProgress {
+        self.books.entry(key.to_string()).or_default()
+    }
+}
+
+impl BookProgress {
+    /// How far through the book the saved position is, as a percentage of a
+    /// book with `chapters` chapters.
+    #[cfg_attr(not(feature = "calibre"), allow(dead_code))]
+    pub fn percent_through(&self, chapters: usize) -> Option<f64> {
+        let position = self.position.as_ref()?;
+        if chapters == 0 {
+            return None;
+        }
+        let in_chapter = if position.lines == 0 {
+            0.0
+        } else {
+            position.line as f64 / position.lines as f64
+        };
+        Some(((position.chapter as f64 + in_chapter) * 100.0 / chapters as f64).min(100.0))
+    }
+
+    fn from_json(json: &Value) -> Self {
+        let read_chapters = json
+            .get("read_chapters")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|chapter| usize::try_from(chapter.as_u64()?).ok())
+            .collect();
+        let position = json.get("position").and_then(ReadingPosition::from_json);
+        let highlights = json
+            .get("highlights")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Highlight::from_json)
+            .collect();
+        Self {
+            read_chapters,
+            position,
+            highlights,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut json = json!({ "read_chapters": self.read_chapters });
+        if let Some(position) = &self.position {
+            json["position"] = position.to_json();
+        }
+        if !self.highlights.is_empty() {
+            json["highlights"] = self.highlights.iter().map(Highlight::to_json).collect();
+        }
+        json
+    }
+}
+
+impl ReadingPosition {
+    fn from_json(json: &Value) -> Option<Self> {
+        let number = |key: &str| usize::try_from(json.get(key)?.as_u64()?).ok();
+        Some(Self {
+            chapter: number("chapter")?,
+            line: number("line")?,
+            lines: number("lines")?,
+            file_hash: json.get("file_hash")?.as_str()?.to_string(),
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "chapter": self.chapter,
+            "line": self.line,
+            "lines": self.lines,
+            "file_hash": self.file_hash,
+        })
+    }
+}
+
+impl Highlight {
+    fn from_json(json: &Value) -> Option<Self> {
+        let number = |key: &str| usize::try_from(json.get(key)?.as_u64()?).ok();
+        Some(Self {
+            chapter: number("chapter")?,
+            start: number("start")?,
+            end: number("end")?,
+            excerpt: json.get("excerpt")?.as_str()?.to_string(),
+            note: json.get("note").and_then(Value::as_str).map(str::to_string),
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let mut json = json!({
+            "chapter": self.chapter,
+            "start": self.start,
+            "end": self.end,
+            "excerpt": self.excerpt,
+        });
+        if let Some(note) = &self.note {
+            json["note"] = json!(note);
+        }
+        json
+    }
+}
+
+/// The key a book's progress is kept under: its absolute path, so the same
+/// file opened from different directories shares one entry.
+pub fn book_key(path: &Path) -> String {
+    std::fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The FNV-1a hash of a file's contents, in hex, read a piece at a time.
+pub fn file_hash(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::with_capacity(HASH_CHUNK_BYTES, File::open(path)?);
+    let mut hash = FNV_OFFSET_BASIS;
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        hash = fnv1a(hash, chunk);
+        let read = chunk.len();
+        reader.consume(read);
+    }
+    Ok(format!("{hash:016x}"))
+}
+
+/// The FNV-1a hash of a book's [`book_key`], in hex, to name files kept
+/// for the book. Unlike the standard library's hasher it's the same from
+/// one build and run to the next.
+pub fn key_hash(key: &str) -> String {
+    format!("{:016x}", fnv1a(FNV_OFFSET_BASIS, key.as_bytes()))
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Read a JSON file of saved state, or `None` if there's none yet. A file
+/// that can't be read as JSON is renamed to `<name>.unreadable-<seconds>`,
+/// keeping what it held for the reader to recover instead of letting the
+/// next save write a fresh start over it.
+pub fn read_state_file(path: &Path) -> Option<Value> {
+    let error = match std::fs::read(path) {
+        Ok(bytes) => match serde_json::from_slice(&bytes) {
+            Ok(json) => return Some(json),
+            Err(e) => e.to_string(),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => e.to_string(),
+    };
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let aside = path.with_file_name(format!("{name}.unreadable-{seconds}"));
+    match std::fs::rename(path, &aside) {
+        Ok(()) => {
+            warn!("Could not read {}: {}; moved it to {}", path.display(), error, aside.display());
+        }
+        Err(e) => warn!("Could not read {}: {}, nor move it aside: {}", path.display(), error, e),
+    }
+    None
+}
+
+/// Write `contents` to a file beside `path` and rename it over `path`, so
+/// that a reader of `path` sees the old contents or the new, never part of
+/// either, and two processes saving at once don't interleave.
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{name}.{}.tmp", std::process::id()));
+    std::fs::write(&temporary, contents)
+        .and_then(|()| std::fs::rename(&temporary, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temporary);
+        })
+}
+
+/// Where reading progress and statistics are kept: `$XDG_STATE_HOME/catatau`,
+/// or `~/.local/state/catatau`.
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", &[".local", "state"])
+}
+
+/// Where files that are slow to make but can be rebuilt, like search
+/// indexes, are kept: `$XDG_DATA_HOME/catatau`, or `~/.local/share/catatau`.
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", &[".local", "share"])
+}
+
+// `catatau` in the directory named by an XDG variable, or in its default
+// under the home directory when the variable is unset
+fn xdg_dir(variable: &str, default: &[&str]) -> Option<PathBuf> {
+    let base = std::env::var_os(variable)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            let home = PathBuf::from(std::env::var_os("HOME")?);
+            Some(default.iter().fold(home, |path, part| path.join(part)))
+        })?;
+    Some(base.join("catatau"))
+}
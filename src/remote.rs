@@ -0,0 +1,151 @@
+use crate::constants::MAX_REMOTE_REQUEST_BYTES;
+use crate::error::RemoteError;
+use serde_json::{Value, json};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// A request from a remote control client, waiting for the reader to
+/// answer it.
+pub struct Call {
+    pub method: String,
+    pub params: Value,
+    reply: Sender<Result<Value, RemoteError>>,
+}
+
+impl Call {
+    pub fn answer(self, result: Result<Value, RemoteError>) {
+        // The client may have hung up
+        let _ = self.reply.send(result);
+    }
+}
+
+/// Listens on a Unix socket for JSON-RPC 2.0 requests, one to a line, for
+/// the reader to answer between redraws. Each response is a line too.
+pub struct RemoteServer {
+    calls: Receiver<Call>,
+    path: PathBuf,
+}
+
+impl RemoteServer {
+    /// Listen on a new socket at `path`. A socket left there by a reader
+    /// that's gone is replaced, but anything else already at `path` is
+    /// left alone and refused.
+    #[cfg(unix)]
+    pub fn listen(path: &Path) -> io::Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.file_type().is_socket() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists and isn't a socket", path.display()),
+                ));
+            }
+            Ok(_) if UnixStream::connect(path).is_err() => std::fs::remove_file(path)?,
+            _ => {}
+        }
+        let listener = UnixListener::bind(path)?;
+        let (sender, calls) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                std::thread::spawn(move || serve(stream, sender));
+            }
+        });
+        Ok(Self {
+            calls,
+            path: path.to_path_buf(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn listen(_path: &Path) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "remote control needs Unix sockets"))
+    }
+
+    /// The next request waiting, if there is one.
+    pub fn next_call(&self) -> Option<Call> {
+        self.calls.try_recv().ok()
+    }
+}
+
+impl Drop for RemoteServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// Answer a client's requests in turn until it hangs up, or sends a line
+// too long to be a request
+#[cfg(unix)]
+fn serve(stream: std::os::unix::net::UnixStream, calls: Sender<Call>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    let mut bytes = Vec::new();
+    loop {
+        bytes.clear();
+        let limit = MAX_REMOTE_REQUEST_BYTES as u64 + 1;
+        match (&mut reader).take(limit).read_until(b'\n', &mut bytes) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        if bytes.len() > MAX_REMOTE_REQUEST_BYTES {
+            let too_long = format!("requests can be at most {MAX_REMOTE_REQUEST_BYTES} bytes");
+            let error = response(Value::Null, Err(RemoteError::Parse(too_long)));
+            let _ = writeln!(writer, "{error}");
+            break;
+        }
+        let line = String::from_utf8_lossy(&bytes);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (id, result) = match parse_request(&line) {
+            Ok((id, method, params)) => {
+                let (reply, answer) = mpsc::channel();
+                if calls.send(Call { method, params, reply }).is_err() {
+                    break;
+                }
+                let stopped = || Err(RemoteError::Failed("The reader has stopped".to_string()));
+                (id, answer.recv().unwrap_or_else(|_| stopped()))
+            }
+            Err(e) => (Some(Value::Null), Err(e)),
+        };
+        // Notifications, which have no id, get no response
+        if let Some(id) = id
+            && writeln!(writer, "{}", response(id, result)).is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// The id, method and params of a JSON-RPC 2.0 request, with no id for a
+/// notification and params of `null` when there are none.
+pub fn parse_request(line: &str) -> Result<(Option<Value>, String, Value), RemoteError> {
+    let request: Value =
+        serde_json::from_str(line).map_err(|e| RemoteError::Parse(e.to_string()))?;
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .ok_or_else(|| RemoteError::Parse("no method given".to_string()))?;
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    Ok((request.get("id").cloned(), method.to_string(), params))
+}
+
+/// The response to the request with `id`.
+pub fn response(id: Value, result: Result<Value, RemoteError>) -> Value {
+    match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": e.code(), "message": e.to_string()},
+        }),
+    }
+}
@@ -0,0 +1,649 @@
+use crate::{
+    constants::{MAX_SAVED_SEARCH_INDEXES, SEARCH_CONTEXT_LINES},
+    epub::{EpubReader, SearchResult},
+    progress,
+};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::{SkimMatcherV2, SkimScoreConfig};
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Write as _,
+    fs::File,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+        mpsc::{self, Receiver},
+    },
+    time::SystemTime,
+};
+use tracing::warn;
+use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+use unicode_width::UnicodeWidthStr;
+
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// Byte ranges of every occurrence of `query` in `text`, from left to right
+/// and not overlapping. Case, accents and soft hyphens are ignored, so
+/// `cafe` finds `Café` and `ca\u{ad}fe`.
+pub fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let query: Vec<char> = fold(query).iter().map(|folded| folded.c).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let chars = fold(text);
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + query.len() <= chars.len() {
+        let candidate = &chars[i..i + query.len()];
+        if !candidate.iter().zip(&query).all(|(folded, &c)| folded.c == c) {
+            i += 1;
+            continue;
+        }
+        // Take in any accents or soft hyphens left trailing the match
+        let mut end = candidate[query.len() - 1].bytes.end;
+        end += text[end..]
+            .chars()
+            .take_while(|&c| c == SOFT_HYPHEN || is_combining_mark(c))
+            .map(char::len_utf8)
+            .sum::<usize>();
+        matches.push(chars[i].bytes.start..end);
+        while i < chars.len() && chars[i].bytes.start < end {
+            i += 1;
+        }
+    }
+    matches
+}
+
+/// A search query. Words run together into one phrase, and words in double
+/// quotes make a phrase of their own; a line matches if it contains any of
+/// these. Marked with `+`, a word or quoted phrase has to appear somewhere
+/// in the chapter, and with `-` it mustn't, so `dragon +castle -prologue`
+/// finds dragons in chapters with a castle and no prologue. With nothing
+/// unmarked, lines match the `+` terms.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub phrases: Vec<String>,
+    pub required: Vec<String>,
+    pub excluded: Vec<String>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Self {
+        let mut parsed = Self::default();
+        let mut words: Vec<&str> = Vec::new();
+        let mut rest = query.trim_start();
+        while !rest.is_empty() {
+            // A lone + or - is just a word
+            let marker = rest
+                .chars()
+                .next()
+                .filter(|&c| c == '+' || c == '-')
+                .filter(|_| rest[1..].starts_with(|c: char| !c.is_whitespace()));
+            if marker.is_some() {
+                rest = &rest[1..];
+            }
+
+            let (term, quoted) = match rest.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"').unwrap_or(quoted.len());
+                    rest = quoted.get(end + 1..).unwrap_or("");
+                    (quoted[..end].trim(), true)
+                }
+                None => {
+                    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    let word = &rest[..end];
+                    rest = &rest[end..];
+                    (word, false)
+                }
+            };
+            rest = rest.trim_start();
+
+            match marker {
+                Some('+') => parsed.required.push(term.to_string()),
+                Some(_) => parsed.excluded.push(term.to_string()),
+                None if quoted => parsed.phrases.push(term.to_string()),
+                None => {
+                    words.push(term);
+                    continue;
+                }
+            }
+            if !words.is_empty() {
+                parsed.phrases.push(words.join(" "));
+                words.clear();
+            }
+        }
+        if !words.is_empty() {
+            parsed.phrases.push(words.join(" "));
+        }
+        for terms in [&mut parsed.phrases, &mut parsed.required, &mut parsed.excluded] {
+            terms.retain(|term| !term.is_empty());
+        }
+        parsed
+    }
+
+    /// Terms lines are matched against.
+    pub fn line_terms(&self) -> &[String] {
+        if self.phrases.is_empty() { &self.required } else { &self.phrases }
+    }
+
+    /// Whether a chapter's text has every `+` term and none of the `-` ones.
+    pub fn allows_chapter(&self, text: &str) -> bool {
+        self.required.iter().all(|term| !find_matches(text, term).is_empty())
+            && self.excluded.iter().all(|term| find_matches(text, term).is_empty())
+    }
+
+    /// Byte ranges of `line` matching any of the line terms, in order.
+    pub fn find(&self, line: &str) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .line_terms()
+            .iter()
+            .flat_map(|term| find_matches(line, term))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+/// A character of text as searches compare it.
+struct Folded {
+    /// Lowercased and stripped of accents.
+    c: char,
+    /// Where the original character is in the text.
+    bytes: Range<usize>,
+}
+
+// Text as searches compare it: decomposed (so ligatures and full-width
+// forms match their plain letters), lowercased, and without combining
+// marks or soft hyphens. Comparing these rather than whole lowercased
+// strings keeps offsets into the text right where folding changes lengths.
+fn fold(text: &str) -> Vec<Folded> {
+    let mut chars = Vec::with_capacity(text.len());
+    for (offset, original) in text.char_indices() {
+        let bytes = offset..offset + original.len_utf8();
+        decompose_compatible(original, |part| {
+            for c in part.to_lowercase() {
+                if c != SOFT_HYPHEN && !is_combining_mark(c) {
+                    chars.push(Folded {
+                        c,
+                        bytes: bytes.clone(),
+                    });
+                }
+            }
+        });
+    }
+    chars
+}
+
+/// Where a query matched a line and how well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    /// Higher is better; substring matches all score zero.
+    pub score: i64,
+    /// Byte ranges of the matched text.
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// Match `query` against `text` as skim does: its characters have to
+/// appear in order, but not next to each other. Case, accents, soft hyphens
+/// and spaces in the query are ignored. Scoring favours the shortest
+/// stretch of text that matches, and characters at the starts of words or
+/// running on from each other.
+pub fn fuzzy_match(text: &str, query: &str) -> Option<Match> {
+    static MATCHER: OnceLock<SkimMatcherV2> = OnceLock::new();
+    let query: String = fold(query)
+        .iter()
+        .map(|folded| folded.c)
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if query.is_empty() {
+        return None;
+    }
+    let chars = fold(text);
+    let folded: String = chars.iter().map(|folded| folded.c).collect();
+    // Lines of prose are long, so letters strewn far apart cost more than
+    // skim charges them in file names
+    let matcher = MATCHER.get_or_init(|| {
+        let scores = SkimScoreConfig {
+            gap_extension: -2,
+            ..SkimScoreConfig::default()
+        };
+        SkimMatcherV2::default().respect_case().score_config(scores)
+    });
+    let (score, positions) = matcher.fuzzy_indices(&folded, &query)?;
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for i in positions {
+        // Characters folded from one original, like a ligature, share its bytes
+        let range = chars[i].bytes.clone();
+        match ranges.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => ranges.push(range),
+        }
+    }
+    Some(Match { score, ranges })
+}
+
+/// How the Search pane matches lines against the query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Lines matching the query as a [`Query`], in book order.
+    #[default]
+    Substring,
+    /// Lines containing the query's characters in order, best first.
+    Fuzzy,
+}
+
+impl SearchMode {
+    /// Where `query` matches `text`, if it does.
+    pub fn find(self, text: &str, query: &str) -> Option<Match> {
+        match self {
+            Self::Substring => {
+                let ranges = Query::parse(query).find(text);
+                (!ranges.is_empty()).then_some(Match { score: 0, ranges })
+            }
+            Self::Fuzzy => fuzzy_match(text, query),
+        }
+    }
+}
+
+/// A line of converted text matching a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub chapter: usize,
+    pub line: usize,
+    pub text: String,
+    /// How well the line matched, as in [`Match`].
+    pub score: i64,
+}
+
+/// A search of the whole book running on its own thread, which sends back
+/// each chapter's matching lines as it goes. Dropping it stops the search.
+#[derive(Debug)]
+pub struct BackgroundSearch {
+    updates: Receiver<Vec<SearchHit>>,
+    mode: SearchMode,
+    cancelled: Arc<AtomicBool>,
+    searched: usize,
+    total: usize,
+}
+
+impl BackgroundSearch {
+    /// Search every line of `chapters` wider than `min_width` columns for
+    /// `query`; an empty query matches them all.
+    pub fn start(
+        epub: Arc<EpubReader>,
+        query: &str,
+        chapters: Vec<usize>,
+        min_width: usize,
+        mode: SearchMode,
+    ) -> Self {
+        let (sender, updates) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let total = chapters.len();
+
+        let query = query.to_string();
+        let parsed = Query::parse(&query);
+        let stop = Arc::clone(&cancelled);
+        std::thread::spawn(move || {
+            for chapter in chapters {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let hits = match epub.read_chapter_uncached(chapter) {
+                    Ok(converted)
+                        if mode == SearchMode::Substring
+                            && !query.is_empty()
+                            && !parsed.allows_chapter(&converted.content) =>
+                    {
+                        Vec::new()
+                    }
+                    Ok(converted) => converted
+                        .content
+                        .lines()
+                        .enumerate()
+                        .filter(|(_, text)| text.trim().width() > min_width)
+                        .filter_map(|(line, text)| {
+                            let score = if query.is_empty() {
+                                0
+                            } else {
+                                mode.find(text.trim(), &query)?.score
+                            };
+                            Some(SearchHit {
+                                chapter,
+                                line,
+                                text: text.to_string(),
+                                score,
+                            })
+                        })
+                        .collect(),
+                    Err(e) => {
+                        warn!("Failed to load chapter {} for search: {}", chapter, e);
+                        Vec::new()
+                    }
+                };
+                if sender.send(hits).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            updates,
+            mode,
+            cancelled,
+            searched: 0,
+            total,
+        }
+    }
+
+    /// Matches found since the last call, without waiting for more.
+    pub fn poll(&mut self) -> Vec<SearchHit> {
+        let mut hits = Vec::new();
+        while let Ok(chapter_hits) = self.updates.try_recv() {
+            self.searched += 1;
+            hits.extend(chapter_hits);
+        }
+        hits
+    }
+
+    pub fn mode(&self) -> SearchMode {
+        self.mode
+    }
+
+    /// Chapters searched so far, and how many there are.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.searched, self.total)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.searched >= self.total
+    }
+}
+
+impl Drop for BackgroundSearch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+const INDEX_HEADER: &str = "catatau search index 2";
+
+/// Which chapters each run of three characters turns up in, so a search
+/// only has to convert the chapters that could match. Building one means
+/// reading the whole book, so it's saved and reused when the book is
+/// opened again.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchIndex {
+    chapter_count: usize,
+    /// Chapters containing each lowercased trigram, in order.
+    trigrams: HashMap<String, Vec<usize>>,
+}
+
+impl SearchIndex {
+    pub fn build(epub: &EpubReader) -> Self {
+        let mut index = Self {
+            chapter_count: epub.chapter_count(),
+            trigrams: HashMap::new(),
+        };
+        for chapter in 0..epub.chapter_count() {
+            match epub.chapter_index_text(chapter) {
+                Ok(text) => index.add_chapter(chapter, &text),
+                Err(e) => warn!("Failed to load chapter {} for the search index: {}", chapter, e),
+            }
+        }
+        index
+    }
+
+    /// The index saved in `dir` for this book, or a new one saved there.
+    /// Only the [`MAX_SAVED_SEARCH_INDEXES`] used most recently are kept.
+    pub fn load_or_build(epub: &EpubReader, dir: &Path) -> Self {
+        let path = match fingerprint(epub) {
+            Ok(fingerprint) => dir.join(format!("{fingerprint}.idx")),
+            Err(e) => {
+                warn!("Failed to read {} for its search index: {}", epub.path().display(), e);
+                return Self::build(epub);
+            }
+        };
+        if let Some(index) = Self::load_for(epub, &path) {
+            return index;
+        }
+
+        let index = Self::build(epub);
+        if let Err(e) = index.save(&path) {
+            warn!("Failed to save search index {}: {}", path.display(), e);
+        }
+        evict(dir, MAX_SAVED_SEARCH_INDEXES);
+        index
+    }
+
+    /// The index saved in `dir` for this book, if it's been indexed with
+    /// the options it was opened with.
+    pub fn load_saved(epub: &EpubReader, dir: &Path) -> Option<Self> {
+        let fingerprint = fingerprint(epub).ok()?;
+        let path = dir.join(format!("{fingerprint}.idx"));
+        if !path.exists() {
+            return None;
+        }
+        Self::load_for(epub, &path)
+    }
+
+    // A saved index, if it fits the book, marked as used so it's among the
+    // last to be evicted
+    fn load_for(epub: &EpubReader, path: &Path) -> Option<Self> {
+        let index = Self::load(path).filter(|index| index.chapter_count == epub.chapter_count())?;
+        let _ = File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        Some(index)
+    }
+
+    /// Read a saved index, if there is one that can be understood.
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        let index = Self::parse(&text);
+        if index.is_none() {
+            warn!("Ignoring unreadable search index {}", path.display());
+        }
+        index
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        if lines.next()? != INDEX_HEADER {
+            return None;
+        }
+        let chapter_count = lines.next()?.strip_prefix("chapters ")?.parse().ok()?;
+        let trigrams = lines
+            .map(|line| {
+                let (trigram, chapters) = line.split_once('\t')?;
+                let chapters = chapters
+                    .split(',')
+                    .map(|chapter| chapter.parse().ok())
+                    .collect::<Option<Vec<usize>>>()?;
+                (trigram.chars().count() == 3).then(|| (trigram.to_string(), chapters))
+            })
+            .collect::<Option<_>>()?;
+        Some(Self {
+            chapter_count,
+            trigrams,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut text = format!("{INDEX_HEADER}\nchapters {}\n", self.chapter_count);
+        for (trigram, chapters) in &self.trigrams {
+            let chapters: Vec<String> = chapters.iter().map(usize::to_string).collect();
+            let _ = writeln!(text, "{trigram}\t{}", chapters.join(","));
+        }
+        progress::write_atomically(path, text)
+    }
+
+    fn add_chapter(&mut self, chapter: usize, text: &str) {
+        // A word can be wrapped differently on screen than here, so index
+        // the text both with its line breaks as spaces and with them closed
+        let spaced = normalize(text);
+        let joined = normalize(&text.lines().map(str::trim).collect::<String>());
+        let trigrams: HashSet<String> = [spaced, joined]
+            .iter()
+            .flat_map(|chars| chars.windows(3).map(|window| window.iter().collect()))
+            .collect();
+        for trigram in trigrams {
+            self.trigrams.entry(trigram).or_default().push(chapter);
+        }
+    }
+
+    /// Chapters that could match `query`, read as a [`Query`], or `None` if
+    /// its terms are too short for the index to narrow them down.
+    pub fn candidates(&self, query: &str) -> Option<Vec<usize>> {
+        let query = Query::parse(query);
+        // Chapters with every + term and any of the phrases; the index
+        // can't rule chapters out for not having a - term
+        let mut candidates: Option<Vec<usize>> = None;
+        for term in &query.required {
+            if let Some(chapters) = self.term_candidates(term) {
+                candidates = Some(match candidates {
+                    None => chapters,
+                    Some(found) => found.into_iter().filter(|c| chapters.contains(c)).collect(),
+                });
+            }
+        }
+        if !query.phrases.is_empty() {
+            let mut any = BTreeSet::new();
+            for phrase in &query.phrases {
+                any.extend(self.term_candidates(phrase)?);
+            }
+            candidates = Some(match candidates {
+                None => any.into_iter().collect(),
+                Some(found) => found.into_iter().filter(|c| any.contains(c)).collect(),
+            });
+        }
+        candidates
+    }
+
+    fn term_candidates(&self, term: &str) -> Option<Vec<usize>> {
+        let query = normalize(term);
+        if query.len() < 3 {
+            return None;
+        }
+        let mut candidates: Option<Vec<usize>> = None;
+        for window in query.windows(3) {
+            let trigram: String = window.iter().collect();
+            let chapters = self.trigrams.get(&trigram).map_or(&[][..], Vec::as_slice);
+            candidates = Some(match candidates {
+                None => chapters.to_vec(),
+                Some(found) => found.into_iter().filter(|c| chapters.contains(c)).collect(),
+            });
+        }
+        candidates
+    }
+}
+
+// Text folded as `find_matches` compares it, with every run of whitespace
+// (line breaks included) as one space
+fn normalize(text: &str) -> Vec<char> {
+    let mut chars = Vec::new();
+    for c in fold(text).into_iter().map(|folded| folded.c) {
+        let c = if c.is_whitespace() { ' ' } else { c };
+        if !(c == ' ' && chars.last() == Some(&' ')) {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+// Remove all but the `keep` indexes in `dir` used most recently, so the
+// cache doesn't grow with every book and edition ever opened
+fn evict(dir: &Path, keep: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut indexes: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "idx"))
+        .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+        .collect();
+    if indexes.len() <= keep {
+        return;
+    }
+    indexes.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, path) in &indexes[keep..] {
+        if let Err(e) = std::fs::remove_file(path) {
+            warn!("Failed to remove search index {}: {}", path.display(), e);
+        }
+    }
+}
+
+// The file's hash along with the options that change its text, so an
+// edited book or different options get an index of their own
+fn fingerprint(epub: &EpubReader) -> io::Result<String> {
+    let options = epub.options();
+    let hash = epub.file_hash()?;
+    Ok(format!("{hash}-{:?}-{}", options.ruby, options.typography).to_lowercase())
+}
+
+/// Matches as `catatau search` prints them: grep-style, with each match's
+/// line marked `chapter:line:` and the lines around it `chapter:line-`, and
+/// `--` between matches.
+pub fn results_text(results: &[SearchResult]) -> String {
+    let mut text = String::new();
+    for (index, result) in results.iter().enumerate() {
+        if index > 0 {
+            text.push_str("--\n");
+        }
+        let first_line = result.line_number.saturating_sub(SEARCH_CONTEXT_LINES);
+        for (offset, line) in result.context.lines().enumerate() {
+            let line_number = first_line + offset;
+            let separator = if line_number == result.line_number { ':' } else { '-' };
+            let _ = writeln!(
+                text,
+                "{}:{}{}{}",
+                result.chapter_index + 1,
+                line_number + 1,
+                separator,
+                line
+            );
+        }
+    }
+    text
+}
+
+/// Matches as `catatau search --json` prints them, numbered from one.
+pub fn results_json(epub: &EpubReader, query: &str, results: &[SearchResult]) -> Value {
+    let matches: Vec<Value> = results
+        .iter()
+        .map(|result| {
+            let first_line = result.line_number.saturating_sub(SEARCH_CONTEXT_LINES);
+            let text = result.context.lines().nth(result.line_number - first_line);
+            json!({
+                "chapter": result.chapter_index + 1,
+                "title": epub.chapter_title(result.chapter_index),
+                "line": result.line_number + 1,
+                "text": text.unwrap_or_default(),
+                "context": result.context,
+            })
+        })
+        .collect();
+    json!({
+        "path": epub.path().display().to_string(),
+        "query": query,
+        "matches": matches,
+    })
+}
@@ -0,0 +1,195 @@
+use crate::constants::READING_IDLE_TIMEOUT_SECS;
+use crate::progress::{read_state_file, write_atomically};
+use serde_json::{Map, Value, json};
+use std::{
+    collections::BTreeMap,
+    io,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Time spent reading, per day and per book, kept between runs in a small
+/// JSON file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadingStats {
+    /// Seconds read on each day, keyed by days since the Unix epoch (UTC).
+    days: BTreeMap<u64, u64>,
+    /// Seconds read in each book, keyed by title.
+    books: BTreeMap<String, u64>,
+}
+
+impl ReadingStats {
+    /// Read the statistics file, starting afresh if it's missing or can't be
+    /// understood. One that can't be is set aside rather than saved over.
+    pub fn load(path: &Path) -> Self {
+        read_state_file(path).map_or_else(Self::default, |json| Self::from_json(&json))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_atomically(path, serde_json::to_string_pretty(&self.to_json())?)
+    }
+
+    fn from_json(json: &Value) -> Self {
+        let entries = |key: &str| {
+            json.get(key)
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_u64()?)))
+        };
+        Self {
+            days: entries("days")
+                .filter_map(|(date, seconds)| Some((parse_date(&date)?, seconds)))
+                .collect(),
+            books: entries("books").collect(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let days: Map<String, Value> = self
+            .days
+            .iter()
+            .map(|(&day, &seconds)| (format_date(day), json!(seconds)))
+            .collect();
+        json!({ "days": days, "books": self.books })
+    }
+
+    /// Add reading time for a book on a day.
+    pub fn record(&mut self, book: &str, day: u64, seconds: u64) {
+        if seconds == 0 {
+            return;
+        }
+        *self.days.entry(day).or_default() += seconds;
+        *self.books.entry(book.to_string()).or_default() += seconds;
+    }
+
+    /// Add all the reading time recorded in `other`.
+    pub fn merge(&mut self, other: &ReadingStats) {
+        for (&day, &seconds) in &other.days {
+            *self.days.entry(day).or_default() += seconds;
+        }
+        for (book, &seconds) in &other.books {
+            *self.books.entry(book.clone()).or_default() += seconds;
+        }
+    }
+
+    /// Seconds read on a day.
+    pub fn day_total(&self, day: u64) -> u64 {
+        self.days.get(&day).copied().unwrap_or(0)
+    }
+
+    /// Seconds read in the seven days up to and including `today`.
+    pub fn week_total(&self, today: u64) -> u64 {
+        self.days.range(today.saturating_sub(6)..=today).map(|(_, seconds)| seconds).sum()
+    }
+
+    /// Seconds read in each book, longest first.
+    pub fn book_totals(&self) -> Vec<(&str, u64)> {
+        let mut totals: Vec<(&str, u64)> = self
+            .books
+            .iter()
+            .map(|(book, &seconds)| (book.as_str(), seconds))
+            .collect();
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        totals
+    }
+}
+
+/// Reading time in the current session. Gaps between keypresses longer
+/// than the idle timeout are taken as breaks and not counted.
+#[derive(Debug)]
+pub struct SessionTimer {
+    last_activity: Instant,
+    session: Duration,
+    unrecorded: Duration,
+}
+
+impl SessionTimer {
+    pub fn new() -> Self {
+        Self {
+            last_activity: Instant::now(),
+            session: Duration::ZERO,
+            unrecorded: Duration::ZERO,
+        }
+    }
+
+    /// Note a keypress, returning the whole seconds read since the last one
+    /// that haven't been handed out yet.
+    pub fn activity(&mut self) -> u64 {
+        let now = Instant::now();
+        let gap = now - self.last_activity;
+        self.last_activity = now;
+        if gap > Duration::from_secs(READING_IDLE_TIMEOUT_SECS) {
+            return 0;
+        }
+        self.session += gap;
+        self.unrecorded += gap;
+        let seconds = self.unrecorded.as_secs();
+        self.unrecorded -= Duration::from_secs(seconds);
+        seconds
+    }
+
+    pub fn session(&self) -> Duration {
+        self.session
+    }
+}
+
+impl Default for SessionTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Days since the Unix epoch, in UTC.
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / SECONDS_PER_DAY)
+}
+
+/// A number of days since the Unix epoch as `YYYY-MM-DD`.
+pub fn format_date(day: u64) -> String {
+    // Howard Hinnant's days-to-civil conversion, shifted so years start in March
+    let days = day + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day_of_month:02}")
+}
+
+/// The inverse of [`format_date`].
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-').map(str::parse::<u64>);
+    let (year, month, day) = (parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        return None;
+    }
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era).checked_sub(719_468)
+}
+
+/// A reading time such as `2h 05m`, `12m` or `<1m`.
+pub fn format_duration(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => "<1m".to_string(),
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes:02}m"),
+    }
+}
@@ -0,0 +1,415 @@
+use crate::{background, config};
+use ratatui::style::{Color, Modifier, Style};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Colours and modifiers for every styled part of the reader.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    /// Fills the whole screen; `Color::Reset` keeps the terminal's own.
+    pub background: Color,
+    pub text: Style,
+    /// Secondary text: key descriptions, labels, separators, skipped chapters.
+    pub muted: Style,
+    /// The book header, scrollbar and list bullets.
+    pub accent: Style,
+    /// Author, series, chapter title and link previews.
+    pub secondary: Style,
+    /// The chapter text border and the contents pane.
+    pub border: Style,
+    /// Heading styles for levels 1, 2, 3 and below.
+    pub headings: [Style; 4],
+    pub code: Style,
+    /// Search matches, link hint labels and the selected search result.
+    pub highlight: Style,
+    /// The selected entry in the contents pane, the passage being selected
+    /// in visual mode and the one being narrated.
+    pub selection: Style,
+    /// Passages the reader has highlighted.
+    pub annotation: Style,
+    /// Status messages and the search pane.
+    pub warning: Style,
+    pub danger: Style,
+    pub confirm: Style,
+    /// Spare colour for the search key in the help line.
+    pub special: Style,
+    /// Foreground for the filled part of the progress bar, background for
+    /// the rest.
+    pub progress: Style,
+    pub shadow: Color,
+    /// syntect theme used for code listings; empty to leave them unhighlighted.
+    pub syntax_theme: String,
+}
+
+fn fg(color: Color) -> Style {
+    Style::default().fg(color)
+}
+
+fn bg(color: Color) -> Style {
+    Style::default().bg(color)
+}
+
+fn fg_bg(foreground: Color, background: Color) -> Style {
+    Style::default().fg(foreground).bg(background)
+}
+
+impl Theme {
+    /// Names of the built-in themes.
+    pub const NAMES: [&'static str; 6] =
+        ["dark", "light", "sepia", "gruvbox", "high-contrast", "monochrome"];
+
+    /// One of the built-in themes.
+    pub fn named(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "sepia" => Some(Self::sepia()),
+            "gruvbox" => Some(Self::gruvbox()),
+            "high-contrast" | "highcontrast" => Some(Self::high_contrast()),
+            "monochrome" | "mono" => Some(Self::monochrome()),
+            _ => None,
+        }
+    }
+
+    /// A theme by name: `<name>.toml` in the user theme directory if there is
+    /// one, otherwise a built-in theme.
+    pub fn load(name: &str) -> Result<Self, String> {
+        Self::load_from(themes_dir().as_deref(), name)
+    }
+
+    /// Like [`Theme::load`], looking for theme files in `dir`.
+    pub fn load_from(dir: Option<&Path>, name: &str) -> Result<Self, String> {
+        if let Some(path) = dir.map(|dir| dir.join(format!("{name}.toml")))
+            && path.is_file()
+        {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| format!("can't read {}: {e}", path.display()))?;
+            return Self::from_toml(name, &text)
+                .map_err(|e| format!("{}: {e}", path.display()));
+        }
+        Self::named(name).ok_or_else(|| {
+            format!("unknown theme '{name}' (expected one of {})", Self::NAMES.join(", "))
+        })
+    }
+
+    /// Parse a theme file. Elements not listed keep the style of the `base`
+    /// theme (dark by default). Each element is either a colour, or a table
+    /// with optional `fg`, `bg` and `modifiers`:
+    ///
+    /// ```toml
+    /// base = "sepia"
+    /// syntax = "InspiredGitHub"
+    /// text = "#3b2f25"
+    /// heading1 = { fg = "red", modifiers = ["bold", "underlined"] }
+    /// highlight = { fg = "black", bg = "lightyellow" }
+    /// ```
+    pub fn from_toml(name: &str, text: &str) -> Result<Self, String> {
+        let table: toml::Table =
+            text.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+
+        let mut theme = match table.get("base") {
+            Some(base) => {
+                let base = base.as_str().ok_or("'base' must be a theme name")?;
+                Self::named(base).ok_or_else(|| format!("unknown base theme '{base}'"))?
+            }
+            None => Self::dark(),
+        };
+        theme.name = name.to_string();
+
+        for (key, value) in &table {
+            match key.as_str() {
+                "base" => {}
+                "syntax" => {
+                    theme.syntax_theme = value
+                        .as_str()
+                        .ok_or("'syntax' must be a syntect theme name")?
+                        .to_string();
+                }
+                "background" | "shadow" => {
+                    let color = match value {
+                        toml::Value::String(color) => parse_color(color)?,
+                        _ => return Err(format!("'{key}' must be a colour")),
+                    };
+                    if key == "background" {
+                        theme.background = color;
+                    } else {
+                        theme.shadow = color;
+                    }
+                }
+                _ => {
+                    let style = theme
+                        .element_mut(key)
+                        .ok_or_else(|| format!("unknown element '{key}'"))?;
+                    *style = parse_style(key, value)?;
+                }
+            }
+        }
+        Ok(theme)
+    }
+
+    fn element_mut(&mut self, element: &str) -> Option<&mut Style> {
+        Some(match element {
+            "text" => &mut self.text,
+            "muted" => &mut self.muted,
+            "accent" => &mut self.accent,
+            "secondary" => &mut self.secondary,
+            "border" => &mut self.border,
+            "heading1" => &mut self.headings[0],
+            "heading2" => &mut self.headings[1],
+            "heading3" => &mut self.headings[2],
+            "heading4" => &mut self.headings[3],
+            "code" => &mut self.code,
+            "highlight" => &mut self.highlight,
+            "selection" => &mut self.selection,
+            "annotation" => &mut self.annotation,
+            "warning" => &mut self.warning,
+            "danger" => &mut self.danger,
+            "confirm" => &mut self.confirm,
+            "special" => &mut self.special,
+            "progress" => &mut self.progress,
+            _ => return None,
+        })
+    }
+
+    /// The original palette, on the terminal's own background.
+    pub fn dark() -> Self {
+        Self {
+            name: "dark".to_string(),
+            background: Color::Reset,
+            text: fg(Color::White),
+            muted: fg(Color::DarkGray),
+            accent: fg(Color::Cyan),
+            secondary: fg(Color::LightBlue),
+            border: fg(Color::Blue),
+            headings: [
+                fg(Color::Cyan),
+                fg(Color::Blue),
+                fg(Color::LightBlue),
+                fg(Color::White),
+            ],
+            code: fg(Color::Gray),
+            highlight: fg_bg(Color::Black, Color::Yellow),
+            selection: fg_bg(Color::White, Color::Blue),
+            annotation: bg(Color::DarkGray),
+            warning: fg(Color::Yellow),
+            danger: fg(Color::Red),
+            confirm: fg(Color::Green),
+            special: fg(Color::Magenta),
+            progress: fg_bg(Color::Cyan, Color::DarkGray),
+            shadow: Color::Black,
+            syntax_theme: "base16-ocean.dark".to_string(),
+        }
+    }
+
+    pub fn light() -> Self {
+        let paper = Color::Rgb(250, 250, 250);
+        let ink = Color::Rgb(30, 30, 30);
+        let teal = Color::Rgb(0, 110, 150);
+        let blue = Color::Rgb(40, 90, 180);
+        Self {
+            name: "light".to_string(),
+            background: paper,
+            text: fg(ink),
+            muted: fg(Color::Rgb(130, 130, 130)),
+            accent: fg(teal),
+            secondary: fg(blue),
+            border: fg(Color::Rgb(60, 100, 170)),
+            headings: [fg(teal), fg(blue), fg(Color::Rgb(60, 100, 170)), fg(ink)],
+            code: fg(Color::Rgb(80, 80, 80)),
+            highlight: fg_bg(ink, Color::Rgb(255, 220, 100)),
+            selection: fg_bg(paper, blue),
+            annotation: bg(Color::Rgb(255, 243, 176)),
+            warning: fg(Color::Rgb(170, 110, 0)),
+            danger: fg(Color::Rgb(190, 30, 30)),
+            confirm: fg(Color::Rgb(30, 130, 50)),
+            special: fg(Color::Rgb(140, 40, 140)),
+            progress: fg_bg(teal, Color::Rgb(220, 220, 220)),
+            shadow: Color::Rgb(200, 200, 200),
+            syntax_theme: "InspiredGitHub".to_string(),
+        }
+    }
+
+    pub fn sepia() -> Self {
+        let paper = Color::Rgb(244, 236, 216);
+        let ink = Color::Rgb(91, 70, 54);
+        let ochre = Color::Rgb(140, 90, 40);
+        let umber = Color::Rgb(120, 80, 50);
+        Self {
+            name: "sepia".to_string(),
+            background: paper,
+            text: fg(ink),
+            muted: fg(Color::Rgb(150, 130, 110)),
+            accent: fg(ochre),
+            secondary: fg(umber),
+            border: fg(Color::Rgb(160, 120, 80)),
+            headings: [fg(Color::Rgb(140, 60, 30)), fg(umber), fg(ochre), fg(ink)],
+            code: fg(Color::Rgb(100, 80, 60)),
+            highlight: fg_bg(paper, ochre),
+            selection: fg_bg(paper, umber),
+            annotation: bg(Color::Rgb(232, 214, 160)),
+            warning: fg(Color::Rgb(170, 110, 0)),
+            danger: fg(Color::Rgb(170, 50, 40)),
+            confirm: fg(Color::Rgb(90, 120, 50)),
+            special: fg(Color::Rgb(130, 70, 110)),
+            progress: fg_bg(ochre, Color::Rgb(225, 214, 190)),
+            shadow: Color::Rgb(210, 200, 180),
+            syntax_theme: "Solarized (light)".to_string(),
+        }
+    }
+
+    pub fn gruvbox() -> Self {
+        let background = Color::Rgb(40, 40, 40);
+        let foreground = Color::Rgb(235, 219, 178);
+        let yellow = Color::Rgb(250, 189, 47);
+        let aqua = Color::Rgb(142, 192, 124);
+        let blue = Color::Rgb(131, 165, 152);
+        Self {
+            name: "gruvbox".to_string(),
+            background,
+            text: fg(foreground),
+            muted: fg(Color::Rgb(146, 131, 116)),
+            accent: fg(aqua),
+            secondary: fg(blue),
+            border: fg(blue),
+            headings: [fg(Color::Rgb(254, 128, 25)), fg(yellow), fg(aqua), fg(foreground)],
+            code: fg(Color::Rgb(213, 196, 161)),
+            highlight: fg_bg(background, yellow),
+            selection: fg_bg(background, blue),
+            annotation: bg(Color::Rgb(80, 73, 69)),
+            warning: fg(yellow),
+            danger: fg(Color::Rgb(251, 73, 52)),
+            confirm: fg(Color::Rgb(184, 187, 38)),
+            special: fg(Color::Rgb(211, 134, 155)),
+            progress: fg_bg(aqua, Color::Rgb(60, 56, 54)),
+            shadow: Color::Rgb(29, 32, 33),
+            syntax_theme: "base16-eighties.dark".to_string(),
+        }
+    }
+
+    /// White on black with bright accents, for low vision or washed-out
+    /// screens.
+    pub fn high_contrast() -> Self {
+        Self {
+            name: "high-contrast".to_string(),
+            background: Color::Black,
+            text: fg(Color::White),
+            muted: fg(Color::Gray),
+            accent: fg(Color::Yellow),
+            secondary: fg(Color::LightCyan),
+            border: fg(Color::White),
+            headings: [
+                fg(Color::Yellow),
+                fg(Color::LightCyan),
+                fg(Color::White),
+                fg(Color::White),
+            ],
+            code: fg(Color::White),
+            highlight: fg_bg(Color::Black, Color::Yellow),
+            selection: fg_bg(Color::Black, Color::LightCyan),
+            annotation: fg_bg(Color::White, Color::Blue),
+            warning: fg(Color::Yellow),
+            danger: fg(Color::LightRed),
+            confirm: fg(Color::LightGreen),
+            special: fg(Color::LightMagenta),
+            progress: fg_bg(Color::Yellow, Color::DarkGray),
+            shadow: Color::Black,
+            syntax_theme: "base16-ocean.dark".to_string(),
+        }
+    }
+
+    /// No colours at all, only bold, underline and reverse video, for
+    /// `NO_COLOR` and `--no-color`.
+    pub fn monochrome() -> Self {
+        let plain = Style::default();
+        let bold = plain.add_modifier(Modifier::BOLD);
+        let reversed = plain.add_modifier(Modifier::REVERSED);
+        Self {
+            name: "monochrome".to_string(),
+            background: Color::Reset,
+            text: plain,
+            muted: plain,
+            accent: bold,
+            secondary: plain,
+            border: plain,
+            headings: [bold.add_modifier(Modifier::UNDERLINED), bold, bold, bold],
+            code: plain,
+            highlight: reversed,
+            selection: reversed,
+            annotation: plain.add_modifier(Modifier::UNDERLINED),
+            warning: bold,
+            danger: bold,
+            confirm: bold,
+            special: bold,
+            progress: plain,
+            shadow: Color::Reset,
+            syntax_theme: String::new(),
+        }
+    }
+
+    /// Whether the theme is meant for a light background.
+    pub fn is_light(&self) -> bool {
+        match self.background {
+            Color::Rgb(r, g, b) => background::is_light((r, g, b)),
+            Color::White | Color::Gray => true,
+            _ => false,
+        }
+    }
+
+    /// Style for a heading of the given level (1 for `<h1>`).
+    pub fn heading(&self, level: usize) -> Style {
+        self.headings[level.clamp(1, self.headings.len()) - 1]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::load(name)
+    }
+}
+
+/// Where user themes live: `$XDG_CONFIG_HOME/catatau/themes`, or
+/// `~/.config/catatau/themes`.
+pub fn themes_dir() -> Option<PathBuf> {
+    Some(config::config_dir()?.join("themes"))
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    Color::from_str(color).map_err(|_| format!("unknown colour '{color}'"))
+}
+
+fn parse_style(element: &str, value: &toml::Value) -> Result<Style, String> {
+    let table = match value {
+        toml::Value::String(color) => return Ok(fg(parse_color(color)?)),
+        toml::Value::Table(table) => table,
+        _ => return Err(format!("'{element}' must be a colour or a table")),
+    };
+
+    let mut style = Style::default();
+    for (key, value) in table {
+        match (key.as_str(), value) {
+            ("fg", toml::Value::String(color)) => style = style.fg(parse_color(color)?),
+            ("bg", toml::Value::String(color)) => style = style.bg(parse_color(color)?),
+            ("modifiers", toml::Value::Array(names)) => {
+                for name in names {
+                    let name = name
+                        .as_str()
+                        .ok_or_else(|| format!("modifiers for '{element}' must be names"))?;
+                    let modifier = Modifier::from_name(&name.to_ascii_uppercase())
+                        .ok_or_else(|| format!("unknown modifier '{name}' for '{element}'"))?;
+                    style = style.add_modifier(modifier);
+                }
+            }
+            _ => return Err(format!("unexpected '{key}' in '{element}'")),
+        }
+    }
+    Ok(style)
+}
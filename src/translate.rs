@@ -0,0 +1,169 @@
+use serde_json::{Value, json};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// What passages are translated with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// The `argos-translate` command, which runs offline once its language
+    /// packages are installed.
+    Argos,
+    /// DeepL's API, with the key from `$DEEPL_API_KEY`.
+    DeepL,
+    /// Google Cloud Translation, with the key from
+    /// `$GOOGLE_TRANSLATE_API_KEY`.
+    Google,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "argos" | "argos-translate" => Ok(Self::Argos),
+            "deepl" => Ok(Self::DeepL),
+            "google" => Ok(Self::Google),
+            other => Err(format!(
+                "unknown translator '{other}' (expected argos, deepl or google)"
+            )),
+        }
+    }
+}
+
+/// Translates passages into the reader's language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translator {
+    pub backend: Backend,
+    /// The language to translate into, as a BCP 47 tag.
+    pub target: String,
+}
+
+impl Translator {
+    pub fn new(backend: Backend, target: &str) -> Self {
+        Self {
+            backend,
+            target: target.to_string(),
+        }
+    }
+
+    /// Translate `text`, written in `source` if the book says (argos needs
+    /// to be told; the APIs work it out), into the target language. This
+    /// waits on another program or the network, so it's best called away
+    /// from the UI thread.
+    pub fn translate(&self, text: &str, source: Option<&str>) -> Result<String, String> {
+        match self.backend {
+            Backend::Argos => {
+                let source = source.map(primary_language).unwrap_or("en");
+                argos(text, source, primary_language(&self.target))
+            }
+            Backend::DeepL => {
+                let key = api_key("DEEPL_API_KEY")?;
+                // Free keys are only accepted by the free API
+                let host = if key.ends_with(":fx") {
+                    "api-free.deepl.com"
+                } else {
+                    "api.deepl.com"
+                };
+                let response = post_json(
+                    &format!("https://{host}/v2/translate"),
+                    ("Authorization", &format!("DeepL-Auth-Key {key}")),
+                    &json!({ "text": [text], "target_lang": self.target.to_uppercase() }),
+                )?;
+                parse_deepl(&response).ok_or_else(|| "DeepL sent no translation".to_string())
+            }
+            Backend::Google => {
+                let key = api_key("GOOGLE_TRANSLATE_API_KEY")?;
+                let response = post_json(
+                    "https://translation.googleapis.com/language/translate/v2",
+                    ("X-goog-api-key", &key),
+                    &json!({ "q": text, "target": self.target, "format": "text" }),
+                )?;
+                parse_google(&response).ok_or_else(|| "Google sent no translation".to_string())
+            }
+        }
+    }
+}
+
+/// The reader's language from the locale, as in `pt` for `pt_BR.UTF-8`,
+/// or English if it isn't set.
+pub fn locale_language() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|variable| std::env::var(variable).ok())
+        .find(|locale| !locale.is_empty() && locale != "C" && locale != "POSIX")
+        .map(|locale| primary_language(&locale).to_string())
+        .unwrap_or_else(|| "en".to_string())
+}
+
+/// The translated text in a response from DeepL's translate API.
+pub fn parse_deepl(json: &Value) -> Option<String> {
+    Some(json.pointer("/translations/0/text")?.as_str()?.to_string())
+}
+
+/// The translated text in a response from Google's translate API.
+pub fn parse_google(json: &Value) -> Option<String> {
+    Some(json.pointer("/data/translations/0/translatedText")?.as_str()?.to_string())
+}
+
+// `en` for `en-GB` or `en_GB.UTF-8`
+fn primary_language(language: &str) -> &str {
+    language.split(['-', '_', '.']).next().unwrap_or(language)
+}
+
+fn api_key(variable: &str) -> Result<String, String> {
+    std::env::var(variable)
+        .ok()
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| format!("Set {variable} to translate"))
+}
+
+fn argos(text: &str, source: &str, target: &str) -> Result<String, String> {
+    let mut child = Command::new("argos-translate")
+        .args(["--from-lang", source, "--to-lang", target])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Couldn't run argos-translate: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(e) = stdin.write_all(text.as_bytes())
+    {
+        // Don't leave the process running, or unreaped, behind the error
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(e.to_string());
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        let reason = error.lines().last().unwrap_or("failed");
+        return Err(format!("argos-translate: {reason}"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// POST `body` with the API key in the `key` header. Errors leave out the
+// URL, which ureq would otherwise put in them, so nothing secret reaches
+// the status line or the log.
+#[cfg(feature = "online")]
+fn post_json(url: &str, key: (&str, &str), body: &Value) -> Result<Value, String> {
+    let response = ureq::post(url)
+        .set("User-Agent", concat!("catatau/", env!("CARGO_PKG_VERSION")))
+        .set("Content-Type", "application/json")
+        .set(key.0, key.1)
+        .timeout(std::time::Duration::from_secs(30))
+        .send_string(&body.to_string())
+        .map_err(|e| match e {
+            ureq::Error::Status(code, _) => format!("the service answered {code}"),
+            ureq::Error::Transport(transport) => transport.kind().to_string(),
+        })
+        .map_err(|reason| format!("Translation failed: {reason}"))?;
+    let text = response.into_string().map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| format!("Translation failed: {e}"))
+}
+
+#[cfg(not(feature = "online"))]
+fn post_json(_url: &str, _key: (&str, &str), _body: &Value) -> Result<Value, String> {
+    Err("Translating through an API needs catatau built with the online feature".to_string())
+}
@@ -1,26 +1,65 @@
+use crate::annotations;
+use crate::bidi;
+use crate::clipboard::Clipboard;
 use crate::constants::{
-    DEFAULT_TERMINAL_HEIGHT, MAX_DISPLAY_LINE_LENGTH, MIN_SEARCH_LINE_LENGTH,
-    SEARCH_RESULT_TOP_OFFSET, UI_RESERVED_HEIGHT,
+    CONTENT_HORIZONTAL_CHROME, CONTENT_VERTICAL_CHROME, DEFAULT_TERMINAL_HEIGHT, EINK_REFRESH_MS,
+    EVENT_POLL_INTERVAL_MS, FOOTER_HEIGHT, HEADER_HEIGHT, JUMP_LIST_SIZE, LINK_HINT_KEYS,
+    MAX_COUNT, MAX_DISPLAY_LINE_LENGTH, MAX_LINE_SPACING, MIN_SEARCH_LINE_LENGTH, MIN_TEXT_WIDTH,
+    PREFETCH_SCREENS, PROGRESS_FILE, RENDER_CACHE_SIZE, SEARCH_POLL_INTERVAL_MS,
+    SEARCH_RESULT_TOP_OFFSET, STATS_FILE, TABLE_SCROLL_STEP, UI_RESERVED_HEIGHT,
 };
-use crate::epub::EpubReader;
-use crate::error::UiError;
+use crate::convert::{self, BlockKind, LIST_BULLETS, Link, SpanStyle};
+use crate::css::TextAlign;
+use crate::crash;
+use crate::epub::{Chapter, EpubReader, Heading};
+#[cfg(feature = "highlight")]
+use crate::highlight;
+use crate::keymap::{self, Action, Binding, KeyChord, Keymap};
+use crate::lookup::{self, Summary};
+use crate::narration::Narrator;
+use crate::translate::Translator;
+use crate::watch::BookWatcher;
+use crate::progress::{self, Highlight, Progress, ReadingPosition};
+use crate::remote::{Call, RemoteServer};
+use crate::search::{self, BackgroundSearch, SearchHit, SearchIndex, SearchMode};
+use crate::stats::{self, ReadingStats, SessionTimer};
+use crate::error::{EpubError, RemoteError, UiError};
+use crate::theme::Theme;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+use serde_json::{Value, json};
 use ratatui::{
     Frame, Terminal,
-    backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    backend::{Backend, CrosstermBackend, TestBackend},
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{
         Block, BorderType, Borders, Clear, Gauge, List, ListItem, ListState, Padding, Paragraph,
         Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap,
     },
 };
-use std::io;
+use lru::LruCache;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::io::{self, Stdout, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::{
+    Arc, OnceLock,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, TryRecvError},
+};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Debug)]
 struct SearchResultLocation {
@@ -33,24 +72,212 @@ struct ChapterLocation {
     chapter: usize,
 }
 
+/// Why [`App::run`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Quit,
+    /// Another of the books opened together was picked, by its index. The
+    /// terminal is left set up for it; see [`App::hand_over_terminal`].
+    SwitchBook(usize),
+}
+
+/// Beside the first line of a highlight that has a note.
+const NOTE_MARKER: &str = "✎";
+
+/// Shown while a passage is being selected.
+const VISUAL_MODE_HELP: &str =
+    "-- VISUAL --  j/k extend · ⏎ highlight · y copy · w Wikipedia · d define · t translate · \
+     Esc cancel";
+
+/// Something that happened, for [`App::update`] to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Key(KeyEvent),
+    Resize(u16, u16),
+    /// Time has passed: background searches, lookups and narration may
+    /// have moved on, and the place in the book is noted.
+    Tick,
+    /// The book's file has changed and should be opened again.
+    BookChanged,
+}
+
+/// Why the run loop stopped waiting.
+enum Wakeup {
+    Input(Event),
+    /// SIGTSTP arrived.
+    Suspend,
+    /// Time to show what a running search or lookup has found.
+    SearchProgress,
+    /// The book's file has been written to.
+    BookChanged,
+    /// A remote control client sent a request.
+    Remote(Call),
+}
+
+/// A line in the book, such as a search match. Ordered by where it comes
+/// in the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Position {
+    chapter: usize,
+    line: usize,
+}
+
+/// A place left in a chapter or kept in the jump list, out of the lines the
+/// chapter had then, so it's found again after the text is rewrapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Place {
+    chapter: usize,
+    line: usize,
+    lines: usize,
+}
+
+/// How much of the book the Search pane looks through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SearchScope {
+    #[default]
+    Book,
+    /// Only the chapter being read, for finding a passage again.
+    Chapter,
+}
+
+#[derive(Debug)]
+struct SearchPane {
+    query: String,
+    /// Grouped by chapter, with the chapters and the matches in each in
+    /// book order, or best match first when matching fuzzily.
+    results: Vec<SearchHit>,
+    selected_index: usize,
+    /// Fills `results` as it works through the book.
+    search: BackgroundSearch,
+    scope: SearchScope,
+}
+
 #[derive(Debug)]
 enum FloatingPane {
     None,
-    Search {
-        query: String,
-        results: Vec<String>,
+    Search(SearchPane),
+    Contents {
+        /// The chapter selected, whether or not the filter hides it.
         selected_index: usize,
+        /// Chapters already read, marked with a tick.
+        read_chapters: BTreeSet<usize>,
+        /// Typed to narrow the list down to chapters matching it fuzzily.
+        filter: String,
     },
-    Contents {
+    BookInfo,
+    Stats {
+        stats: ReadingStats,
+        session: u64,
+    },
+    Outline {
+        headings: Vec<Heading>,
+        selected_index: usize,
+    },
+    Annotations {
+        highlights: Vec<Highlight>,
+        selected_index: usize,
+    },
+    /// Writing the note for the selected highlight of the Highlights pane,
+    /// which comes back when the note is saved or abandoned.
+    NoteEditor {
+        highlights: Vec<Highlight>,
+        selected_index: usize,
+        input: String,
+    },
+    /// Notes on the highlights on screen.
+    Notes {
+        highlights: Vec<Highlight>,
+    },
+    /// The books opened together, to switch to another.
+    Books {
+        titles: Vec<String>,
         selected_index: usize,
     },
+    /// What Wikipedia or Wiktionary says about a word or phrase.
+    Lookup {
+        summary: Summary,
+        scroll: u16,
+    },
+    /// A passage beside its translation.
+    Translation {
+        original: String,
+        translated: String,
+        scroll: u16,
+    },
+    LinkHints {
+        hints: Vec<LinkHint>,
+        typed: String,
+    },
+    OpenUrl {
+        url: String,
+    },
+    Command {
+        input: String,
+    },
+    ConfirmQuit,
+}
+
+impl FloatingPane {
+    /// Whether keys typed go into a query, filter, note or command.
+    fn takes_text(&self) -> bool {
+        matches!(
+            self,
+            FloatingPane::Search(_)
+                | FloatingPane::Contents { .. }
+                | FloatingPane::NoteEditor { .. }
+                | FloatingPane::Command { .. }
+        )
+    }
 }
 
+// What the `:` prompt can do
 #[derive(Debug)]
+enum PromptCommand {
+    BookPercent(usize),
+    Line(usize),
+    Chapter(usize),
+    /// Write the highlights out as Markdown, to the file given or next to
+    /// the book.
+    Export(Option<PathBuf>),
+    /// Look a word or phrase up online.
+    Lookup(lookup::Source, String),
+}
+
+#[derive(Debug)]
+struct LinkHint {
+    label: String,
+    link: Link,
+}
+
+#[derive(Debug, Clone)]
 struct NavigationState {
     current_chapter: usize,
     scroll_offset: usize,
-    highlighted_search_term: Option<String>,
+    block_offset: usize,
+    /// The last query jumped to from the search pane, highlighted until
+    /// Esc and searched again by `n` and `N`.
+    search_query: Option<String>,
+    /// Line of the match last jumped to.
+    search_match: Option<Position>,
+    /// Which of the query's matching lines that is, and how many there are.
+    match_number: Option<(usize, usize)>,
+    /// Show the chapter's original XHTML instead of the converted text.
+    show_source: bool,
+    /// The first and last lines of the passage being selected in visual
+    /// mode; the last moves with `j` and `k`.
+    visual: Option<(usize, usize)>,
+    /// The first and last lines of the passage being read out by the
+    /// book's narration.
+    narrated: Option<(usize, usize)>,
+    /// Move through the chapter a page at a time, with breaks between
+    /// paragraphs, instead of scrolling.
+    paged: bool,
+    /// Where each chapter was left, so stepping back to it returns there.
+    chapter_offsets: HashMap<usize, Place>,
+    /// Places left by jumps, most recent last, for Ctrl-o.
+    jumps_back: Vec<Place>,
+    /// Places left by Ctrl-o, for Ctrl-i.
+    jumps_forward: Vec<Place>,
 }
 
 impl NavigationState {
@@ -58,830 +285,4282 @@ impl NavigationState {
         Self {
             current_chapter: 0,
             scroll_offset: 0,
-            highlighted_search_term: None,
+            block_offset: 0,
+            search_query: None,
+            search_match: None,
+            match_number: None,
+            show_source: false,
+            visual: None,
+            narrated: None,
+            paged: false,
+            chapter_offsets: HashMap::new(),
+            jumps_back: Vec::new(),
+            jumps_forward: Vec::new(),
         }
     }
 
-    fn clear_highlight(&mut self) {
-        self.highlighted_search_term = None;
+    fn clear_search(&mut self) {
+        self.search_query = None;
+        self.search_match = None;
+        self.match_number = None;
     }
 
     fn reset_scroll(&mut self) {
         self.scroll_offset = 0;
+        self.block_offset = 0;
     }
 }
 
-pub struct App {
-    epub: EpubReader,
-    nav_state: NavigationState,
-    floating_pane: FloatingPane,
-    terminal_height: usize,
-    terminal: Option<Terminal<CrosstermBackend<std::io::Stdout>>>,
+/// Reader-facing preferences, set from the command line and config file.
+#[derive(Debug, Clone)]
+pub struct DisplayOptions {
+    /// Cap on the text column width; the column is centred when the
+    /// terminal is wider.
+    pub max_width: Option<usize>,
+    pub theme: Theme,
+    /// Hide the header, footer, borders and scrollbar.
+    pub zen: bool,
+    /// Ask before `q` quits; `ZZ` always quits straight away.
+    pub confirm_quit: bool,
+    /// Open the book in paged mode.
+    pub paged: bool,
+    /// How many lines `j` and `k` scroll by.
+    pub scroll_lines: usize,
+    pub line_spacing: LineSpacing,
+    pub profile: Profile,
+    pub keymap: Keymap,
 }
 
-impl App {
-    pub fn new(epub: EpubReader) -> Self {
+impl Default for DisplayOptions {
+    fn default() -> Self {
         Self {
-            epub,
-            nav_state: NavigationState::new(),
-            floating_pane: FloatingPane::None,
-            terminal_height: DEFAULT_TERMINAL_HEIGHT,
-            terminal: None,
+            max_width: None,
+            theme: Theme::default(),
+            zen: false,
+            confirm_quit: false,
+            paged: false,
+            scroll_lines: 1,
+            line_spacing: LineSpacing::SINGLE,
+            profile: Profile::Standard,
+            keymap: Keymap::default(),
         }
     }
+}
 
-    // Public accessors for testing
-    #[allow(dead_code)]
-    pub fn current_chapter(&self) -> usize {
-        self.nav_state.current_chapter
-    }
-
-    #[allow(dead_code)]
-    pub fn scroll_offset(&self) -> usize {
-        self.nav_state.scroll_offset
-    }
+/// The kind of screen the reader is drawn for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    #[default]
+    Standard,
+    /// E-ink and other slow-refreshing screens: nothing blinks or spins,
+    /// progress is written rather than drawn as a bar, and the screen is
+    /// redrawn as seldom as it can be.
+    Eink,
+}
 
-    #[allow(dead_code)]
-    pub fn epub(&self) -> &EpubReader {
-        &self.epub
+impl Profile {
+    fn animated(self) -> bool {
+        self == Profile::Standard
     }
+}
 
-    pub fn run(&mut self) -> Result<(), UiError> {
-        self.setup_terminal()?;
-
-        loop {
-            if let Some(terminal) = self.terminal.as_mut() {
-                self.terminal_height = terminal.size()?.height as usize;
-                let current_chapter = self.nav_state.current_chapter;
-                let scroll_offset = self.nav_state.scroll_offset;
-                let terminal_height = self.terminal_height;
-                let epub = &self.epub;
-                let highlighted_search_term = &self.nav_state.highlighted_search_term;
-                let floating_pane = &self.floating_pane;
-
-                terminal.draw(|f| {
-                    Self::draw_ui(
-                        f,
-                        epub,
-                        current_chapter,
-                        scroll_offset,
-                        terminal_height,
-                        highlighted_search_term,
-                        floating_pane,
-                    );
-                })?;
-            }
-
-            if let Event::Key(key) = event::read()? {
-                if self.handle_floating_pane_input(key) {
-                    continue;
-                }
+impl FromStr for Profile {
+    type Err = String;
 
-                match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Down | KeyCode::Char('j') => {
-                        self.scroll_down();
-                        self.nav_state.clear_highlight();
-                    }
-                    KeyCode::Up | KeyCode::Char('k') => {
-                        self.scroll_up();
-                        self.nav_state.clear_highlight();
-                    }
-                    KeyCode::PageDown | KeyCode::Char(' ') => {
-                        self.page_down();
-                        self.nav_state.clear_highlight();
-                    }
-                    KeyCode::PageUp | KeyCode::Char('b') => {
-                        self.page_up();
-                        self.nav_state.clear_highlight();
-                    }
-                    KeyCode::Right | KeyCode::Char('l') => {
-                        self.next_chapter();
-                        self.nav_state.clear_highlight();
-                    }
-                    KeyCode::Left | KeyCode::Char('h') => {
-                        self.prev_chapter();
-                        self.nav_state.clear_highlight();
-                    }
-                    KeyCode::Home | KeyCode::Char('g') => {
-                        self.go_to_beginning();
-                        self.nav_state.clear_highlight();
-                    }
-                    KeyCode::End | KeyCode::Char('G') => {
-                        self.go_to_end();
-                        self.nav_state.clear_highlight();
-                    }
-                    KeyCode::Char('/') => self.open_search_pane(),
-                    KeyCode::Char('-') => self.open_contents_pane(),
-                    _ => {}
-                }
-            }
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "standard" | "default" => Ok(Self::Standard),
+            "eink" | "e-ink" => Ok(Self::Eink),
+            other => Err(format!("unknown profile '{other}' (expected standard or eink)")),
         }
+    }
+}
+
+/// How far apart lines of text are set, in rows: at 1.5 a blank row follows
+/// every other line, and at 2 every line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineSpacing {
+    // Blank rows for every ten lines, so that rows are counted exactly
+    extra_tenths: usize,
+}
 
-        self.cleanup_terminal()?;
+impl LineSpacing {
+    pub const SINGLE: Self = Self { extra_tenths: 0 };
 
-        Ok(())
+    /// The screen row the `index`th line from the top is drawn on.
+    pub fn row_of(self, index: usize) -> usize {
+        index + index * self.extra_tenths / 10
     }
 
-    fn setup_terminal(&mut self) -> Result<(), UiError> {
-        enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-        let backend = CrosstermBackend::new(stdout);
-        let terminal = Terminal::new(backend)?;
-        self.terminal = Some(terminal);
-        Ok(())
+    /// How many lines fit in `rows`.
+    pub fn lines_in(self, rows: usize) -> usize {
+        (0..rows).take_while(|&index| self.row_of(index) < rows).count()
     }
 
-    fn cleanup_terminal(&mut self) -> Result<(), UiError> {
-        if let Some(mut terminal) = self.terminal.take() {
-            disable_raw_mode()?;
-            execute!(
-                terminal.backend_mut(),
-                LeaveAlternateScreen,
-                DisableMouseCapture
-            )?;
-            terminal.show_cursor()?;
+    // The lines with the blank rows due after each put in
+    fn spread<T: Default>(self, lines: Vec<T>) -> Vec<T> {
+        if self == Self::SINGLE {
+            return lines;
         }
-        Ok(())
+        let mut spread = Vec::with_capacity(self.row_of(lines.len()));
+        for (index, line) in lines.into_iter().enumerate() {
+            spread.push(line);
+            let gap = self.row_of(index + 1) - self.row_of(index) - 1;
+            spread.extend(std::iter::repeat_with(T::default).take(gap));
+        }
+        spread
     }
+}
 
-    fn draw_ui(
-        f: &mut Frame,
-        epub: &EpubReader,
-        current_chapter: usize,
-        scroll_offset: usize,
-        terminal_height: usize,
-        highlighted_search_term: &Option<String>,
-        floating_pane: &FloatingPane,
-    ) {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(4), // Header
-                Constraint::Min(0),    // Content
-                Constraint::Length(3), // Footer with progress
-            ])
-            .split(f.area());
+impl FromStr for LineSpacing {
+    type Err = String;
 
-        // Modern header with rounded borders and better styling
-        let title_line = Line::from(vec![
-            Span::styled(
-                "📖 ",
-                Style::default().fg(Color::Cyan),
-            ),
-            Span::styled(
-                &epub.title,
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            ),
-        ]);
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let rows = match value.to_ascii_lowercase().as_str() {
+            "single" => 1.0,
+            "double" => 2.0,
+            rows => rows.parse().unwrap_or(f64::NAN),
+        };
+        if !(1.0..=MAX_LINE_SPACING).contains(&rows) {
+            return Err(format!(
+                "line spacing must be single, double or a number from 1 to {MAX_LINE_SPACING}"
+            ));
+        }
+        Ok(Self {
+            extra_tenths: ((rows - 1.0) * 10.0).round() as usize,
+        })
+    }
+}
 
-        let author_line = Line::from(vec![
-            Span::styled("   by ", Style::default().fg(Color::DarkGray)),
-            Span::styled(
-                &epub.author,
-                Style::default()
-                    .fg(Color::LightBlue)
-                    .add_modifier(Modifier::ITALIC),
-            ),
-        ]);
+/// The view that isn't focused while the screen is split. Commands act on
+/// `App::nav_state`, so switching views swaps the two.
+#[derive(Debug)]
+struct SplitView {
+    other: NavigationState,
+    focus_top: bool,
+}
 
-        let header = Paragraph::new(vec![title_line, author_line])
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Rounded)
-                    .border_style(Style::default().fg(Color::Cyan))
-                    .padding(Padding::horizontal(1)),
-            )
-            .alignment(Alignment::Left);
-        f.render_widget(header, chunks[0]);
+/// What a chapter's cached lines were styled for: anything that changes
+/// how a line looks, other than the selection and the narrated passage,
+/// which are marked as each frame is drawn.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RenderKey {
+    chapter: usize,
+    width: usize,
+    theme: String,
+    query: Option<String>,
+    block_offset: usize,
+    /// Where the chapter's highlighted passages start and end.
+    highlights: Vec<(usize, usize)>,
+}
 
-        if let Ok(chapter) = epub.get_chapter(current_chapter) {
-            let total_lines = chapter.content.lines().count();
-            let visible_lines = terminal_height.saturating_sub(UI_RESERVED_HEIGHT);
+/// Chapter lines as styled for recent frames, so that drawing only styles
+/// lines not seen before rather than every visible line each time. Lines
+/// are styled as they first come into view. The XHTML of chapters shown
+/// as source is kept too, rather than read from the book for each frame.
+struct RenderCache {
+    lines: RefCell<LruCache<RenderKey, Vec<Option<Line<'static>>>>>,
+    sources: RefCell<LruCache<usize, Arc<str>>>,
+}
 
-            let lines: Vec<Line> = if let Some(search_term) = highlighted_search_term {
-                chapter
-                    .content
-                    .lines()
-                    .skip(scroll_offset)
-                    .take(visible_lines)
-                    .map(|line| Self::highlight_line(line, search_term))
-                    .collect()
-            } else {
-                chapter
-                    .content
-                    .lines()
-                    .skip(scroll_offset)
-                    .take(visible_lines)
-                    .map(|line| Self::style_line(line))
-                    .collect()
-            };
+impl RenderCache {
+    fn new() -> Self {
+        let size = NonZeroUsize::new(RENDER_CACHE_SIZE).unwrap();
+        Self {
+            lines: RefCell::new(LruCache::new(size)),
+            sources: RefCell::new(LruCache::new(size)),
+        }
+    }
 
-            let chapter_title = format!("│ {} ", chapter.title);
-            let content = Paragraph::new(lines)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .border_type(BorderType::Rounded)
-                        .border_style(Style::default().fg(Color::Blue))
-                        .title(chapter_title)
-                        .title_style(Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD))
-                        .padding(Padding::new(2, 1, 0, 0)),
-                )
-                .style(Style::default().fg(Color::White))
-                .wrap(Wrap { trim: false });
-            f.render_widget(content, chunks[1]);
-
-            // Render scrollbar indicator
-            if total_lines > visible_lines {
-                let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
-                    .begin_symbol(Some("↑"))
-                    .end_symbol(Some("↓"))
-                    .track_symbol(Some("│"))
-                    .thumb_symbol("█")
-                    .style(Style::default().fg(Color::Cyan));
-
-                let mut scrollbar_state = ScrollbarState::new(total_lines.saturating_sub(visible_lines))
-                    .position(scroll_offset);
-
-                let scrollbar_area = Rect {
-                    x: chunks[1].x + chunks[1].width.saturating_sub(1),
-                    y: chunks[1].y + 1,
-                    width: 1,
-                    height: chunks[1].height.saturating_sub(2),
-                };
+    /// Forget every line, for when the book or the theme has been reloaded.
+    fn clear(&self) {
+        self.lines.borrow_mut().clear();
+        self.sources.borrow_mut().clear();
+    }
 
-                f.render_stateful_widget(scrollbar, scrollbar_area, &mut scrollbar_state);
-            }
+    // The chapter's XHTML, read from the book the first time it's shown
+    fn source(&self, epub: &EpubReader, chapter: usize) -> Option<Arc<str>> {
+        let mut sources = self.sources.borrow_mut();
+        if let Some(html) = sources.get(&chapter) {
+            return Some(html.clone());
         }
+        let html: Arc<str> = epub.get_chapter_html(chapter).ok()?.into();
+        sources.put(chapter, html.clone());
+        Some(html)
+    }
 
-        // Modern footer with progress bar and icons
-        let chapter_progress = if epub.chapter_count() > 0 {
-            ((current_chapter + 1) as f64 / epub.chapter_count() as f64) * 100.0
-        } else {
-            0.0
-        };
+    // Lines `range` of the `line_count` in a chapter, styling any not
+    // cached yet with `style`, which is given the lines it must style
+    fn lines(
+        &self,
+        key: RenderKey,
+        line_count: usize,
+        range: std::ops::Range<usize>,
+        style: impl FnOnce(std::ops::Range<usize>) -> Vec<Line<'static>>,
+    ) -> Vec<Line<'static>> {
+        let mut cache = self.lines.borrow_mut();
+        let lines = cache.get_or_insert_mut(key, || vec![None; line_count]);
+        let range = range.start.min(lines.len())..range.end.min(lines.len());
+        let missing = lines[range.clone()].iter().position(Option::is_none).map(|first| {
+            let last = lines[range.clone()].iter().rposition(Option::is_none).unwrap_or(first);
+            range.start + first..range.start + last + 1
+        });
+        if let Some(missing) = missing {
+            for (slot, line) in lines[missing.clone()].iter_mut().zip(style(missing)) {
+                *slot = Some(line);
+            }
+        }
+        lines[range].iter().map(|line| line.clone().unwrap_or_default()).collect()
+    }
+}
 
-        let footer_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(1), // Progress bar
-                Constraint::Length(2), // Help text
-            ])
-            .split(chunks[2]);
+/// A ratatui backend the reader can draw to, and what it takes to hand
+/// the terminal over to the shell or an editor and to get it back.
+/// Backends that don't draw to a terminal, like ratatui's `TestBackend`,
+/// have nothing to do.
+pub trait ReaderBackend: Backend {
+    /// Take the terminal over to draw on.
+    fn enter(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 
-        // Progress bar
-        let progress_label = format!("Chapter {}/{}", current_chapter + 1, epub.chapter_count());
-        let progress = Gauge::default()
-            .block(Block::default())
-            .gauge_style(Style::default().fg(Color::Cyan).bg(Color::DarkGray))
-            .percent(chapter_progress as u16)
-            .label(progress_label);
-        f.render_widget(progress, footer_chunks[0]);
-
-        // Help text with icons
-        let help_text = vec![
-            Line::from(vec![
-                Span::styled(" q", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                Span::styled(":quit ", Style::default().fg(Color::DarkGray)),
-                Span::styled("↑↓", Style::default().fg(Color::Cyan)),
-                Span::styled(":scroll ", Style::default().fg(Color::DarkGray)),
-                Span::styled("←→", Style::default().fg(Color::Green)),
-                Span::styled(":chapter ", Style::default().fg(Color::DarkGray)),
-                Span::styled("⎵", Style::default().fg(Color::Yellow)),
-                Span::styled(":page ", Style::default().fg(Color::DarkGray)),
-                Span::styled("/", Style::default().fg(Color::Magenta)),
-                Span::styled(":search ", Style::default().fg(Color::DarkGray)),
-                Span::styled("-", Style::default().fg(Color::Blue)),
-                Span::styled(":contents", Style::default().fg(Color::DarkGray)),
-            ]),
-        ];
-        let footer = Paragraph::new(help_text)
-            .block(
-                Block::default()
-                    .borders(Borders::TOP | Borders::LEFT | Borders::RIGHT)
-                    .border_type(BorderType::Rounded)
-                    .border_style(Style::default().fg(Color::DarkGray))
-            )
-            .alignment(Alignment::Center);
-        f.render_widget(footer, footer_chunks[1]);
+    /// Give the terminal back as it was.
+    fn leave(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
 
-        Self::render_floating_pane(f, floating_pane, epub);
+impl<W: Write> ReaderBackend for CrosstermBackend<W> {
+    fn enter(&mut self) -> io::Result<()> {
+        enable_raw_mode()?;
+        execute!(self, EnterAlternateScreen, EnableMouseCapture)
     }
 
-    fn style_line(line: &str) -> Line<'static> {
-        let trimmed = line.trim_start();
+    fn leave(&mut self) -> io::Result<()> {
+        disable_raw_mode()?;
+        execute!(self, LeaveAlternateScreen, DisableMouseCapture)
+    }
+}
 
-        // Detect markdown-style headers
-        if trimmed.starts_with("# ") {
-            let text = trimmed[2..].to_string();
-            return Line::from(vec![Span::styled(
-                text,
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            )]);
-        } else if trimmed.starts_with("## ") {
-            let text = trimmed[3..].to_string();
-            return Line::from(vec![Span::styled(
-                text,
-                Style::default()
-                    .fg(Color::Blue)
-                    .add_modifier(Modifier::BOLD),
-            )]);
-        } else if trimmed.starts_with("### ") {
-            let text = trimmed[4..].to_string();
-            return Line::from(vec![Span::styled(
-                text,
-                Style::default()
-                    .fg(Color::LightBlue)
-                    .add_modifier(Modifier::BOLD),
-            )]);
-        } else if trimmed.starts_with("#### ") || trimmed.starts_with("##### ") || trimmed.starts_with("###### ") {
-            let hash_count = trimmed.chars().take_while(|&c| c == '#').count();
-            let text = trimmed[hash_count + 1..].to_string();
-            return Line::from(vec![Span::styled(
-                text,
-                Style::default()
-                    .fg(Color::White)
-                    .add_modifier(Modifier::BOLD),
-            )]);
-        }
-
-        // Parse inline formatting (**bold**, *italic*)
-        Self::parse_inline_formatting(line)
-    }
-
-    fn parse_inline_formatting(text: &str) -> Line<'static> {
-        let mut spans = Vec::new();
-        let mut current_text = String::new();
-        let mut chars = text.chars().peekable();
-
-        while let Some(ch) = chars.next() {
-            if ch == '*' {
-                if chars.peek() == Some(&'*') {
-                    // Handle **bold**
-                    chars.next();
-                    if !current_text.is_empty() {
-                        spans.push(Span::styled(
-                            current_text.clone(),
-                            Style::default().fg(Color::White),
-                        ));
-                        current_text.clear();
-                    }
-                    let mut bold_text = String::new();
-                    let mut found_close = false;
-                    while let Some(ch2) = chars.next() {
-                        if ch2 == '*' && chars.peek() == Some(&'*') {
-                            chars.next();
-                            found_close = true;
-                            break;
-                        }
-                        bold_text.push(ch2);
-                    }
-                    if found_close && !bold_text.is_empty() {
-                        spans.push(Span::styled(
-                            bold_text,
-                            Style::default()
-                                .fg(Color::White)
-                                .add_modifier(Modifier::BOLD),
-                        ));
-                    }
-                } else {
-                    // Handle *italic*
-                    if !current_text.is_empty() {
-                        spans.push(Span::styled(
-                            current_text.clone(),
-                            Style::default().fg(Color::White),
-                        ));
-                        current_text.clear();
-                    }
-                    let mut italic_text = String::new();
-                    let mut found_close = false;
-                    while let Some(ch2) = chars.next() {
-                        if ch2 == '*' {
-                            found_close = true;
-                            break;
-                        }
-                        italic_text.push(ch2);
-                    }
-                    if found_close && !italic_text.is_empty() {
-                        spans.push(Span::styled(
-                            italic_text,
-                            Style::default()
-                                .fg(Color::White)
-                                .add_modifier(Modifier::ITALIC),
-                        ));
-                    }
-                }
-            } else {
-                current_text.push(ch);
-            }
-        }
+impl ReaderBackend for TestBackend {}
 
-        if !current_text.is_empty() {
-            spans.push(Span::styled(
-                current_text,
-                Style::default().fg(Color::White),
-            ));
-        }
+pub struct App<B: Backend = CrosstermBackend<Stdout>> {
+    /// Shared with background searches.
+    epub: Arc<EpubReader>,
+    display: DisplayOptions,
+    nav_state: NavigationState,
+    floating_pane: FloatingPane,
+    status_message: Option<String>,
+    split: Option<SplitView>,
+    /// Ctrl-w was pressed and the next key picks a window command.
+    awaiting_window_key: bool,
+    /// Digits typed so far as a count for the next motion, as in `10j`.
+    pending_count: Option<usize>,
+    /// The start of a key sequence such as `gg`.
+    pending_keys: Vec<KeyChord>,
+    /// Keys given with `--keys`, still to be played.
+    script: VecDeque<KeyEvent>,
+    /// The keys typed since recording started, if it has.
+    recording: Option<Vec<KeyChord>>,
+    /// Taking requests from other programs, as for `--listen`.
+    remote: Option<RemoteServer>,
+    /// Leave the run loop before the next redraw.
+    quitting: bool,
+    /// Titles of the books opened together, when there's more than one,
+    /// and which of them this is.
+    open_books: Vec<String>,
+    book_index: usize,
+    /// Leave the run loop to read another of the open books.
+    switch_to: Option<usize>,
+    /// `run` has been called before, so the index is being built and
+    /// signals are caught already.
+    started: bool,
+    /// Matches of the last query searched with `n` at the last text width,
+    /// since finding them means converting every chapter.
+    search_matches: Option<(String, usize, Vec<Position>)>,
+    /// Filled in from a background thread once the book is indexed.
+    search_index: Arc<OnceLock<SearchIndex>>,
+    /// Where the search index is saved, if it's kept.
+    index_dir: Option<PathBuf>,
+    /// How the Search pane last matched, kept for the next time it opens.
+    search_mode: SearchMode,
+    search_scope: SearchScope,
+    /// Set on SIGTSTP, so the terminal can be restored before stopping.
+    suspend_requested: Arc<AtomicBool>,
+    reading_stats: ReadingStats,
+    /// Reading time not yet added to the statistics file.
+    unsaved_stats: ReadingStats,
+    progress: Progress,
+    /// Key of this book in `progress`.
+    book_key: String,
+    /// Where statistics and progress are saved on exit, if they're kept.
+    state_dir: Option<PathBuf>,
+    /// Hash of the book's file, recorded with the reading position.
+    file_hash: Option<String>,
+    /// The chapter the position was last saved in, to save it again on
+    /// moving to another.
+    saved_chapter: Option<usize>,
+    timer: SessionTimer,
+    clipboard: Clipboard,
+    /// A lookup still waiting on the network.
+    pending_lookup: Option<Receiver<Result<Summary, String>>>,
+    /// What selected passages are translated with, if anything.
+    translator: Option<Translator>,
+    /// A translation still being made, with the passage it's of.
+    pending_translation: Option<(String, Receiver<Result<String, String>>)>,
+    /// The narration playing, with the chapter it's of.
+    narrator: Option<(usize, Narrator)>,
+    /// Watching the book's file, to reload it when it changes.
+    watcher: Option<BookWatcher>,
+    /// The chapter last loaded ahead of the reader, and the text width it
+    /// was loaded at.
+    prefetched: Option<(usize, usize)>,
+    terminal_height: usize,
+    /// Width and height as of the last redraw, to notice missed resizes.
+    screen_size: (u16, u16),
+    render_cache: RenderCache,
+    terminal: Option<Terminal<B>>,
+}
 
-        if spans.is_empty() {
-            Line::from(vec![Span::styled(
-                text.to_string(),
-                Style::default().fg(Color::White),
-            )])
-        } else {
-            Line::from(spans)
-        }
+impl App {
+    #[allow(dead_code)]
+    pub fn new(epub: EpubReader) -> Self {
+        Self::with_options(epub, DisplayOptions::default())
     }
 
-    fn highlight_line(line: &str, search_term: &str) -> Line<'static> {
-        let search_lower = search_term.to_lowercase();
+    pub fn with_options(epub: EpubReader, display: DisplayOptions) -> Self {
+        Self::build(epub, display)
+    }
 
-        // First check if this is a header
-        let trimmed = line.trim_start();
-        let (is_header, header_level, text_after_hash) = if trimmed.starts_with("# ") {
-            (true, 1, trimmed[2..].to_string())
-        } else if trimmed.starts_with("## ") {
-            (true, 2, trimmed[3..].to_string())
-        } else if trimmed.starts_with("### ") {
-            (true, 3, trimmed[4..].to_string())
-        } else {
-            (false, 0, line.to_string())
-        };
+    pub fn run(&mut self) -> Result<Exit, UiError> {
+        // Stopping with the terminal in raw mode would leave the shell
+        // unusable. The handler is only ours while this book is being read,
+        // so each book opened in a session doesn't leave another behind.
+        #[cfg(unix)]
+        let sigtstp = signal_hook::flag::register(
+            signal_hook::consts::SIGTSTP,
+            Arc::clone(&self.suspend_requested),
+        )?;
+        let exit = self.read_until_exit();
+        #[cfg(unix)]
+        signal_hook::low_level::unregister(sigtstp);
+        exit
+    }
 
-        let text_to_search = if is_header { &text_after_hash } else { line };
-        let text_lower = text_to_search.to_lowercase();
+    fn read_until_exit(&mut self) -> Result<Exit, UiError> {
+        if !std::mem::replace(&mut self.started, true) {
+            self.index_book();
+        }
+        // A Ctrl-Z left over from the last time this book was read is stale
+        self.suspend_requested.store(false, Ordering::Relaxed);
+        if self.terminal.is_none() {
+            self.setup_terminal()?;
+        }
 
-        if let Some(pos) = text_lower.find(&search_lower) {
-            let mut spans = Vec::new();
+        loop {
+            if self.quitting || self.switch_to.is_some() {
+                break;
+            }
+            if let Some(size) = self.terminal.as_ref().map(|t| t.size()).transpose()? {
+                self.update(Message::Resize(size.width, size.height))?;
+            }
+            self.update(Message::Tick)?;
+            if let Some(mut terminal) = self.terminal.take() {
+                let drawn = terminal.draw(|f| self.view(f)).map(drop);
+                self.terminal = Some(terminal);
+                drawn?;
+            }
 
-            let base_style = if is_header {
-                match header_level {
-                    1 => Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
-                    2 => Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
-                    3 => Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD),
-                    _ => Style::default().fg(Color::White).add_modifier(Modifier::BOLD),
+            match self.next_event()? {
+                Wakeup::Input(Event::Key(key)) => self.update(Message::Key(key))?,
+                Wakeup::Input(Event::Resize(width, height)) => {
+                    self.update(Message::Resize(width, height))?;
                 }
-            } else {
-                Style::default().fg(Color::White)
-            };
-
-            if pos > 0 {
-                spans.push(Span::styled(
-                    text_to_search[..pos].to_string(),
-                    base_style,
-                ));
+                Wakeup::Suspend => self.suspend()?,
+                Wakeup::BookChanged => self.update(Message::BookChanged)?,
+                Wakeup::Remote(call) => {
+                    let result = self.remote_call(&call.method, &call.params);
+                    call.answer(result);
+                }
+                Wakeup::Input(_) | Wakeup::SearchProgress => {}
             }
+        }
 
-            let end_pos = pos + search_term.len();
-            spans.push(Span::styled(
-                text_to_search[pos..end_pos.min(text_to_search.len())].to_string(),
-                Style::default().bg(Color::Yellow).fg(Color::Black),
-            ));
-
-            if end_pos < text_to_search.len() {
-                spans.push(Span::styled(
-                    text_to_search[end_pos..].to_string(),
-                    base_style,
-                ));
+        let exit = match self.switch_to.take() {
+            Some(index) => Exit::SwitchBook(index),
+            None => {
+                self.release_terminal()?;
+                self.terminal = None;
+                Exit::Quit
             }
+        };
 
-            Line::from(spans)
-        } else {
-            Self::style_line(line)
-        }
+        self.record_position();
+        self.save_stats();
+        self.save_progress();
+
+        Ok(exit)
     }
 
-    fn get_page_size(&self) -> usize {
-        self.terminal_height.saturating_sub(UI_RESERVED_HEIGHT)
+    // Whether a search, lookup or translation is still running, which the
+    // next scripted key may be waiting on
+    fn still_working(&self) -> bool {
+        self.pending_lookup.is_some()
+            || self.pending_translation.is_some()
+            || matches!(
+                &self.floating_pane,
+                FloatingPane::Search(pane) if !pane.search.is_finished()
+            )
     }
 
-    fn get_max_scroll_for_chapter(&self, chapter_index: usize) -> usize {
-        if let Ok(chapter) = self.epub.get_chapter(chapter_index) {
-            let total_lines = chapter.content.lines().count();
-            total_lines.saturating_sub(self.get_page_size())
+    // Wait for input, or play the next scripted key, checking the size
+    // every so often for terminals that don't report being resized, and
+    // waking often while a search runs or remote requests may come. E-ink
+    // screens are only redrawn for a search's progress once a second.
+    fn next_event(&mut self) -> Result<Wakeup, UiError> {
+        let searching = self.still_working() || self.narrator.is_some();
+        let interval = if searching || self.remote.is_some() {
+            SEARCH_POLL_INTERVAL_MS
         } else {
-            0
+            EVENT_POLL_INTERVAL_MS
+        };
+        let refresh = match self.display.profile {
+            Profile::Standard => SEARCH_POLL_INTERVAL_MS,
+            Profile::Eink => EINK_REFRESH_MS,
+        };
+        let waiting = Instant::now();
+        loop {
+            if let Some(call) = self.remote.as_ref().and_then(RemoteServer::next_call) {
+                return Ok(Wakeup::Remote(call));
+            }
+            if self.suspend_requested.swap(false, Ordering::Relaxed) {
+                return Ok(Wakeup::Suspend);
+            }
+            if !self.still_working()
+                && let Some(key) = self.script.pop_front()
+            {
+                // The last of a finished search's hits may not be listed yet
+                self.collect_search_hits();
+                return Ok(Wakeup::Input(Event::Key(key)));
+            }
+            if event::poll(Duration::from_millis(interval))? {
+                return Ok(Wakeup::Input(event::read()?));
+            }
+            let (width, height) = crossterm::terminal::size()?;
+            if (width, height) != self.screen_size {
+                return Ok(Wakeup::Input(Event::Resize(width, height)));
+            }
+            if self.watcher.as_mut().is_some_and(BookWatcher::changed) {
+                return Ok(Wakeup::BookChanged);
+            }
+            if searching && waiting.elapsed() >= Duration::from_millis(refresh) {
+                return Ok(Wakeup::SearchProgress);
+            }
         }
     }
 
-    fn get_current_chapter_max_scroll(&self) -> usize {
-        self.get_max_scroll_for_chapter(self.nav_state.current_chapter)
+    // Give the terminal back to the shell and stop, as Ctrl-Z does outside
+    // raw mode, then take it over again once the shell resumes us. SIGTSTP
+    // is caught while running, so stop with SIGSTOP, which can't be.
+    #[cfg(unix)]
+    fn suspend(&mut self) -> Result<(), UiError> {
+        self.release_terminal()?;
+        signal_hook::low_level::raise(signal_hook::consts::SIGSTOP)?;
+        self.reclaim_terminal()
     }
 
-    fn clamp_scroll_to_limits(&mut self, chapter_index: usize) {
-        let max_scroll = self.get_max_scroll_for_chapter(chapter_index);
-        self.nav_state.scroll_offset = self.nav_state.scroll_offset.min(max_scroll);
+    #[cfg(not(unix))]
+    fn suspend(&mut self) -> Result<(), UiError> {
+        Ok(())
     }
 
-    fn scroll_down(&mut self) {
-        let max_scroll = self.get_current_chapter_max_scroll();
-        if self.nav_state.scroll_offset < max_scroll {
-            self.nav_state.scroll_offset += 1;
-        }
+    fn setup_terminal(&mut self) -> Result<(), UiError> {
+        let mut backend = CrosstermBackend::new(io::stdout());
+        backend.enter()?;
+        self.terminal = Some(Terminal::new(backend)?);
+        Ok(())
+    }
+}
+
+impl<B: ReaderBackend> App<B> {
+    /// Read `epub` drawing to `backend` instead of the terminal, to see
+    /// the whole reader at work with ratatui's `TestBackend`.
+    #[allow(dead_code)]
+    pub fn with_backend(
+        epub: EpubReader,
+        display: DisplayOptions,
+        backend: B,
+    ) -> Result<Self, UiError> {
+        let mut app = Self::build(epub, display);
+        app.terminal = Some(Terminal::new(backend)?);
+        Ok(app)
     }
 
-    fn scroll_up(&mut self) {
-        if self.nav_state.scroll_offset > 0 {
-            self.nav_state.scroll_offset -= 1;
+    fn build(epub: EpubReader, display: DisplayOptions) -> Self {
+        let mut nav_state = NavigationState::new();
+        nav_state.paged = display.paged;
+        nav_state.current_chapter = (0..epub.chapter_count())
+            .find(|&i| !epub.is_skippable(i))
+            .unwrap_or(0);
+        let book_key = progress::book_key(epub.path());
+
+        Self {
+            epub: Arc::new(epub),
+            display,
+            nav_state,
+            floating_pane: FloatingPane::None,
+            status_message: None,
+            split: None,
+            awaiting_window_key: false,
+            pending_count: None,
+            pending_keys: Vec::new(),
+            script: VecDeque::new(),
+            recording: None,
+            remote: None,
+            quitting: false,
+            open_books: Vec::new(),
+            book_index: 0,
+            switch_to: None,
+            started: false,
+            search_matches: None,
+            search_index: Arc::new(OnceLock::new()),
+            index_dir: None,
+            search_mode: SearchMode::default(),
+            search_scope: SearchScope::default(),
+            suspend_requested: Arc::new(AtomicBool::new(false)),
+            reading_stats: ReadingStats::default(),
+            unsaved_stats: ReadingStats::default(),
+            progress: Progress::default(),
+            book_key,
+            state_dir: None,
+            file_hash: None,
+            saved_chapter: None,
+            timer: SessionTimer::new(),
+            clipboard: Clipboard::default(),
+            pending_lookup: None,
+            translator: None,
+            pending_translation: None,
+            narrator: None,
+            watcher: None,
+            prefetched: None,
+            terminal_height: DEFAULT_TERMINAL_HEIGHT,
+            screen_size: (0, 0),
+            render_cache: RenderCache::new(),
+            terminal: None,
         }
     }
 
-    fn page_down(&mut self) {
-        let page_size = self.get_page_size();
-        let max_scroll = self.get_current_chapter_max_scroll();
-        self.nav_state.scroll_offset = (self.nav_state.scroll_offset + page_size).min(max_scroll);
+    // Public accessors for testing
+    #[allow(dead_code)]
+    pub fn current_chapter(&self) -> usize {
+        self.nav_state.current_chapter
     }
 
-    fn page_up(&mut self) {
-        let page_size = self.get_page_size();
-        self.nav_state.scroll_offset = self.nav_state.scroll_offset.saturating_sub(page_size);
+    #[allow(dead_code)]
+    pub fn scroll_offset(&self) -> usize {
+        self.nav_state.scroll_offset
     }
 
-    fn next_chapter(&mut self) {
-        if self.nav_state.current_chapter < self.epub.chapter_count().saturating_sub(1) {
-            self.nav_state.current_chapter += 1;
-            self.nav_state.reset_scroll();
-        }
+    #[allow(dead_code)]
+    pub fn epub(&self) -> &EpubReader {
+        &self.epub
     }
 
-    fn prev_chapter(&mut self) {
-        if self.nav_state.current_chapter > 0 {
-            self.nav_state.current_chapter -= 1;
-            self.nav_state.reset_scroll();
-        }
+    #[allow(dead_code)]
+    pub fn status_message(&self) -> Option<&str> {
+        self.status_message.as_deref()
     }
 
-    fn go_to_beginning(&mut self) {
-        self.nav_state.reset_scroll();
+    #[allow(dead_code)]
+    pub fn suspend_requested(&self) -> bool {
+        self.suspend_requested.load(Ordering::Relaxed)
     }
 
-    fn go_to_end(&mut self) {
-        self.nav_state.scroll_offset = self.get_current_chapter_max_scroll();
+    /// Keep reading statistics and progress in files in `dir`, which are
+    /// updated on exit.
+    pub fn keep_state(&mut self, dir: PathBuf) {
+        self.reading_stats = ReadingStats::load(&dir.join(STATS_FILE));
+        self.progress = Progress::load(&dir.join(PROGRESS_FILE));
+        self.state_dir = Some(dir);
+        self.file_hash = self
+            .epub
+            .file_hash()
+            .map_err(|e| warn!("Could not read {}: {}", self.epub.path().display(), e))
+            .ok();
     }
 
-    fn build_search_items(&self) -> Vec<String> {
-        let mut all_lines = Vec::new();
-        for chapter_index in 0..self.epub.chapter_count() {
-            if let Ok(chapter) = self.epub.get_chapter(chapter_index) {
-                for (line_index, line) in chapter.content.lines().enumerate() {
-                    if !line.trim().is_empty() && line.trim().len() > MIN_SEARCH_LINE_LENGTH {
-                        let truncated = self.truncate_line_for_display(line);
-                        all_lines.push(format!(
-                            "Ch{:2} L{:3}: {}",
-                            chapter_index + 1,
-                            line_index + 1,
-                            truncated.trim()
-                        ));
-                    }
-                }
-            }
+    /// Go back to where the book was left last time, unless the file has
+    /// changed since.
+    pub fn resume(&mut self) {
+        let Some(position) = self
+            .progress
+            .book(&self.book_key)
+            .and_then(|book| book.position.clone())
+        else {
+            return;
+        };
+        if self.file_hash.as_ref() != Some(&position.file_hash)
+            || position.chapter >= self.epub.chapter_count()
+        {
+            return;
         }
-        all_lines
+        self.nav_state.current_chapter = position.chapter;
+        self.nav_state.reset_scroll();
+        self.nav_state.scroll_offset = position.line;
+        self.rescale_scroll(position.lines);
+        self.saved_chapter = Some(position.chapter);
     }
 
-    fn truncate_line_for_display(&self, line: &str) -> String {
-        if line.chars().count() > MAX_DISPLAY_LINE_LENGTH {
-            let truncated_chars: String = line.chars().take(MAX_DISPLAY_LINE_LENGTH - 3).collect();
-            format!("{}...", truncated_chars)
-        } else {
-            line.to_string()
+    // Note the position in the book for next time, and save it straight
+    // away when it's in a new chapter so a crash loses little
+    fn record_position(&mut self) {
+        let Some(file_hash) = self.file_hash.clone() else {
+            return;
+        };
+        let chapter = self.nav_state.current_chapter;
+        let showing_text = (!self.nav_state.show_source)
+            .then(|| (self.nav_state.scroll_offset, self.displayed_line_count(chapter)));
+        let book = self.progress.book_mut(&self.book_key);
+        // Source lines don't map onto the text, so keep what was there
+        let (line, lines) = match (showing_text, &book.position) {
+            (Some(place), _) => place,
+            (None, Some(position)) if position.chapter == chapter => {
+                (position.line, position.lines)
+            }
+            (None, _) => (0, 0),
+        };
+        book.position = Some(ReadingPosition {
+            chapter,
+            line,
+            lines,
+            file_hash,
+        });
+
+        if self.saved_chapter != Some(chapter) {
+            self.saved_chapter = Some(chapter);
+            self.save_progress();
         }
     }
 
-    fn parse_and_jump_to_search_selection(&mut self, selected_text: &str, search_query: &str) {
-        if let Some(location) = Self::parse_search_result_location(selected_text) {
-            self.jump_to_search_location(location, search_query);
+    // Write this book's progress into the progress file, leaving the other
+    // books there as they are, since they may have been read since this one
+    // was opened
+    fn save_progress(&self) {
+        let (Some(dir), Some(book)) = (&self.state_dir, self.progress.book(&self.book_key)) else {
+            return;
+        };
+        let progress_path = dir.join(PROGRESS_FILE);
+        let mut progress = Progress::load(&progress_path);
+        *progress.book_mut(&self.book_key) = book.clone();
+        if let Err(e) = progress.save(&progress_path) {
+            warn!("Could not save reading progress to {}: {}", progress_path.display(), e);
         }
     }
 
-    fn parse_search_result_location(text: &str) -> Option<SearchResultLocation> {
-        let ch_pos = text.find("Ch")?;
-        let l_pos = text.find(" L")?;
-        let colon_pos = text.find(": ")?;
+    /// Start at a line found by searching for `query`, given as the chapter
+    /// and the line in it counted as the book is first shown, with the
+    /// match highlighted so `n` and `N` carry on from it.
+    pub fn show_match(&mut self, query: &str, chapter: usize, line: usize) {
+        let location = SearchResultLocation {
+            chapter: chapter + 1,
+            line: line + 1,
+        };
+        self.jump_to_search_location(location, query);
+    }
 
-        let chapter_str = text[ch_pos + 2..l_pos].trim();
-        let line_str = text[l_pos + 2..colon_pos].trim();
+    /// Go to the first line matching `query`, as `/` would find it.
+    pub fn find_first(&mut self, query: &str) -> Result<(), String> {
+        let Some(found) = self.epub.search(query).into_iter().next() else {
+            return Err(format!("No match for \"{query}\" in {}", self.epub.title));
+        };
+        self.show_match(query, found.chapter_index, found.line_number);
+        Ok(())
+    }
 
-        let chapter = chapter_str.parse().ok()?;
-        let line = line_str.parse().ok()?;
+    /// Translate passages with `translator` when asked to.
+    pub fn translate_with(&mut self, translator: Translator) {
+        self.translator = Some(translator);
+    }
 
-        Some(SearchResultLocation { chapter, line })
+    /// Save the search index in `dir`, and use the one saved there when
+    /// the book is opened again.
+    pub fn keep_search_index(&mut self, dir: PathBuf) {
+        self.index_dir = Some(dir);
     }
 
-    fn jump_to_search_location(&mut self, location: SearchResultLocation, search_query: &str) {
-        if location.chapter == 0 || location.chapter > self.epub.chapter_count() {
-            return;
-        }
+    /// Reload the book whenever its file changes, as for `--watch`.
+    pub fn watch(&mut self) -> notify::Result<()> {
+        self.watcher = Some(BookWatcher::new(self.epub.path())?);
+        Ok(())
+    }
 
-        self.nav_state.current_chapter = location.chapter - 1;
+    /// Open the book's file again, for when it's been changed, keeping the
+    /// place as nearly as the new text allows: the same chapter, the same
+    /// way through it. Places that can't be carried over, like the jump
+    /// list and a split view, are dropped.
+    pub fn reload(&mut self) -> Result<(), EpubError> {
+        let epub = self.epub.options().clone().open(self.epub.path())?;
+        epub.set_text_width(self.epub.text_width());
+        let lines = self.current_chapter_line_count();
+        self.epub = Arc::new(epub);
+        self.render_cache.clear();
 
-        if self.epub.get_chapter(self.nav_state.current_chapter).is_ok() {
-            let target_line = location.line.saturating_sub(1);
-            self.nav_state.scroll_offset = target_line.saturating_sub(SEARCH_RESULT_TOP_OFFSET);
-            self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+        let last = self.epub.chapter_count().saturating_sub(1);
+        let chapter = self.nav_state.current_chapter.min(last);
+        let mut nav_state = NavigationState::new();
+        nav_state.paged = self.nav_state.paged;
+        nav_state.show_source = self.nav_state.show_source;
+        nav_state.current_chapter = chapter;
+        nav_state.scroll_offset = self.nav_state.scroll_offset;
+        self.nav_state = nav_state;
+        self.rescale_scroll(lines);
 
-            if !search_query.is_empty() {
-                self.nav_state.highlighted_search_term = Some(search_query.to_string());
-            }
+        self.split = None;
+        self.floating_pane = FloatingPane::None;
+        self.search_matches = None;
+        self.prefetched = None;
+        self.narrator = None;
+        self.pending_keys.clear();
+        self.pending_count = None;
+        if self.file_hash.is_some() {
+            self.file_hash = self
+                .epub
+                .file_hash()
+                .map_err(|e| warn!("Could not read {}: {}", self.epub.path().display(), e))
+                .ok();
+        }
+        self.search_index = Arc::new(OnceLock::new());
+        if self.started {
+            self.index_book();
         }
+        Ok(())
     }
 
-    fn parse_and_jump_to_chapter(&mut self, selected_text: &str) {
-        if let Some(location) = Self::parse_chapter_location(selected_text) {
-            self.jump_to_chapter_location(location);
+    // Load or build the search index away from the UI thread
+    fn index_book(&self) {
+        let epub = Arc::clone(&self.epub);
+        let search_index = Arc::clone(&self.search_index);
+        let dir = self.index_dir.clone();
+        std::thread::spawn(move || {
+            let index = match dir {
+                Some(dir) => SearchIndex::load_or_build(&epub, &dir),
+                None => SearchIndex::build(&epub),
+            };
+            let _ = search_index.set(index);
+        });
+    }
+
+    // Chapters that could match `query`: those the index lists once it's
+    // ready, and all of them until then
+    fn chapters_to_search(&self, query: &str) -> Vec<usize> {
+        self.search_index
+            .get()
+            .and_then(|index| index.candidates(query))
+            .unwrap_or_else(|| (0..self.epub.chapter_count()).collect())
+    }
+
+    /// Tell the book it was opened with others, given all their titles in
+    /// order and which of them it is, so `gt`, `gT` and `B` can switch
+    /// between them.
+    pub fn set_open_books(&mut self, titles: Vec<String>, index: usize) {
+        self.open_books = titles;
+        self.book_index = index;
+    }
+
+    /// Give the terminal left set up by [`Exit::SwitchBook`] to the book
+    /// being switched to.
+    pub fn hand_over_terminal(&mut self, to: &mut App<B>) {
+        to.terminal = self.terminal.take();
+        to.script = std::mem::take(&mut self.script);
+        to.remote = self.remote.take();
+    }
+
+    /// Take remote control requests on a Unix socket at `path`.
+    pub fn listen(&mut self, path: &Path) -> io::Result<()> {
+        self.remote = Some(RemoteServer::listen(path)?);
+        Ok(())
+    }
+
+    /// Carry out a remote control request, giving where the reader is
+    /// afterwards. The methods are `get-position`, `goto-chapter` with a
+    /// `chapter` counted from one, `goto-percent` with a `percent` of the
+    /// way through the book, `search` for a `query`, going to its first
+    /// match, `keys` to type, written as for `--keys`, and `quit`.
+    pub fn remote_call(&mut self, method: &str, params: &Value) -> Result<Value, RemoteError> {
+        let param = |name: &str| {
+            params
+                .get(name)
+                .ok_or_else(|| RemoteError::InvalidParams(format!("no {name} given")))
+        };
+        let number = |name: &str| {
+            param(name)?
+                .as_u64()
+                .map(|number| number as usize)
+                .ok_or_else(|| RemoteError::InvalidParams(format!("{name} must be a number")))
+        };
+        let text = |name: &str| {
+            param(name)?
+                .as_str()
+                .ok_or_else(|| RemoteError::InvalidParams(format!("{name} must be a string")))
+        };
+        match method {
+            "get-position" => {}
+            "goto-chapter" => {
+                self.go_to_chapter(number("chapter")?).map_err(RemoteError::Failed)?;
+            }
+            "goto-percent" => match number("percent")? {
+                percent @ 0..=100 => self.go_to_book_percent(percent),
+                _ => return Err(RemoteError::InvalidParams("percent is over 100".to_string())),
+            },
+            "search" => self.find_first(text("query")?).map_err(RemoteError::Failed)?,
+            "keys" => {
+                let keys = keymap::parse_keys(text("keys")?).map_err(RemoteError::InvalidParams)?;
+                for key in keys {
+                    self.handle_key_event(key.into())
+                        .map_err(|e| RemoteError::Failed(e.to_string()))?;
+                }
+            }
+            "quit" => self.quitting = true,
+            other => return Err(RemoteError::UnknownMethod(other.to_string())),
         }
+        Ok(self.position_json())
     }
 
-    fn parse_chapter_location(text: &str) -> Option<ChapterLocation> {
-        let colon_pos = text.find(": ")?;
+    // Where the reader is, for remote control clients
+    fn position_json(&self) -> Value {
+        let chapter = self.nav_state.current_chapter;
+        json!({
+            "title": self.epub.title,
+            "chapter": chapter + 1,
+            "chapters": self.epub.chapter_count(),
+            "chapter_title": self.epub.chapter_title(chapter),
+            "line": self.nav_state.scroll_offset + 1,
+            "lines": self.current_chapter_line_count(),
+            "percent": self.book_percent(),
+        })
+    }
 
-        let chapter_str = text[0..colon_pos].trim();
-        let chapter = chapter_str.parse().ok()?;
+    // How far through the book the top of the screen is, weighing chapters
+    // by size as `go_to_book_percent` does
+    fn book_percent(&self) -> usize {
+        let sizes = self.epub.chapter_sizes();
+        let chapter = self.nav_state.current_chapter.min(sizes.len());
+        let before: u64 = sizes[..chapter].iter().sum();
+        let lines = self.current_chapter_line_count().max(1) as u64;
+        let size = sizes.get(chapter).copied().unwrap_or(0);
+        let into = size * (self.nav_state.scroll_offset as u64).min(lines) / lines;
+        ((before + into) * 100).checked_div(sizes.iter().sum()).unwrap_or(0) as usize
+    }
 
-        Some(ChapterLocation { chapter })
+    /// Type `keys` once the reader starts, one at a time as the screen is
+    /// redrawn, waiting for searches to finish before going on.
+    pub fn play_keys(&mut self, keys: &[KeyChord]) {
+        self.script.extend(keys.iter().map(|&key| KeyEvent::from(key)));
     }
 
-    fn jump_to_chapter_location(&mut self, location: ChapterLocation) {
-        if location.chapter == 0 || location.chapter > self.epub.chapter_count() {
+    // Add the reading time since the last save to the statistics file,
+    // which other books may have added to in the meantime
+    fn save_stats(&mut self) {
+        let Some(dir) = &self.state_dir else {
             return;
+        };
+        let stats_path = dir.join(STATS_FILE);
+        let mut stats = ReadingStats::load(&stats_path);
+        stats.merge(&self.unsaved_stats);
+        match stats.save(&stats_path) {
+            Ok(()) => {
+                self.unsaved_stats = ReadingStats::default();
+                self.reading_stats = stats;
+            }
+            Err(e) => {
+                warn!("Could not save reading statistics to {}: {}", stats_path.display(), e);
+            }
         }
+    }
 
-        self.nav_state.current_chapter = location.chapter - 1;
-        self.nav_state.reset_scroll();
+    /// Act on `message`, changing the reader's state but drawing nothing;
+    /// [`App::view`] shows the result. This is the whole of the reader
+    /// bar the terminal, so it can be driven without one.
+    pub fn update(&mut self, message: Message) -> Result<(), UiError> {
+        match message {
+            Message::Key(key) => self.handle_key_event(key)?,
+            Message::Resize(width, height) => self.resize(width, height),
+            Message::Tick => {
+                self.note_chapter_read();
+                self.record_position();
+                crash::note_position(
+                    self.epub.path(),
+                    self.nav_state.current_chapter,
+                    self.nav_state.scroll_offset,
+                );
+                self.collect_search_hits();
+                self.collect_lookup();
+                self.collect_translation();
+                self.follow_narration();
+                self.prefetch_next_chapter();
+            }
+            Message::BookChanged => self.reload_book(),
+        }
+        Ok(())
     }
 
-    fn handle_floating_pane_input(&mut self, key: crossterm::event::KeyEvent) -> bool {
-        let floating_pane = std::mem::replace(&mut self.floating_pane, FloatingPane::None);
+    /// Act on a key as if it were typed while reading.
+    #[allow(dead_code)]
+    pub fn handle_key(&mut self, key: KeyEvent) -> Result<(), UiError> {
+        self.update(Message::Key(key))
+    }
 
-        match floating_pane {
-            FloatingPane::None => {
-                self.floating_pane = FloatingPane::None;
-                false
+    /// Draw the reader as [`App::run`] would, at the size of its backend,
+    /// and give what was drawn.
+    #[allow(dead_code)]
+    pub fn render_to_buffer(&mut self) -> Result<Buffer, UiError> {
+        let mut terminal = self
+            .terminal
+            .take()
+            .ok_or_else(|| UiError::Terminal("the reader has no terminal yet".into()))?;
+        let size = terminal.size()?;
+        self.update(Message::Resize(size.width, size.height))?;
+        self.update(Message::Tick)?;
+        let drawn = terminal.draw(|f| self.view(f)).map(|frame| frame.buffer.clone());
+        self.terminal = Some(terminal);
+        Ok(drawn?)
+    }
+
+    /// Draw the reader as it stands, changing nothing.
+    pub fn view(&self, f: &mut Frame) {
+        let highlights = self
+            .progress
+            .book(&self.book_key)
+            .map_or(&[][..], |book| &book.highlights);
+        Self::draw_ui(
+            f,
+            &self.epub,
+            &self.nav_state,
+            self.split.as_ref(),
+            &self.floating_pane,
+            &self.status_message,
+            &self.display,
+            highlights,
+            &self.render_cache,
+        );
+    }
+
+    // A key goes first to the pane or mode that's open, and otherwise
+    // builds up a count and a key sequence until it's bound to an action
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<(), UiError> {
+        let typed = matches!(key.code, KeyCode::Char(_))
+            && !key.modifiers.contains(KeyModifiers::CONTROL);
+        if typed && self.floating_pane.takes_text() {
+            crash::note_typed_key();
+        } else {
+            crash::note_key(KeyChord::from(key));
+        }
+        if let Some(keys) = self.recording.as_mut() {
+            keys.push(KeyChord::from(key));
+        }
+        let seconds = self.timer.activity();
+        self.reading_stats.record(&self.epub.title, stats::today(), seconds);
+        self.unsaved_stats.record(&self.epub.title, stats::today(), seconds);
+        self.status_message = None;
+        if self.handle_floating_pane_input(key) {
+            return Ok(());
+        }
+        if self.nav_state.visual.is_some() {
+            self.handle_visual_key(key.code);
+            return Ok(());
+        }
+        if std::mem::take(&mut self.awaiting_window_key) {
+            self.handle_window_key(key.code);
+            return Ok(());
+        }
+        // A leading 0 isn't a count
+        if let KeyCode::Char(digit @ '0'..='9') = key.code
+            && self.pending_keys.is_empty()
+            && (digit != '0' || self.pending_count.is_some())
+        {
+            let digit = digit as usize - '0' as usize;
+            let count = (self.pending_count.unwrap_or(0) * 10 + digit).min(MAX_COUNT);
+            self.pending_count = Some(count);
+            self.status_message = Some(count.to_string());
+            return Ok(());
+        }
+        self.pending_keys.push(KeyChord::from(key));
+        match self.display.keymap.lookup(&self.pending_keys) {
+            Binding::Action(action) => {
+                self.pending_keys.clear();
+                let count = self.pending_count.take();
+                self.run_action(action, count)?;
             }
-            FloatingPane::Search {
-                mut query,
-                results,
-                mut selected_index,
-            } => {
-                match key.code {
-                    KeyCode::Esc => true,
-                    KeyCode::Char(c) => {
-                        query.push(c);
-                        let new_results = self.filter_search_results(&query);
-                        self.floating_pane = FloatingPane::Search {
-                            query,
-                            results: new_results,
-                            selected_index: 0,
-                        };
-                        true
-                    }
-                    KeyCode::Backspace => {
-                        query.pop();
-                        let new_results = self.filter_search_results(&query);
-                        self.floating_pane = FloatingPane::Search {
-                            query,
-                            results: new_results,
-                            selected_index: 0,
-                        };
-                        true
-                    }
-                    KeyCode::Up => {
-                        selected_index = selected_index.saturating_sub(1);
-                        self.floating_pane = FloatingPane::Search {
-                            query,
-                            results,
-                            selected_index,
-                        };
-                        true
-                    }
-                    KeyCode::Down => {
-                        if selected_index < results.len().saturating_sub(1) {
-                            selected_index += 1;
-                        }
-                        self.floating_pane = FloatingPane::Search {
-                            query,
-                            results,
-                            selected_index,
-                        };
-                        true
-                    }
-                    KeyCode::Enter => {
-                        if let Some(selected_text) = results.get(selected_index) {
-                            let query_copy = query.clone();
-                            let selected_text_copy = selected_text.clone();
-                            self.parse_and_jump_to_search_selection(
-                                &selected_text_copy,
-                                &query_copy,
-                            );
-                        } else {
-                            self.floating_pane = FloatingPane::Search {
-                                query,
-                                results,
-                                selected_index,
-                            };
-                        }
-                        true
-                    }
-                    _ => {
-                        self.floating_pane = FloatingPane::Search {
-                            query,
-                            results,
-                            selected_index,
-                        };
-                        true
-                    }
-                }
+            Binding::Prefix => {
+                self.status_message = Some(keymap::display_keys(&self.pending_keys));
             }
-            FloatingPane::Contents { mut selected_index } => {
-                match key.code {
-                    KeyCode::Esc => true,
-                    KeyCode::Up => {
-                        selected_index = selected_index.saturating_sub(1);
-                        self.floating_pane = FloatingPane::Contents { selected_index };
-                        true
-                    }
-                    KeyCode::Down => {
-                        if selected_index < self.epub.chapter_count().saturating_sub(1) {
-                            selected_index += 1;
-                        }
-                        self.floating_pane = FloatingPane::Contents { selected_index };
-                        true
-                    }
-                    KeyCode::Enter => {
-                        let title = self
-                            .epub
-                            .get_chapter(selected_index)
-                            .map(|ch| ch.title)
-                            .unwrap_or_else(|_| String::from(""));
-                        let selected_text = format!("{}: {}", selected_index + 1, title);
-                        self.parse_and_jump_to_chapter(&selected_text);
-                        true
-                    }
-                    _ => {
-                        self.floating_pane = FloatingPane::Contents { selected_index };
-                        true
-                    }
-                }
+            Binding::Unbound => {
+                self.pending_keys.clear();
+                self.pending_count = None;
             }
         }
+        Ok(())
     }
 
-    fn open_search_pane(&mut self) {
-        let results = self.build_search_items();
-        self.floating_pane = FloatingPane::Search {
-            query: String::new(),
-            results,
-            selected_index: 0,
-        };
+    // Start loading the next chapter on another thread once the reader is
+    // near the end of this one, so that turning to it doesn't wait on
+    // unzipping and converting it
+    fn prefetch_next_chapter(&mut self) {
+        let next = self.nav_state.current_chapter + 1;
+        let width = self.epub.text_width();
+        if next >= self.epub.chapter_count() || self.prefetched == Some((next, width)) {
+            return;
+        }
+        let left = self.current_chapter_line_count().saturating_sub(self.nav_state.scroll_offset);
+        if left > self.get_page_size() * PREFETCH_SCREENS {
+            return;
+        }
+        self.prefetched = Some((next, width));
+        if self.epub.is_chapter_cached(next) {
+            return;
+        }
+        let epub = Arc::clone(&self.epub);
+        std::thread::spawn(move || {
+            if let Err(e) = epub.get_chapter(next) {
+                warn!("Could not load chapter {} ahead: {}", next + 1, e);
+            }
+        });
+    }
+
+    // Re-wrap for the width and, when the size changed, keep the views inside
+    // the new page limits, ready for the next redraw
+    fn resize(&mut self, width: u16, height: u16) {
+        self.terminal_height = height as usize;
+        self.update_text_width(width as usize);
+        if (width, height) != self.screen_size {
+            self.screen_size = (width, height);
+            self.clamp_views();
+        }
+    }
+
+
+    // Give the terminal back as it was, keeping it to take over again
+    fn release_terminal(&mut self) -> Result<(), UiError> {
+        if let Some(terminal) = self.terminal.as_mut() {
+            terminal.backend_mut().leave()?;
+            terminal.show_cursor()?;
+        }
+        Ok(())
+    }
+
+    // Take the terminal back, redrawing all of it since whatever had it
+    // will have drawn over the screen
+    fn reclaim_terminal(&mut self) -> Result<(), UiError> {
+        if let Some(terminal) = self.terminal.as_mut() {
+            terminal.backend_mut().enter()?;
+            terminal.clear()?;
+        }
+        Ok(())
     }
 
-    fn open_contents_pane(&mut self) {
-        self.floating_pane = FloatingPane::Contents {
-            selected_index: self.nav_state.current_chapter,
-        };
-    }
+    #[allow(clippy::too_many_arguments)]
+    fn draw_ui(
+        f: &mut Frame,
+        epub: &EpubReader,
+        nav_state: &NavigationState,
+        split: Option<&SplitView>,
+        floating_pane: &FloatingPane,
+        status_message: &Option<String>,
+        display: &DisplayOptions,
+        highlights: &[Highlight],
+        render_cache: &RenderCache,
+    ) {
+        let current_chapter = nav_state.current_chapter;
+        let theme = &display.theme;
+        let zen = display.zen;
+
+        f.render_widget(
+            Block::default().style(theme.text.bg(theme.background)),
+            f.area(),
+        );
+
+        // Zen mode keeps a line at the bottom only while there's a link
+        // preview or a message to show
+        let notice = Self::notice_line(floating_pane, status_message, theme);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(if zen {
+                [
+                    Constraint::Length(0),
+                    Constraint::Min(0),
+                    Constraint::Length(u16::from(notice.is_some())),
+                ]
+            } else {
+                [
+                    Constraint::Length(HEADER_HEIGHT as u16),
+                    Constraint::Min(0),
+                    Constraint::Length(FOOTER_HEIGHT as u16),
+                ]
+            })
+            .split(f.area());
+
+        // Modern header with rounded borders and better styling
+        let title_line = Line::from(vec![
+            Span::styled("📖 ", theme.accent),
+            Span::styled(&epub.title, theme.accent.add_modifier(Modifier::BOLD)),
+        ]);
+
+        let mut author_spans = vec![
+            Span::styled("   by ", theme.muted),
+            Span::styled(&epub.author, theme.secondary.add_modifier(Modifier::ITALIC)),
+        ];
+        if let Some(series) = &epub.series {
+            author_spans.push(Span::styled("  ·  ", theme.muted));
+            author_spans.push(Span::styled(series.to_string(), theme.secondary));
+        }
+        let author_line = Line::from(author_spans);
+
+        let header = Paragraph::new(vec![title_line, author_line])
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.accent)
+                    .padding(Padding::horizontal(1)),
+            )
+            .alignment(Alignment::Left);
+        if !zen {
+            f.render_widget(header, chunks[0]);
+        }
+
+        let visible_lines = Self::visible_line_count(
+            f.area().height as usize,
+            zen,
+            split.is_some(),
+            display.line_spacing,
+        );
+        let page = match split {
+            None => Self::render_view(
+                f,
+                chunks[1],
+                epub,
+                nav_state,
+                Some(floating_pane),
+                display,
+                highlights,
+                render_cache,
+                visible_lines,
+            ),
+            Some(split) => {
+                // A rule between the views stands in for their borders in zen mode
+                let areas = Layout::vertical([
+                    Constraint::Fill(1),
+                    Constraint::Length(u16::from(zen)),
+                    Constraint::Fill(1),
+                ])
+                .split(chunks[1]);
+                let rule = Block::default().borders(Borders::TOP).border_style(theme.muted);
+                f.render_widget(rule, areas[1]);
+
+                let (top, bottom) = if split.focus_top { (0, 2) } else { (2, 0) };
+                let other = &split.other;
+                Self::render_view(
+                    f,
+                    areas[bottom],
+                    epub,
+                    other,
+                    None,
+                    display,
+                    highlights,
+                    render_cache,
+                    visible_lines,
+                );
+                Self::render_view(
+                    f,
+                    areas[top],
+                    epub,
+                    nav_state,
+                    Some(floating_pane),
+                    display,
+                    highlights,
+                    render_cache,
+                    visible_lines,
+                )
+            }
+        };
+
+        if zen {
+            if let Some(notice) = notice {
+                f.render_widget(Paragraph::new(notice).alignment(Alignment::Center), chunks[2]);
+            }
+            Self::render_floating_pane(f, floating_pane, epub, display);
+            return;
+        }
+
+        // Modern footer with progress bar and icons
+        let chapter_progress = if epub.chapter_count() > 0 {
+            ((current_chapter + 1) as f64 / epub.chapter_count() as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        let footer_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // Progress bar
+                Constraint::Length(2), // Help text
+            ])
+            .split(chunks[2]);
+
+        // Progress bar
+        let mut progress_label =
+            format!("Chapter {}/{}", current_chapter + 1, epub.chapter_count());
+        if let Some((number, count)) = page {
+            progress_label.push_str(&format!(" · p. {number}/{count}"));
+        }
+        if let Some((number, count)) = nav_state.match_number {
+            progress_label.push_str(&format!(" · match {number} of {count}"));
+        }
+        // A filling bar would be redrawn with every chapter on e-ink
+        if display.profile.animated() {
+            let progress = Gauge::default()
+                .block(Block::default())
+                .gauge_style(theme.progress)
+                .percent(chapter_progress as u16)
+                .label(progress_label);
+            f.render_widget(progress, footer_chunks[0]);
+        } else {
+            progress_label.push_str(&format!(" · {}%", chapter_progress as u16));
+            let progress = Paragraph::new(progress_label).alignment(Alignment::Center);
+            f.render_widget(progress, footer_chunks[0]);
+        }
+
+        // Help text with icons, replaced by a link preview or status message
+        let help_text = if let Some(notice) = notice {
+            vec![notice]
+        } else {
+            vec![
+            Line::from(vec![
+                Span::styled(" q", theme.danger.add_modifier(Modifier::BOLD)),
+                Span::styled(":quit ", theme.muted),
+                Span::styled("↑↓", theme.accent),
+                Span::styled(":scroll ", theme.muted),
+                Span::styled("←→", theme.confirm),
+                Span::styled(":chapter ", theme.muted),
+                Span::styled("⎵", theme.warning),
+                Span::styled(":page ", theme.muted),
+                Span::styled("/", theme.special),
+                Span::styled(":search ", theme.muted),
+                Span::styled("-", theme.border),
+                Span::styled(":contents ", theme.muted),
+                Span::styled("i", theme.secondary),
+                Span::styled(":info", theme.muted),
+            ]),
+            ]
+        };
+        let footer = Paragraph::new(help_text)
+            .block(
+                Block::default()
+                    .borders(Borders::TOP | Borders::LEFT | Borders::RIGHT)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.muted)
+            )
+            .alignment(Alignment::Center);
+        f.render_widget(footer, footer_chunks[1]);
+
+        Self::render_floating_pane(f, floating_pane, epub, display);
+    }
+
+    /// Draw one view of the book into `area`, returning the page number and
+    /// count in paged mode. `floating_pane` is only given for the focused
+    /// view, which is the one that shows link hints.
+    #[allow(clippy::too_many_arguments)]
+    fn render_view(
+        f: &mut Frame,
+        area: Rect,
+        epub: &EpubReader,
+        nav_state: &NavigationState,
+        floating_pane: Option<&FloatingPane>,
+        display: &DisplayOptions,
+        highlights: &[Highlight],
+        render_cache: &RenderCache,
+        mut visible_lines: usize,
+    ) -> Option<(usize, usize)> {
+        let scroll_offset = nav_state.scroll_offset;
+        let theme = &display.theme;
+        let zen = display.zen;
+        let chapter = epub.get_chapter(nav_state.current_chapter).ok()?;
+        let mut page = None;
+        let source = nav_state
+            .show_source
+            .then(|| render_cache.source(epub, nav_state.current_chapter))
+            .flatten();
+        let text = source.as_deref().unwrap_or(&chapter.content);
+        let total_lines = match &source {
+            Some(html) => html.lines().count(),
+            None => chapter.line_count(),
+        };
+        if nav_state.paged {
+            let starts = convert::page_starts(text, visible_lines);
+            let index = Self::page_index(&starts, scroll_offset);
+            let end = starts.get(index + 1).copied().unwrap_or(total_lines);
+            visible_lines = visible_lines.min(end.saturating_sub(scroll_offset));
+            page = Some((index + 1, starts.len()));
+        }
+        let lines = match &source {
+            Some(html) => Self::source_lines(html, nav_state, visible_lines, theme),
+            None => {
+                let book_rtl = epub.language.as_deref().is_some_and(bidi::is_rtl_language);
+                let highlights: Vec<&Highlight> = highlights
+                    .iter()
+                    .filter(|highlight| highlight.chapter == nav_state.current_chapter)
+                    .collect();
+                let key = RenderKey {
+                    chapter: nav_state.current_chapter,
+                    width: epub.text_width(),
+                    theme: theme.name.clone(),
+                    query: nav_state.search_query.clone(),
+                    block_offset: nav_state.block_offset,
+                    highlights: highlights.iter().map(|h| (h.start, h.end)).collect(),
+                };
+                let visible = scroll_offset..scroll_offset + visible_lines;
+                let styled = render_cache.lines(key, chapter.line_count(), visible, |lines| {
+                    Self::style_chapter_lines(&chapter, nav_state, lines, &highlights, theme)
+                });
+                Self::mark_chapter_lines(&chapter, nav_state, styled, book_rtl, theme)
+            }
+        };
+        let spacing = display.line_spacing;
+        let lines = spacing.spread(lines);
+
+        let available_width =
+            (area.width as usize).saturating_sub(Self::horizontal_chrome(zen));
+        let margin = (available_width.saturating_sub(epub.text_width()) / 2) as u16;
+
+        let chapter_title = match source {
+            Some(_) => format!("│ {} · XHTML ", chapter.title),
+            None => format!("│ {} ", chapter.title),
+        };
+        // The view that isn't focused has a dimmer border
+        let border = if floating_pane.is_some() { theme.border } else { theme.muted };
+        let block = if zen {
+            Block::default().padding(Padding::horizontal(margin))
+        } else {
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(border)
+                .title(chapter_title)
+                .title_style(theme.secondary.add_modifier(Modifier::BOLD))
+                .padding(Padding::new(2 + margin, 1 + margin, 0, 0))
+        };
+        let content = Paragraph::new(lines).block(block).style(theme.text);
+        f.render_widget(content, area);
+
+        let text_area = if zen {
+            area.inner(Margin::new(margin, 0))
+        } else {
+            Rect {
+                x: area.x + 3 + margin,
+                y: area.y + 1,
+                width: area.width.saturating_sub(5 + 2 * margin),
+                height: area.height.saturating_sub(2),
+            }
+        };
+        if let Some(FloatingPane::LinkHints { hints, typed }) = floating_pane
+            && !nav_state.show_source
+        {
+            Self::render_link_hints(
+                f, text_area, &chapter, nav_state, spacing, hints, typed, theme,
+            );
+        }
+
+        // Highlights with notes are marked in the gutter beside their first
+        // line, where there's room for one
+        if !nav_state.show_source && text_area.x >= area.x + 2 {
+            let noted: Vec<&Highlight> = highlights
+                .iter()
+                .filter(|highlight| {
+                    highlight.chapter == nav_state.current_chapter && highlight.note.is_some()
+                })
+                .collect();
+            if !noted.is_empty() {
+                let offsets = Self::text_offsets(&chapter.content);
+                for highlight in noted {
+                    let line = Self::line_at_offset(&offsets, highlight.start);
+                    let row = line.wrapping_sub(scroll_offset);
+                    if row >= visible_lines {
+                        continue;
+                    }
+                    let row = spacing.row_of(row);
+                    if row < text_area.height as usize {
+                        f.buffer_mut().set_string(
+                            text_area.x - 2,
+                            text_area.y + row as u16,
+                            NOTE_MARKER,
+                            theme.accent,
+                        );
+                    }
+                }
+            }
+        }
+
+        // Render scrollbar indicator
+        if total_lines > visible_lines && !zen {
+            let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(Some("↑"))
+                .end_symbol(Some("↓"))
+                .track_symbol(Some("│"))
+                .thumb_symbol("█")
+                .style(theme.accent);
+
+            let mut scrollbar_state = ScrollbarState::new(total_lines.saturating_sub(visible_lines))
+                .position(scroll_offset);
+
+            let scrollbar_area = Rect {
+                x: area.x + area.width.saturating_sub(1),
+                y: area.y + 1,
+                width: 1,
+                height: area.height.saturating_sub(2),
+            };
+
+            f.render_stateful_widget(scrollbar, scrollbar_area, &mut scrollbar_state);
+        }
+        page
+    }
+
+    // A link preview or status message, shown in place of the key help
+    fn notice_line<'a>(
+        floating_pane: &'a FloatingPane,
+        status_message: &'a Option<String>,
+        theme: &Theme,
+    ) -> Option<Line<'a>> {
+        match floating_pane {
+            FloatingPane::OpenUrl { url } => Some(Line::from(vec![
+                Span::styled(" Open ", theme.muted),
+                Span::styled(url.as_str(), theme.secondary.add_modifier(Modifier::UNDERLINED)),
+                Span::styled(" in browser? ", theme.muted),
+                Span::styled("⏎", theme.confirm.add_modifier(Modifier::BOLD)),
+                Span::styled(":open ", theme.muted),
+                Span::styled("Esc", theme.danger.add_modifier(Modifier::BOLD)),
+                Span::styled(":cancel", theme.muted),
+            ])),
+            FloatingPane::ConfirmQuit => Some(Line::from(vec![
+                Span::styled(" Quit? ", theme.muted),
+                Span::styled("y", theme.confirm.add_modifier(Modifier::BOLD)),
+                Span::styled(":quit ", theme.muted),
+                Span::styled("n", theme.danger.add_modifier(Modifier::BOLD)),
+                Span::styled(":keep reading", theme.muted),
+            ])),
+            FloatingPane::Command { input } => Some(Line::from(vec![
+                Span::styled(":", theme.accent.add_modifier(Modifier::BOLD)),
+                Span::styled(input.as_str(), theme.text),
+                Span::styled("█", theme.accent),
+            ])),
+            _ => status_message
+                .as_ref()
+                .map(|message| Line::from(Span::styled(message.as_str(), theme.warning))),
+        }
+    }
+
+    // Text lines that fit in each view, with the blank rows between them;
+    // split views share the space, and have a rule between them in zen mode
+    // instead of borders
+    fn visible_line_count(
+        terminal_height: usize,
+        zen: bool,
+        split: bool,
+        spacing: LineSpacing,
+    ) -> usize {
+        let rows = match (zen, split) {
+            (true, false) => terminal_height,
+            (true, true) => terminal_height.saturating_sub(1) / 2,
+            (false, false) => terminal_height.saturating_sub(UI_RESERVED_HEIGHT),
+            (false, true) => {
+                let content = terminal_height.saturating_sub(HEADER_HEIGHT + FOOTER_HEIGHT);
+                (content / 2).saturating_sub(CONTENT_VERTICAL_CHROME)
+            }
+        };
+        spacing.lines_in(rows)
+    }
+
+    fn horizontal_chrome(zen: bool) -> usize {
+        if zen { 0 } else { CONTENT_HORIZONTAL_CHROME }
+    }
+
+    // Lines `lines` of the converted chapter text, with blocks shifted
+    // sideways, links underlined and highlighted passages and search
+    // matches marked
+    fn style_chapter_lines(
+        chapter: &Chapter,
+        nav_state: &NavigationState,
+        lines: std::ops::Range<usize>,
+        highlights: &[&Highlight],
+        theme: &Theme,
+    ) -> Vec<Line<'static>> {
+        let offsets = if highlights.is_empty() {
+            Vec::new()
+        } else {
+            Self::text_offsets(&chapter.content)
+        };
+        let mut code_lines = Self::highlight_code(chapter, lines.start, lines.len(), theme);
+        chapter
+            .lines_from(lines.start)
+            .zip(lines.clone())
+            .map(|(line, index)| {
+                let in_block = chapter.block_at(index).map(|block| &block.kind);
+                let styled = match in_block {
+                    Some(BlockKind::Table) => {
+                        Self::shift_line(Line::from(line.to_string()), nav_state.block_offset)
+                    }
+                    Some(BlockKind::Code { .. }) => {
+                        let styled = code_lines.remove(&index).unwrap_or_else(|| {
+                            Line::styled(line.to_string(), theme.code)
+                        });
+                        Self::shift_line(styled, nav_state.block_offset)
+                    }
+                    Some(BlockKind::Image) => Line::from(line.to_string()),
+                    None => Self::style_line(chapter, index, line, theme),
+                };
+
+                let styled = chapter
+                    .links
+                    .iter()
+                    .filter(|link| link.line == index)
+                    .fold(styled, |styled, link| {
+                        let display_column = |column| {
+                            Self::link_display_column(
+                                line,
+                                column,
+                                in_block.is_some(),
+                                nav_state.block_offset,
+                            )
+                        };
+                        let columns =
+                            display_column(link.columns.start)..display_column(link.columns.end);
+                        Self::underline_columns(styled, columns)
+                    });
+                let styled = highlights.iter().fold(styled, |styled, highlight| {
+                    let line_start = offsets[index];
+                    if highlight.end <= line_start || highlight.start >= offsets[index + 1] {
+                        return styled;
+                    }
+                    let text: String =
+                        styled.spans.iter().map(|span| span.content.as_ref()).collect();
+                    let characters = highlight.start.saturating_sub(line_start)
+                        ..highlight.end - line_start;
+                    let range = Self::character_range(&text, characters);
+                    Self::highlight_ranges(styled, &[range], theme.annotation)
+                });
+                match &nav_state.search_query {
+                    Some(query) => Self::highlight_matches(styled, query, theme),
+                    None => styled,
+                }
+            })
+            .collect()
+    }
+
+    // Styled lines from the top of the screen with the selection or the
+    // passage being narrated marked, right-to-left text reordered, and
+    // lines aligned as the book's styles ask
+    fn mark_chapter_lines(
+        chapter: &Chapter,
+        nav_state: &NavigationState,
+        styled: Vec<Line<'static>>,
+        book_rtl: bool,
+        theme: &Theme,
+    ) -> Vec<Line<'static>> {
+        let scroll_offset = nav_state.scroll_offset;
+        styled
+            .into_iter()
+            .zip(chapter.lines_from(scroll_offset))
+            .zip(scroll_offset..)
+            .map(|((styled, line), index)| {
+                let selected = nav_state
+                    .visual
+                    .map(|(first, last)| (first.min(last), first.max(last)))
+                    .or(nav_state.narrated)
+                    .is_some_and(|(first, last)| (first..=last).contains(&index));
+                let styled = if selected {
+                    let whole = 0..styled.spans.iter().map(|span| span.content.len()).sum();
+                    Self::highlight_ranges(styled, &[whole], theme.selection)
+                } else {
+                    styled
+                };
+
+                // Hebrew and Arabic are stored in logical order; put them in
+                // display order, taking the direction from the whole paragraph
+                if chapter.block_at(index).is_some() {
+                    return styled;
+                }
+                let styled = if book_rtl || bidi::has_rtl(line) {
+                    let is_blank = |line: &str| line.trim().is_empty();
+                    let start = (0..index)
+                        .rev()
+                        .find(|&before| chapter.line(before).is_some_and(is_blank))
+                        .map_or(0, |blank| blank + 1);
+                    let paragraph = chapter.lines_from(start).take_while(|line| !is_blank(line));
+                    let rtl = bidi::paragraph_is_rtl(paragraph).unwrap_or(book_rtl);
+                    bidi::reorder_line(styled, rtl)
+                } else {
+                    styled
+                };
+                match chapter.alignment(index) {
+                    Some(TextAlign::Center) => styled.centered(),
+                    Some(TextAlign::Right) => styled.right_aligned(),
+                    _ => styled,
+                }
+            })
+            .collect()
+    }
+
+    // Raw XHTML isn't wrapped, so long lines scroll sideways like tables
+    fn source_lines(
+        html: &str,
+        nav_state: &NavigationState,
+        visible_lines: usize,
+        theme: &Theme,
+    ) -> Vec<Line<'static>> {
+        html.lines()
+            .skip(nav_state.scroll_offset)
+            .take(visible_lines)
+            .map(|line| {
+                let line = Line::styled(line.replace('\t', "    "), theme.code);
+                Self::shift_line(line, nav_state.block_offset)
+            })
+            .collect()
+    }
+
+    // Tables and code keep their natural width, so wide lines are shifted
+    // rather than wrapped
+    fn shift_line(line: Line<'static>, offset: usize) -> Line<'static> {
+        let mut remaining = offset;
+        let spans: Vec<Span> = line
+            .spans
+            .into_iter()
+            .filter_map(|span| {
+                let width = span.content.width();
+                if remaining >= width {
+                    remaining -= width;
+                    return None;
+                }
+                let mut content = String::new();
+                for grapheme in span.content.graphemes(true) {
+                    let width = grapheme.width();
+                    if remaining == 0 {
+                        content.push_str(grapheme);
+                    } else if width <= remaining {
+                        remaining -= width;
+                    } else {
+                        // A wide character cut in half by the left edge
+                        content.push_str(&" ".repeat(width - remaining));
+                        remaining = 0;
+                    }
+                }
+                Some(Span::styled(content, span.style))
+            })
+            .collect();
+        Line::from(spans).style(line.style)
+    }
+
+    // Screen column of a character column, once a block has been scrolled
+    // sideways. Double-width characters take two screen columns.
+    fn link_display_column(line: &str, column: usize, in_block: bool, block_offset: usize) -> usize {
+        let before: String = line.chars().take(column).collect();
+        if in_block {
+            before.width().saturating_sub(block_offset)
+        } else {
+            before.width()
+        }
+    }
+
+    fn underline_columns(line: Line<'static>, columns: std::ops::Range<usize>) -> Line<'static> {
+        let mut position = 0;
+        let mut spans = Vec::with_capacity(line.spans.len() + 2);
+        for span in line.spans {
+            let width = span.content.width();
+            let span_columns = position..position + width;
+            position += width;
+            if span_columns.end <= columns.start || span_columns.start >= columns.end {
+                spans.push(span);
+                continue;
+            }
+
+            let split_at = |column: usize| {
+                let target = column.clamp(span_columns.start, span_columns.end) - span_columns.start;
+                let mut width = 0;
+                for (byte, grapheme) in span.content.grapheme_indices(true) {
+                    if width >= target {
+                        return byte;
+                    }
+                    width += grapheme.width();
+                }
+                span.content.len()
+            };
+            let (start, end) = (split_at(columns.start), split_at(columns.end));
+            let content = span.content.to_string();
+            if start > 0 {
+                spans.push(Span::styled(content[..start].to_string(), span.style));
+            }
+            spans.push(Span::styled(
+                content[start..end].to_string(),
+                span.style.add_modifier(Modifier::UNDERLINED),
+            ));
+            if end < content.len() {
+                spans.push(Span::styled(content[end..].to_string(), span.style));
+            }
+        }
+        Line::from(spans).style(line.style)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_link_hints(
+        f: &mut Frame,
+        text_area: Rect,
+        chapter: &Chapter,
+        nav_state: &NavigationState,
+        spacing: LineSpacing,
+        hints: &[LinkHint],
+        typed: &str,
+        theme: &Theme,
+    ) {
+        for hint in hints.iter().filter(|hint| hint.label.starts_with(typed)) {
+            let Some(row) = hint.link.line.checked_sub(nav_state.scroll_offset) else {
+                continue;
+            };
+            let row = spacing.row_of(row);
+            let line = chapter.line(hint.link.line).unwrap_or_default();
+            let column = Self::link_display_column(
+                line,
+                hint.link.columns.start,
+                chapter.block_at(hint.link.line).is_some(),
+                nav_state.block_offset,
+            );
+            let room = (text_area.width as usize).saturating_sub(line.width());
+            let column = column
+                + match chapter.alignment(hint.link.line) {
+                    Some(TextAlign::Center) => room / 2,
+                    Some(TextAlign::Right) => room,
+                    _ => 0,
+                };
+            let label_width = hint.label.width() as u16;
+            if row as u16 >= text_area.height || column as u16 + label_width > text_area.width {
+                continue;
+            }
+
+            let label = Paragraph::new(hint.label.as_str()).style(
+                theme.highlight.add_modifier(Modifier::BOLD),
+            );
+            let area = Rect {
+                x: text_area.x + column as u16,
+                y: text_area.y + row as u16,
+                width: label_width,
+                height: 1,
+            };
+            f.render_widget(label, area);
+        }
+    }
+
+    #[cfg(feature = "highlight")]
+    fn highlight_code(
+        chapter: &Chapter,
+        first_line: usize,
+        line_count: usize,
+        theme: &Theme,
+    ) -> HashMap<usize, Line<'static>> {
+        let visible = first_line..first_line + line_count;
+        let mut highlighted = HashMap::new();
+        if theme.syntax_theme.is_empty() {
+            return highlighted;
+        }
+
+        for block in &chapter.blocks {
+            let BlockKind::Code {
+                language: Some(language),
+            } = &block.kind
+            else {
+                continue;
+            };
+            if block.lines.end <= visible.start || block.lines.start >= visible.end {
+                continue;
+            }
+
+            // Highlighting is stateful, so start from the top of the listing
+            let end = block.lines.end.min(visible.end);
+            let lines: Vec<&str> =
+                chapter.lines_from(block.lines.start).take(end - block.lines.start).collect();
+            if let Some(styled) = highlight::highlight(language, &lines, &theme.syntax_theme) {
+                highlighted.extend(
+                    (block.lines.start..end)
+                        .zip(styled)
+                        .filter(|(index, _)| visible.contains(index)),
+                );
+            }
+        }
+
+        highlighted
+    }
+
+    #[cfg(not(feature = "highlight"))]
+    fn highlight_code(_: &Chapter, _: usize, _: usize, _: &Theme) -> HashMap<usize, Line<'static>> {
+        HashMap::new()
+    }
+
+    // Headings in their level's style and list bullets dimmed, with the
+    // emphasis, strong text, code and alt text the chapter marks on the line
+    fn style_line(chapter: &Chapter, index: usize, line: &str, theme: &Theme) -> Line<'static> {
+        let base = match chapter.heading_level(index) {
+            Some(level) => theme.heading(level).add_modifier(Modifier::BOLD),
+            None => theme.text,
+        };
+        let spans = chapter.spans_on(index);
+        let trimmed = line.trim_start();
+        let bullet_end = trimmed
+            .chars()
+            .next()
+            .filter(|c| chapter.heading_level(index).is_none() && LIST_BULLETS.contains(c))
+            .map(|_| line.chars().count() - trimmed.chars().count() + 1);
+
+        let columns = line.chars().count();
+        let mut boundaries: Vec<usize> = spans
+            .iter()
+            .flat_map(|span| [span.columns.start, span.columns.end])
+            .chain(bullet_end)
+            .chain([0, columns])
+            .filter(|&column| column <= columns)
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        if boundaries.len() < 2 {
+            return Line::from(Span::styled(line.to_string(), base));
+        }
+
+        let byte_at =
+            |column: usize| line.char_indices().nth(column).map_or(line.len(), |(byte, _)| byte);
+        let styled: Vec<Span> = boundaries
+            .windows(2)
+            .map(|pair| {
+                let (start, end) = (pair[0], pair[1]);
+                let style = if bullet_end.is_some_and(|bullet| end <= bullet) {
+                    theme.accent
+                } else {
+                    spans
+                        .iter()
+                        .filter(|span| span.columns.start <= start && end <= span.columns.end)
+                        .fold(base, |style, span| match span.style {
+                            SpanStyle::Emphasis => style.add_modifier(Modifier::ITALIC),
+                            SpanStyle::Strong => style.add_modifier(Modifier::BOLD),
+                            SpanStyle::Code => style.patch(theme.code),
+                            SpanStyle::Image => style.patch(theme.muted),
+                        })
+                };
+                Span::styled(line[byte_at(start)..byte_at(end)].to_string(), style)
+            })
+            .collect();
+        Line::from(styled)
+    }
+
+    // Mark every occurrence of the query in a styled line, keeping the
+    // styles of the text around and under each match
+    fn highlight_matches(line: Line<'static>, query: &str, theme: &Theme) -> Line<'static> {
+        let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+        let matches = search::Query::parse(query).find(&text);
+        Self::highlight_ranges(line, &matches, theme.highlight)
+    }
+
+    // Patch `style` onto the byte ranges `matches` of the line's text,
+    // splitting spans where a match starts or ends inside one
+    fn highlight_ranges(
+        line: Line<'static>,
+        matches: &[std::ops::Range<usize>],
+        style: Style,
+    ) -> Line<'static> {
+        if matches.is_empty() {
+            return line;
+        }
+
+        let mut spans = Vec::new();
+        let mut span_start = 0;
+        for span in &line.spans {
+            let content = span.content.as_ref();
+            let span_end = span_start + content.len();
+            let mut cut = span_start;
+            let overlapping = matches
+                .iter()
+                .filter(|m| !m.is_empty() && m.start < span_end && m.end > span_start);
+            for found in overlapping {
+                let start = found.start.max(span_start);
+                let end = found.end.min(span_end);
+                if start > cut {
+                    let before = &content[cut - span_start..start - span_start];
+                    spans.push(Span::styled(before.to_string(), span.style));
+                }
+                let matched = &content[start - span_start..end - span_start];
+                spans.push(Span::styled(matched.to_string(), span.style.patch(style)));
+                cut = end;
+            }
+            if cut < span_end {
+                spans.push(Span::styled(content[cut - span_start..].to_string(), span.style));
+            }
+            span_start = span_end;
+        }
+        Line { spans, ..line }
+    }
+
+    // Non-whitespace characters before each line of the text, and after the
+    // last, which is how highlights are placed
+    fn text_offsets(text: &str) -> Vec<usize> {
+        let mut offsets = vec![0];
+        for line in text.lines() {
+            let characters = line.chars().filter(|c| !c.is_whitespace()).count();
+            offsets.push(offsets[offsets.len() - 1] + characters);
+        }
+        offsets
+    }
+
+    // The line holding the non-whitespace character `offset` characters in
+    fn line_at_offset(offsets: &[usize], offset: usize) -> usize {
+        offsets.partition_point(|&start| start <= offset).saturating_sub(1)
+    }
+
+    // Byte range of a line from the first to the last of the numbered
+    // non-whitespace characters, counting from zero
+    fn character_range(
+        text: &str,
+        characters: std::ops::Range<usize>,
+    ) -> std::ops::Range<usize> {
+        let positions: Vec<(usize, char)> =
+            text.char_indices().filter(|(_, c)| !c.is_whitespace()).collect();
+        let start = positions.get(characters.start).map_or(text.len(), |&(byte, _)| byte);
+        let end = positions[..characters.end.min(positions.len())]
+            .last()
+            .map_or(start, |&(byte, c)| byte + c.len_utf8());
+        start..end.max(start)
+    }
+
+    fn update_text_width(&mut self, terminal_width: usize) {
+        let mut width = terminal_width.saturating_sub(Self::horizontal_chrome(self.display.zen));
+        if let Some(max_width) = self.display.max_width {
+            width = width.min(max_width);
+        }
+        // The book wraps no narrower than this, so narrower terminals
+        // don't change how it's wrapped
+        let width = width.max(MIN_TEXT_WIDTH);
+        if width == self.epub.text_width() {
+            return;
+        }
+
+        // Keep both views at the same relative position when the chapters re-wrap
+        let old_lines = self.current_chapter_line_count();
+        let other_old_lines = self.with_other_view(|app| app.current_chapter_line_count());
+        self.epub.set_text_width(width);
+        self.rescale_scroll(old_lines);
+        if let Some(old_lines) = other_old_lines {
+            self.with_other_view(|app| app.rescale_scroll(old_lines));
+        }
+    }
+
+    fn rescale_scroll(&mut self, old_lines: usize) {
+        let new_lines = self.current_chapter_line_count();
+        if let Some(offset) = (self.nav_state.scroll_offset * new_lines).checked_div(old_lines) {
+            self.nav_state.scroll_offset = offset;
+        }
+        self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+    }
+
+    // Run `action` with the unfocused view swapped in, if the screen is split
+    fn with_other_view<R>(&mut self, action: impl FnOnce(&mut Self) -> R) -> Option<R> {
+        let mut split = self.split.take()?;
+        std::mem::swap(&mut self.nav_state, &mut split.other);
+        let result = action(self);
+        std::mem::swap(&mut self.nav_state, &mut split.other);
+        self.split = Some(split);
+        Some(result)
+    }
+
+    fn handle_window_key(&mut self, key: KeyCode) {
+        match key {
+            // Open a second view of the same place, above, and focus it
+            KeyCode::Char('s') if self.split.is_none() => {
+                self.split = Some(SplitView {
+                    other: self.nav_state.clone(),
+                    focus_top: true,
+                });
+            }
+            KeyCode::Char('w') | KeyCode::Tab | KeyCode::Up | KeyCode::Down => {
+                if let Some(split) = &mut self.split {
+                    std::mem::swap(&mut self.nav_state, &mut split.other);
+                    split.focus_top = !split.focus_top;
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Char('c') => {
+                if let Some(split) = self.split.take() {
+                    self.nav_state = split.other;
+                }
+            }
+            KeyCode::Char('o') => self.split = None,
+            _ => return,
+        }
+        self.clamp_views();
+    }
+
+    // Page sizes change when the screen is split or zen mode toggles
+    fn clamp_views(&mut self) {
+        self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+        self.with_other_view(|app| app.clamp_scroll_to_limits(app.nav_state.current_chapter));
+    }
+
+    fn current_chapter_line_count(&self) -> usize {
+        self.displayed_line_count(self.nav_state.current_chapter)
+    }
+
+    // Lines in whichever of the converted text or the XHTML is on screen
+    fn displayed_line_count(&self, chapter_index: usize) -> usize {
+        if self.nav_state.show_source {
+            return self
+                .render_cache
+                .source(&self.epub, chapter_index)
+                .map_or(0, |html| html.lines().count());
+        }
+        self.epub
+            .get_chapter(chapter_index)
+            .map(|chapter| chapter.line_count())
+            .unwrap_or(0)
+    }
+
+    fn get_page_size(&self) -> usize {
+        Self::visible_line_count(
+            self.terminal_height,
+            self.display.zen,
+            self.split.is_some(),
+            self.display.line_spacing,
+        )
+    }
+
+    fn get_max_scroll_for_chapter(&self, chapter_index: usize) -> usize {
+        if self.nav_state.paged {
+            return self.page_starts(chapter_index).last().copied().unwrap_or(0);
+        }
+        self.displayed_line_count(chapter_index)
+            .saturating_sub(self.get_page_size())
+    }
+
+    // Where each page begins in whichever text is on screen
+    fn page_starts(&self, chapter_index: usize) -> Vec<usize> {
+        let page_size = self.get_page_size();
+        let starts = if self.nav_state.show_source {
+            self.render_cache
+                .source(&self.epub, chapter_index)
+                .map(|html| convert::page_starts(&html, page_size))
+        } else {
+            self.epub
+                .get_chapter(chapter_index)
+                .ok()
+                .map(|chapter| convert::page_starts(&chapter.content, page_size))
+        };
+        starts.unwrap_or_else(|| vec![0])
+    }
+
+    // Index of the page holding `line`
+    fn page_index(starts: &[usize], line: usize) -> usize {
+        starts.partition_point(|&start| start <= line).saturating_sub(1)
+    }
+
+    // The lines currently on screen
+    fn visible_line_range(&self) -> std::ops::Range<usize> {
+        let start = self.nav_state.scroll_offset;
+        let mut end = start + self.get_page_size();
+        if self.nav_state.paged {
+            let starts = self.page_starts(self.nav_state.current_chapter);
+            if let Some(&next) = starts.get(Self::page_index(&starts, start) + 1) {
+                end = end.min(next);
+            }
+        }
+        start..end
+    }
+
+    fn get_current_chapter_max_scroll(&self) -> usize {
+        self.get_max_scroll_for_chapter(self.nav_state.current_chapter)
+    }
+
+    fn clamp_scroll_to_limits(&mut self, chapter_index: usize) {
+        if self.nav_state.paged {
+            // Snap to the start of the page the line is on
+            let starts = self.page_starts(chapter_index);
+            self.nav_state.scroll_offset =
+                starts[Self::page_index(&starts, self.nav_state.scroll_offset)];
+            return;
+        }
+        let max_scroll = self.get_max_scroll_for_chapter(chapter_index);
+        self.nav_state.scroll_offset = self.nav_state.scroll_offset.min(max_scroll);
+    }
+
+    fn scroll_down(&mut self) {
+        if self.nav_state.paged {
+            return self.page_down();
+        }
+        let max_scroll = self.get_current_chapter_max_scroll();
+        if self.nav_state.scroll_offset < max_scroll {
+            self.nav_state.scroll_offset =
+                (self.nav_state.scroll_offset + self.display.scroll_lines).min(max_scroll);
+        }
+    }
+
+    fn scroll_up(&mut self) {
+        if self.nav_state.paged {
+            return self.page_up();
+        }
+        self.nav_state.scroll_offset =
+            self.nav_state.scroll_offset.saturating_sub(self.display.scroll_lines);
+    }
+
+    fn page_down(&mut self) {
+        if self.nav_state.paged {
+            let starts = self.page_starts(self.nav_state.current_chapter);
+            let index = Self::page_index(&starts, self.nav_state.scroll_offset);
+            if let Some(&next) = starts.get(index + 1) {
+                self.nav_state.scroll_offset = next;
+            }
+            return;
+        }
+        let page_size = self.get_page_size();
+        let max_scroll = self.get_current_chapter_max_scroll();
+        self.nav_state.scroll_offset = (self.nav_state.scroll_offset + page_size).min(max_scroll);
+    }
+
+    fn page_up(&mut self) {
+        if self.nav_state.paged {
+            let starts = self.page_starts(self.nav_state.current_chapter);
+            let index = Self::page_index(&starts, self.nav_state.scroll_offset);
+            self.nav_state.scroll_offset = starts[index.saturating_sub(1)];
+            return;
+        }
+        let page_size = self.get_page_size();
+        self.nav_state.scroll_offset = self.nav_state.scroll_offset.saturating_sub(page_size);
+    }
+
+    fn scroll_blocks_right(&mut self) {
+        let current_chapter = self.nav_state.current_chapter;
+        let widest = if self.nav_state.show_source {
+            self.render_cache
+                .source(&self.epub, current_chapter)
+                .map_or(0, |html| {
+                    html.lines()
+                        .map(|line| line.replace('\t', "    ").width())
+                        .max()
+                        .unwrap_or(0)
+                })
+        } else {
+            self.widest_block_line(current_chapter)
+        };
+        let max_offset = widest.saturating_sub(self.epub.text_width());
+        self.nav_state.block_offset = (self.nav_state.block_offset + TABLE_SCROLL_STEP).min(max_offset);
+    }
+
+    fn widest_block_line(&self, chapter_index: usize) -> usize {
+        self.epub
+            .get_chapter(chapter_index)
+            .map(|chapter| {
+                chapter
+                    .content
+                    .lines()
+                    .enumerate()
+                    .filter(|(index, _)| chapter.block_at(*index).is_some())
+                    .map(|(_, line)| line.width())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .unwrap_or(0)
+    }
+
+    // Line numbers differ between the two views, so start from the top
+    fn toggle_source(&mut self) {
+        self.nav_state.show_source = !self.nav_state.show_source;
+        self.nav_state.reset_scroll();
+    }
+
+    /// Hide or show everything but the text. The text width follows on the
+    /// next redraw.
+    fn toggle_zen(&mut self) {
+        self.display.zen = !self.display.zen;
+        self.clamp_views();
+    }
+
+    /// Switch between scrolling and turning whole pages.
+    fn toggle_paged(&mut self) {
+        self.nav_state.paged = !self.nav_state.paged;
+        self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+    }
+
+    /// Swap between the light and dark themes, for when the guess at startup
+    /// was wrong or the terminal's background has changed.
+    fn toggle_theme(&mut self) {
+        self.display.theme = if self.display.theme.is_light() {
+            Theme::dark()
+        } else {
+            Theme::light()
+        };
+    }
+
+    /// Re-read the current theme, so edits to a theme file show up without
+    /// restarting.
+    fn reload_book(&mut self) {
+        self.status_message = Some(match self.reload() {
+            Ok(()) => "Reloaded the book".to_string(),
+            Err(e) => format!("Couldn't reload the book: {e}"),
+        });
+    }
+
+    fn reload_theme(&mut self) {
+        let name = self.display.theme.name.clone();
+        self.status_message = Some(match Theme::load(&name) {
+            Ok(theme) => {
+                self.display.theme = theme;
+                self.render_cache.clear();
+                format!("Reloaded theme {name}")
+            }
+            Err(e) => format!("Couldn't reload theme: {e}"),
+        });
+    }
+
+    fn scroll_blocks_left(&mut self) {
+        self.nav_state.block_offset = self.nav_state.block_offset.saturating_sub(TABLE_SCROLL_STEP);
+    }
+
+    fn next_chapter(&mut self) {
+        let next = (self.nav_state.current_chapter + 1..self.epub.chapter_count())
+            .find(|&i| !self.epub.is_skippable(i));
+        if let Some(next) = next {
+            self.step_to_chapter(next);
+        }
+    }
+
+    fn prev_chapter(&mut self) {
+        let prev = (0..self.nav_state.current_chapter)
+            .rev()
+            .find(|&i| !self.epub.is_skippable(i));
+        if let Some(prev) = prev {
+            self.step_to_chapter(prev);
+        }
+    }
+
+    // Leave the current chapter for a neighbouring one, returning to where
+    // that one was last left
+    fn step_to_chapter(&mut self, chapter: usize) {
+        let here = self.place();
+        self.nav_state.chapter_offsets.insert(here.chapter, here);
+        let place = self.nav_state.chapter_offsets.get(&chapter).copied();
+        self.go_to_place(place.unwrap_or(Place {
+            chapter,
+            line: 0,
+            lines: 0,
+        }));
+    }
+
+    fn place(&self) -> Place {
+        Place {
+            chapter: self.nav_state.current_chapter,
+            line: self.nav_state.scroll_offset,
+            lines: self.current_chapter_line_count(),
+        }
+    }
+
+    fn go_to_place(&mut self, place: Place) {
+        self.nav_state.current_chapter = place.chapter;
+        self.nav_state.reset_scroll();
+        self.nav_state.scroll_offset = place.line;
+        self.rescale_scroll(place.lines);
+    }
+
+    fn go_to_beginning(&mut self) {
+        self.nav_state.reset_scroll();
+    }
+
+    /// Feed a key to the sequence being typed. Returns whether the key was
+    /// taken, either as part of a sequence or dropped after a bad one.
+    // Do what a key is bound to, `count` times where that makes sense
+    fn run_action(&mut self, action: Action, count: Option<usize>) -> Result<(), UiError> {
+        let times = count.unwrap_or(1);
+        match action {
+            Action::ScrollDown => self.repeat(times, Self::scroll_down),
+            Action::ScrollUp => self.repeat(times, Self::scroll_up),
+            Action::PageDown => self.repeat(times, Self::page_down),
+            Action::PageUp => self.repeat(times, Self::page_up),
+            Action::NextChapter => self.repeat(times, Self::next_chapter),
+            Action::PreviousChapter => self.repeat(times, Self::prev_chapter),
+            Action::ChapterStart => self.go_to_beginning(),
+            Action::ChapterEnd => match count {
+                Some(percent) => self.go_to_percent(percent),
+                None => self.go_to_end(),
+            },
+            Action::FirstChapter | Action::LastChapter => {
+                let readable = |i: &usize| !self.epub.is_skippable(*i);
+                let chapter = match action {
+                    Action::FirstChapter => (0..self.epub.chapter_count()).find(readable),
+                    _ => (0..self.epub.chapter_count()).rev().find(readable),
+                };
+                if let Some(chapter) = chapter
+                    && chapter != self.nav_state.current_chapter
+                {
+                    self.record_jump();
+                    self.nav_state.current_chapter = chapter;
+                    self.nav_state.reset_scroll();
+                }
+            }
+            Action::NextHeading => self.repeat(times, Self::next_heading),
+            Action::PreviousHeading => self.repeat(times, Self::prev_heading),
+            Action::ScrollRight => (0..times).for_each(|_| self.scroll_blocks_right()),
+            Action::ScrollLeft => (0..times).for_each(|_| self.scroll_blocks_left()),
+            Action::Search => self.open_search_pane(),
+            Action::ClearSearch => self.nav_state.clear_search(),
+            Action::NextMatch => (0..times).for_each(|_| self.next_match(true)),
+            Action::PreviousMatch => (0..times).for_each(|_| self.next_match(false)),
+            Action::Command => {
+                self.floating_pane = FloatingPane::Command {
+                    input: String::new(),
+                };
+            }
+            Action::Contents => self.open_contents_pane(),
+            Action::Outline => self.open_outline_pane(),
+            Action::BookInfo => self.floating_pane = FloatingPane::BookInfo,
+            Action::Stats => {
+                self.floating_pane = FloatingPane::Stats {
+                    stats: self.reading_stats.clone(),
+                    session: self.timer.session().as_secs(),
+                };
+            }
+            Action::LinkHints => self.open_link_hints(),
+            Action::ToggleSource => self.toggle_source(),
+            Action::Visual => self.start_visual(),
+            Action::Highlights => self.open_annotations_pane(),
+            Action::Notes => self.open_notes_pane(),
+            Action::Books | Action::NextBook | Action::PreviousBook
+                if self.open_books.len() < 2 =>
+            {
+                self.status_message = Some("Only one book is open".to_string());
+            }
+            Action::Books => {
+                self.floating_pane = FloatingPane::Books {
+                    titles: self.open_books.clone(),
+                    selected_index: self.book_index,
+                };
+            }
+            Action::NextBook | Action::PreviousBook => {
+                let count = self.open_books.len();
+                let step = if action == Action::NextBook { 1 } else { count - 1 };
+                self.switch_to = Some((self.book_index + step) % count);
+            }
+            Action::CopyParagraph => self.copy_paragraph(),
+            Action::TranslateParagraph => self.translate_paragraph(),
+            Action::Narrate => self.toggle_narration(),
+            Action::Zen => self.toggle_zen(),
+            Action::Paged => self.toggle_paged(),
+            Action::ReloadBook => self.reload_book(),
+            Action::ReloadTheme => self.reload_theme(),
+            Action::ToggleTheme => self.toggle_theme(),
+            // Left to the run loop, which has the terminal to give back
+            Action::Suspend => self.suspend_requested.store(true, Ordering::Relaxed),
+            Action::Quit if self.display.confirm_quit => {
+                self.floating_pane = FloatingPane::ConfirmQuit;
+            }
+            Action::Quit | Action::QuitNow => self.quitting = true,
+            Action::JumpBack => self.jump_back(),
+            Action::JumpForward => self.jump_forward(),
+            Action::Window => {
+                self.awaiting_window_key = true;
+                self.status_message =
+                    Some("Window: s split · w switch · q close · o only".to_string());
+            }
+            Action::RecordKeys => self.toggle_recording(),
+        }
+        Ok(())
+    }
+
+    // Start noting down the keys typed, or stop and show them written as
+    // `--keys` takes them, leaving out the keys that stopped it
+    fn toggle_recording(&mut self) {
+        let Some(mut keys) = self.recording.take() else {
+            self.recording = Some(Vec::new());
+            self.status_message = Some("Recording keys".to_string());
+            return;
+        };
+        let stop = (1..=keys.len()).find(|&length| {
+            let typed = &keys[keys.len() - length..];
+            self.display.keymap.lookup(typed) == Binding::Action(Action::RecordKeys)
+        });
+        keys.truncate(keys.len() - stop.unwrap_or(0));
+        let keys: String = keys.iter().map(KeyChord::to_string).collect();
+        info!("Recorded keys: {}", keys);
+        self.status_message = Some(format!("Recorded: {keys}"));
+    }
+
+    // Move to a point part way through the chapter, as in `25G`
+    fn go_to_percent(&mut self, percent: usize) {
+        let lines = self.current_chapter_line_count();
+        self.nav_state.scroll_offset = lines * percent.min(100) / 100;
+        self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+    }
+
+    // Parse what was typed at the `:` prompt: `50%`, `goto 1200` (or just
+    // `1200`), `chapter 7`, `export notes.md`, `wiki Lisbon` and `define
+    // saudade`. Lines and chapters count from one.
+    fn parse_command(input: &str) -> Result<PromptCommand, String> {
+        let input = input.trim();
+        let number = |text: &str| match text.trim() {
+            "" => Err("Missing a number".to_string()),
+            text => text.parse::<usize>().map_err(|_| format!("Not a number: {text}")),
+        };
+        if let Some(percent) = input.strip_suffix('%') {
+            let percent = number(percent)?;
+            if percent > 100 {
+                return Err(format!("No such point in the book: {percent}%"));
+            }
+            return Ok(PromptCommand::BookPercent(percent));
+        }
+        let (name, argument) = match input.split_once(char::is_whitespace) {
+            Some(split) => split,
+            None if input.starts_with(|c: char| c.is_ascii_digit()) => ("", input),
+            None => (input, ""),
+        };
+        match name {
+            "" | "goto" => Ok(PromptCommand::Line(number(argument)?)),
+            "chapter" => Ok(PromptCommand::Chapter(number(argument)?)),
+            "export" => Ok(PromptCommand::Export(
+                Some(argument.trim()).filter(|path| !path.is_empty()).map(PathBuf::from),
+            )),
+            "wiki" | "define" if argument.trim().is_empty() => {
+                Err(format!("Missing what to look up, as in :{name} Lisbon"))
+            }
+            "wiki" => Ok(PromptCommand::Lookup(lookup::Source::Wikipedia, argument.to_string())),
+            "define" => {
+                Ok(PromptCommand::Lookup(lookup::Source::Wiktionary, argument.to_string()))
+            }
+            _ => Err(format!("Unknown command: {name}")),
+        }
+    }
+
+    fn run_command(&mut self, input: &str) -> Result<(), String> {
+        if input.trim().is_empty() {
+            return Ok(());
+        }
+        match Self::parse_command(input)? {
+            PromptCommand::BookPercent(percent) => self.go_to_book_percent(percent),
+            PromptCommand::Line(line) => {
+                let lines = self.current_chapter_line_count();
+                if !(1..=lines).contains(&line) {
+                    return Err(format!("No line {line}: this chapter has {lines}"));
+                }
+                self.record_jump();
+                self.nav_state.scroll_offset = line - 1;
+                self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+            }
+            PromptCommand::Chapter(number) => self.go_to_chapter(number)?,
+            PromptCommand::Export(path) => {
+                let highlights = self
+                    .progress
+                    .book(&self.book_key)
+                    .map(|book| book.highlights.as_slice())
+                    .unwrap_or_default();
+                if highlights.is_empty() {
+                    return Err("No highlights to export".to_string());
+                }
+                let path = path.unwrap_or_else(|| self.epub.path().with_extension("md"));
+                let format = annotations::Format::for_path(&path);
+                std::fs::write(&path, annotations::export(&self.epub, highlights, format))
+                    .map_err(|e| format!("Couldn't write {}: {e}", path.display()))?;
+                self.status_message = Some(format!("Highlights written to {}", path.display()));
+            }
+            PromptCommand::Lookup(source, phrase) => self.start_lookup(source, &phrase),
+        }
+        Ok(())
+    }
+
+    /// Go to the start of chapter `number`, counting from one.
+    pub fn go_to_chapter(&mut self, number: usize) -> Result<(), String> {
+        let chapters = self.epub.chapter_count();
+        if !(1..=chapters).contains(&number) {
+            return Err(format!("No chapter {number}: the book has {chapters}"));
+        }
+        self.record_jump();
+        self.nav_state.current_chapter = number - 1;
+        self.nav_state.reset_scroll();
+        Ok(())
+    }
+
+    /// Go to a point part way through the whole book, weighing chapters by
+    /// the size of their XHTML since only the current one is laid out.
+    pub fn go_to_book_percent(&mut self, percent: usize) {
+        let sizes = self.epub.chapter_sizes();
+        let target = sizes.iter().sum::<u64>() * percent as u64 / 100;
+        let mut start = 0;
+        for (chapter, &size) in sizes.iter().enumerate() {
+            if target < start + size || chapter + 1 == sizes.len() {
+                self.record_jump();
+                self.nav_state.current_chapter = chapter;
+                self.nav_state.reset_scroll();
+                let lines = self.current_chapter_line_count() as u64;
+                let into = target.saturating_sub(start).min(size);
+                self.nav_state.scroll_offset = (lines * into / size.max(1)) as usize;
+                self.clamp_scroll_to_limits(chapter);
+                return;
+            }
+            start += size;
+        }
+    }
+
+    // Repeat a motion for a count prefix, stopping once it gets no further
+    fn repeat(&mut self, count: usize, motion: fn(&mut Self)) {
+        for _ in 0..count {
+            let before = self.position();
+            motion(self);
+            if self.position() == before {
+                break;
+            }
+        }
+    }
+
+    fn go_to_end(&mut self) {
+        self.nav_state.scroll_offset = self.get_current_chapter_max_scroll();
+    }
+
+    // Measured in screen columns, so CJK and emoji don't overflow the list
+    fn truncate_line_for_display(line: &str) -> String {
+        if line.width() <= MAX_DISPLAY_LINE_LENGTH {
+            return line.to_string();
+        }
+        let mut truncated = String::new();
+        let mut width = 0;
+        for grapheme in line.graphemes(true) {
+            width += grapheme.width();
+            if width > MAX_DISPLAY_LINE_LENGTH - 3 {
+                break;
+            }
+            truncated.push_str(grapheme);
+        }
+        format!("{}...", truncated)
+    }
+
+    fn jump_to_search_location(&mut self, location: SearchResultLocation, search_query: &str) {
+        if location.chapter == 0 || location.chapter > self.epub.chapter_count() {
+            return;
+        }
+
+        self.record_jump();
+        // Results are lines of converted text
+        self.nav_state.show_source = false;
+        self.nav_state.current_chapter = location.chapter - 1;
+
+        if self.epub.get_chapter(self.nav_state.current_chapter).is_ok() {
+            let target_line = location.line.saturating_sub(1);
+            self.nav_state.scroll_offset = target_line.saturating_sub(SEARCH_RESULT_TOP_OFFSET);
+            self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+
+            if !search_query.is_empty() {
+                let target = Position {
+                    chapter: self.nav_state.current_chapter,
+                    line: target_line,
+                };
+                let matches = self.matches_of(search_query);
+                self.nav_state.search_query = Some(search_query.to_string());
+                self.nav_state.search_match = Some(target);
+                self.nav_state.match_number = matches
+                    .iter()
+                    .position(|&m| m == target)
+                    .map(|index| (index + 1, matches.len()));
+            }
+        }
+    }
+
+    // Every line matching the query, worked out again only when the query
+    // or the text width changes
+    fn matches_of(&mut self, query: &str) -> Vec<Position> {
+        let width = self.epub.text_width();
+        if let Some((cached, cached_width, matches)) = &self.search_matches
+            && cached == query
+            && *cached_width == width
+        {
+            return matches.clone();
+        }
+        let chapters = self.chapters_to_search(query);
+        let matches: Vec<Position> = self
+            .epub
+            .search_chapters(query, chapters)
+            .into_iter()
+            .map(|result| Position {
+                chapter: result.chapter_index,
+                line: result.line_number,
+            })
+            .collect();
+        self.search_matches = Some((query.to_string(), width, matches.clone()));
+        matches
+    }
+
+    // Jump to the next or previous match of the last search, counting from
+    // the last match if it's still on screen and from the top of the view if
+    // not, and wrapping around the ends of the book
+    fn next_match(&mut self, forward: bool) {
+        let Some(query) = self.nav_state.search_query.clone() else {
+            self.status_message = Some("No previous search".to_string());
+            return;
+        };
+        let matches = self.matches_of(&query);
+        let (Some(&first), Some(&last)) = (matches.first(), matches.last()) else {
+            self.status_message = Some(format!("Not found: {query}"));
+            return;
+        };
+
+        let top = Position {
+            chapter: self.nav_state.current_chapter,
+            line: self.nav_state.scroll_offset,
+        };
+        let here = self
+            .nav_state
+            .search_match
+            .filter(|m| {
+                m.chapter == top.chapter && self.visible_line_range().contains(&m.line)
+            })
+            .unwrap_or(top);
+        let found = if forward {
+            matches.iter().find(|&&m| m > here)
+        } else {
+            matches.iter().rev().find(|&&m| m < here)
+        };
+        let target = match found {
+            Some(&target) => target,
+            None => {
+                self.status_message = Some(if forward {
+                    "Search wrapped to the start".to_string()
+                } else {
+                    "Search wrapped to the end".to_string()
+                });
+                if forward { first } else { last }
+            }
+        };
+        let location = SearchResultLocation {
+            chapter: target.chapter + 1,
+            line: target.line + 1,
+        };
+        self.jump_to_search_location(location, &query);
+    }
+
+    fn parse_and_jump_to_chapter(&mut self, selected_text: &str) {
+        if let Some(location) = Self::parse_chapter_location(selected_text) {
+            self.jump_to_chapter_location(location);
+        }
+    }
+
+    fn parse_chapter_location(text: &str) -> Option<ChapterLocation> {
+        let colon_pos = text.find(": ")?;
+
+        let chapter_str = text[0..colon_pos].trim();
+        let chapter = chapter_str.parse().ok()?;
+
+        Some(ChapterLocation { chapter })
+    }
+
+    fn jump_to_chapter_location(&mut self, location: ChapterLocation) {
+        if location.chapter == 0 || location.chapter > self.epub.chapter_count() {
+            return;
+        }
+
+        self.record_jump();
+        self.nav_state.current_chapter = location.chapter - 1;
+        self.nav_state.reset_scroll();
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            chapter: self.nav_state.current_chapter,
+            line: self.nav_state.scroll_offset,
+        }
+    }
+
+    // Remember where we are before jumping elsewhere, for Ctrl-o
+    fn record_jump(&mut self) {
+        let here = self.place();
+        let nav_state = &mut self.nav_state;
+        nav_state.jumps_forward.clear();
+        if nav_state.jumps_back.last() != Some(&here) {
+            nav_state.jumps_back.push(here);
+        }
+        if nav_state.jumps_back.len() > JUMP_LIST_SIZE {
+            nav_state.jumps_back.remove(0);
+        }
+    }
+
+    fn jump_back(&mut self) {
+        if let Some(place) = self.nav_state.jumps_back.pop() {
+            let here = self.place();
+            self.nav_state.jumps_forward.push(here);
+            self.go_to_place(place);
+        }
+    }
+
+    fn jump_forward(&mut self) {
+        if let Some(place) = self.nav_state.jumps_forward.pop() {
+            let here = self.place();
+            self.nav_state.jumps_back.push(here);
+            self.go_to_place(place);
+        }
+    }
+
+    fn go_to_position(&mut self, position: Position) {
+        self.nav_state.current_chapter = position.chapter;
+        self.nav_state.reset_scroll();
+        self.nav_state.scroll_offset = position.line;
+        self.clamp_scroll_to_limits(position.chapter);
+    }
+
+    fn handle_floating_pane_input(&mut self, key: crossterm::event::KeyEvent) -> bool {
+        let floating_pane = std::mem::replace(&mut self.floating_pane, FloatingPane::None);
+
+        match floating_pane {
+            FloatingPane::None => {
+                self.floating_pane = FloatingPane::None;
+                false
+            }
+            FloatingPane::Search(mut pane) => {
+                match key.code {
+                    KeyCode::Esc => {}
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.search_mode = match self.search_mode {
+                            SearchMode::Substring => SearchMode::Fuzzy,
+                            SearchMode::Fuzzy => SearchMode::Substring,
+                        };
+                        self.start_search(pane.query);
+                    }
+                    KeyCode::Tab => {
+                        self.search_scope = match self.search_scope {
+                            SearchScope::Book => SearchScope::Chapter,
+                            SearchScope::Chapter => SearchScope::Book,
+                        };
+                        self.start_search(pane.query);
+                    }
+                    KeyCode::Char(_) | KeyCode::Backspace => {
+                        // Changing the query drops the search for the old one
+                        match key.code {
+                            KeyCode::Char(c) => pane.query.push(c),
+                            _ => {
+                                pane.query.pop();
+                            }
+                        }
+                        self.start_search(pane.query);
+                    }
+                    KeyCode::Enter if pane.selected_index < pane.results.len() => {
+                        let hit = &pane.results[pane.selected_index];
+                        let location = SearchResultLocation {
+                            chapter: hit.chapter + 1,
+                            line: hit.line + 1,
+                        };
+                        // n and N step through substring matches only
+                        let query = match pane.search.mode() {
+                            SearchMode::Substring => pane.query.as_str(),
+                            SearchMode::Fuzzy => "",
+                        };
+                        self.jump_to_search_location(location, query);
+                    }
+                    code => {
+                        match code {
+                            KeyCode::Up => {
+                                pane.selected_index = pane.selected_index.saturating_sub(1);
+                            }
+                            KeyCode::Down if pane.selected_index + 1 < pane.results.len() => {
+                                pane.selected_index += 1;
+                            }
+                            _ => {}
+                        }
+                        self.floating_pane = FloatingPane::Search(pane);
+                    }
+                }
+                true
+            }
+            FloatingPane::Contents {
+                mut selected_index,
+                read_chapters,
+                mut filter,
+            } => {
+                let entries = Self::contents_entries(&self.epub, &filter);
+                let position = entries.iter().position(|(chapter, _)| *chapter == selected_index);
+                match key.code {
+                    KeyCode::Esc if filter.is_empty() => return true,
+                    KeyCode::Esc => filter.clear(),
+                    KeyCode::Enter if position.is_some() => {
+                        let title = self.epub.chapter_title(selected_index);
+                        let selected_text = format!("{}: {}", selected_index + 1, title);
+                        self.parse_and_jump_to_chapter(&selected_text);
+                        return true;
+                    }
+                    KeyCode::Up => {
+                        if let Some((chapter, _)) = position
+                            .map_or(entries.first(), |p| entries.get(p.saturating_sub(1)))
+                        {
+                            selected_index = *chapter;
+                        }
+                    }
+                    KeyCode::Down => {
+                        if let Some((chapter, _)) =
+                            position.map_or(entries.first(), |p| entries.get(p + 1))
+                        {
+                            selected_index = *chapter;
+                        }
+                    }
+                    KeyCode::Char(c) => filter.push(c),
+                    KeyCode::Backspace => {
+                        filter.pop();
+                    }
+                    _ => {}
+                }
+                // A changed filter selects its best match
+                if matches!(key.code, KeyCode::Char(_) | KeyCode::Backspace)
+                    && !filter.is_empty()
+                    && let Some((chapter, _)) =
+                        Self::contents_entries(&self.epub, &filter).first()
+                {
+                    selected_index = *chapter;
+                }
+                self.floating_pane = FloatingPane::Contents {
+                    selected_index,
+                    read_chapters,
+                    filter,
+                };
+                true
+            }
+            FloatingPane::Outline {
+                headings,
+                mut selected_index,
+            } => {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('o') => {}
+                    KeyCode::Enter => {
+                        if let Some(heading) = headings.get(selected_index) {
+                            self.record_jump();
+                            self.nav_state.scroll_offset = heading.line;
+                            self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+                        }
+                    }
+                    code => {
+                        match code {
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                selected_index = selected_index.saturating_sub(1);
+                            }
+                            KeyCode::Down | KeyCode::Char('j')
+                                if selected_index + 1 < headings.len() =>
+                            {
+                                selected_index += 1;
+                            }
+                            _ => {}
+                        }
+                        self.floating_pane = FloatingPane::Outline {
+                            headings,
+                            selected_index,
+                        };
+                    }
+                }
+                true
+            }
+            FloatingPane::Annotations {
+                mut highlights,
+                mut selected_index,
+            } => {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('a') => {}
+                    KeyCode::Enter => {
+                        if let Some(highlight) = highlights.get(selected_index) {
+                            self.go_to_highlight(highlight);
+                        }
+                    }
+                    code => {
+                        match code {
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                selected_index = selected_index.saturating_sub(1);
+                            }
+                            KeyCode::Down | KeyCode::Char('j')
+                                if selected_index + 1 < highlights.len() =>
+                            {
+                                selected_index += 1;
+                            }
+                            KeyCode::Char('n') if selected_index < highlights.len() => {
+                                let input =
+                                    highlights[selected_index].note.clone().unwrap_or_default();
+                                self.floating_pane = FloatingPane::NoteEditor {
+                                    highlights,
+                                    selected_index,
+                                    input,
+                                };
+                                return true;
+                            }
+                            KeyCode::Char('d') if selected_index < highlights.len() => {
+                                let highlight = highlights.remove(selected_index);
+                                self.delete_highlight(&highlight);
+                                selected_index =
+                                    selected_index.min(highlights.len().saturating_sub(1));
+                            }
+                            _ => {}
+                        }
+                        if !highlights.is_empty() {
+                            self.floating_pane = FloatingPane::Annotations {
+                                highlights,
+                                selected_index,
+                            };
+                        }
+                    }
+                }
+                true
+            }
+            FloatingPane::NoteEditor {
+                mut highlights,
+                selected_index,
+                mut input,
+            } => {
+                match key.code {
+                    KeyCode::Esc => {}
+                    KeyCode::Enter => {
+                        let note = Some(input.trim().to_string()).filter(|note| !note.is_empty());
+                        self.set_note(&mut highlights, selected_index, note);
+                    }
+                    KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        match self.edit_externally(&input) {
+                            Ok(edited) => {
+                                let note = Some(edited).filter(|note| !note.trim().is_empty());
+                                self.set_note(&mut highlights, selected_index, note);
+                            }
+                            Err(e) => self.status_message = Some(format!("Couldn't edit: {e}")),
+                        }
+                    }
+                    code => {
+                        match code {
+                            KeyCode::Char(c) => input.push(c),
+                            KeyCode::Backspace => {
+                                input.pop();
+                            }
+                            _ => {}
+                        }
+                        self.floating_pane = FloatingPane::NoteEditor {
+                            highlights,
+                            selected_index,
+                            input,
+                        };
+                        return true;
+                    }
+                }
+                self.floating_pane = FloatingPane::Annotations {
+                    highlights,
+                    selected_index,
+                };
+                true
+            }
+            FloatingPane::Books {
+                titles,
+                mut selected_index,
+            } => {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('B') => {}
+                    KeyCode::Enter => {
+                        if selected_index != self.book_index {
+                            self.switch_to = Some(selected_index);
+                        }
+                    }
+                    code => {
+                        match code {
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                selected_index = selected_index.saturating_sub(1);
+                            }
+                            KeyCode::Down | KeyCode::Char('j')
+                                if selected_index + 1 < titles.len() =>
+                            {
+                                selected_index += 1;
+                            }
+                            _ => {}
+                        }
+                        self.floating_pane = FloatingPane::Books {
+                            titles,
+                            selected_index,
+                        };
+                    }
+                }
+                true
+            }
+            FloatingPane::Notes { highlights } => {
+                if !matches!(key.code, KeyCode::Esc | KeyCode::Char('K')) {
+                    self.floating_pane = FloatingPane::Notes { highlights };
+                }
+                true
+            }
+            FloatingPane::Lookup { summary, scroll } => {
+                let scroll = match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => return true,
+                    KeyCode::Char('o') => {
+                        if let Err(e) = Self::open_in_browser(&summary.url) {
+                            self.status_message = Some(format!("Couldn't open browser: {e}"));
+                        }
+                        return true;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => scroll.saturating_add(1),
+                    KeyCode::Up | KeyCode::Char('k') => scroll.saturating_sub(1),
+                    _ => scroll,
+                };
+                self.floating_pane = FloatingPane::Lookup { summary, scroll };
+                true
+            }
+            FloatingPane::Translation {
+                original,
+                translated,
+                scroll,
+            } => {
+                let scroll = match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => return true,
+                    KeyCode::Char('y') => {
+                        self.status_message = Some(match self.clipboard.copy(&translated) {
+                            Ok(()) => "Copied the translation".to_string(),
+                            Err(e) => format!("Couldn't copy: {e}"),
+                        });
+                        scroll
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => scroll.saturating_add(1),
+                    KeyCode::Up | KeyCode::Char('k') => scroll.saturating_sub(1),
+                    _ => scroll,
+                };
+                self.floating_pane = FloatingPane::Translation {
+                    original,
+                    translated,
+                    scroll,
+                };
+                true
+            }
+            FloatingPane::Stats { stats, session } => {
+                if !matches!(key.code, KeyCode::Esc | KeyCode::Char('S')) {
+                    self.floating_pane = FloatingPane::Stats { stats, session };
+                }
+                true
+            }
+            FloatingPane::BookInfo => {
+                if !matches!(key.code, KeyCode::Esc | KeyCode::Char('i')) {
+                    self.floating_pane = FloatingPane::BookInfo;
+                }
+                true
+            }
+            FloatingPane::OpenUrl { url } => {
+                if matches!(key.code, KeyCode::Enter | KeyCode::Char('y'))
+                    && let Err(e) = Self::open_in_browser(&url)
+                {
+                    self.status_message = Some(format!("Couldn't open {url}: {e}"));
+                }
+                true
+            }
+            FloatingPane::ConfirmQuit => {
+                self.quitting = matches!(
+                    key.code,
+                    KeyCode::Char('y' | 'Y' | 'q') | KeyCode::Enter
+                );
+                true
+            }
+            FloatingPane::Command { mut input } => {
+                match key.code {
+                    KeyCode::Esc => {}
+                    KeyCode::Enter => {
+                        if let Err(message) = self.run_command(&input) {
+                            self.status_message = Some(message);
+                        }
+                    }
+                    // Deleting past the start closes the prompt, as in vim
+                    KeyCode::Backspace => {
+                        if input.pop().is_some() {
+                            self.floating_pane = FloatingPane::Command { input };
+                        }
+                    }
+                    KeyCode::Char(c) => {
+                        input.push(c);
+                        self.floating_pane = FloatingPane::Command { input };
+                    }
+                    _ => self.floating_pane = FloatingPane::Command { input },
+                }
+                true
+            }
+            FloatingPane::LinkHints { hints, mut typed } => {
+                // Any key that can't complete a label cancels hint mode
+                if let KeyCode::Char(c) = key.code {
+                    typed.push(c);
+                    if let Some(hint) = hints.iter().find(|hint| hint.label == typed) {
+                        let target = hint.link.target.clone();
+                        self.follow_link(&target);
+                    } else if hints.iter().any(|hint| hint.label.starts_with(&typed)) {
+                        self.floating_pane = FloatingPane::LinkHints { hints, typed };
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    // Headings only make sense in the converted text, not the XHTML source
+    fn current_headings(&self) -> Vec<Heading> {
+        if self.nav_state.show_source {
+            return Vec::new();
+        }
+        self.epub
+            .get_chapter(self.nav_state.current_chapter)
+            .map(|chapter| chapter.headings())
+            .unwrap_or_default()
+    }
+
+    fn open_outline_pane(&mut self) {
+        let headings = self.current_headings();
+        if headings.is_empty() {
+            self.status_message = Some("No headings in this chapter".to_string());
+            return;
+        }
+        // Start on the section being read
+        let selected_index = headings
+            .iter()
+            .rposition(|heading| heading.line <= self.nav_state.scroll_offset)
+            .unwrap_or(0);
+        self.floating_pane = FloatingPane::Outline {
+            headings,
+            selected_index,
+        };
+    }
+
+    fn start_visual(&mut self) {
+        if self.nav_state.show_source {
+            self.status_message = Some("Passages are highlighted in the book's text".to_string());
+            return;
+        }
+        let top = self.nav_state.scroll_offset;
+        self.nav_state.visual = Some((top, top));
+        self.status_message = Some(VISUAL_MODE_HELP.to_string());
+    }
+
+    // In visual mode, movement keys move the end of the selection, keeping
+    // it on screen, Enter highlights the passage, `y` copies it and Esc or
+    // `v` drops it
+    fn handle_visual_key(&mut self, key: KeyCode) {
+        let Some((first, last)) = self.nav_state.visual else {
+            return;
+        };
+        let chapter = self.nav_state.current_chapter;
+        let bottom = self.displayed_line_count(chapter).saturating_sub(1);
+        let page = self.get_page_size();
+        let last = match key {
+            KeyCode::Down | KeyCode::Char('j') => last + 1,
+            KeyCode::Up | KeyCode::Char('k') => last.saturating_sub(1),
+            KeyCode::PageDown | KeyCode::Char(' ') => last + page,
+            KeyCode::PageUp | KeyCode::Char('b') => last.saturating_sub(page),
+            KeyCode::Enter => {
+                self.nav_state.visual = None;
+                return self.add_highlight(first, last);
+            }
+            KeyCode::Char('y') => {
+                self.nav_state.visual = None;
+                return self.copy_lines(first.min(last), first.max(last));
+            }
+            KeyCode::Char(key @ ('w' | 'd')) => {
+                self.nav_state.visual = None;
+                let source = match key {
+                    'w' => lookup::Source::Wikipedia,
+                    _ => lookup::Source::Wiktionary,
+                };
+                let phrase = self.selected_text(first.min(last), first.max(last));
+                return self.start_lookup(source, &phrase);
+            }
+            KeyCode::Char('t') => {
+                self.nav_state.visual = None;
+                let passage = self.selected_text(first.min(last), first.max(last));
+                return self.start_translation(passage);
+            }
+            KeyCode::Esc | KeyCode::Char('v') => {
+                self.nav_state.visual = None;
+                return;
+            }
+            _ => last,
+        }
+        .min(bottom);
+        self.nav_state.visual = Some((first, last));
+        self.status_message = Some(VISUAL_MODE_HELP.to_string());
+
+        let top = self.nav_state.scroll_offset;
+        if self.nav_state.paged || last < top {
+            self.nav_state.scroll_offset = last;
+        } else if last >= top + page {
+            self.nav_state.scroll_offset = last + 1 - page;
+        }
+        self.clamp_scroll_to_limits(chapter);
+    }
+
+    // Keep lines `first` to `last` of the chapter, in either order, as a
+    // highlight, and save it straight away
+    fn add_highlight(&mut self, first: usize, last: usize) {
+        let chapter_index = self.nav_state.current_chapter;
+        let Ok(chapter) = self.epub.get_chapter(chapter_index) else {
+            return;
+        };
+        let (first, last) = (first.min(last), first.max(last));
+        let offsets = Self::text_offsets(&chapter.content);
+        let excerpt = chapter
+            .lines_from(first)
+            .take(last + 1 - first)
+            .flat_map(str::split_whitespace)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let (Some(&start), Some(&end)) = (offsets.get(first), offsets.get(last + 1)) else {
+            return;
+        };
+        if excerpt.is_empty() {
+            self.status_message = Some("Nothing to highlight there".to_string());
+            return;
+        }
+
+        let highlights = &mut self.progress.book_mut(&self.book_key).highlights;
+        let at = highlights.partition_point(|highlight| {
+            (highlight.chapter, highlight.start) <= (chapter_index, start)
+        });
+        highlights.insert(
+            at,
+            Highlight {
+                chapter: chapter_index,
+                start,
+                end,
+                excerpt,
+                note: None,
+            },
+        );
+        self.save_progress();
+        self.status_message = Some("Highlighted".to_string());
+    }
+
+    // Copy the paragraph at the top of the screen
+    fn copy_paragraph(&mut self) {
+        if let Some((first, last)) = self.top_paragraph() {
+            self.copy_lines(first, last);
+        }
+    }
+
+    // Translate the paragraph at the top of the screen
+    fn translate_paragraph(&mut self) {
+        if let Some((first, last)) = self.top_paragraph() {
+            let passage = self.selected_text(first, last);
+            self.start_translation(passage);
+        }
+    }
+
+    // The first and last lines of the paragraph at the top of the screen
+    fn top_paragraph(&self) -> Option<(usize, usize)> {
+        let chapter = self.epub.get_chapter(self.nav_state.current_chapter).ok()?;
+        let lines: Vec<&str> = chapter.content.lines().collect();
+        let first = (self.nav_state.scroll_offset..lines.len())
+            .find(|&index| !lines[index].trim().is_empty())?;
+        let start = lines[..first]
+            .iter()
+            .rposition(|line| line.trim().is_empty())
+            .map_or(0, |blank| blank + 1);
+        let end = lines[first..]
+            .iter()
+            .position(|line| line.trim().is_empty())
+            .map_or(lines.len(), |blank| first + blank);
+        Some((start, end - 1))
+    }
+
+    // Copy lines `first` to `last` of the chapter, joining the lines of
+    // each paragraph back together
+    fn copy_lines(&mut self, first: usize, last: usize) {
+        let text = self.selected_text(first, last);
+        if text.is_empty() {
+            self.status_message = Some("Nothing to copy there".to_string());
+            return;
+        }
+        self.status_message = Some(match self.clipboard.copy(&text) {
+            Ok(()) => format!("Copied {} characters", text.chars().count()),
+            Err(e) => format!("Couldn't copy: {e}"),
+        });
+    }
+
+    // Lines `first` to `last` of the chapter as plain paragraphs, unwrapped
+    fn selected_text(&self, first: usize, last: usize) -> String {
+        let Ok(chapter) = self.epub.get_chapter(self.nav_state.current_chapter) else {
+            return String::new();
+        };
+        let lines: Vec<&str> = chapter.lines_from(first).take(last + 1 - first).collect();
+        lines
+            .split(|line| line.trim().is_empty())
+            .filter(|paragraph| !paragraph.is_empty())
+            .map(|paragraph| {
+                let words: Vec<&str> =
+                    paragraph.iter().flat_map(|line| line.split_whitespace()).collect();
+                words.join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    // Look the phrase up on a thread of its own, showing what's found once
+    // it arrives
+    fn start_lookup(&mut self, source: lookup::Source, phrase: &str) {
+        let phrase = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+        let language = self.epub.language.clone().unwrap_or_default();
+        let (sender, receiver) = mpsc::channel();
+        self.status_message = Some(format!("Looking up {phrase} on {}…", source.name()));
+        std::thread::spawn(move || {
+            let _ = sender.send(lookup::fetch(source, &phrase, &language));
+        });
+        self.pending_lookup = Some(receiver);
+    }
+
+    fn collect_lookup(&mut self) {
+        let Some(receiver) = &self.pending_lookup else {
+            return;
+        };
+        match receiver.try_recv() {
+            Err(TryRecvError::Empty) => return,
+            Ok(Ok(summary)) => self.floating_pane = FloatingPane::Lookup { summary, scroll: 0 },
+            Ok(Err(message)) => self.status_message = Some(message),
+            Err(TryRecvError::Disconnected) => {}
+        }
+        self.pending_lookup = None;
+    }
+
+    // Read the chapter out from the top of the screen with the book's
+    // narration, or stop reading
+    fn toggle_narration(&mut self) {
+        if self.narrator.take().is_some() {
+            self.nav_state.narrated = None;
+            self.status_message = Some("Narration stopped".to_string());
+            return;
+        }
+        let chapter = self.nav_state.current_chapter;
+        if !self.epub.has_narration(chapter) {
+            self.status_message = Some("This chapter has no narration".to_string());
+            return;
+        }
+        self.start_narration(chapter, self.nav_state.scroll_offset);
+    }
+
+    // Narrate the chapter from the first clip reading out line `from` or
+    // below
+    fn start_narration(&mut self, chapter_index: usize, from: usize) {
+        let (clips, chapter) = match (
+            self.epub.narration(chapter_index),
+            self.epub.get_chapter(chapter_index),
+        ) {
+            (Ok(clips), Ok(chapter)) => (clips, chapter),
+            (Err(e), _) | (_, Err(e)) => {
+                self.status_message = Some(format!("Couldn't read the narration: {e}"));
+                return;
+            }
+        };
+        let first = clips
+            .iter()
+            .position(|clip| chapter.anchor_line(&clip.fragment).is_some_and(|line| line >= from))
+            .unwrap_or(0);
+        let narrator = Narrator::new(Arc::clone(&self.epub), clips, first);
+        self.narrator = Some((chapter_index, narrator));
+        self.status_message = Some("Narrating · P stops".to_string());
+    }
+
+    // Mark the passage being read out, keeping it on screen, and go on to
+    // the next chapter's narration at the end of this one's
+    fn follow_narration(&mut self) {
+        let Some((chapter_index, narrator)) = self.narrator.as_mut() else {
+            return;
+        };
+        let chapter_index = *chapter_index;
+        if chapter_index != self.nav_state.current_chapter {
+            self.narrator = None;
+            self.nav_state.narrated = None;
+            return;
+        }
+        let playing = match narrator.update() {
+            Ok(playing) => playing,
+            Err(message) => {
+                self.narrator = None;
+                self.nav_state.narrated = None;
+                self.status_message = Some(message);
+                return;
+            }
+        };
+        let Some(index) = playing else {
+            self.narrator = None;
+            self.nav_state.narrated = None;
+            let next = chapter_index + 1;
+            if next < self.epub.chapter_count() && self.epub.has_narration(next) {
+                self.step_to_chapter(next);
+                self.nav_state.reset_scroll();
+                self.start_narration(next, 0);
+            } else {
+                self.status_message = Some("Narration finished".to_string());
+            }
+            return;
+        };
+
+        let Ok(chapter) = self.epub.get_chapter(chapter_index) else {
+            return;
+        };
+        let clips = narrator.clips();
+        let Some(first) = chapter.anchor_line(&clips[index].fragment) else {
+            return;
+        };
+        // Up to where the next clip starts, or to the end of the paragraph
+        let last = match clips
+            .get(index + 1)
+            .and_then(|next| chapter.anchor_line(&next.fragment))
+        {
+            Some(next) if next > first => next - 1,
+            Some(_) => first,
+            None => {
+                let lines: Vec<&str> = chapter.content.lines().collect();
+                let end = lines[first..]
+                    .iter()
+                    .position(|line| line.trim().is_empty())
+                    .map_or(lines.len(), |blank| first + blank);
+                end.saturating_sub(1).max(first)
+            }
+        };
+        if self.nav_state.narrated == Some((first, last)) {
+            return;
+        }
+        self.nav_state.narrated = Some((first, last));
+        let top = self.nav_state.scroll_offset;
+        if first < top || last >= top + self.get_page_size() {
+            self.nav_state.scroll_offset = first;
+            self.clamp_scroll_to_limits(chapter_index);
+        }
+    }
+
+    // Translate the passage on a thread of its own, showing it beside the
+    // original once the translation arrives
+    fn start_translation(&mut self, passage: String) {
+        let Some(translator) = self.translator.clone() else {
+            self.status_message =
+                Some("Pick a translator with --translator to translate".to_string());
+            return;
+        };
+        if passage.trim().is_empty() {
+            self.status_message = Some("Nothing to translate there".to_string());
+            return;
+        }
+        let language = self.epub.language.clone();
+        let text = passage.clone();
+        let (sender, receiver) = mpsc::channel();
+        self.status_message = Some(format!("Translating into {}…", translator.target));
+        std::thread::spawn(move || {
+            let _ = sender.send(translator.translate(&text, language.as_deref()));
+        });
+        self.pending_translation = Some((passage, receiver));
+    }
+
+    fn collect_translation(&mut self) {
+        let Some((original, receiver)) = self.pending_translation.take() else {
+            return;
+        };
+        match receiver.try_recv() {
+            Err(TryRecvError::Empty) => self.pending_translation = Some((original, receiver)),
+            Ok(Ok(translated)) => {
+                self.status_message = None;
+                self.floating_pane = FloatingPane::Translation {
+                    original,
+                    translated,
+                    scroll: 0,
+                };
+            }
+            Ok(Err(message)) => self.status_message = Some(message),
+            Err(TryRecvError::Disconnected) => {}
+        }
+    }
+
+    fn open_annotations_pane(&mut self) {
+        let highlights = self
+            .progress
+            .book(&self.book_key)
+            .map(|book| book.highlights.clone())
+            .unwrap_or_default();
+        if highlights.is_empty() {
+            self.status_message =
+                Some("No highlights yet; select a passage with v".to_string());
+            return;
+        }
+        // Start on the last highlight up to the chapter being read
+        let selected_index = highlights
+            .iter()
+            .rposition(|highlight| highlight.chapter <= self.nav_state.current_chapter)
+            .unwrap_or(0);
+        self.floating_pane = FloatingPane::Annotations {
+            highlights,
+            selected_index,
+        };
+    }
+
+    fn go_to_highlight(&mut self, highlight: &Highlight) {
+        let Ok(chapter) = self.epub.get_chapter(highlight.chapter) else {
+            return;
+        };
+        let line = Self::line_at_offset(&Self::text_offsets(&chapter.content), highlight.start);
+        self.record_jump();
+        self.go_to_position(Position {
+            chapter: highlight.chapter,
+            line,
+        });
+    }
+
+    fn open_notes_pane(&mut self) {
+        let chapter_index = self.nav_state.current_chapter;
+        let highlights = match self.epub.get_chapter(chapter_index) {
+            Ok(chapter) => {
+                let offsets = Self::text_offsets(&chapter.content);
+                let top = self.nav_state.scroll_offset;
+                let on_screen = top..top + self.get_page_size();
+                self.progress
+                    .book(&self.book_key)
+                    .into_iter()
+                    .flat_map(|book| &book.highlights)
+                    .filter(|highlight| {
+                        highlight.chapter == chapter_index
+                            && highlight.note.is_some()
+                            && on_screen.contains(&Self::line_at_offset(&offsets, highlight.start))
+                    })
+                    .cloned()
+                    .collect()
+            }
+            Err(_) => Vec::new(),
+        };
+        if highlights.is_empty() {
+            self.status_message = Some("No notes on this page".to_string());
+            return;
+        }
+        self.floating_pane = FloatingPane::Notes { highlights };
+    }
+
+    // Replace the note of a highlight, both in the book's progress and in
+    // the Highlights pane's copy of the list
+    fn set_note(&mut self, highlights: &mut [Highlight], index: usize, note: Option<String>) {
+        let Some(highlight) = highlights.get_mut(index) else {
+            return;
+        };
+        let book = self.progress.book_mut(&self.book_key);
+        if let Some(kept) = book.highlights.iter_mut().find(|kept| **kept == *highlight) {
+            kept.note = note.clone();
+            self.save_progress();
+        }
+        highlight.note = note;
+    }
+
+    // Hand a note to `$EDITOR` (or vi), giving the terminal over to it
+    // until it exits, and read back what was written
+    fn edit_externally(&mut self, text: &str) -> Result<String, UiError> {
+        let path = std::env::temp_dir().join(format!("catatau-note-{}.md", std::process::id()));
+        std::fs::write(&path, text)?;
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+
+        self.release_terminal()?;
+        let status = Command::new(program).args(words).arg(&path).status();
+        self.reclaim_terminal()?;
+
+        let edited = std::fs::read_to_string(&path);
+        let _ = std::fs::remove_file(&path);
+        if !status?.success() {
+            return Ok(text.to_string());
+        }
+        Ok(edited?.trim_end().to_string())
+    }
+
+    fn delete_highlight(&mut self, highlight: &Highlight) {
+        let highlights = &mut self.progress.book_mut(&self.book_key).highlights;
+        if let Some(index) = highlights.iter().position(|kept| kept == highlight) {
+            highlights.remove(index);
+            self.save_progress();
+        }
+    }
+
+    // Scroll to the nearest heading that actually moves the view, since
+    // headings near the end or mid-page in paged mode can't reach the top
+    fn go_to_heading<'a>(&mut self, candidates: impl Iterator<Item = &'a Heading>) {
+        let before = self.nav_state.scroll_offset;
+        for heading in candidates {
+            self.nav_state.scroll_offset = heading.line;
+            self.clamp_scroll_to_limits(self.nav_state.current_chapter);
+            if self.nav_state.scroll_offset != before {
+                return;
+            }
+        }
+        self.nav_state.scroll_offset = before;
+    }
+
+    fn next_heading(&mut self) {
+        let headings = self.current_headings();
+        let here = self.nav_state.scroll_offset;
+        self.go_to_heading(headings.iter().filter(|heading| heading.line > here));
+    }
+
+    fn prev_heading(&mut self) {
+        let headings = self.current_headings();
+        let here = self.nav_state.scroll_offset;
+        self.go_to_heading(headings.iter().rev().filter(|heading| heading.line < here));
+    }
+
+    fn open_link_hints(&mut self) {
+        if self.nav_state.show_source {
+            return;
+        }
+        let Ok(chapter) = self.epub.get_chapter(self.nav_state.current_chapter) else {
+            return;
+        };
+        let visible = self.visible_line_range();
+        let links: Vec<Link> = chapter
+            .links
+            .iter()
+            .filter(|link| visible.contains(&link.line))
+            .cloned()
+            .collect();
+        if links.is_empty() {
+            return;
+        }
+
+        let hints = Self::hint_labels(links.len())
+            .into_iter()
+            .zip(links)
+            .map(|(label, link)| LinkHint { label, link })
+            .collect();
+        self.floating_pane = FloatingPane::LinkHints {
+            hints,
+            typed: String::new(),
+        };
+    }
+
+    // Single keys while they last, then two-key labels
+    fn hint_labels(count: usize) -> Vec<String> {
+        let keys: Vec<char> = LINK_HINT_KEYS.chars().collect();
+        if count <= keys.len() {
+            keys.iter().take(count).map(|key| key.to_string()).collect()
+        } else {
+            keys.iter()
+                .flat_map(|first| keys.iter().map(move |second| format!("{first}{second}")))
+                .take(count)
+                .collect()
+        }
+    }
+
+    fn follow_link(&mut self, target: &str) {
+        if EpubReader::is_external_link(target) {
+            let lower = target.to_ascii_lowercase();
+            if lower.starts_with("http://") || lower.starts_with("https://") {
+                // Preview the URL in the footer before handing it to the browser
+                self.floating_pane = FloatingPane::OpenUrl {
+                    url: target.to_string(),
+                };
+            } else {
+                self.status_message = Some(format!("Can't open {target}"));
+            }
+            return;
+        }
+
+        match self.epub.resolve_link(self.nav_state.current_chapter, target) {
+            Some(destination) => {
+                self.record_jump();
+                self.nav_state.current_chapter = destination.chapter;
+                self.nav_state.reset_scroll();
+
+                // Land on the anchored element rather than the chapter top
+                let anchor_line = destination.anchor.and_then(|anchor| {
+                    self.epub
+                        .get_chapter(destination.chapter)
+                        .ok()?
+                        .anchor_line(&anchor)
+                });
+                if let Some(line) = anchor_line {
+                    self.nav_state.scroll_offset = line;
+                    self.clamp_scroll_to_limits(destination.chapter);
+                }
+            }
+            None => self.status_message = Some(format!("Link target not in this book: {target}")),
+        }
+    }
+
+    fn open_in_browser(url: &str) -> io::Result<()> {
+        let mut command = if cfg!(target_os = "macos") {
+            Command::new("open")
+        } else if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        } else {
+            Command::new("xdg-open")
+        };
+
+        let mut child = command
+            .arg(url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // Reap the launcher once it exits so it doesn't linger as a zombie
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    fn open_search_pane(&mut self) {
+        self.start_search(String::new());
+    }
+
+    fn start_search(&mut self, query: String) {
+        let epub = Arc::clone(&self.epub);
+        // The index only knows about runs of characters, which fuzzy
+        // matches needn't have
+        let chapters = match (self.search_scope, self.search_mode) {
+            (SearchScope::Chapter, _) => vec![self.nav_state.current_chapter],
+            (SearchScope::Book, SearchMode::Substring) => self.chapters_to_search(&query),
+            (SearchScope::Book, SearchMode::Fuzzy) => (0..self.epub.chapter_count()).collect(),
+        };
+        let search = BackgroundSearch::start(
+            epub,
+            &query,
+            chapters,
+            MIN_SEARCH_LINE_LENGTH,
+            self.search_mode,
+        );
+        self.floating_pane = FloatingPane::Search(SearchPane {
+            query,
+            results: Vec::new(),
+            selected_index: 0,
+            search,
+            scope: self.search_scope,
+        });
+    }
+
+    // Add whatever the running search has found since the last redraw
+    fn collect_search_hits(&mut self) {
+        let FloatingPane::Search(pane) = &mut self.floating_pane else {
+            return;
+        };
+        let hits = pane.search.poll();
+        if hits.is_empty() {
+            return;
+        }
+        // Hits arrive in book order, which is the order they're listed in
+        // unless they're ranked
+        pane.results.extend(hits);
+        if pane.search.mode() == SearchMode::Fuzzy {
+            let mut best: HashMap<usize, i64> = HashMap::new();
+            for hit in &pane.results {
+                let score = best.entry(hit.chapter).or_insert(hit.score);
+                *score = (*score).max(hit.score);
+            }
+            pane.results.sort_by(|a, b| {
+                best[&b.chapter]
+                    .cmp(&best[&a.chapter])
+                    .then(a.chapter.cmp(&b.chapter))
+                    .then(b.score.cmp(&a.score))
+                    .then(a.line.cmp(&b.line))
+            });
+        }
+    }
+
+    fn search_item(
+        hit: &SearchHit,
+        query: &str,
+        mode: SearchMode,
+        theme: &Theme,
+    ) -> Line<'static> {
+        let prefix = format!("L{:4}: ", hit.line + 1);
+        let text = Self::truncate_line_for_display(&hit.text).trim().to_string();
+        let ranges = mode.find(&text, query).map_or(Vec::new(), |found| found.ranges);
+        let ranges = ranges
+            .into_iter()
+            .map(|range| range.start + prefix.len()..range.end + prefix.len())
+            .collect::<Vec<_>>();
+        let line = Line::from(vec![Span::raw(prefix), Span::raw(text)]);
+        Self::highlight_ranges(line, &ranges, theme.highlight)
+    }
+
+    // The paragraph a search hit is in, or as much of it around the hit as
+    // fits in `area` once wrapped, with the matches highlighted. Fuzzy
+    // matches are only shown on the hit's own line, where they mean something.
+    fn search_preview(
+        epub: &EpubReader,
+        hit: &SearchHit,
+        query: &str,
+        mode: SearchMode,
+        area: Rect,
+        theme: &Theme,
+    ) -> Vec<Line<'static>> {
+        let Ok(chapter) = epub.get_chapter(hit.chapter) else {
+            return vec![Line::raw(hit.text.clone())];
+        };
+        let lines: Vec<&str> = chapter.content.lines().collect();
+        // The text may have been re-wrapped since the search
+        if lines.get(hit.line).is_none_or(|line| line.trim() != hit.text.trim()) {
+            return vec![Line::raw(hit.text.clone())];
+        }
+        let is_break = |line: &&str| line.trim().is_empty();
+
+        let paragraph_start = lines[..hit.line]
+            .iter()
+            .rposition(is_break)
+            .map_or(0, |blank| blank + 1);
+        let paragraph_end = lines
+            .iter()
+            .skip(hit.line)
+            .position(is_break)
+            .map_or(lines.len(), |blank| hit.line + blank);
+        let height = usize::from(area.height);
+        let rows = |line: &str| line.width().div_ceil(usize::from(area.width).max(1)).max(1);
+        let mut start = hit.line;
+        let mut used = 0;
+        while start > paragraph_start && used + rows(lines[start - 1]) <= height / 2 {
+            start -= 1;
+            used += rows(lines[start]);
+        }
+        let mut end = hit.line;
+        while end < paragraph_end && (end == hit.line || used + rows(lines[end]) <= height) {
+            used += rows(lines[end]);
+            end += 1;
+        }
+
+        (start..end)
+            .map(|index| {
+                let line = Line::raw(lines[index].to_string());
+                if query.is_empty() || (mode == SearchMode::Fuzzy && index != hit.line) {
+                    return line;
+                }
+                let ranges = mode.find(lines[index], query).map_or(Vec::new(), |m| m.ranges);
+                Self::highlight_ranges(line, &ranges, theme.highlight)
+            })
+            .collect()
+    }
+
+    // Chapters listed in the Contents pane, with the byte ranges of their
+    // "number: title" labels matching the filter. Unfiltered, that's every
+    // chapter in order; filtered, the matching ones, best first.
+    fn contents_entries(
+        epub: &EpubReader,
+        filter: &str,
+    ) -> Vec<(usize, Vec<std::ops::Range<usize>>)> {
+        let labels = (0..epub.chapter_count())
+            .map(|i| (i, format!("{}: {}", i + 1, epub.chapter_title(i))));
+        if filter.trim().is_empty() {
+            return labels.map(|(i, _)| (i, Vec::new())).collect();
+        }
+        let mut matches: Vec<(usize, search::Match)> = labels
+            .filter_map(|(i, label)| Some((i, search::fuzzy_match(&label, filter)?)))
+            .collect();
+        matches.sort_by_key(|(_, found)| std::cmp::Reverse(found.score));
+        matches.into_iter().map(|(i, found)| (i, found.ranges)).collect()
+    }
+
+    fn open_contents_pane(&mut self) {
+        self.epub.scan_chapters();
+        self.floating_pane = FloatingPane::Contents {
+            selected_index: self.nav_state.current_chapter,
+            read_chapters: self
+                .progress
+                .book(&self.book_key)
+                .map(|book| book.read_chapters.clone())
+                .unwrap_or_default(),
+            filter: String::new(),
+        };
+    }
+
+    // A chapter counts as read once its end has been on screen
+    fn note_chapter_read(&mut self) {
+        if self.nav_state.show_source
+            || self.nav_state.scroll_offset < self.get_current_chapter_max_scroll()
+        {
+            return;
+        }
+        let chapter = self.nav_state.current_chapter;
+        self.progress.book_mut(&self.book_key).read_chapters.insert(chapter);
+    }
+
+    fn render_floating_pane(
+        f: &mut Frame,
+        floating_pane: &FloatingPane,
+        epub: &EpubReader,
+        display: &DisplayOptions,
+    ) {
+        let theme = &display.theme;
+        match floating_pane {
+            FloatingPane::None => {}
+            FloatingPane::Search(pane) => {
+                Self::render_search_pane(f, pane, epub, display.profile, theme);
+            }
+            FloatingPane::Contents {
+                selected_index,
+                read_chapters,
+                filter,
+            } => {
+                Self::render_contents_pane(
+                    f,
+                    epub,
+                    *selected_index,
+                    read_chapters,
+                    filter,
+                    theme,
+                );
+            }
+            FloatingPane::BookInfo => {
+                Self::render_book_info_pane(f, epub, theme);
+            }
+            FloatingPane::Stats { stats, session } => {
+                Self::render_stats_pane(f, stats, *session, &epub.title, theme);
+            }
+            FloatingPane::Outline {
+                headings,
+                selected_index,
+            } => {
+                Self::render_outline_pane(f, headings, *selected_index, theme);
+            }
+            FloatingPane::Annotations {
+                highlights,
+                selected_index,
+            } => {
+                Self::render_annotations_pane(f, highlights, *selected_index, epub, theme);
+            }
+            FloatingPane::NoteEditor {
+                highlights,
+                selected_index,
+                input,
+            } => {
+                Self::render_annotations_pane(f, highlights, *selected_index, epub, theme);
+                if let Some(highlight) = highlights.get(*selected_index) {
+                    Self::render_note_editor(f, highlight, input, theme);
+                }
+            }
+            FloatingPane::Notes { highlights } => {
+                Self::render_notes_pane(f, highlights, theme);
+            }
+            FloatingPane::Lookup { summary, scroll } => {
+                Self::render_lookup_pane(f, summary, *scroll, theme);
+            }
+            FloatingPane::Translation {
+                original,
+                translated,
+                scroll,
+            } => {
+                Self::render_translation_pane(f, original, translated, *scroll, theme);
+            }
+            FloatingPane::Books {
+                titles,
+                selected_index,
+            } => {
+                Self::render_books_pane(f, titles, *selected_index, theme);
+            }
+            // Hints are drawn over the chapter text; the URL preview, command
+            // prompt and quit question in the footer
+            FloatingPane::LinkHints { .. }
+            | FloatingPane::OpenUrl { .. }
+            | FloatingPane::Command { .. }
+            | FloatingPane::ConfirmQuit => {}
+        }
+    }
+
+    fn render_search_pane(
+        f: &mut Frame,
+        pane: &SearchPane,
+        epub: &EpubReader,
+        profile: Profile,
+        theme: &Theme,
+    ) {
+        let SearchPane {
+            query,
+            results,
+            selected_index,
+            search,
+            scope,
+        } = pane;
+        let area = f.area();
+
+        let popup_width = area.width.saturating_mul(80).saturating_div(100);
+        let popup_height = area.height.saturating_mul(60).saturating_div(100);
+        let x = area.width.saturating_sub(popup_width).saturating_div(2);
+        let y = area.height.saturating_sub(popup_height).saturating_div(2);
+
+        let popup_area = Rect {
+            x,
+            y,
+            width: popup_width,
+            height: popup_height,
+        };
+
+        // Render shadow effect
+        let shadow_area = Rect {
+            x: x + 1,
+            y: y + 1,
+            width: popup_width,
+            height: popup_height,
+        };
+        f.render_widget(
+            Block::default().style(Style::default().bg(theme.shadow)),
+            shadow_area,
+        );
+
+        f.render_widget(Clear, popup_area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Search input
+                Constraint::Min(0),    // Results
+                Constraint::Length(1), // Help text
+            ])
+            .split(popup_area);
+
+        // Search input with blinking cursor effect, held steady on e-ink
+        let cursor = if !profile.animated()
+            || std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis()
+                % 1000
+                < 500
+        {
+            "█"
+        } else {
+            " "
+        };
+
+        let input = Paragraph::new(format!("🔍 Search: {}{}", query, cursor))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.warning)
+                    .title(match (scope, search.mode()) {
+                        (SearchScope::Book, SearchMode::Substring) => "Search Content",
+                        (SearchScope::Book, SearchMode::Fuzzy) => "Search Content (fuzzy)",
+                        (SearchScope::Chapter, SearchMode::Substring) => "Search This Chapter",
+                        (SearchScope::Chapter, SearchMode::Fuzzy) => {
+                            "Search This Chapter (fuzzy)"
+                        }
+                    })
+                    .style(theme.warning),
+            )
+            .wrap(Wrap { trim: false });
+        f.render_widget(input, chunks[0]);
+
+        // Matches listed under their chapter's title on the left, and the
+        // paragraph around the selected one on the right
+        let panels = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[1]);
+
+        let mut items: Vec<ListItem> = Vec::new();
+        let mut selected_row = None;
+        for (index, hit) in results.iter().enumerate() {
+            if index == 0 || results[index - 1].chapter != hit.chapter {
+                let heading = format!("{}: {}", hit.chapter + 1, epub.chapter_title(hit.chapter));
+                items.push(ListItem::new(Line::styled(
+                    heading,
+                    theme.accent.add_modifier(Modifier::BOLD),
+                )));
+            }
+            if index == *selected_index {
+                selected_row = Some(items.len());
+            }
+            items.push(ListItem::new(Self::search_item(hit, query, search.mode(), theme)));
+        }
+
+        let mut title = format!(
+            "Results ({}/{})",
+            if results.is_empty() { 0 } else { selected_index + 1 },
+            results.len()
+        );
+        if !search.is_finished() {
+            const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+            if profile.animated() {
+                let millis = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_millis());
+                title.push(' ');
+                title.push(SPINNER[(millis / 100) as usize % SPINNER.len()]);
+            }
+            let (searched, total) = search.progress();
+            title.push_str(&format!(" searching {searched}/{total} chapters"));
+        }
+        let results_list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.accent)
+                    .title(title),
+            )
+            .style(theme.text)
+            .highlight_style(
+                theme.highlight.add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("▶ ");
+
+        let mut list_state = ListState::default();
+        list_state.select(selected_row);
+
+        f.render_stateful_widget(results_list, panels[0], &mut list_state);
+
+        let inner = panels[1].inner(Margin::new(1, 1));
+        let (preview_title, preview) = match results.get(*selected_index) {
+            Some(hit) => (
+                epub.chapter_title(hit.chapter),
+                Self::search_preview(epub, hit, query, search.mode(), inner, theme),
+            ),
+            None => (String::new(), Vec::new()),
+        };
+        let preview = Paragraph::new(preview)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.accent)
+                    .title(preview_title),
+            )
+            .style(theme.text)
+            .wrap(Wrap { trim: false });
+        f.render_widget(preview, panels[1]);
+
+        // Help text
+        let help = Paragraph::new(Line::from(vec![
+            Span::styled("↑↓", theme.warning),
+            Span::raw(" navigate  "),
+            Span::styled("Enter", theme.warning),
+            Span::raw(" select  "),
+            Span::styled("Tab", theme.warning),
+            Span::raw(match scope {
+                SearchScope::Book => " this chapter  ",
+                SearchScope::Chapter => " whole book  ",
+            }),
+            Span::styled("Ctrl-f", theme.warning),
+            Span::raw(match search.mode() {
+                SearchMode::Substring => " fuzzy  ",
+                SearchMode::Fuzzy => " exact  ",
+            }),
+            Span::styled("Esc", theme.warning),
+            Span::raw(" close"),
+        ]))
+        .alignment(Alignment::Center);
+        f.render_widget(help, chunks[2]);
+    }
+
+    fn render_contents_pane(
+        f: &mut Frame,
+        epub: &EpubReader,
+        selected_index: usize,
+        read_chapters: &BTreeSet<usize>,
+        filter: &str,
+        theme: &Theme,
+    ) {
+        let area = f.area();
+
+        let popup_width = area.width.saturating_mul(60).saturating_div(100);
+        let popup_height = area.height.saturating_mul(50).saturating_div(100);
+        let x = area.width.saturating_sub(popup_width).saturating_div(2);
+        let y = area.height.saturating_sub(popup_height).saturating_div(2);
+
+        let popup_area = Rect {
+            x,
+            y,
+            width: popup_width,
+            height: popup_height,
+        };
+
+        // Render shadow effect
+        let shadow_area = Rect {
+            x: x + 1,
+            y: y + 1,
+            width: popup_width,
+            height: popup_height,
+        };
+        f.render_widget(
+            Block::default().style(Style::default().bg(theme.shadow)),
+            shadow_area,
+        );
+
+        f.render_widget(Clear, popup_area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(popup_area);
+
+        // Each chapter's length is shown as a bar scaled to the longest
+        let word_counts: Vec<usize> =
+            (0..epub.chapter_count()).map(|i| epub.chapter_word_count(i)).collect();
+        let longest = word_counts.iter().copied().max().unwrap_or(0).max(1);
+        let entries = Self::contents_entries(epub, filter);
+        let items: Vec<ListItem> = entries
+            .iter()
+            .map(|(i, ranges)| {
+                let (i, words) = (*i, word_counts[*i]);
+                let read = read_chapters.contains(&i);
+                let spans = vec![
+                    Span::styled(if read { "✓ " } else { "  " }, theme.confirm),
+                    Span::styled(
+                        format!("{:<8}", Self::length_bar(words, longest, 8)),
+                        theme.progress,
+                    ),
+                    Span::styled(format!("{:>6} ", Self::short_count(words)), theme.muted),
+                ];
+                let prefix: usize = spans.iter().map(|span| span.content.len()).sum();
+                let mut line = Line::from(spans);
+                line.spans.push(Span::raw(format!("{}: {}", i + 1, epub.chapter_title(i))));
+                let ranges: Vec<_> =
+                    ranges.iter().map(|r| r.start + prefix..r.end + prefix).collect();
+                let item = ListItem::new(Self::highlight_ranges(line, &ranges, theme.highlight));
+                if read || epub.is_skippable(i) {
+                    item.style(theme.muted)
+                } else {
+                    item
+                }
+            })
+            .collect();
+
+        let contents_list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.border)
+                    .title(if filter.is_empty() {
+                        format!("📑 Table of Contents ({} chapters)", epub.chapter_count())
+                    } else {
+                        format!(
+                            "📑 Table of Contents: {} ({} of {} chapters)",
+                            filter,
+                            entries.len(),
+                            epub.chapter_count()
+                        )
+                    })
+                    .style(theme.border),
+            )
+            .style(theme.text)
+            .highlight_style(
+                theme.selection.add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("▶ ");
 
-    fn filter_search_results(&self, query: &str) -> Vec<String> {
-        if query.is_empty() {
-            self.build_search_items()
-        } else {
-            let all_items = self.build_search_items();
-            let query_lower = query.to_lowercase();
-            all_items
-                .into_iter()
-                .filter(|item| item.to_lowercase().contains(&query_lower))
-                .collect()
-        }
-    }
+        let mut list_state = ListState::default();
+        list_state.select(entries.iter().position(|(i, _)| *i == selected_index));
 
-    fn render_floating_pane(f: &mut Frame, floating_pane: &FloatingPane, epub: &EpubReader) {
-        match floating_pane {
-            FloatingPane::None => {}
-            FloatingPane::Search {
-                query,
-                results,
-                selected_index,
-            } => {
-                Self::render_search_pane(f, query, results, *selected_index);
-            }
-            FloatingPane::Contents { selected_index } => {
-                Self::render_contents_pane(f, epub, *selected_index);
-            }
-        }
+        f.render_stateful_widget(contents_list, chunks[0], &mut list_state);
+
+        // Help text
+        let help = Paragraph::new(Line::from(vec![
+            Span::styled("↑↓", theme.border),
+            Span::raw(" navigate  "),
+            Span::styled("Enter", theme.border),
+            Span::raw(" select  "),
+            Span::styled("type", theme.border),
+            Span::raw(" to filter  "),
+            Span::styled("Esc", theme.border),
+            Span::raw(if filter.is_empty() { " close" } else { " clear filter" }),
+        ]))
+        .alignment(Alignment::Center);
+        f.render_widget(help, chunks[1]);
     }
 
-    fn render_search_pane(f: &mut Frame, query: &str, results: &[String], selected_index: usize) {
+    fn render_outline_pane(
+        f: &mut Frame,
+        headings: &[Heading],
+        selected_index: usize,
+        theme: &Theme,
+    ) {
         let area = f.area();
 
-        let popup_width = area.width.saturating_mul(80).saturating_div(100);
-        let popup_height = area.height.saturating_mul(60).saturating_div(100);
+        let popup_width = area.width.saturating_mul(60).saturating_div(100);
+        let popup_height = area.height.saturating_mul(50).saturating_div(100);
         let x = area.width.saturating_sub(popup_width).saturating_div(2);
         let y = area.height.saturating_sub(popup_height).saturating_div(2);
 
@@ -892,7 +4571,6 @@ impl App {
             height: popup_height,
         };
 
-        // Render shadow effect
         let shadow_area = Rect {
             x: x + 1,
             y: y + 1,
@@ -900,7 +4578,7 @@ impl App {
             height: popup_height,
         };
         f.render_widget(
-            Block::default().style(Style::default().bg(Color::Black)),
+            Block::default().style(Style::default().bg(theme.shadow)),
             shadow_area,
         );
 
@@ -908,87 +4586,61 @@ impl App {
 
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(3), // Search input
-                Constraint::Min(0),    // Results
-                Constraint::Length(1), // Help text
-            ])
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
             .split(popup_area);
 
-        // Search input with blinking cursor effect
-        let cursor = if std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis()
-            % 1000
-            < 500
-        {
-            "█"
-        } else {
-            " "
-        };
-
-        let input = Paragraph::new(format!("🔍 Search: {}{}", query, cursor))
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Rounded)
-                    .border_style(Style::default().fg(Color::Yellow))
-                    .title("Search Content")
-                    .style(Style::default().fg(Color::Yellow)),
-            )
-            .wrap(Wrap { trim: false });
-        f.render_widget(input, chunks[0]);
-
-        let items: Vec<ListItem> = results
+        // Subheadings are indented under the headings they belong to
+        let top_level = headings.iter().map(|heading| heading.level).min().unwrap_or(1);
+        let items: Vec<ListItem> = headings
             .iter()
-            .map(|result| ListItem::new(result.as_str()))
+            .map(|heading| {
+                let indent = "  ".repeat(heading.level - top_level);
+                ListItem::new(format!("{indent}{}", heading.text))
+                    .style(theme.heading(heading.level))
+            })
             .collect();
 
-        let results_list = List::new(items)
+        let outline_list = List::new(items)
             .block(
                 Block::default()
                     .borders(Borders::ALL)
                     .border_type(BorderType::Rounded)
-                    .border_style(Style::default().fg(Color::Cyan))
-                    .title(format!(
-                        "Results ({}/{})",
-                        if results.is_empty() { 0 } else { selected_index + 1 },
-                        results.len()
-                    )),
-            )
-            .style(Style::default().fg(Color::White))
-            .highlight_style(
-                Style::default()
-                    .bg(Color::Yellow)
-                    .fg(Color::Black)
-                    .add_modifier(Modifier::BOLD),
+                    .border_style(theme.border)
+                    .title(format!("🧭 Outline ({} headings)", headings.len()))
+                    .style(theme.border),
             )
+            .style(theme.text)
+            .highlight_style(theme.selection.add_modifier(Modifier::BOLD))
             .highlight_symbol("▶ ");
 
         let mut list_state = ListState::default();
-        list_state.select(if results.is_empty() { None } else { Some(selected_index) });
+        list_state.select(Some(selected_index));
 
-        f.render_stateful_widget(results_list, chunks[1], &mut list_state);
+        f.render_stateful_widget(outline_list, chunks[0], &mut list_state);
 
-        // Help text
         let help = Paragraph::new(Line::from(vec![
-            Span::styled("↑↓", Style::default().fg(Color::Yellow)),
+            Span::styled("↑↓", theme.border),
             Span::raw(" navigate  "),
-            Span::styled("Enter", Style::default().fg(Color::Yellow)),
-            Span::raw(" select  "),
-            Span::styled("Esc", Style::default().fg(Color::Yellow)),
+            Span::styled("Enter", theme.border),
+            Span::raw(" jump  "),
+            Span::styled("Esc", theme.border),
             Span::raw(" close"),
         ]))
         .alignment(Alignment::Center);
-        f.render_widget(help, chunks[2]);
+        f.render_widget(help, chunks[1]);
     }
 
-    fn render_contents_pane(f: &mut Frame, epub: &EpubReader, selected_index: usize) {
+    fn render_annotations_pane(
+        f: &mut Frame,
+        highlights: &[Highlight],
+        selected_index: usize,
+        epub: &EpubReader,
+        theme: &Theme,
+    ) {
         let area = f.area();
 
-        let popup_width = area.width.saturating_mul(60).saturating_div(100);
-        let popup_height = area.height.saturating_mul(50).saturating_div(100);
+        let popup_width = area.width.saturating_mul(70).saturating_div(100);
+        let popup_height = area.height.saturating_mul(60).saturating_div(100);
         let x = area.width.saturating_sub(popup_width).saturating_div(2);
         let y = area.height.saturating_sub(popup_height).saturating_div(2);
 
@@ -999,7 +4651,6 @@ impl App {
             height: popup_height,
         };
 
-        // Render shadow effect
         let shadow_area = Rect {
             x: x + 1,
             y: y + 1,
@@ -1007,7 +4658,7 @@ impl App {
             height: popup_height,
         };
         f.render_widget(
-            Block::default().style(Style::default().bg(Color::Black)),
+            Block::default().style(Style::default().bg(theme.shadow)),
             shadow_area,
         );
 
@@ -1018,47 +4669,418 @@ impl App {
             .constraints([Constraint::Min(0), Constraint::Length(1)])
             .split(popup_area);
 
-        let items: Vec<ListItem> = (0..epub.chapter_count())
-            .filter_map(|i| {
-                epub.get_chapter(i)
-                    .ok()
-                    .map(|chapter| ListItem::new(format!("{}: {}", i + 1, chapter.title)))
+        let items: Vec<ListItem> = highlights
+            .iter()
+            .map(|highlight| {
+                let title = epub.chapter_title(highlight.chapter);
+                let mut lines = vec![Line::from(vec![
+                    Span::styled(format!("{title}  "), theme.muted),
+                    Span::styled(highlight.excerpt.clone(), theme.text.patch(theme.annotation)),
+                ])];
+                let note = highlight.note.as_deref().unwrap_or_default();
+                lines.extend(note.lines().map(|line| {
+                    Line::styled(format!("  {NOTE_MARKER} {line}"), theme.secondary)
+                }));
+                ListItem::new(lines)
             })
             .collect();
 
-        let contents_list = List::new(items)
+        let annotations_list = List::new(items)
             .block(
                 Block::default()
                     .borders(Borders::ALL)
                     .border_type(BorderType::Rounded)
-                    .border_style(Style::default().fg(Color::Blue))
-                    .title(format!("📑 Table of Contents ({} chapters)", epub.chapter_count()))
-                    .style(Style::default().fg(Color::Blue)),
-            )
-            .style(Style::default().fg(Color::White))
-            .highlight_style(
-                Style::default()
-                    .bg(Color::Blue)
-                    .fg(Color::White)
-                    .add_modifier(Modifier::BOLD),
+                    .border_style(theme.border)
+                    .title(format!("🖍 Highlights ({})", highlights.len()))
+                    .style(theme.border),
             )
+            .style(theme.text)
+            .highlight_style(theme.selection.add_modifier(Modifier::BOLD))
             .highlight_symbol("▶ ");
 
         let mut list_state = ListState::default();
         list_state.select(Some(selected_index));
 
-        f.render_stateful_widget(contents_list, chunks[0], &mut list_state);
+        f.render_stateful_widget(annotations_list, chunks[0], &mut list_state);
 
-        // Help text
         let help = Paragraph::new(Line::from(vec![
-            Span::styled("↑↓", Style::default().fg(Color::Blue)),
+            Span::styled("↑↓", theme.border),
             Span::raw(" navigate  "),
-            Span::styled("Enter", Style::default().fg(Color::Blue)),
-            Span::raw(" select  "),
-            Span::styled("Esc", Style::default().fg(Color::Blue)),
+            Span::styled("Enter", theme.border),
+            Span::raw(" jump  "),
+            Span::styled("n", theme.border),
+            Span::raw(" note  "),
+            Span::styled("d", theme.border),
+            Span::raw(" delete  "),
+            Span::styled("Esc", theme.border),
+            Span::raw(" close"),
+        ]))
+        .alignment(Alignment::Center);
+        f.render_widget(help, chunks[1]);
+    }
+
+    // A box over the Highlights pane for typing a note
+    fn render_note_editor(f: &mut Frame, highlight: &Highlight, input: &str, theme: &Theme) {
+        let area = f.area();
+        let width = area.width.saturating_mul(60).saturating_div(100);
+        let height = 8.min(area.height);
+        let popup_area = Rect {
+            x: area.width.saturating_sub(width) / 2,
+            y: area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        };
+        f.render_widget(Clear, popup_area);
+
+        let text = vec![
+            Line::styled(highlight.excerpt.clone(), theme.muted.add_modifier(Modifier::ITALIC)),
+            Line::default(),
+            Line::from(vec![
+                Span::styled(input.to_string(), theme.text),
+                Span::styled("█", theme.accent),
+            ]),
+        ];
+        let editor = Paragraph::new(text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.accent)
+                    .title(format!("{NOTE_MARKER} Note"))
+                    .title_bottom(Line::from(vec![
+                        Span::styled(" ⏎", theme.confirm.add_modifier(Modifier::BOLD)),
+                        Span::styled(":save ", theme.muted),
+                        Span::styled("Ctrl-e", theme.accent.add_modifier(Modifier::BOLD)),
+                        Span::styled(":$EDITOR ", theme.muted),
+                        Span::styled("Esc", theme.danger.add_modifier(Modifier::BOLD)),
+                        Span::styled(":cancel ", theme.muted),
+                    ]))
+                    .padding(Padding::horizontal(1)),
+            )
+            .wrap(Wrap { trim: false });
+        f.render_widget(editor, popup_area);
+    }
+
+    fn render_books_pane(f: &mut Frame, titles: &[String], selected_index: usize, theme: &Theme) {
+        let area = f.area();
+        let width = area.width.saturating_mul(50).saturating_div(100);
+        let height = (titles.len() as u16 + 3).min(area.height);
+        let popup_area = Rect {
+            x: area.width.saturating_sub(width) / 2,
+            y: area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        };
+        f.render_widget(
+            Block::default().style(Style::default().bg(theme.shadow)),
+            Rect {
+                x: popup_area.x + 1,
+                y: popup_area.y + 1,
+                ..popup_area
+            },
+        );
+        f.render_widget(Clear, popup_area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(popup_area);
+        let items: Vec<ListItem> = titles
+            .iter()
+            .enumerate()
+            .map(|(index, title)| ListItem::new(format!("{}: {title}", index + 1)))
+            .collect();
+        let books_list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.border)
+                    .title(format!("📚 Open books ({})", titles.len()))
+                    .style(theme.border),
+            )
+            .style(theme.text)
+            .highlight_style(theme.selection.add_modifier(Modifier::BOLD))
+            .highlight_symbol("▶ ");
+        let mut list_state = ListState::default();
+        list_state.select(Some(selected_index));
+        f.render_stateful_widget(books_list, chunks[0], &mut list_state);
+
+        let help = Paragraph::new(Line::from(vec![
+            Span::styled("↑↓", theme.border),
+            Span::raw(" navigate  "),
+            Span::styled("Enter", theme.border),
+            Span::raw(" switch  "),
+            Span::styled("Esc", theme.border),
             Span::raw(" close"),
         ]))
         .alignment(Alignment::Center);
         f.render_widget(help, chunks[1]);
     }
+
+    fn render_notes_pane(f: &mut Frame, highlights: &[Highlight], theme: &Theme) {
+        let area = f.area();
+        let width = area.width.saturating_mul(60).saturating_div(100);
+        let height = area.height.saturating_mul(50).saturating_div(100);
+        let popup_area = Rect {
+            x: area.width.saturating_sub(width) / 2,
+            y: area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        };
+        f.render_widget(Clear, popup_area);
+
+        let mut text = Vec::new();
+        for highlight in highlights {
+            if !text.is_empty() {
+                text.push(Line::default());
+            }
+            text.push(Line::styled(
+                highlight.excerpt.clone(),
+                theme.text.patch(theme.annotation),
+            ));
+            let note = highlight.note.as_deref().unwrap_or_default();
+            text.extend(note.lines().map(|line| Line::styled(line.to_string(), theme.secondary)));
+        }
+        let notes = Paragraph::new(text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.border)
+                    .title(format!("{NOTE_MARKER} Notes"))
+                    .padding(Padding::horizontal(1)),
+            )
+            .wrap(Wrap { trim: false });
+        f.render_widget(notes, popup_area);
+    }
+
+    fn render_lookup_pane(f: &mut Frame, summary: &Summary, scroll: u16, theme: &Theme) {
+        let area = f.area();
+        let width = area.width.saturating_mul(60).saturating_div(100);
+        let height = area.height.saturating_mul(50).saturating_div(100);
+        let popup_area = Rect {
+            x: area.width.saturating_sub(width) / 2,
+            y: area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        };
+        f.render_widget(Clear, popup_area);
+
+        let text: Vec<Line> = summary
+            .text
+            .lines()
+            .map(|line| Line::styled(line.to_string(), theme.text))
+            .collect();
+        let help = Line::from(vec![
+            Span::styled(" o", theme.accent),
+            Span::styled(":open in browser ", theme.muted),
+            Span::styled("Esc", theme.danger),
+            Span::styled(":close ", theme.muted),
+        ]);
+        let lookup = Paragraph::new(text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.border)
+                    .title(format!("🌐 {} · {}", summary.source.name(), summary.title))
+                    .title_bottom(help)
+                    .padding(Padding::horizontal(1)),
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0));
+        f.render_widget(lookup, popup_area);
+    }
+
+    // The passage on the left and its translation on the right, scrolled
+    // together
+    fn render_translation_pane(
+        f: &mut Frame,
+        original: &str,
+        translated: &str,
+        scroll: u16,
+        theme: &Theme,
+    ) {
+        let area = f.area();
+        let width = area.width.saturating_mul(80).saturating_div(100);
+        let height = area.height.saturating_mul(60).saturating_div(100);
+        let popup_area = Rect {
+            x: area.width.saturating_sub(width) / 2,
+            y: area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        };
+        f.render_widget(Clear, popup_area);
+
+        let help = Line::from(vec![
+            Span::styled(" y", theme.accent),
+            Span::styled(":copy translation ", theme.muted),
+            Span::styled("Esc", theme.danger),
+            Span::styled(":close ", theme.muted),
+        ]);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(theme.border)
+            .title("🌐 Translation")
+            .title_bottom(help);
+        let inner = block.inner(popup_area);
+        f.render_widget(block, popup_area);
+
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(inner);
+        for (text, column, style, borders) in [
+            (original, columns[0], theme.muted, Borders::RIGHT),
+            (translated, columns[1], theme.text, Borders::NONE),
+        ] {
+            let lines: Vec<Line> =
+                text.lines().map(|line| Line::styled(line.to_string(), style)).collect();
+            let paragraph = Paragraph::new(lines)
+                .block(
+                    Block::default()
+                        .borders(borders)
+                        .border_style(theme.border)
+                        .padding(Padding::horizontal(1)),
+                )
+                .wrap(Wrap { trim: false })
+                .scroll((scroll, 0));
+            f.render_widget(paragraph, column);
+        }
+    }
+
+    // A bar of eighth blocks `width` cells long when `value` is `max`
+    fn length_bar(value: usize, max: usize, width: usize) -> String {
+        const EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+        let eighths = (value * width * 8).div_ceil(max.max(1));
+        let mut bar = "█".repeat(eighths / 8);
+        if let Some(partial) = (eighths % 8).checked_sub(1) {
+            bar.push(EIGHTHS[partial]);
+        }
+        bar
+    }
+
+    // Word counts such as `850` or `3.4k`, to fit a narrow column
+    fn short_count(count: usize) -> String {
+        if count < 1000 {
+            count.to_string()
+        } else {
+            format!("{:.1}k", count as f64 / 1000.0)
+        }
+    }
+
+    fn render_book_info_pane(f: &mut Frame, epub: &EpubReader, theme: &Theme) {
+        let area = f.area();
+
+        let popup_width = area.width.saturating_mul(60).saturating_div(100);
+        let popup_height = area.height.saturating_mul(50).saturating_div(100);
+        let x = area.width.saturating_sub(popup_width).saturating_div(2);
+        let y = area.height.saturating_sub(popup_height).saturating_div(2);
+
+        let popup_area = Rect {
+            x,
+            y,
+            width: popup_width,
+            height: popup_height,
+        };
+
+        f.render_widget(Clear, popup_area);
+
+        let label_style = theme.muted;
+        let value_style = theme.text;
+        let field = |label: &str, value: String| {
+            Line::from(vec![
+                Span::styled(format!("{:>10}  ", label), label_style),
+                Span::styled(value, value_style),
+            ])
+        };
+
+        let mut lines = vec![
+            field("Title", epub.title.clone()),
+            field("Author", epub.author.clone()),
+        ];
+        if let Some(series) = &epub.series {
+            lines.push(field("Series", series.to_string()));
+        }
+        lines.push(field("Chapters", epub.chapter_count().to_string()));
+        for (label, value) in &epub.custom_columns {
+            lines.push(field(label, value.clone()));
+        }
+
+        let info = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.secondary)
+                    .title("ℹ Book Info")
+                    .padding(Padding::uniform(1)),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(info, popup_area);
+    }
+    fn render_stats_pane(
+        f: &mut Frame,
+        stats: &ReadingStats,
+        session: u64,
+        current_book: &str,
+        theme: &Theme,
+    ) {
+        let area = f.area();
+
+        let popup_width = area.width.saturating_mul(60).saturating_div(100);
+        let popup_height = area.height.saturating_mul(50).saturating_div(100);
+        let x = area.width.saturating_sub(popup_width).saturating_div(2);
+        let y = area.height.saturating_sub(popup_height).saturating_div(2);
+
+        let popup_area = Rect {
+            x,
+            y,
+            width: popup_width,
+            height: popup_height,
+        };
+
+        f.render_widget(Clear, popup_area);
+
+        let field = |label: &str, seconds: u64| {
+            Line::from(vec![
+                Span::styled(format!("{:>12}  ", label), theme.muted),
+                Span::styled(stats::format_duration(seconds), theme.text),
+            ])
+        };
+
+        let today = stats::today();
+        let mut lines = vec![
+            field("This session", session),
+            field("Today", stats.day_total(today)),
+            field("This week", stats.week_total(today)),
+            Line::default(),
+            Line::from(Span::styled("Books", theme.heading(2).add_modifier(Modifier::BOLD))),
+        ];
+        for (book, seconds) in stats.book_totals() {
+            let style = if book == current_book {
+                theme.accent.add_modifier(Modifier::BOLD)
+            } else {
+                theme.text
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:>8}  ", stats::format_duration(seconds)), theme.muted),
+                Span::styled(book.to_string(), style),
+            ]));
+        }
+
+        let pane = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(theme.secondary)
+                    .title("⏱ Reading Statistics")
+                    .padding(Padding::uniform(1)),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(pane, popup_area);
+    }
 }
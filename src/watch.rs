@@ -0,0 +1,52 @@
+use crate::constants::WATCH_SETTLE_MS;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// Notices when a book's file is written to, for `--watch`.
+///
+/// The directory is watched rather than the file, since tools like pandoc
+/// replace the file with a new one instead of writing into it.
+pub struct BookWatcher {
+    // Stops watching when dropped
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    path: PathBuf,
+    /// When the file last changed, if it hasn't been reported yet.
+    changed_at: Option<Instant>,
+}
+
+impl BookWatcher {
+    pub fn new(path: &Path) -> notify::Result<Self> {
+        let path = std::path::absolute(path)?;
+        let dir = path.parent().unwrap_or(Path::new("/"));
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            path,
+            changed_at: None,
+        })
+    }
+
+    /// Whether the file has changed since last asked, once it's been left
+    /// alone long enough for whatever was writing it to have finished.
+    pub fn changed(&mut self) -> bool {
+        for event in self.events.try_iter() {
+            let Ok(event) = event else { continue };
+            if !event.kind.is_access() && event.paths.contains(&self.path) {
+                self.changed_at = Some(Instant::now());
+            }
+        }
+        match self.changed_at {
+            Some(at) if at.elapsed() >= Duration::from_millis(WATCH_SETTLE_MS) => {
+                self.changed_at = None;
+                self.path.is_file()
+            }
+            _ => false,
+        }
+    }
+}
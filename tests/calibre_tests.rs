@@ -0,0 +1,82 @@
+#![cfg(feature = "calibre")]
+
+use catatau::calibre::{find_book, save_progress};
+use catatau::error::CalibreError;
+use rusqlite::Connection;
+use std::path::Path;
+use tempfile::TempDir;
+
+// A cut-down Calibre metadata.db with three books, two by the same author
+fn write_library(dir: &Path) {
+    let db = Connection::open(dir.join("metadata.db")).unwrap();
+    db.execute_batch(
+        "CREATE TABLE books (id INTEGER PRIMARY KEY, title TEXT, path TEXT);
+         CREATE TABLE authors (id INTEGER PRIMARY KEY, name TEXT);
+         CREATE TABLE books_authors_link (id INTEGER PRIMARY KEY, book INTEGER, author INTEGER);
+         CREATE TABLE data (id INTEGER PRIMARY KEY, book INTEGER, format TEXT, name TEXT);
+         CREATE TABLE custom_columns (id INTEGER PRIMARY KEY, label TEXT, datatype TEXT);
+         CREATE TABLE custom_column_1 (id INTEGER PRIMARY KEY, book INTEGER, value INT,
+                                       UNIQUE(book));
+         CREATE TABLE custom_column_2 (id INTEGER PRIMARY KEY, book INTEGER, value TEXT,
+                                       UNIQUE(book));
+         INSERT INTO custom_columns VALUES (1, 'progress', 'int'), (2, 'shelf', 'text');
+         INSERT INTO authors VALUES (1, 'George Eliot'), (2, 'James Joyce');
+         INSERT INTO books VALUES
+             (1, 'Middlemarch', 'George Eliot/Middlemarch (1)'),
+             (2, 'Dubliners', 'James Joyce/Dubliners (2)'),
+             (3, 'Middlemarch, Volume 2', 'George Eliot/Middlemarch, Volume 2 (3)');
+         INSERT INTO books_authors_link (book, author) VALUES (1, 1), (2, 2), (3, 1);
+         INSERT INTO data (book, format, name) VALUES
+             (1, 'EPUB', 'Middlemarch - George Eliot'),
+             (2, 'EPUB', 'Dubliners - James Joyce'),
+             (3, 'EPUB', 'Middlemarch, Volume 2 - George Eliot');",
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_calibre_books_are_found_by_id_or_author_and_title() {
+    let dir = TempDir::new().unwrap();
+    write_library(dir.path());
+
+    let book = find_book(dir.path(), "2").unwrap();
+    assert_eq!(book.title, "Dubliners");
+    assert_eq!(book.authors, "James Joyce");
+    assert_eq!(
+        book.path,
+        dir.path().join("James Joyce/Dubliners (2)/Dubliners - James Joyce.epub")
+    );
+
+    // An exact title wins over one that only contains it
+    assert_eq!(find_book(dir.path(), "eliot/middlemarch").unwrap().id, 1);
+    assert_eq!(find_book(dir.path(), "Volume 2").unwrap().id, 3);
+    assert!(matches!(find_book(dir.path(), "Eliot/"), Err(CalibreError::AmbiguousBook(books))
+        if books.len() == 2));
+    assert!(matches!(
+        find_book(dir.path(), "Joyce/Ulysses"),
+        Err(CalibreError::BookNotFound(_))
+    ));
+}
+
+#[test]
+fn test_reading_progress_is_written_to_a_calibre_column() {
+    let dir = TempDir::new().unwrap();
+    write_library(dir.path());
+
+    save_progress(dir.path(), 1, "#progress", 41.6).unwrap();
+    save_progress(dir.path(), 1, "progress", 57.2).unwrap();
+    let db = Connection::open(dir.path().join("metadata.db")).unwrap();
+    let value: i64 = db
+        .query_row("SELECT value FROM custom_column_1 WHERE book = 1", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(value, 57);
+
+    assert!(matches!(
+        save_progress(dir.path(), 1, "shelf", 10.0),
+        Err(CalibreError::UnsupportedColumn { .. })
+    ));
+    assert!(matches!(
+        save_progress(dir.path(), 1, "missing", 10.0),
+        Err(CalibreError::ColumnNotFound(_))
+    ));
+}
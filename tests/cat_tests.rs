@@ -0,0 +1,51 @@
+mod common;
+
+use common::EpubBuilder;
+use tempfile::TempDir;
+use catatau::EpubReader;
+use catatau::cat::{ChapterRange, write_text};
+
+fn create_epub(chapters: &[&str]) -> (TempDir, std::path::PathBuf) {
+    let book = chapters.iter().fold(EpubBuilder::new("Cat Book"), |book, body| book.body(body));
+    book.build("cat.epub")
+}
+
+#[test]
+fn test_chapter_ranges_count_from_one() {
+    let range = |text: &str| text.parse::<ChapterRange>();
+    assert_eq!(range("3").unwrap().indices(5), Ok(2..3));
+    assert_eq!(range("2..4").unwrap().indices(5), Ok(1..4));
+    assert_eq!(range("4..").unwrap().indices(5), Ok(3..5));
+    assert_eq!(range("..2").unwrap().indices(5), Ok(0..2));
+    assert_eq!(ChapterRange::default().indices(5), Ok(0..5));
+    assert!(range("6").unwrap().indices(5).unwrap_err().contains("the book has 5"));
+    assert!(range("0").is_err());
+    assert!(range("4..2").unwrap_err().contains("comes after"));
+    assert!(range("x").is_err());
+}
+
+#[test]
+fn test_book_text_is_printed_at_the_width_asked_for() {
+    let long = "word ".repeat(40);
+    let (_temp_dir, epub_path) = create_epub(&[
+        "<h1>One</h1><p>The first chapter.</p>",
+        &format!("<h1>Two</h1><p>{long}</p>"),
+        "<h1>Three</h1><p>The last chapter.</p>",
+    ]);
+    let epub = EpubReader::new(&epub_path).unwrap();
+    epub.set_text_width(30);
+
+    let mut out = Vec::new();
+    write_text(&epub, 0..3, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains("The first chapter."));
+    assert!(text.contains("The last chapter."));
+    assert!(text.lines().all(|line| line.chars().count() <= 30), "{text}");
+    assert!(!text.lines().any(|line| line != line.trim_end()));
+
+    let mut out = Vec::new();
+    write_text(&epub, 1..2, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains("Two"));
+    assert!(!text.contains("first chapter"));
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::EpubBuilder;
+use tempfile::TempDir;
+use catatau::EpubReaderOptions;
+use catatau::check::{check_epub, Severity};
+
+fn create_epub(mimetype: &str, manifest: &str, spine: &str, files: &[(&str, &str)]) -> (TempDir, std::path::PathBuf) {
+    let book = EpubBuilder::new("Check Book")
+        .opf_path("OEBPS/content.opf")
+        .mimetype(mimetype)
+        .manifest(manifest)
+        .spine(spine);
+    let book = files.iter().fold(book, |book, (name, body)| book.file(name, body));
+    book.build("check.epub")
+}
+
+const CHAPTER_ONE: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>One</title></head>
+<body><h1 id="top">One</h1><p>The first chapter links to <a href="two.xhtml#middle">the second</a> and the web <a href="https://example.com">site</a>.</p></body></html>"#;
+const CHAPTER_TWO: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>Two</title></head>
+<body><h1>Two</h1><p id="middle">The second chapter links back to <a href="one.xhtml#top">the first</a> for good measure.</p></body></html>"#;
+
+#[test]
+fn test_check_valid_epub() {
+    let (_temp_dir, epub_path) = create_epub(
+        "application/epub+zip",
+        r#"<item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+           <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>"#,
+        r#"<itemref idref="one"/><itemref idref="two"/>"#,
+        &[("OEBPS/one.xhtml", CHAPTER_ONE), ("OEBPS/two.xhtml", CHAPTER_TWO)],
+    );
+
+    let report = check_epub(&epub_path, &EpubReaderOptions::default());
+    assert!(!report.has_errors(), "unexpected issues: {:?}", report.issues);
+    assert_eq!(report.warning_count(), 0);
+    assert_eq!(report.to_json()["valid"], true);
+}
+
+#[test]
+fn test_check_reports_problems() {
+    let broken_links = r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>Two</title></head>
+<body><p>This chapter points at <a href="missing.xhtml">a missing file</a> and <a href="one.xhtml#nowhere">a missing anchor</a>.</p></body></html>"#;
+    let (_temp_dir, epub_path) = create_epub(
+        "application/zip",
+        r#"<item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+           <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+           <item id="ghost" href="ghost.xhtml" media-type="application/xhtml+xml"/>"#,
+        r#"<itemref idref="one"/><itemref idref="two"/><itemref idref="unknown"/>"#,
+        &[("OEBPS/one.xhtml", CHAPTER_ONE), ("OEBPS/two.xhtml", broken_links)],
+    );
+
+    let report = check_epub(&epub_path, &EpubReaderOptions::default());
+    let codes: Vec<_> = report.issues.iter().map(|issue| (issue.severity, issue.code)).collect();
+
+    assert!(codes.contains(&(Severity::Error, "bad-mimetype")));
+    assert!(codes.contains(&(Severity::Error, "missing-manifest-item")));
+    assert!(codes.contains(&(Severity::Error, "missing-spine-item")));
+    assert!(codes.contains(&(Severity::Error, "broken-link")));
+    assert!(codes.contains(&(Severity::Warning, "missing-anchor")));
+    assert!(report.has_errors());
+    assert_eq!(report.to_json()["valid"], false);
+}
+
+#[test]
+fn test_check_resolves_hrefs_exactly() {
+    let chapter = r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>Two</title></head>
+<body><p><a href="One.xhtml#top">Wrong case</a>, <a href="../OEBPS/./one.xhtml#top">the long way</a>,
+<a href="../../one.xhtml">above the root</a> and <a href="t%C3%A9.xhtml">escaped</a>.</p></body></html>"#;
+    let (_temp_dir, epub_path) = create_epub(
+        "application/epub+zip",
+        r#"<item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+           <item id="two" href="Two.xhtml" media-type="application/xhtml+xml"/>
+           <item id="te" href="té.xhtml" media-type="application/xhtml+xml"/>"#,
+        r#"<itemref idref="one"/><itemref idref="two"/><itemref idref="te"/>"#,
+        &[
+            ("OEBPS/one.xhtml", CHAPTER_ONE),
+            ("OEBPS/two.xhtml", CHAPTER_TWO),
+            ("OEBPS/té.xhtml", chapter),
+        ],
+    );
+
+    let report = check_epub(&epub_path, &EpubReaderOptions::default());
+    let messages: Vec<(&str, &str)> = report
+        .issues
+        .iter()
+        .map(|issue| (issue.code, issue.message.as_str()))
+        .collect();
+    assert!(
+        messages.contains(&(
+            "missing-manifest-item",
+            "manifest item \"two\" is not in the archive \
+             (only \"OEBPS/two.xhtml\", which differs in case or folder)"
+        )),
+        "{messages:?}"
+    );
+    let broken: Vec<&str> = messages
+        .iter()
+        .filter(|(code, _)| *code == "broken-link")
+        .map(|(_, message)| *message)
+        .collect();
+    assert_eq!(broken.len(), 2, "{broken:?}");
+    assert!(broken[0].starts_with("link target \"One.xhtml#top\""));
+    assert!(broken[1].starts_with("link target \"../../one.xhtml\""));
+}
@@ -0,0 +1,158 @@
+//! Books made up for tests: an EPUB put together from the parts of its
+//! package document and whatever other files it should hold.
+
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use zip::{ZipWriter, write::FileOptions, CompressionMethod};
+
+pub struct EpubBuilder {
+    mimetype: String,
+    opf_path: String,
+    version: String,
+    metadata: String,
+    manifest: String,
+    spine: String,
+    chapters: usize,
+    package: Option<String>,
+    files: Vec<(String, Vec<u8>)>,
+    omitted: Vec<String>,
+}
+
+impl EpubBuilder {
+    /// A book called `title`, with no chapters yet and its package document
+    /// at `content.opf`.
+    pub fn new(title: &str) -> Self {
+        Self {
+            mimetype: "application/epub+zip".to_string(),
+            opf_path: "content.opf".to_string(),
+            version: "2.0".to_string(),
+            metadata: format!("<dc:title>{title}</dc:title>"),
+            manifest: String::new(),
+            spine: String::new(),
+            chapters: 0,
+            package: None,
+            files: Vec::new(),
+            omitted: Vec::new(),
+        }
+    }
+
+    /// Where in the archive the package document goes.
+    pub fn opf_path(mut self, path: &str) -> Self {
+        self.opf_path = path.to_string();
+        self
+    }
+
+    /// The EPUB version the package document declares.
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// What the `mimetype` file says, to make broken books.
+    pub fn mimetype(mut self, mimetype: &str) -> Self {
+        self.mimetype = mimetype.to_string();
+        self
+    }
+
+    /// Metadata elements after the title.
+    pub fn metadata(mut self, xml: &str) -> Self {
+        self.metadata.push_str(xml);
+        self
+    }
+
+    /// Manifest items, as written.
+    pub fn manifest(mut self, xml: &str) -> Self {
+        self.manifest.push_str(xml);
+        self
+    }
+
+    /// Spine itemrefs, as written.
+    pub fn spine(mut self, xml: &str) -> Self {
+        self.spine.push_str(xml);
+        self
+    }
+
+    /// The whole package document, written instead of the one made from the
+    /// title, manifest and spine.
+    pub fn package(mut self, xml: &str) -> Self {
+        self.package = Some(xml.to_string());
+        self
+    }
+
+    /// A chapter at `href`, relative to the package document, added to the
+    /// manifest and the end of the spine.
+    pub fn chapter(mut self, href: &str, html: &str) -> Self {
+        let id = format!("c{}", self.chapters);
+        self.chapters += 1;
+        self.manifest.push_str(&format!(
+            r#"<item id="{id}" href="{href}" media-type="application/xhtml+xml"/>"#
+        ));
+        self.spine.push_str(&format!(r#"<itemref idref="{id}"/>"#));
+        let dir = Path::new(&self.opf_path).parent().unwrap_or(Path::new(""));
+        let name = dir.join(href).to_string_lossy().into_owned();
+        self.file(&name, html)
+    }
+
+    /// A chapter with `body` as the whole of its `<body>`.
+    pub fn body(self, body: &str) -> Self {
+        let href = format!("c{}.xhtml", self.chapters);
+        self.chapter(&href, &format!("<html><body>{body}</body></html>"))
+    }
+
+    /// Any other file, at `name` in the archive.
+    pub fn file(mut self, name: &str, contents: impl AsRef<[u8]>) -> Self {
+        self.files.push((name.to_string(), contents.as_ref().to_vec()));
+        self
+    }
+
+    /// Leave `name` out of the archive, such as the container or the package
+    /// document, to make broken books.
+    pub fn omit(mut self, name: &str) -> Self {
+        self.omitted.push(name.to_string());
+        self
+    }
+
+    /// Write the book over `path`.
+    pub fn write(&self, path: &Path) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        zip.start_file("mimetype", FileOptions::<()>::default().compression_method(CompressionMethod::Stored)).unwrap();
+        zip.write_all(self.mimetype.as_bytes()).unwrap();
+        let container = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="{}" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#, self.opf_path);
+        let package = self.package.clone().unwrap_or_else(|| format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="{}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">{}</metadata>
+  <manifest>{}</manifest>
+  <spine>{}</spine>
+</package>"#, self.version, self.metadata, self.manifest, self.spine));
+        let parts = [
+            ("META-INF/container.xml", container.as_bytes()),
+            (self.opf_path.as_str(), package.as_bytes()),
+        ];
+        let files = self.files.iter().map(|(name, contents)| (name.as_str(), contents.as_slice()));
+        for (name, contents) in parts.into_iter().chain(files) {
+            if self.omitted.iter().any(|omitted| omitted == name) {
+                continue;
+            }
+            zip.start_file(name, FileOptions::<()>::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// Write the book as `name` in a new temporary directory.
+    pub fn build(&self, name: &str) -> (TempDir, PathBuf) {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(name);
+        self.write(&path);
+        (temp_dir, path)
+    }
+}
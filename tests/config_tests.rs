@@ -0,0 +1,106 @@
+#![cfg(feature = "tui")]
+
+use catatau::Profile;
+use catatau::config::Config;
+use catatau::convert::ParagraphStyle;
+use catatau::error::ConfigError;
+use catatau::translate::Backend;
+use std::path::PathBuf;
+use tempfile::TempDir;
+use tracing::level_filters::LevelFilter;
+
+#[test]
+fn test_config_file_settings() {
+    let config = Config::parse(
+        r#"
+        theme = "sepia"
+        width = 72
+        line-spacing = 1.5
+        profile = "eink"
+        confirm-quit = true
+        resume = false
+        cache-mb = 12
+        library = ["/srv/books", "~/Documents/papers"]
+        translator = "deepl"
+        log-file = "~/catatau.log"
+        log-level = "debug"
+
+        [scroll]
+        paged = true
+        lines = 3
+
+        [paragraphs]
+        style = "indented"
+        indent = 4
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.theme.unwrap().name, "sepia");
+    assert_eq!(config.width, Some(72));
+    assert_eq!(config.line_spacing, Some("1.5".parse().unwrap()));
+    assert_eq!(config.profile, Some(Profile::Eink));
+    assert!(config.confirm_quit);
+    assert!(!config.resume);
+    assert_eq!(config.cache_mb.map(|mb| mb.get()), Some(12));
+    assert_eq!(config.library[0], PathBuf::from("/srv/books"));
+    if let Some(home) = std::env::var_os("HOME") {
+        assert_eq!(config.library[1], PathBuf::from(home).join("Documents/papers"));
+    }
+    assert_eq!(config.translator, Some(Backend::DeepL));
+    if let Some(home) = std::env::var_os("HOME") {
+        assert_eq!(config.log_file, Some(PathBuf::from(home).join("catatau.log")));
+    }
+    assert_eq!(config.log_level, Some(LevelFilter::DEBUG));
+    assert!(config.scroll.paged);
+    assert_eq!(config.scroll.lines.get(), 3);
+    assert_eq!(config.paragraphs.style, Some(ParagraphStyle::Indented));
+    assert_eq!(config.paragraphs.indent, 4);
+}
+
+#[test]
+fn test_missing_settings_keep_their_defaults() {
+    let config = Config::parse("width = 90").unwrap();
+    assert_eq!(config.width, Some(90));
+    assert!(config.theme.is_none());
+    assert!(config.resume);
+    assert!(!config.scroll.paged);
+    assert_eq!(config.scroll.lines.get(), 1);
+    assert_eq!(config.paragraphs.style, None);
+    assert_eq!(config.paragraphs.indent, 2);
+    assert!(config.library.is_empty());
+}
+
+#[test]
+fn test_bad_config_values_are_explained() {
+    let error = |text: &str| Config::parse(text).unwrap_err();
+
+    assert!(error("colour = \"red\"").contains("unknown field `colour`"));
+    assert!(error("theme = \"neon\"").contains("unknown theme 'neon'"));
+    assert!(error("width = 5").contains("width must be at least 20 columns"));
+    assert!(error("width = \"wide\"").contains("line 1"));
+    assert!(error("line-spacing = 4").contains("line spacing must be"));
+    assert!(error("profile = \"crt\"").contains("unknown profile 'crt'"));
+    assert!(error("cache-mb = 0").contains("nonzero"));
+    assert!(error("[scroll]\nlines = 0").contains("nonzero"));
+    assert!(error("[paragraphs]\nstyle = \"ragged\"").contains("unknown paragraph style"));
+    assert!(error("[paragraphs]\nindent = 20").contains("at most 8 columns"));
+    assert!(error("translator = \"babelfish\"").contains("unknown translator"));
+    assert!(error("log-level = \"loud\"").contains("line 1"));
+}
+
+#[test]
+fn test_config_files_are_read_from_disk() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, "confirm-quit = true\n").unwrap();
+    assert!(Config::load(&path).unwrap().confirm_quit);
+
+    std::fs::write(&path, "confirm-quit = \"yes\"\n").unwrap();
+    let error = Config::load(&path).unwrap_err();
+    assert!(matches!(error, ConfigError::Invalid { .. }));
+    assert!(error.to_string().contains(&path.display().to_string()));
+
+    let missing = dir.path().join("missing.toml");
+    assert!(matches!(Config::load(&missing), Err(ConfigError::Io { .. })));
+}
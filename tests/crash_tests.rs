@@ -0,0 +1,56 @@
+use catatau::crash::{self, Snapshot};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+#[test]
+fn test_the_last_keys_and_position_are_remembered() {
+    crash::note_position(&PathBuf::from("/books/moby-dick.epub"), 2, 40);
+    for key in 0..30 {
+        crash::note_key(key);
+    }
+
+    let snapshot = crash::snapshot().unwrap();
+    assert_eq!(snapshot.book, Some(PathBuf::from("/books/moby-dick.epub")));
+    assert_eq!((snapshot.chapter, snapshot.offset), (2, 40));
+    assert_eq!(snapshot.keys.len(), 20);
+    assert_eq!(snapshot.keys.front().map(String::as_str), Some("10"));
+    assert_eq!(snapshot.keys.back().map(String::as_str), Some("29"));
+}
+
+#[test]
+fn test_crash_reports_say_what_the_reader_was_doing() {
+    let snapshot = Snapshot {
+        book: Some(PathBuf::from("/books/moby-dick.epub")),
+        chapter: 2,
+        offset: 40,
+        keys: VecDeque::from(["j".to_string(), "<C-d>".to_string(), "]".to_string()]),
+    };
+    let report = crash::report(
+        "panicked at src/ui.rs:10:5:\nindex out of bounds",
+        &"0: catatau::ui::App::run",
+        Some(&snapshot),
+    );
+    assert!(report.contains("index out of bounds"));
+    assert!(report.contains("src/ui.rs:10:5"));
+    assert!(report.contains("Book:      /books/moby-dick.epub"));
+    assert!(report.contains("chapter 3, line 41"));
+    assert!(report.contains("Last keys: j <C-d> ]"));
+    assert!(report.contains("Backtrace:\n0: catatau::ui::App::run"));
+
+    let report = crash::report("panicked", &"", None);
+    assert!(report.contains("couldn't be read"));
+}
+
+#[test]
+fn test_crash_reports_are_written_to_the_state_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = crash::write_report(temp_dir.path(), "catatau crashed\n").unwrap();
+    assert!(path.starts_with(temp_dir.path().join("crash-reports")));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "catatau crashed\n");
+
+    // A second crash in the same second doesn't write over the first
+    let again = crash::write_report(temp_dir.path(), "catatau crashed again\n").unwrap();
+    assert_ne!(again, path);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "catatau crashed\n");
+}
@@ -0,0 +1,56 @@
+use catatau::css::{Style, Stylesheet, TextAlign};
+
+#[test]
+fn test_declarations_the_reader_follows_are_read() {
+    let style =
+        Style::parse("font-style: Italic; font-weight: 700; color: red; text-align: center");
+    assert_eq!(style.italic, Some(true));
+    assert_eq!(style.bold, Some(true));
+    assert_eq!(style.align, Some(TextAlign::Center));
+    assert_eq!(style.indent, None);
+
+    assert_eq!(Style::parse("font-weight: normal !important").bold, Some(false));
+    assert_eq!(Style::parse("text-indent: 1.5em").indent, Some(3));
+    assert_eq!(Style::parse("text-indent: 0").indent, Some(0));
+    assert_eq!(Style::parse("text-indent: 12pt").indent, Some(2));
+    assert_eq!(Style::parse("text-indent: 40em").indent, Some(8));
+    assert_eq!(Style::parse("text-indent: -1em").indent, None);
+    assert_eq!(Style::parse("text-indent: 5%").indent, None);
+}
+
+#[test]
+fn test_more_specific_and_later_rules_win() {
+    let stylesheet = Stylesheet::parse(
+        r#"/* body text */
+        p { text-indent: 1em; text-align: justify }
+        @media amzn-kf8 { p { text-indent: 4em } }
+        @import url("fonts.css");
+        p.first, .noindent { text-indent: 0 }
+        .centre { text-align: center; font-style: italic }
+        p.centre { font-style: normal }
+        div > p, a:hover, #title { font-weight: bold }
+        .centre { font-weight: bold }"#,
+    );
+
+    let plain = stylesheet.style_for("p", &[]);
+    assert_eq!(plain.indent, Some(2));
+    assert_eq!(plain.align, Some(TextAlign::Left));
+    assert_eq!(plain.bold, None, "rules with other selectors are passed over");
+    assert_eq!(stylesheet.style_for("p", &["first"]).indent, Some(0));
+    assert_eq!(stylesheet.style_for("div", &["noindent"]).indent, Some(0));
+
+    let centre = stylesheet.style_for("p", &["centre", "extra"]);
+    assert_eq!(centre.align, Some(TextAlign::Center));
+    assert_eq!(centre.italic, Some(false));
+    assert_eq!(centre.bold, Some(true));
+    assert_eq!(centre.indent, Some(2));
+    assert_eq!(stylesheet.style_for("span", &["centre"]).italic, Some(true));
+}
+
+#[test]
+fn test_inherited_properties_fill_in_unset_ones() {
+    let parent = Style::parse("font-style: italic; text-align: right");
+    let child = Style::parse("font-style: normal").or(parent);
+    assert_eq!(child.italic, Some(false));
+    assert_eq!(child.align, Some(TextAlign::Right));
+}
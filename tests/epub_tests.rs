@@ -1,49 +1,15 @@
-use std::fs::File;
-use std::io::Write;
+mod common;
+
+use common::EpubBuilder;
 use tempfile::TempDir;
-use zip::{ZipWriter, write::FileOptions, CompressionMethod};
-use catatau::{EpubReader, EpubError};
+use catatau::{EpubReader, EpubReaderOptions, EpubError};
+use catatau::convert::SpanStyle;
+use catatau::css::TextAlign;
 
 fn create_test_epub() -> (TempDir, std::path::PathBuf) {
-    let temp_dir = TempDir::new().unwrap();
-    let epub_path = temp_dir.path().join("test.epub");
-    let file = File::create(&epub_path).unwrap();
-    let mut zip = ZipWriter::new(file);
-
-    // Add mimetype
-    zip.start_file("mimetype", FileOptions::<()>::default().compression_method(CompressionMethod::Stored)).unwrap();
-    zip.write_all(b"application/epub+zip").unwrap();
-
-    // Add container.xml
-    zip.start_file("META-INF/container.xml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
-<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
-  <rootfiles>
-    <rootfile full-path="content.opf" media-type="application/oebps-package+xml"/>
-  </rootfiles>
-</container>"#).unwrap();
-
-    // Add OPF file
-    zip.start_file("content.opf", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
-<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="uuid_id" version="2.0">
-  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
-    <dc:title>Test Book</dc:title>
-    <dc:creator>Test Author</dc:creator>
-  </metadata>
-  <manifest>
-    <item id="chapter1" href="OEBPS/chapter1.xhtml" media-type="application/xhtml+xml"/>
-    <item id="chapter2" href="OEBPS/chapter2.xhtml" media-type="application/xhtml+xml"/>
-  </manifest>
-  <spine>
-    <itemref idref="chapter1"/>
-    <itemref idref="chapter2"/>
-  </spine>
-</package>"#).unwrap();
-
-    // Add chapter files
-    zip.start_file("OEBPS/chapter1.xhtml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+    EpubBuilder::new("Test Book")
+        .metadata("<dc:creator>Test Author</dc:creator><dc:language>en</dc:language>")
+        .chapter("OEBPS/chapter1.xhtml", r#"<?xml version="1.0" encoding="UTF-8"?>
 <html xmlns="http://www.w3.org/1999/xhtml">
 <head><title>Chapter 1</title></head>
 <body>
@@ -51,10 +17,8 @@ fn create_test_epub() -> (TempDir, std::path::PathBuf) {
 <p>This is the first chapter of our test book. It contains some sample text that we can search through and navigate.</p>
 <p>Lorem ipsum dolor sit amet, consectetur adipiscing elit. Sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.</p>
 </body>
-</html>"#).unwrap();
-
-    zip.start_file("OEBPS/chapter2.xhtml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+</html>"#)
+        .chapter("OEBPS/chapter2.xhtml", r#"<?xml version="1.0" encoding="UTF-8"?>
 <html xmlns="http://www.w3.org/1999/xhtml">
 <head><title>Chapter 2</title></head>
 <body>
@@ -62,10 +26,8 @@ fn create_test_epub() -> (TempDir, std::path::PathBuf) {
 <p>This is the second chapter. It has different content that we can use for testing search functionality.</p>
 <p>Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat.</p>
 </body>
-</html>"#).unwrap();
-
-    zip.finish().unwrap();
-    (temp_dir, epub_path)
+</html>"#)
+        .build("test.epub")
 }
 
 #[test]
@@ -75,6 +37,7 @@ fn test_epub_parsing() {
 
     assert_eq!(epub.title, "Test Book");
     assert_eq!(epub.author, "Test Author");
+    assert_eq!(epub.language.as_deref(), Some("en"));
     assert_eq!(epub.chapter_count(), 2);
 
     let chapter0 = epub.get_chapter(0).expect("Failed to get chapter 0");
@@ -101,6 +64,33 @@ fn test_chapter_content_extraction() {
     assert!(!chapter1.content.contains("<p>"));
 }
 
+#[test]
+fn test_chapter_lines_are_reached_directly() {
+    let (_temp_dir, epub_path) = create_test_epub();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+
+    let chapter = epub.get_chapter(0).expect("Failed to get chapter 0");
+    let lines: Vec<&str> = chapter.content.lines().collect();
+    assert_eq!(chapter.line_count(), lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        assert_eq!(chapter.line(index), Some(*line));
+        assert_eq!(chapter.lines_from(index).collect::<Vec<_>>(), lines[index..]);
+    }
+    assert_eq!(chapter.line(lines.len()), None);
+    assert_eq!(chapter.lines_from(lines.len() + 5).count(), 0);
+}
+
+#[test]
+fn test_chapter_html_is_untouched() {
+    let (_temp_dir, epub_path) = create_test_epub();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+
+    let html = epub.get_chapter_html(0).expect("Failed to get chapter 0 source");
+    assert!(html.contains("<h1>"));
+    assert!(html.contains("Lorem ipsum"));
+    assert!(matches!(epub.get_chapter_html(999), Err(EpubError::InvalidChapterIndex(999))));
+}
+
 #[test]
 fn test_search_content() {
     let (_temp_dir, epub_path) = create_test_epub();
@@ -116,6 +106,26 @@ fn test_search_content() {
     assert_eq!(search_results_2[0].chapter_index, 1);
 }
 
+#[test]
+fn test_search_results_point_at_their_lines() {
+    let (_temp_dir, epub_path) = EpubBuilder::new("Repeats")
+        .body("<p>One cat.</p><p>A cat and another cat.</p><p>No animals.</p><p>Last cat.</p>")
+        .build("repeats.epub");
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB");
+    let chapter = epub.get_chapter(0).unwrap();
+
+    // One result a line, however often the line matches
+    let results = epub.search("cat");
+    assert_eq!(results.len(), 3);
+    for result in &results {
+        let line = chapter.line(result.line_number).unwrap();
+        assert!(line.contains("cat"));
+        assert!(chapter.content[result.position..].starts_with(line));
+        assert!(result.context.contains(line));
+    }
+    assert_eq!(epub.get_chapter_line_count(0), chapter.content.lines().count());
+}
+
 #[test]
 fn test_empty_search() {
     let (_temp_dir, epub_path) = create_test_epub();
@@ -139,17 +149,11 @@ fn test_case_insensitive_search() {
 
 #[test] 
 fn test_missing_container_xml() {
-    let temp_dir = TempDir::new().unwrap();
-    let epub_path = temp_dir.path().join("no_container.epub");
-    let file = File::create(&epub_path).unwrap();
-    let mut zip = ZipWriter::new(file);
-    
-    // Add mimetype but no container.xml
-    zip.start_file("mimetype", FileOptions::<()>::default().compression_method(CompressionMethod::Stored)).unwrap();
-    zip.write_all(b"application/epub+zip").unwrap();
-    
-    zip.finish().unwrap();
-    
+    let (_temp_dir, epub_path) = EpubBuilder::new("Test Book")
+        .omit("META-INF/container.xml")
+        .omit("content.opf")
+        .build("no_container.epub");
+
     let result = EpubReader::new(&epub_path);
     assert!(result.is_err());
     match result.unwrap_err() {
@@ -160,26 +164,12 @@ fn test_missing_container_xml() {
 
 #[test]
 fn test_missing_opf_file() {
-    let temp_dir = TempDir::new().unwrap();
-    let epub_path = temp_dir.path().join("no_opf.epub");
-    let file = File::create(&epub_path).unwrap();
-    let mut zip = ZipWriter::new(file);
-    
-    // Add mimetype
-    zip.start_file("mimetype", FileOptions::<()>::default().compression_method(CompressionMethod::Stored)).unwrap();
-    zip.write_all(b"application/epub+zip").unwrap();
-    
-    // Add container.xml pointing to non-existent OPF
-    zip.start_file("META-INF/container.xml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
-<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
-  <rootfiles>
-    <rootfile full-path="missing.opf" media-type="application/oebps-package+xml"/>
-  </rootfiles>
-</container>"#).unwrap();
-    
-    zip.finish().unwrap();
-    
+    // The container points to a package document that isn't there
+    let (_temp_dir, epub_path) = EpubBuilder::new("Test Book")
+        .opf_path("missing.opf")
+        .omit("missing.opf")
+        .build("no_opf.epub");
+
     let result = EpubReader::new(&epub_path);
     assert!(result.is_err());
     match result.unwrap_err() {
@@ -190,27 +180,9 @@ fn test_missing_opf_file() {
 
 #[test] 
 fn test_invalid_opf_structure() {
-    let temp_dir = TempDir::new().unwrap();
-    let epub_path = temp_dir.path().join("invalid_opf.epub");
-    let file = File::create(&epub_path).unwrap();
-    let mut zip = ZipWriter::new(file);
-    
-    // Add mimetype
-    zip.start_file("mimetype", FileOptions::<()>::default().compression_method(CompressionMethod::Stored)).unwrap();
-    zip.write_all(b"application/epub+zip").unwrap();
-    
-    // Add container.xml
-    zip.start_file("META-INF/container.xml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
-<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
-  <rootfiles>
-    <rootfile full-path="content.opf" media-type="application/oebps-package+xml"/>
-  </rootfiles>
-</container>"#).unwrap();
-    
-    // Add OPF file with no spine (invalid structure)
-    zip.start_file("content.opf", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+    // A package document with no spine
+    let (_temp_dir, epub_path) = EpubBuilder::new("Test Book")
+        .package(r#"<?xml version="1.0" encoding="UTF-8"?>
 <package xmlns="http://www.idpf.org/2007/opf" unique-identifier="uuid_id" version="2.0">
   <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
     <dc:title>Test Book</dc:title>
@@ -219,10 +191,9 @@ fn test_invalid_opf_structure() {
   <manifest>
     <item id="chapter1" href="chapter1.xhtml" media-type="application/xhtml+xml"/>
   </manifest>
-</package>"#).unwrap();
-    
-    zip.finish().unwrap();
-    
+</package>"#)
+        .build("invalid_opf.epub");
+
     let result = EpubReader::new(&epub_path);
     assert!(result.is_err());
     match result.unwrap_err() {
@@ -233,41 +204,12 @@ fn test_invalid_opf_structure() {
 
 #[test]
 fn test_chapter_not_found() {
-    let temp_dir = TempDir::new().unwrap();
-    let epub_path = temp_dir.path().join("missing_chapters.epub");
-    let file = File::create(&epub_path).unwrap();
-    let mut zip = ZipWriter::new(file);
-    
-    // Add mimetype
-    zip.start_file("mimetype", FileOptions::<()>::default().compression_method(CompressionMethod::Stored)).unwrap();
-    zip.write_all(b"application/epub+zip").unwrap();
-    
-    // Add container.xml
-    zip.start_file("META-INF/container.xml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
-<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
-  <rootfiles>
-    <rootfile full-path="content.opf" media-type="application/oebps-package+xml"/>
-  </rootfiles>
-</container>"#).unwrap();
-    
-    // Add OPF file referencing missing chapter files
-    zip.start_file("content.opf", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
-<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="uuid_id" version="2.0">
-  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
-    <dc:title>Test Book</dc:title>
-    <dc:creator>Test Author</dc:creator>
-  </metadata>
-  <manifest>
-    <item id="chapter1" href="missing_chapter.xhtml" media-type="application/xhtml+xml"/>
-  </manifest>
-  <spine>
-    <itemref idref="chapter1"/>
-  </spine>
-</package>"#).unwrap();
-    
-    zip.finish().unwrap();
+    // The manifest and spine name a chapter the archive doesn't hold
+    let (_temp_dir, epub_path) = EpubBuilder::new("Test Book")
+        .metadata("<dc:creator>Test Author</dc:creator>")
+        .manifest(r#"<item id="chapter1" href="missing_chapter.xhtml" media-type="application/xhtml+xml"/>"#)
+        .spine(r#"<itemref idref="chapter1"/>"#)
+        .build("missing_chapters.epub");
 
     let result = EpubReader::new(&epub_path);
     assert!(result.is_ok());
@@ -288,6 +230,7 @@ fn test_lazy_loading() {
     let chapter0_again = epub.get_chapter(0).expect("Failed to load chapter 0 from cache");
     assert_eq!(chapter0_again.title, "Chapter 1");
     assert_eq!(chapter0.content, chapter0_again.content);
+    assert!(std::sync::Arc::ptr_eq(&chapter0, &chapter0_again));
 }
 
 #[test]
@@ -307,6 +250,7 @@ fn test_invalid_chapter_index() {
 fn test_chapter_caching() {
     let (_temp_dir, epub_path) = create_test_epub();
     let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+    assert!(!epub.is_chapter_cached(0));
 
     for _ in 0..3 {
         let chapter = epub.get_chapter(0).expect("Failed to load chapter");
@@ -317,4 +261,871 @@ fn test_chapter_caching() {
         let chapter = epub.get_chapter(1).expect("Failed to load chapter");
         assert!(chapter.content.contains("Chapter Two"));
     }
-}
\ No newline at end of file
+    assert!(epub.is_chapter_cached(0) && epub.is_chapter_cached(1));
+
+    // Re-wrapping the text leaves the chapters to be converted again
+    epub.set_text_width(epub.text_width() + 10);
+    assert!(!epub.is_chapter_cached(0));
+}
+
+#[test]
+fn test_chapters_can_be_read_from_many_threads_at_once() {
+    let (_temp_dir, epub_path) = create_test_epub();
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let chapters: Vec<String> = (0..epub.chapter_count())
+        .map(|index| epub.get_chapter_html(index).unwrap())
+        .collect();
+
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for _ in 0..20 {
+                    for (index, html) in chapters.iter().enumerate() {
+                        assert_eq!(&epub.get_chapter_html(index).unwrap(), html);
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn test_chapter_cache_keeps_to_its_budget() {
+    let (_temp_dir, epub_path) = create_test_epub();
+    let first = EpubReader::new(&epub_path).unwrap().get_chapter(0).unwrap().memory_size();
+    assert!(first > 0);
+
+    // Room for the first chapter only, and then only until another is read
+    let epub = EpubReaderOptions::new().cache_budget(first).open(&epub_path).unwrap();
+    epub.get_chapter(0).unwrap();
+    assert!(epub.is_chapter_cached(0));
+    epub.get_chapter(1).unwrap();
+    assert!(epub.is_chapter_cached(1));
+    assert!(!epub.is_chapter_cached(0));
+
+    // Nothing fits, but the chapter being read is kept all the same
+    let epub = EpubReaderOptions::new().cache_budget(0).open(&epub_path).unwrap();
+    epub.get_chapter(1).unwrap();
+    assert!(epub.is_chapter_cached(1));
+}
+
+#[test]
+fn test_chapter_headings() {
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Sections</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+  </spine>
+</package>"#;
+    let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>Sections</title></head>
+<body>
+<h1>Part One</h1>
+<p>Opening paragraph with enough text to count as real content for the reader.</p>
+<h2>First Section</h2>
+<pre><code># not a heading</code></pre>
+<h3>A Subsection</h3>
+<p>Closing paragraph with enough text to count as real content for the reader.</p>
+</body>
+</html>"#;
+    let (_temp_dir, epub_path) = create_epub_with_chapters(opf, &[("OEBPS/one.xhtml", body)]);
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB with headings");
+    let chapter = epub.get_chapter(0).unwrap();
+
+    let headings = chapter.headings();
+    let outline: Vec<(usize, &str)> = headings
+        .iter()
+        .map(|heading| (heading.level, heading.text.as_str()))
+        .collect();
+    assert_eq!(outline, [(1, "Part One"), (2, "First Section"), (3, "A Subsection")]);
+    let lines: Vec<&str> = chapter.content.lines().collect();
+    for heading in &headings {
+        assert_eq!(lines[heading.line], heading.text, "headings are marked apart from the text");
+    }
+}
+
+#[test]
+fn test_linked_stylesheets_set_emphasis_alignment_and_indents() {
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Styled</dc:title>
+  </metadata>
+  <manifest>
+    <item id="css" href="styles/book.css" media-type="text/css"/>
+    <item id="one" href="text/one.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+  </spine>
+</package>"#;
+    let css = "p { text-indent: 1em } .i { font-style: italic } p.centre { text-align: center }";
+    let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+<title>Styled</title>
+<link rel="stylesheet" type="text/css" href="../styles/book.css"/>
+<style>.b { font-weight: bold }</style>
+</head>
+<body>
+<p>A paragraph with a <span class="i">ship's name</span> and a <span class="b">warning</span> in it.</p>
+<p class="centre">A centred line</p>
+<p style="text-indent: 0">Flush with the margin.</p>
+</body>
+</html>"#;
+    let (_temp_dir, epub_path) = create_epub_with_chapters(
+        opf,
+        &[("OEBPS/styles/book.css", css), ("OEBPS/text/one.xhtml", body)],
+    );
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let chapter = epub.get_chapter(0).unwrap();
+    let lines: Vec<&str> = chapter.content.lines().collect();
+    let styled = |style: SpanStyle| -> Vec<String> {
+        chapter
+            .spans
+            .iter()
+            .filter(|span| span.style == style)
+            .map(|span| {
+                let line = lines[span.line];
+                line.chars().skip(span.columns.start).take(span.columns.len()).collect()
+            })
+            .collect()
+    };
+    assert_eq!(styled(SpanStyle::Emphasis), ["ship's name"]);
+    assert_eq!(styled(SpanStyle::Strong), ["warning"]);
+
+    assert!(lines[0].starts_with("  A paragraph"), "{lines:?}");
+    let centred = lines.iter().position(|line| line.contains("A centred line")).unwrap();
+    assert!(lines[centred].starts_with("  A centred"));
+    assert_eq!(chapter.alignment(centred), Some(TextAlign::Center));
+    assert_eq!(chapter.alignment(0), None);
+    assert!(lines.contains(&"Flush with the margin."));
+}
+
+#[test]
+fn test_headings_are_found_in_quotes_and_after_tables() {
+    let html = r#"<section><h1>Part One</h1><p>Opening.</p></section>
+<blockquote><p>Before.</p><h3>Quoted heading</h3><p>After.</p></blockquote>
+<table><tr><td><h5>Not a heading</h5></td></tr></table>
+<h2></h2>
+<h6>The end</h6>"#;
+    let converted = catatau::convert::convert_html(html, 40);
+    let lines: Vec<&str> = converted.text.lines().collect();
+    let headings: Vec<(usize, &str)> = converted
+        .headings
+        .iter()
+        .map(|heading| (heading.level, lines[heading.lines.start]))
+        .collect();
+    assert_eq!(
+        headings,
+        [(1, "Part One"), (3, "> Quoted heading"), (6, "The end")],
+        "{lines:?}"
+    );
+    assert!(lines.windows(3).any(|run| run == ["> Before.", ">", "> Quoted heading"]));
+    assert!(lines.windows(3).any(|run| run == ["> Quoted heading", ">", "> After."]));
+    assert!(!converted.text.chars().any(|c| c.is_control() && c != '\n'));
+}
+
+#[test]
+fn test_text_is_styled_without_markers() {
+    let html = r#"<h2>A heading long enough that it has to be wrapped</h2>
+<p>Sums like 2 * 3 * 4 keep their <em>stars</em>, and <strong>bold <em>and</em> plain</strong>
+text run on, as does <code>code</code>.</p>"#;
+    let converted = catatau::convert::convert_html(html, 24);
+    let lines: Vec<&str> = converted.text.lines().collect();
+    assert_eq!(converted.headings.len(), 1, "{:?}", converted.headings);
+    let heading = &converted.headings[0];
+    assert_eq!(heading.level, 2);
+    assert!(heading.lines.len() > 1, "{lines:?}");
+    assert!(lines[heading.lines.start].starts_with("A heading"));
+    assert!(converted.text.contains("2 * 3 * 4"));
+    assert!(!converted.text.contains('`'));
+
+    let styled = |style: SpanStyle| -> Vec<String> {
+        converted
+            .spans
+            .iter()
+            .filter(|span| span.style == style)
+            .map(|span| {
+                let line = lines[span.line];
+                line.chars().skip(span.columns.start).take(span.columns.len()).collect()
+            })
+            .collect()
+    };
+    assert_eq!(styled(SpanStyle::Emphasis), ["stars", "and"]);
+    assert_eq!(styled(SpanStyle::Strong).concat().replace(' ', ""), "boldandplain");
+    assert_eq!(styled(SpanStyle::Code), ["code"]);
+}
+
+#[test]
+fn test_chapter_word_counts() {
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Counting</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+  </spine>
+</package>"#;
+    let one = chapter_xhtml("Counting", "One two three — four, five; six seven eight nine ten.");
+    let (_temp_dir, epub_path) = create_epub_with_chapters(opf, &[("OEBPS/one.xhtml", &one)]);
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB");
+
+    // The heading adds one word and the dash isn't one
+    assert_eq!(epub.chapter_word_count(0), 11);
+    assert_eq!(epub.chapter_word_count(1), 0);
+}
+
+#[test]
+fn test_chapter_sizes() {
+    let (_temp_dir, epub_path) = create_test_epub();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+
+    let sizes = epub.chapter_sizes();
+    assert_eq!(sizes.len(), 2);
+    for (index, size) in sizes.into_iter().enumerate() {
+        assert_eq!(size, epub.get_chapter_html(index).unwrap().len() as u64);
+    }
+}
+fn create_epub_with_chapters(opf: &str, chapters: &[(&str, &str)]) -> (TempDir, std::path::PathBuf) {
+    chapters
+        .iter()
+        .fold(EpubBuilder::new("").opf_path("OEBPS/content.opf").package(opf), |book, (name, html)| {
+            book.file(name, html)
+        })
+        .build("book.epub")
+}
+
+fn chapter_xhtml(title: &str, text: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>{title}</title></head>
+<body>
+<h1>{title}</h1>
+<p>{text}</p>
+</body>
+</html>"#
+    )
+}
+
+#[test]
+fn test_percent_encoded_hrefs() {
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Encoded Hrefs</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="Chapter%20One.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href=" Chapter%20Two.xhtml#start " media-type="application/xhtml+xml"/>
+    <item id="three" href="Chapter Three.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+    <itemref idref="two"/>
+    <itemref idref="three"/>
+  </spine>
+</package>"#;
+    let one = chapter_xhtml("Chapter One", "The first chapter is stored with a literal space in its name.");
+    let two = chapter_xhtml("Chapter Two", "The second chapter href carries a fragment and stray whitespace.");
+    let three = chapter_xhtml("Chapter Three", "The third chapter is stored with the encoded name in the zip.");
+    let (_temp_dir, epub_path) = create_epub_with_chapters(
+        opf,
+        &[
+            ("OEBPS/Chapter One.xhtml", &one),
+            ("OEBPS/Chapter Two.xhtml", &two),
+            ("OEBPS/Chapter%20Three.xhtml", &three),
+        ],
+    );
+
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB with encoded hrefs");
+    assert_eq!(epub.chapter_count(), 3);
+    assert!(epub.get_chapter(0).unwrap().content.contains("literal space"));
+    assert!(epub.get_chapter(1).unwrap().content.contains("stray whitespace"));
+    assert!(epub.get_chapter(2).unwrap().content.contains("encoded name"));
+}
+
+#[test]
+fn test_reader_options_limits() {
+    let (_temp_dir, epub_path) = create_test_epub();
+
+    let result = EpubReaderOptions::new().max_file_size(16).open(&epub_path);
+    match result.unwrap_err() {
+        EpubError::FileTooLarge { max, .. } => assert_eq!(max, 16),
+        other => panic!("Expected FileTooLarge, got: {:?}", other),
+    }
+
+    let epub = EpubReaderOptions::new()
+        .max_chapter_size(64)
+        .open(&epub_path)
+        .expect("Opening should succeed; chapter limits apply on load");
+    match epub.get_chapter(0).unwrap_err() {
+        EpubError::ChapterTooLarge { max, .. } => assert_eq!(max, 64),
+        other => panic!("Expected ChapterTooLarge, got: {:?}", other),
+    }
+
+    let epub = EpubReaderOptions::new()
+        .max_file_size(500 * 1024 * 1024)
+        .open(&epub_path)
+        .expect("Raised limits should still open the book");
+    assert_eq!(epub.chapter_count(), 2);
+}
+
+#[test]
+fn test_case_insensitive_and_prefixless_hrefs() {
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Mixed Case</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="Text/Chapter1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="chapter2.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+    <itemref idref="two"/>
+  </spine>
+</package>"#;
+    let one = chapter_xhtml("Chapter One", "This chapter lives under a lowercased directory and file name.");
+    let two = chapter_xhtml("Chapter Two", "This chapter lives in a directory the href never mentions.");
+    let (_temp_dir, epub_path) = create_epub_with_chapters(
+        opf,
+        &[
+            ("oebps/text/chapter1.xhtml", &one),
+            ("OEBPS/Text/Nested/chapter2.xhtml", &two),
+        ],
+    );
+
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB with mismatched case");
+    assert_eq!(epub.chapter_count(), 2);
+    assert!(epub.get_chapter(0).unwrap().content.contains("lowercased directory"));
+    assert!(epub.get_chapter(1).unwrap().content.contains("never mentions"));
+}
+
+#[test]
+fn test_calibre_series_metadata() {
+    let opf = r##"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:title>Cibola Burn</dc:title>
+    <dc:creator>James S. A. Corey</dc:creator>
+    <meta name="calibre:series" content="The Expanse"/>
+    <meta name="calibre:series_index" content="4.0"/>
+    <meta name="calibre:user_metadata:#shelf" content="{&quot;name&quot;: &quot;Shelf&quot;, &quot;#value#&quot;: [&quot;sci-fi&quot;, &quot;space&quot;]}"/>
+    <meta name="calibre:user_metadata:#empty" content="{&quot;name&quot;: &quot;Empty&quot;, &quot;#value#&quot;: null}"/>
+  </metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+  </spine>
+</package>"##;
+    let one = chapter_xhtml("Chapter One", "The ring gates have opened and settlers head for Ilus.");
+    let (_temp_dir, epub_path) = create_epub_with_chapters(opf, &[("OEBPS/one.xhtml", &one)]);
+
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB with calibre metadata");
+    let series = epub.series.clone().expect("Series should be parsed");
+    assert_eq!(series.name, "The Expanse");
+    assert_eq!(series.index, Some(4.0));
+    assert_eq!(series.to_string(), "Book 4 of The Expanse");
+    assert_eq!(epub.custom_columns.get("Shelf").map(String::as_str), Some("sci-fi, space"));
+    assert!(!epub.custom_columns.contains_key("Empty"));
+
+    let (_temp_dir, epub_path) = create_test_epub();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+    assert!(epub.series.is_none());
+    assert!(epub.custom_columns.is_empty());
+}
+
+#[test]
+fn test_duplicate_spine_entries_are_skipped() {
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Duplicates</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="one-again" href="./one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+    <itemref idref="two"/>
+    <itemref idref="one"/>
+    <itemref idref="one-again"/>
+  </spine>
+</package>"#;
+    let one = chapter_xhtml("Chapter One", "The first chapter should appear exactly once in the book.");
+    let two = chapter_xhtml("Chapter Two", "The second chapter keeps its position after the first one.");
+    let (_temp_dir, epub_path) = create_epub_with_chapters(
+        opf,
+        &[("OEBPS/one.xhtml", &one), ("OEBPS/two.xhtml", &two)],
+    );
+
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB with duplicate spine");
+    assert_eq!(epub.chapter_count(), 2);
+    assert_eq!(epub.get_chapter(0).unwrap().title, "Chapter One");
+    assert_eq!(epub.get_chapter(1).unwrap().title, "Chapter Two");
+}
+
+#[test]
+fn test_short_documents_are_skippable() {
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Cover First</dc:title>
+  </metadata>
+  <manifest>
+    <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="cover"/>
+    <itemref idref="one"/>
+  </spine>
+</package>"#;
+    let cover = r#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p>Cover</p></body></html>"#;
+    let one = chapter_xhtml("Opening", "A chapter long enough to count as real reading material for the reader.");
+    let (_temp_dir, epub_path) = create_epub_with_chapters(
+        opf,
+        &[("OEBPS/cover.xhtml", cover), ("OEBPS/one.xhtml", &one)],
+    );
+
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB with a cover page");
+    assert_eq!(epub.chapter_count(), 2);
+    assert!(epub.is_skippable(0));
+    assert!(!epub.is_skippable(1));
+    assert_eq!(epub.chapter_title(0), "Cover");
+    assert_eq!(epub.chapter_title(1), "Opening");
+    assert_eq!(epub.get_chapter(1).unwrap().title, "Opening");
+}
+
+#[test]
+fn test_eager_scan_matches_lazy_titles() {
+    let (_temp_dir, epub_path) = create_test_epub();
+    let lazy = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+    let eager = EpubReaderOptions::new()
+        .eager_scan(true)
+        .open(&epub_path)
+        .expect("Failed to parse test EPUB eagerly");
+
+    assert_eq!(lazy.chapter_count(), eager.chapter_count());
+    for index in 0..eager.chapter_count() {
+        assert_eq!(lazy.chapter_title(index), eager.chapter_title(index));
+        assert_eq!(lazy.is_skippable(index), eager.is_skippable(index));
+    }
+}
+
+#[test]
+fn test_text_width_rewraps_chapters() {
+    let (_temp_dir, epub_path) = create_test_epub();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+
+    let wide = epub.get_chapter(0).expect("Failed to load chapter at default width");
+    assert!(wide.content.lines().all(|line| line.chars().count() <= 80));
+
+    epub.set_text_width(30);
+    assert_eq!(epub.text_width(), 30);
+    let narrow = epub.get_chapter(0).expect("Failed to load chapter at narrow width");
+    assert!(narrow.content.lines().all(|line| line.chars().count() <= 30));
+    assert!(narrow.content.lines().count() > wide.content.lines().count());
+
+    epub.set_text_width(1);
+    assert!(epub.text_width() > 1, "width should be clamped to a usable minimum");
+}
+
+#[test]
+fn test_tables_render_with_box_drawing() {
+    let narrow = "<table><tr><th>Name</th><th>Age</th></tr><tr><td>Ada</td><td>36</td></tr></table>";
+    let text = catatau::convert::html_to_text(&format!("<p>Before</p>{narrow}<p>After</p>"), 60);
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert!(lines.iter().any(|line| line.contains('┬')));
+    assert!(lines.iter().any(|line| line.contains("Ada") && line.contains('│') && line.contains("36")));
+    assert!(lines.iter().all(|line| line.chars().count() <= 60));
+    assert!(text.find("Before").unwrap() < text.find("Ada").unwrap());
+    assert!(text.find("Ada").unwrap() < text.find("After").unwrap());
+
+    // A table with many columns keeps its natural layout instead of being crushed
+    let cells: String = (0..12).map(|i| format!("<td>Column{i}</td>")).collect();
+    let wide = format!("<table><tr>{cells}</tr></table>");
+    let text = catatau::convert::html_to_text(&wide, 40);
+    let row = text
+        .lines()
+        .find(|line| line.contains("Column0"))
+        .expect("table row should be rendered");
+    assert!(row.contains("Column11"), "wide rows should not be wrapped: {text}");
+    assert!(row.chars().count() > 40);
+
+    let converted = catatau::convert::convert_html(&wide, 40);
+    assert_eq!(converted.blocks.len(), 1);
+    assert_eq!(converted.blocks[0].kind, catatau::convert::BlockKind::Table);
+    assert!(converted.text.lines().count() == converted.blocks[0].lines.end);
+}
+
+#[test]
+fn test_code_listings_are_kept_verbatim() {
+    let html = r#"<p>Intro</p>
+<pre class="programlisting"><code class="language-rust">fn main() {
+	let numbers = vec![1, 2, 3]; // a comment long enough to run well past the column
+    println!("{} &lt;ok&gt;", numbers.len());
+}</code></pre>
+<p>After</p>"#;
+    let converted = catatau::convert::convert_html(html, 30);
+    let lines: Vec<&str> = converted.text.lines().collect();
+
+    assert_eq!(converted.blocks.len(), 1);
+    let block = &converted.blocks[0];
+    assert_eq!(
+        block.kind,
+        catatau::convert::BlockKind::Code {
+            language: Some("rust".to_string())
+        }
+    );
+    assert_eq!(&lines[block.lines.clone()], &[
+        "fn main() {",
+        "    let numbers = vec![1, 2, 3]; // a comment long enough to run well past the column",
+        "    println!(\"{} <ok>\", numbers.len());",
+        "}",
+    ]);
+    assert!(converted.text.find("Intro").unwrap() < converted.text.find("fn main").unwrap());
+    assert!(converted.text.find("fn main").unwrap() < converted.text.find("After").unwrap());
+}
+
+#[test]
+fn test_nested_lists_keep_markers_and_hanging_indents() {
+    let html = "<ul><li>A first item long enough that it has to wrap</li>\
+        <li>Second<ul><li>Nested item that also wraps onto a second line</li></ul></li></ul>\
+        <ol start=\"3\"><li>Three</li><li>Four</li></ol>";
+    let text = catatau::convert::html_to_text(html, 30);
+    let lines: Vec<&str> = text.lines().map(str::trim_end).filter(|line| !line.is_empty()).collect();
+
+    assert!(lines[0].starts_with("• A first item"));
+    assert!(lines[1].starts_with("  ") && !lines[1].starts_with("   "), "hanging indent: {text}");
+    assert!(lines.iter().any(|line| line.starts_with("• Second")));
+    let nested = lines.iter().position(|line| line.starts_with("  ◦ Nested")).expect("nested bullet");
+    assert!(lines[nested + 1].starts_with("    ") && !lines[nested + 1].starts_with("     "));
+    assert!(lines.contains(&"3. Three"));
+    assert!(lines.contains(&"4. Four"));
+}
+
+#[test]
+fn test_verse_keeps_line_breaks_and_indentation() {
+    let html = r#"<p class="stanza">Shall I compare thee to a summer's day?<br/>
+&#160;&#160;Thou art more lovely<br/>
+Rough winds do shake<br/><br/>
+&#160;&#160;&#160;&#160;New stanza</p>
+<p><span>Of Man's first disobedience</span><br/><span class="i1">and the fruit</span></p>
+<div style="white-space: pre">  indented
+      more indented</div>
+<P>Between</P>
+<DIV STYLE="WHITE-SPACE: PRE">  Shouted
+    AND INDENTED</DIV>"#;
+    let text = catatau::convert::html_to_text(html, 60);
+    let lines: Vec<&str> = text.lines().collect();
+
+    let first = lines.iter().position(|line| line.starts_with("Shall I compare")).unwrap();
+    assert_eq!(lines[first + 1], "  Thou art more lovely");
+    assert_eq!(lines[first + 2], "Rough winds do shake");
+    assert_eq!(lines[first + 3].trim(), "");
+    assert_eq!(lines[first + 4], "    New stanza");
+    assert!(lines.contains(&"  and the fruit"));
+    assert!(lines.contains(&"  indented"));
+    assert!(lines.contains(&"      more indented"));
+    assert!(lines.contains(&"  Shouted"));
+    assert!(lines.contains(&"    AND INDENTED"));
+}
+
+#[test]
+fn test_wide_characters_are_measured_in_columns() {
+    use unicode_width::UnicodeWidthStr;
+
+    let html = r#"<p>吾輩は猫である。名前はまだ無い。どこで生れたかとんと見当がつかぬ。</p>
+<table><tr><th>名前</th><th>説明</th></tr><tr><td>猫</td><td>名前はまだ無い</td></tr></table>"#;
+    let converted = catatau::convert::convert_html(html, 30);
+    assert!(converted.text.lines().all(|line| line.width() <= 30));
+    assert!(converted.text.contains("名前はまだ無い"));
+    assert_eq!(converted.blocks.len(), 1);
+}
+
+#[test]
+fn test_superscripts_and_subscripts_use_unicode_forms() {
+    let html = r##"<p>E = mc<sup>2</sup>, H<sub>2</sub>O, the 1<sup>st</sup> note<sup><a href="#fn1">12</a></sup> and x<sup>Q</sup>.</p>"##;
+    let converted = catatau::convert::convert_html(html, 80);
+    assert!(converted.text.contains("E = mc², H₂O, the 1ˢᵗ note¹²"), "{}", converted.text);
+    assert!(!converted.text.contains("xQ"), "unmappable scripts stay marked");
+
+    let note = converted.links.iter().find(|link| link.target == "#fn1").expect("footnote link kept");
+    let line = converted.text.lines().nth(note.line).unwrap();
+    let text: String = line.chars().skip(note.columns.start).take(note.columns.len()).collect();
+    assert_eq!(text, "¹²");
+}
+
+#[test]
+fn test_typography_cleanup_is_optional() {
+    use catatau::convert::{ConvertOptions, convert_html_with};
+
+    let html = r#"<p>Some&amp;nbsp;text with&nbsp;&nbsp; doubled spaces, a hy&shy;phen&#173;ated word -- and more.</p>
+<pre>x -- y</pre>"#;
+    let untouched = catatau::convert::html_to_text(html, 120);
+    assert!(untouched.contains("&nbsp;"));
+    assert!(untouched.contains("word -- and"));
+
+    let options = ConvertOptions {
+        typography: true,
+        ..ConvertOptions::default()
+    };
+    let tidied = convert_html_with(html, 120, &options, &|_| None).text;
+    assert!(tidied.contains("Some text with doubled spaces, a hyphenated word — and more."), "{tidied}");
+    assert!(tidied.contains("x -- y"), "code listings are kept verbatim");
+}
+
+#[test]
+fn test_ruby_readings_follow_or_drop() {
+    use catatau::convert::{ConvertOptions, RubyStyle, convert_html_with};
+
+    let html = r#"<p><ruby>漢<rp>(</rp><rt>かん</rt><rp>)</rp>字<rt>じ</rt></ruby>を<ruby><rb>東京</rb><rt>とうきょう</rt></ruby>で</p>"#;
+    let inline = catatau::convert::html_to_text(html, 80);
+    assert_eq!(inline.trim(), "漢(かん)字(じ)を東京(とうきょう)で");
+
+    let options = ConvertOptions {
+        ruby: RubyStyle::Strip,
+        ..ConvertOptions::default()
+    };
+    let stripped = convert_html_with(html, 80, &options, &|_| None);
+    assert_eq!(stripped.text.trim(), "漢字を東京で");
+}
+
+#[test]
+fn test_paragraphs_can_be_spaced_or_indented() {
+    use catatau::convert::{ConvertOptions, ParagraphStyle, convert_html_with};
+
+    let html = r#"<style>p { text-indent: 1em } p.flush { text-indent: 0 }</style>
+<h2>One</h2>
+<p>The first paragraph.</p>
+<p>The second paragraph.</p>
+<p class="flush">A flush paragraph.</p>
+<p>The last paragraph.</p>"#;
+    let lines = |paragraphs: ParagraphStyle| -> Vec<String> {
+        let options = ConvertOptions {
+            paragraphs,
+            paragraph_indent: 4,
+            ..ConvertOptions::default()
+        };
+        let text = convert_html_with(html, 80, &options, &|_| None).text;
+        text.lines().skip(2).map(str::to_string).collect()
+    };
+
+    let book = lines(ParagraphStyle::Book);
+    assert_eq!(book[0], "  The first paragraph.");
+    assert_eq!(book[1], "");
+    assert_eq!(book[2], "  The second paragraph.");
+    assert_eq!(book[4], "A flush paragraph.");
+
+    let spaced = lines(ParagraphStyle::Spaced);
+    assert_eq!(spaced[0], "The first paragraph.");
+    assert_eq!(spaced[1], "");
+    assert_eq!(spaced[2], "The second paragraph.");
+
+    let indented = lines(ParagraphStyle::Indented);
+    assert_eq!(
+        indented[..4],
+        [
+            "The first paragraph.",
+            "    The second paragraph.",
+            "A flush paragraph.",
+            "    The last paragraph.",
+        ]
+    );
+}
+
+#[test]
+fn test_links_are_kept_and_resolved() {
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Links</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="text/one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="notes" href="text/notes.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+    <itemref idref="notes"/>
+  </spine>
+</package>"#;
+    let one = chapter_xhtml(
+        "Chapter One",
+        r#"As explained in <a href="notes.xhtml#note-1">the <em>first</em> note</a>, and on <a href="https://example.com/">the web</a>, this chapter links elsewhere."#,
+    );
+    let notes = chapter_xhtml("Notes", "The first note explains everything that needs explaining here.");
+    let (_temp_dir, epub_path) = create_epub_with_chapters(
+        opf,
+        &[("OEBPS/text/one.xhtml", &one), ("OEBPS/text/notes.xhtml", &notes)],
+    );
+
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse EPUB with links");
+    let chapter = epub.get_chapter(0).unwrap();
+    assert!(!chapter.content.contains("[1]"), "links should not be footnoted");
+    assert_eq!(chapter.links.len(), 2);
+
+    let note = &chapter.links[0];
+    assert_eq!(note.target, "notes.xhtml#note-1");
+    let line = chapter.content.lines().nth(note.line).unwrap();
+    let text: String = line.chars().skip(note.columns.start).take(note.columns.len()).collect();
+    assert_eq!(text, "the first note");
+    let emphasis = chapter.spans_on(note.line);
+    assert_eq!(emphasis.len(), 1);
+    assert_eq!(emphasis[0].style, SpanStyle::Emphasis);
+    assert_eq!(emphasis[0].columns, note.columns.start + 4..note.columns.start + 9);
+
+    let target = epub.resolve_link(0, &note.target).expect("internal link should resolve");
+    assert_eq!(target.chapter, 1);
+    assert_eq!(target.anchor.as_deref(), Some("note-1"));
+    assert_eq!(epub.resolve_link(1, "#top").map(|target| target.chapter), Some(1));
+    assert!(epub.resolve_link(0, &chapter.links[1].target).is_none());
+    assert!(EpubReader::is_external_link("https://example.com/"));
+    assert!(!EpubReader::is_external_link("notes.xhtml#note-1"));
+}
+
+#[test]
+fn test_anchors_map_to_output_lines() {
+    let html = r#"<h1 id="top">Title</h1>
+<p>Opening paragraph of text.</p>
+<table id="figures"><tr><td>Cell</td><td id="cell">Value</td></tr></table>
+<pre id="listing">let x = 1;</pre>
+<p>Middle paragraph.</p>
+<h2 id="section-3">Section Three</h2>
+<p><a name="old-style"></a>Old style anchor.</p>
+<section id="part-two"><h2>Part Two</h2></section>"#;
+    let converted = catatau::convert::convert_html(html, 40);
+    let lines: Vec<&str> = converted.text.lines().collect();
+    let line_of = |id: &str| converted.anchors.get(id).copied().unwrap_or_else(|| panic!("missing anchor {id}"));
+
+    assert!(lines[line_of("top")].contains("Title"));
+    assert!(lines[line_of("section-3")].contains("Section Three"));
+    assert!(lines[line_of("old-style")].contains("Old style anchor"));
+    assert!(lines[line_of("part-two")].contains("Part Two"));
+    assert!(lines[line_of("listing")].contains("let x = 1;"));
+    let table = converted.blocks.iter().find(|block| block.kind == catatau::convert::BlockKind::Table).unwrap();
+    assert!(table.lines.contains(&line_of("figures")));
+    assert!(lines[line_of("cell")].contains("Value"));
+}
+
+#[cfg(feature = "image-art")]
+#[test]
+fn test_images_render_as_text_art() {
+    use catatau::convert::{BlockKind, ImageArt};
+
+    // A black square in the middle of a white 40x20 picture
+    let picture = image::GrayImage::from_fn(40, 20, |x, y| {
+        let inside = (10..30).contains(&x) && (5..15).contains(&y);
+        image::Luma([if inside { 0 } else { 255 }])
+    });
+    let mut png = std::io::Cursor::new(Vec::new());
+    picture.write_to(&mut png, image::ImageFormat::Png).unwrap();
+
+    let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Pictures</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="text/one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="fig" href="images/fig.png" media-type="image/png"/>
+  </manifest>
+  <spine>
+    <itemref idref="one"/>
+  </spine>
+</package>"#;
+    let chapter = chapter_xhtml(
+        "Figures",
+        r#"The square below is a diagram.</p><div><img src="../images/fig.png" alt="A square"/></div><div><img src="../images/missing.png" alt="Lost figure"/></div><p>After the figures."#,
+    );
+    let (temp_dir, epub_path) = EpubBuilder::new("Pictures")
+        .opf_path("OEBPS/content.opf")
+        .package(opf)
+        .file("OEBPS/text/one.xhtml", &chapter)
+        .file("OEBPS/images/fig.png", png.get_ref())
+        .build("book.epub");
+
+    let plain = EpubReader::new(&epub_path).unwrap().get_chapter(0).unwrap();
+    assert!(plain.content.contains("[A square]"));
+    assert!(plain.blocks.is_empty());
+
+    // At 20 columns the picture is halved for half blocks and kept at full
+    // size for braille, which fits two pixels to a column
+    for (style, ink) in [(ImageArt::HalfBlock, '█'), (ImageArt::Braille, '⣿')] {
+        let epub = EpubReaderOptions::new().image_art(style).open(&epub_path).unwrap();
+        epub.set_text_width(20);
+        let chapter = epub.get_chapter(0).unwrap();
+        let art = chapter.blocks.iter().find(|block| block.kind == BlockKind::Image).expect("image block");
+        let lines: Vec<&str> = chapter.content.lines().skip(art.lines.start).take(art.lines.len()).collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines.iter().all(|line| line.chars().count() <= 20));
+        assert!(lines.iter().any(|line| line.contains(ink)));
+        assert!(chapter.content.contains("[Lost figure]"));
+        assert!(chapter.content.contains("After the figures."));
+    }
+    drop(temp_dir);
+}
+
+#[cfg(feature = "image-art")]
+#[test]
+fn test_chapter_summaries_dont_depend_on_how_the_chapter_was_opened() {
+    use catatau::convert::ImageArt;
+
+    let picture =
+        image::GrayImage::from_fn(40, 40, |x, _| image::Luma([if x < 20 { 0 } else { 255 }]));
+    let mut png = std::io::Cursor::new(Vec::new());
+    picture.write_to(&mut png, image::ImageFormat::Png).unwrap();
+    let (_temp_dir, epub_path) = EpubBuilder::new("Plates")
+        .body(r#"<div><img src="plate.png" alt=""/></div>"#)
+        .body(&"<p>A chapter with enough words in it to stop on.</p>".repeat(3))
+        .file("plate.png", png.get_ref())
+        .build("plates.epub");
+
+    let scanned = EpubReader::new(&epub_path).unwrap();
+    scanned.scan_chapters();
+    // Opened first, with art and at another width, the chapter is
+    // summarised the same
+    let opened = EpubReaderOptions::new().image_art(ImageArt::HalfBlock).open(&epub_path).unwrap();
+    opened.set_text_width(30);
+    assert!(opened.get_chapter(0).unwrap().content.contains('█'));
+    for chapter in 0..2 {
+        assert_eq!(opened.is_skippable(chapter), scanned.is_skippable(chapter));
+        assert_eq!(opened.chapter_word_count(chapter), scanned.chapter_word_count(chapter));
+    }
+    assert!(opened.is_skippable(0));
+}
+
+#[test]
+fn test_pages_break_between_paragraphs() {
+    use catatau::convert::page_starts;
+
+    let text = "one\none\none\n\ntwo\ntwo\ntwo\ntwo\n\nthree\nthree\n";
+    // The second paragraph would straddle the first page, so it starts the next
+    assert_eq!(page_starts(text, 6), vec![0, 4, 9]);
+    // A page that already ends on a paragraph break is left alone
+    assert_eq!(page_starts(text, 4), vec![0, 4, 9]);
+    // Paragraphs longer than a page are split where the page ends
+    assert_eq!(page_starts("a\nb\nc\nd\ne\n", 2), vec![0, 2, 4]);
+    assert_eq!(page_starts("", 10), vec![0]);
+    assert_eq!(page_starts(text, 40), vec![0]);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::EpubBuilder;
+use tempfile::TempDir;
+use catatau::EpubReader;
+use catatau::export::{Format, export_book};
+
+fn create_epub(dir: &TempDir) -> std::path::PathBuf {
+    let epub_path = dir.path().join("export.epub");
+    EpubBuilder::new("Export Book")
+        .opf_path("OEBPS/content.opf")
+        .chapter("text/one.xhtml", r#"<html><body><h1>The Voyage Out</h1>
+<p>Read <em>on</em> in <a href="two.xhtml#start">the next chapter</a>.</p>
+<p><img src="../images/map.png" alt="Map"/></p></body></html>"#)
+        .chapter("text/two.xhtml", r#"<html><body><h1 id="start">Night and Day</h1><p id="end">The end.</p>
+<p><img src="../plates/map.png" alt="Plate"/></p></body></html>"#)
+        .manifest(r#"<item id="map" href="images/map.png" media-type="image/png"/>"#)
+        .file("OEBPS/images/map.png", "not really a png")
+        .file("OEBPS/plates/map.png", "another map")
+        .write(&epub_path);
+    epub_path
+}
+
+#[test]
+fn test_chapters_are_exported_as_markdown_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub = EpubReader::new(&create_epub(&temp_dir)).unwrap();
+    let out = temp_dir.path().join("out");
+
+    let written = export_book(&epub, Format::Markdown, &out).unwrap();
+    assert_eq!(written, vec![out.join("01-the-voyage-out.md"), out.join("02-night-and-day.md")]);
+    let first = std::fs::read_to_string(&written[0]).unwrap();
+    assert_eq!(
+        first,
+        "# The Voyage Out\n\n\
+         Read *on* in [the next chapter](02-night-and-day.md#start).\n\n\
+         ![Map](images/map.png)\n"
+    );
+    assert_eq!(std::fs::read(out.join("images/map.png")).unwrap(), b"not really a png");
+    let second = std::fs::read_to_string(&written[1]).unwrap();
+    assert_eq!(
+        second,
+        "# <a id=\"start\"></a>Night and Day\n\n\
+         The end.\n\n\
+         ![Plate](images/map-2.png)\n"
+    );
+    assert_eq!(std::fs::read(out.join("images/map-2.png")).unwrap(), b"another map");
+}
+
+#[test]
+fn test_chapters_are_exported_as_plain_text() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub = EpubReader::new(&create_epub(&temp_dir)).unwrap();
+    let out = temp_dir.path().join("out");
+
+    let written = export_book(&epub, Format::Text, &out).unwrap();
+    assert_eq!(written[1], out.join("02-night-and-day.txt"));
+    let second = std::fs::read_to_string(&written[1]).unwrap();
+    assert!(second.contains("Night and Day") && second.contains("The end."), "{second}");
+    assert!(!second.contains('*'));
+    assert_eq!("txt".parse::<Format>(), Ok(Format::Text));
+    assert!("pdf".parse::<Format>().is_err());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::EpubBuilder;
+use tempfile::TempDir;
+use catatau::EpubReader;
+use catatau::info::{info_json, info_text};
+
+fn create_epub(dir: &TempDir) -> std::path::PathBuf {
+    let epub_path = dir.path().join("info.epub");
+    EpubBuilder::new("Good Omens")
+        .metadata(r#"
+    <dc:creator>Terry Pratchett</dc:creator>
+    <dc:creator>Neil Gaiman</dc:creator>
+    <dc:language>en-GB</dc:language>
+    <dc:identifier>urn:isbn:9780575048003</dc:identifier>
+    <dc:identifier>urn:uuid:5f0c4a4e-0000-4000-8000-000000000000</dc:identifier>
+    <dc:publisher>Gollancz</dc:publisher>
+    <dc:date>1990-05-01</dc:date>"#)
+        .chapter("one.xhtml", "<html><body><h1>In the Beginning</h1><p>It was a nice day, as all the days had been.</p></body></html>")
+        .chapter("two.xhtml", "<html><body><h1>Eleven Years Ago</h1><p>Satanists are busy people.</p></body></html>")
+        .write(&epub_path);
+    epub_path
+}
+
+#[test]
+fn test_book_details_come_from_the_package() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub = EpubReader::new(&create_epub(&temp_dir)).unwrap();
+    assert_eq!(epub.authors, vec!["Terry Pratchett", "Neil Gaiman"]);
+    assert_eq!(epub.identifiers.len(), 2);
+    assert_eq!(epub.publisher.as_deref(), Some("Gollancz"));
+    assert_eq!(epub.published.as_deref(), Some("1990-05-01"));
+
+    let text = info_text(&epub);
+    assert!(text.contains("Authors:     Terry Pratchett, Neil Gaiman"), "{text}");
+    assert!(text.contains("urn:isbn:9780575048003"));
+    assert!(text.contains("Chapters:    2"));
+    assert!(text.lines().any(|line| line.starts_with("2") && line.ends_with("Eleven Years Ago")));
+}
+
+#[test]
+fn test_book_details_as_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub = EpubReader::new(&create_epub(&temp_dir)).unwrap();
+    let json = info_json(&epub);
+    assert_eq!(json["title"], "Good Omens");
+    assert_eq!(json["language"], "en-GB");
+    assert_eq!(json["authors"][1], "Neil Gaiman");
+    assert_eq!(json["chapters"][0]["number"], 1);
+    assert_eq!(json["chapters"][0]["title"], "In the Beginning");
+    let words: u64 = json["chapters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|chapter| chapter["words"].as_u64().unwrap())
+        .sum();
+    assert_eq!(json["words"], words);
+    assert!(words > 10);
+    assert!(json["series"].is_null());
+}
+
+#[test]
+fn test_words_and_sentences_are_counted() {
+    use catatau::info::{stats_json, text_stats};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub = EpubReader::new(&create_epub(&temp_dir)).unwrap();
+    let stats = text_stats(&epub);
+    // "In the Beginning" is a sentence of its own, having no full stop
+    assert_eq!(stats.chapters[0].1.words, 14);
+    assert_eq!(stats.chapters[0].1.sentences, 2);
+    assert_eq!(stats.chapters[1].0, "Eleven Years Ago");
+    assert_eq!(stats.words, 14 + 7);
+    // "the" comes twice, but counts once
+    assert_eq!(stats.unique_words, stats.words - 1);
+    assert!((stats.average_sentence_length() - 21.0 / 4.0).abs() < 1e-9);
+    assert_eq!(stats.reading_seconds(), 21 * 60 / 250);
+
+    let json = stats_json(&stats);
+    assert_eq!(json["words"], 21);
+    assert_eq!(json["chapters"][1]["number"], 2);
+    assert_eq!(json["chapters"][1]["sentences"], 2);
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "tui")]
+
+use catatau::config::Config;
+use catatau::keymap::{Action, Binding, KeyChord, Keymap, Preset, display_keys, parse_keys};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+fn keys(text: &str) -> Vec<KeyChord> {
+    parse_keys(text).unwrap()
+}
+
+#[test]
+fn test_keys_are_written_as_in_vim() {
+    assert_eq!(
+        keys("gT"),
+        vec![
+            KeyChord::new(KeyCode::Char('g'), KeyModifiers::NONE),
+            KeyChord::new(KeyCode::Char('T'), KeyModifiers::NONE),
+        ]
+    );
+    assert_eq!(keys("<C-w>"), vec![KeyChord::new(KeyCode::Char('w'), KeyModifiers::CONTROL)]);
+    assert_eq!(keys("<c-W>"), keys("<C-w>"));
+    assert_eq!(keys("<A-PageDown>")[0].modifiers, KeyModifiers::ALT);
+    assert_eq!(keys("<S-Tab>")[0].code, KeyCode::BackTab);
+    assert_eq!(keys("<F5>")[0].code, KeyCode::F(5));
+    assert_eq!(keys("<lt>"), keys("<"));
+    assert_eq!(keys("<Space>")[0].code, KeyCode::Char(' '));
+    assert!(parse_keys("<Hyper-x>").is_err());
+    assert!(parse_keys("<Nope>").is_err());
+    assert!(parse_keys("").is_err());
+
+    assert_eq!(keys("<A-gt>"), vec![KeyChord::new(KeyCode::Char('>'), KeyModifiers::ALT)]);
+
+    for written in [
+        "gg", "<C-w>", "<Space>", "<lt>", "<PageDown>", "<A-j>", "<S-Tab>", "<F5>", "<A-gt>",
+        "jjl/magic<Enter>",
+    ] {
+        assert_eq!(display_keys(&keys(written)), written);
+    }
+}
+
+#[test]
+fn test_terminals_reporting_shift_get_the_same_binding() {
+    let shifted = KeyChord::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+    assert_eq!(shifted, keys("G")[0]);
+    let control = KeyChord::new(KeyCode::Char('O'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+    assert_eq!(control, keys("<C-o>")[0]);
+}
+
+#[test]
+fn test_key_sequences_wait_for_the_rest() {
+    let keymap = Keymap::default();
+    assert_eq!(keymap.lookup(&keys("j")), Binding::Action(Action::ScrollDown));
+    assert_eq!(keymap.lookup(&keys("<Down>")), Binding::Action(Action::ScrollDown));
+    assert_eq!(keymap.lookup(&keys("g")), Binding::Prefix);
+    assert_eq!(keymap.lookup(&keys("g$")), Binding::Action(Action::LastChapter));
+    assert_eq!(keymap.lookup(&keys("gx")), Binding::Unbound);
+    assert_eq!(keymap.lookup(&keys("R")), Binding::Action(Action::ReloadBook));
+    assert_eq!(keymap.lookup(&keys("<C-r>")), Binding::Action(Action::ReloadTheme));
+    assert_eq!(keymap.lookup(&keys("<C-w>")), Binding::Action(Action::Window));
+}
+
+#[test]
+fn test_bindings_can_be_changed_and_removed() {
+    let mut keymap = Keymap::default();
+    keymap.bind(keys("n"), Some(Action::ScrollDown)).unwrap();
+    keymap.bind(keys("j"), None).unwrap();
+    assert_eq!(keymap.lookup(&keys("n")), Binding::Action(Action::ScrollDown));
+    assert_eq!(keymap.lookup(&keys("j")), Binding::Unbound);
+
+    // A key can't hide a sequence that starts with it
+    let error = keymap.bind(keys("g"), Some(Action::ChapterEnd)).unwrap_err();
+    assert!(error.contains("while g"), "{error}");
+    assert_eq!(keymap.lookup(&keys("g")), Binding::Prefix);
+}
+
+#[test]
+fn test_key_bindings_come_from_the_config_file() {
+    let config = Config::parse(
+        r#"
+        [keys]
+        n = "scroll-down"
+        e = "scroll-up"
+        "<C-d>" = "page-down"
+        k = "none"
+        gg = "none"
+        g0 = "none"
+        "g$" = "none"
+        gt = "none"
+        gT = "none"
+        g = "chapter-start"
+        "#,
+    )
+    .unwrap();
+    let keymap = &config.keys;
+    assert_eq!(keymap.lookup(&keys("n")), Binding::Action(Action::ScrollDown));
+    assert_eq!(keymap.lookup(&keys("e")), Binding::Action(Action::ScrollUp));
+    assert_eq!(keymap.lookup(&keys("<C-d>")), Binding::Action(Action::PageDown));
+    assert_eq!(keymap.lookup(&keys("k")), Binding::Unbound);
+    assert_eq!(keymap.lookup(&keys("g")), Binding::Action(Action::ChapterStart));
+    assert_eq!(keymap.lookup(&keys("q")), Binding::Action(Action::Quit));
+
+    let error = |text: &str| Config::parse(text).unwrap_err();
+    assert!(error("[keys]\nx = \"fly\"").contains("unknown action 'fly'"));
+    assert!(error("[keys]\n\"<Hyper-x>\" = \"quit\"").contains("unknown modifier 'Hyper'"));
+    assert!(error("[keys]\ng = \"quit\"").contains("can't be bound"));
+}
+
+#[test]
+fn test_emacs_and_less_keymaps_are_built_in() {
+    assert_eq!("Emacs".parse::<Preset>(), Ok(Preset::Emacs));
+    assert!("nano".parse::<Preset>().unwrap_err().contains("unknown keymap 'nano'"));
+
+    let emacs = Keymap::preset(Preset::Emacs);
+    assert_eq!(emacs.lookup(&keys("<C-v>")), Binding::Action(Action::PageDown));
+    assert_eq!(emacs.lookup(&keys("<A-gt>")), Binding::Action(Action::ChapterEnd));
+    assert_eq!(emacs.lookup(&keys("<C-x>")), Binding::Prefix);
+    assert_eq!(emacs.lookup(&keys("<C-x><C-c>")), Binding::Action(Action::QuitNow));
+    assert_eq!(emacs.lookup(&keys("j")), Binding::Action(Action::ScrollDown));
+
+    let less = Keymap::preset(Preset::Less);
+    assert_eq!(less.lookup(&keys("g")), Binding::Action(Action::ChapterStart));
+    assert_eq!(less.lookup(&keys("G")), Binding::Action(Action::ChapterEnd));
+    assert_eq!(less.lookup(&keys("f")), Binding::Action(Action::PageDown));
+    assert_eq!(less.lookup(&keys("y")), Binding::Action(Action::ScrollUp));
+    assert_eq!(less.lookup(&keys("<Left>")), Binding::Action(Action::ScrollLeft));
+    assert_eq!(less.lookup(&keys("/")), Binding::Action(Action::Search));
+
+    let mut config = Config::parse("keymap = \"less\"\n[keys]\nx = \"zen\"").unwrap();
+    assert_eq!(config.keys.lookup(&keys("e")), Binding::Action(Action::ScrollDown));
+    assert_eq!(config.keys.lookup(&keys("x")), Binding::Action(Action::Zen));
+    // --keymap keeps the file's changes
+    config.use_keymap(Preset::Vim).unwrap();
+    assert_eq!(config.keys.lookup(&keys("g")), Binding::Prefix);
+    assert_eq!(config.keys.lookup(&keys("x")), Binding::Action(Action::Zen));
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "tui")]
+
+mod common;
+
+use common::EpubBuilder;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+use catatau::EpubReaderOptions;
+use catatau::library::{LibraryCache, find_books, load_books, search_books};
+use catatau::search::SearchIndex;
+use catatau::progress::{Progress, book_key, key_hash};
+
+fn write_book(path: &Path, title: &str, author: &str) {
+    write_book_with_cover(path, title, author, None);
+}
+
+fn write_book_with_cover(path: &Path, title: &str, author: &str, cover: Option<&[u8]>) {
+    let book = EpubBuilder::new(title)
+        .metadata(&format!("<dc:creator>{author}</dc:creator>"))
+        .chapter("one.xhtml", "<html><body><p>Text</p></body></html>")
+        .chapter("two.xhtml", "<html><body><p>Text</p></body></html>")
+        .manifest(r#"<item id="cover" href="cover.jpg" media-type="image/jpeg" properties="cover-image"/>"#);
+    match cover {
+        Some(cover) => book.file("cover.jpg", cover).write(path),
+        None => book.write(path),
+    }
+}
+
+#[test]
+fn test_books_are_found_under_library_directories() {
+    let dir = TempDir::new().unwrap();
+    let nested = dir.path().join("eliot").join("novels");
+    std::fs::create_dir_all(&nested).unwrap();
+    write_book(&nested.join("middlemarch.epub"), "Middlemarch", "George Eliot");
+    write_book(&dir.path().join("DUBLINERS.EPUB"), "Dubliners", "James Joyce");
+    std::fs::write(dir.path().join("notes.txt"), "not a book").unwrap();
+
+    let found = find_books(&[dir.path().to_path_buf()]);
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&nested.join("middlemarch.epub")));
+    assert!(find_books(&[dir.path().join("missing")]).is_empty());
+}
+
+#[test]
+fn test_library_lists_books_by_title_with_progress() {
+    let dir = TempDir::new().unwrap();
+    write_book(&dir.path().join("b.epub"), "Middlemarch", "George Eliot");
+    write_book(&dir.path().join("a.epub"), "Dubliners", "James Joyce");
+    std::fs::write(dir.path().join("broken.epub"), "not a zip").unwrap();
+
+    let paths = find_books(&[dir.path().to_path_buf()]);
+    let books = load_books(&paths, &EpubReaderOptions::new(), &mut LibraryCache::default(), None);
+    let titles: Vec<&str> = books.iter().map(|book| book.title.as_str()).collect();
+    assert_eq!(titles, ["Dubliners", "Middlemarch"]);
+    assert_eq!(books[1].author, "George Eliot");
+
+    let mut progress = Progress::default();
+    assert_eq!(books[1].percent_read(&progress), None);
+    progress.book_mut(&book_key(&dir.path().join("b.epub"))).read_chapters.insert(0);
+    assert_eq!(books[1].percent_read(&progress), Some(50));
+    assert_eq!(books[0].percent_read(&progress), None);
+}
+
+#[test]
+fn test_library_cache_is_used_until_a_book_changes() {
+    let dir = TempDir::new().unwrap();
+    let covers = dir.path().join("covers");
+    let cache_file = dir.path().join("library.json");
+    let book = dir.path().join("middlemarch.epub");
+    write_book_with_cover(&book, "Middlemarch", "George Eliot", Some(b"not really a jpeg"));
+    let paths = vec![book.clone()];
+    let options = EpubReaderOptions::new();
+
+    let mut cache = LibraryCache::load(&cache_file);
+    assert_eq!(cache, LibraryCache::default());
+    let books = load_books(&paths, &options, &mut cache, Some(&covers));
+    assert_eq!(books[0].words, 2);
+    let cover = books[0].cover.clone().unwrap();
+    assert!(cover.starts_with(&covers));
+    assert_eq!(std::fs::read(&cover).unwrap(), b"not really a jpeg");
+    // Named the same in every build, so the cache outlives upgrades
+    assert_eq!(cover.file_name().unwrap(), format!("{}.jpg", key_hash(&book_key(&book))).as_str());
+    assert_eq!(std::fs::read_dir(&covers).unwrap().count(), 1);
+    cache.save(&cache_file).unwrap();
+    assert_eq!(LibraryCache::load(&cache_file), cache);
+
+    // An unchanged file is listed from the cache without being opened
+    let edited = std::fs::read_to_string(&cache_file).unwrap().replace("Middlemarch", "Cached");
+    std::fs::write(&cache_file, edited).unwrap();
+    let mut cache = LibraryCache::load(&cache_file);
+    assert_eq!(load_books(&paths, &options, &mut cache, Some(&covers))[0].title, "Cached");
+
+    // A changed one is read again
+    write_book(&book, "Middlemarch", "George Eliot");
+    let later = SystemTime::now() + Duration::from_secs(60);
+    File::options().write(true).open(&book).unwrap().set_modified(later).unwrap();
+    let books = load_books(&paths, &options, &mut cache, Some(&covers));
+    assert_eq!(books[0].title, "Middlemarch");
+    assert_eq!(books[0].cover, None);
+
+    std::fs::remove_file(&book).unwrap();
+    assert!(load_books(&[], &options, &mut cache, Some(&covers)).is_empty());
+    assert_eq!(cache, LibraryCache::default());
+}
+
+#[test]
+fn test_library_search_looks_through_indexed_books() {
+    let dir = TempDir::new().unwrap();
+    let index_dir = dir.path().join("index");
+    write_book(&dir.path().join("a.epub"), "Dubliners", "James Joyce");
+    write_book(&dir.path().join("b.epub"), "Middlemarch", "George Eliot");
+    let options = EpubReaderOptions::new();
+    let paths = find_books(&[dir.path().to_path_buf()]);
+    let books = load_books(&paths, &options, &mut LibraryCache::default(), None);
+    assert!(search_books(&books, "text", &options, &index_dir).is_empty());
+
+    let epub = options.clone().open(&books[1].path).unwrap();
+    SearchIndex::load_or_build(&epub, &index_dir);
+    let hits = search_books(&books, "text", &options, &index_dir);
+    assert_eq!(hits.len(), 2);
+    assert!(hits.iter().all(|hit| hit.book == 1 && hit.snippet == "Text"));
+    assert_eq!((hits[0].chapter, hits[1].chapter), (0, 1));
+    assert!(search_books(&books, "dragons", &options, &index_dir).is_empty());
+}
@@ -0,0 +1,61 @@
+use catatau::lookup::{Source, parse_wikipedia, parse_wiktionary, primary_language};
+use serde_json::json;
+
+#[test]
+fn test_wikipedia_summaries_are_read() {
+    let response = json!({
+        "type": "standard",
+        "title": "Lisbon",
+        "extract": "Lisbon is the capital and largest city of Portugal. ",
+        "content_urls": { "desktop": { "page": "https://en.wikipedia.org/wiki/Lisbon" } },
+    });
+    let summary = parse_wikipedia(&response).unwrap();
+    assert_eq!(summary.source, Source::Wikipedia);
+    assert_eq!(summary.title, "Lisbon");
+    assert_eq!(summary.text, "Lisbon is the capital and largest city of Portugal.");
+    assert_eq!(summary.url, "https://en.wikipedia.org/wiki/Lisbon");
+
+    assert_eq!(parse_wikipedia(&json!({ "title": "Lisbon", "extract": "" })), None);
+}
+
+#[test]
+fn test_wiktionary_definitions_are_read_for_the_books_language() {
+    let response = json!({
+        "en": [{
+            "partOfSpeech": "Noun",
+            "language": "English",
+            "definitions": [{ "definition": "A feeling of <a href=\"/wiki/longing\">longing</a>." }],
+        }],
+        "pt": [
+            {
+                "partOfSpeech": "Noun",
+                "language": "Portuguese",
+                "definitions": [
+                    { "definition": "<b>longing</b>, yearning" },
+                    { "definition": "" },
+                    { "definition": "nostalgia" },
+                ],
+            },
+            { "partOfSpeech": "Verb", "definitions": [] },
+        ],
+    });
+    let summary = parse_wiktionary(&response, "saudade", "pt").unwrap();
+    assert_eq!(summary.source, Source::Wiktionary);
+    assert_eq!(summary.text, "Noun\n1. longing, yearning\n2. nostalgia");
+    assert_eq!(summary.url, "https://en.wiktionary.org/wiki/saudade");
+
+    let summary = parse_wiktionary(&response, "saudade", "fr").unwrap();
+    assert_eq!(summary.text, "Noun\n1. A feeling of longing.");
+    assert_eq!(parse_wiktionary(&json!({}), "saudade", "pt"), None);
+}
+
+#[test]
+fn test_only_plain_language_codes_pick_the_wikipedia() {
+    assert_eq!(primary_language("pt-BR"), "pt");
+    assert_eq!(primary_language("EN_gb"), "en");
+    assert_eq!(primary_language("grc"), "grc");
+    assert_eq!(primary_language(""), "en");
+    assert_eq!(primary_language("evil.example/x?"), "en");
+    assert_eq!(primary_language("x-klingon"), "en");
+    assert_eq!(primary_language("dé"), "en");
+}
@@ -0,0 +1,110 @@
+use catatau::markdown::{Reference, html_to_markdown};
+
+fn markdown(html: &str) -> String {
+    html_to_markdown(html, &mut |_, url| url.to_string())
+}
+
+#[test]
+fn test_headings_emphasis_and_links_become_markdown() {
+    let html = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>Ignored</title><style>p { margin: 0 }</style></head>
+<body>
+  <h1>Chapter  One</h1>
+  <p>It was <em>not</em> the <strong>best</strong> of times,
+     said <a href="https://example.com/dickens">Dickens</a>.</p>
+  <p><i>Emphasis </i>keeps its spaces outside&nbsp;the markers.</p>
+  <hr/>
+  <h2 id="next">Next<br/>part</h2>
+</body>
+</html>"#;
+    assert_eq!(
+        markdown(html),
+        "# Chapter One\n\n\
+         It was *not* the **best** of times, said [Dickens](https://example.com/dickens).\n\n\
+         *Emphasis* keeps its spaces outside\u{a0}the markers.\n\n\
+         ---\n\n\
+         ## <a id=\"next\"></a>Next part\n"
+    );
+}
+
+#[test]
+fn test_anchors_are_kept_only_when_asked_for() {
+    let html = r#"<p id="p1">One.</p><p id="p2">Two.</p><table id="t"><tr><td>x</td></tr></table>"#;
+    let text = html_to_markdown(html, &mut |reference, url| match reference {
+        Reference::Anchor if url != "p1" => url.to_string(),
+        Reference::Anchor => String::new(),
+        _ => url.to_string(),
+    });
+    assert_eq!(
+        text,
+        "One.\n\n<a id=\"p2\"></a>Two.\n\n<a id=\"t\"></a>\n\n| x |\n| --- |\n"
+    );
+}
+
+#[test]
+fn test_ruby_keeps_its_reading_in_brackets() {
+    let html = "<p><ruby>漢<rp>(</rp><rt>kan</rt><rp>)</rp>字<rt>ji</rt></ruby>です</p>";
+    assert_eq!(markdown(html), "漢(kan)字(ji)です\n");
+}
+
+#[test]
+fn test_non_breaking_space_indents_are_dropped() {
+    let indent = "&#160;".repeat(4);
+    let html = format!("<p>{indent}Indented by hand,<br/>{indent}twice.</p>");
+    assert_eq!(markdown(&html), "Indented by hand,\\\ntwice.\n");
+}
+
+#[test]
+fn test_literal_markup_characters_are_escaped() {
+    assert_eq!(
+        markdown("<p>2 * 3 = 6, not_this [or] `that`</p>"),
+        "2 \\* 3 = 6, not\\_this \\[or\\] \\`that\\`\n"
+    );
+    assert_eq!(markdown("<p># not a heading</p>"), "\\# not a heading\n");
+    assert_eq!(markdown("<p>1984. A year.</p>"), "1984\\. A year.\n");
+    assert_eq!(markdown("<p>Use <code>a*b</code> here</p>"), "Use `a*b` here\n");
+}
+
+#[test]
+fn test_lists_quotes_code_and_tables_keep_their_structure() {
+    let html = "<body>\
+        <ul><li>one</li><li>two<ol start=\"3\"><li>three</li><li>four</li></ol></li></ul>\
+        <blockquote><p>Quoted</p><p>twice</p></blockquote>\
+        <pre class=\"language-rust\">fn main() {\n    println!(\"*\");\n}</pre>\
+        <table><tr><th>Name</th><th>Age</th></tr><tr><td>Ann | B</td><td>30</td></tr></table>\
+        </body>";
+    assert_eq!(
+        markdown(html),
+        "- one\n\
+         - two\n\
+         \x20 3. three\n\
+         \x20 4. four\n\n\
+         > Quoted\n\n\
+         > twice\n\n\
+         ```rust\nfn main() {\n    println!(\"*\");\n}\n```\n\n\
+         | Name | Age |\n\
+         | --- | --- |\n\
+         | Ann \\| B | 30 |\n"
+    );
+}
+
+#[test]
+fn test_links_and_images_are_rewritten() {
+    let mut seen = Vec::new();
+    let text = html_to_markdown(
+        r#"<p><a href="notes.xhtml#n1">1</a> <img src="../images/map.png" alt="A  map"/></p>"#,
+        &mut |reference, url| {
+            seen.push((reference, url.to_string()));
+            format!("out/{}", url.len())
+        },
+    );
+    assert_eq!(text, "[1](out/14) ![A map](out/17)\n");
+    assert_eq!(
+        seen,
+        vec![
+            (Reference::Link, "notes.xhtml#n1".to_string()),
+            (Reference::Image, "../images/map.png".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use catatau::epub::{EpubReader, EpubReaderOptions};
+use catatau::error::EpubError;
+use catatau::narration::{Clip, parse_clock, parse_smil};
+use common::EpubBuilder;
+use std::time::Duration;
+
+const SMIL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<smil xmlns="http://www.w3.org/ns/SMIL" xmlns:epub="http://www.idpf.org/2007/ops" version="3.0">
+  <body>
+    <seq epub:textref="chapter1.xhtml">
+      <par id="p1">
+        <text src="chapter1.xhtml#s1"/>
+        <audio src="../Audio/chapter%201.mp3" clipBegin="0:00:00.000" clipEnd="0:00:02.500"/>
+      </par>
+      <par id="p2">
+        <text src="chapter1.xhtml#s2"/>
+        <audio src="../Audio/chapter%201.mp3" clipBegin="2.5s" clipEnd="4100ms"/>
+      </par>
+      <par id="p3">
+        <text src="chapter1.xhtml#s3"/>
+        <audio src="../Audio/chapter%201.mp3" clipBegin="00:04.1"/>
+      </par>
+    </seq>
+  </body>
+</smil>"#;
+
+#[test]
+fn test_smil_clock_values() {
+    assert_eq!(parse_clock("0:01:02.5"), Some(Duration::from_millis(62_500)));
+    assert_eq!(parse_clock("01:02"), Some(Duration::from_secs(62)));
+    assert_eq!(parse_clock("3.5s"), Some(Duration::from_millis(3500)));
+    assert_eq!(parse_clock("250ms"), Some(Duration::from_millis(250)));
+    assert_eq!(parse_clock("2min"), Some(Duration::from_secs(120)));
+    assert_eq!(parse_clock("1h"), Some(Duration::from_secs(3600)));
+    assert_eq!(parse_clock("12"), Some(Duration::from_secs(12)));
+    assert_eq!(parse_clock("soon"), None);
+}
+
+#[test]
+fn test_media_overlay_clips_are_read_in_order() {
+    let clips = parse_smil(SMIL).unwrap();
+    assert_eq!(clips.len(), 3);
+    assert_eq!(
+        clips[1],
+        Clip {
+            document: "chapter1.xhtml".to_string(),
+            fragment: "s2".to_string(),
+            audio: "../Audio/chapter%201.mp3".to_string(),
+            begin: Duration::from_millis(2500),
+            end: Some(Duration::from_millis(4100)),
+        }
+    );
+    assert_eq!(clips[2].end, None);
+}
+
+#[test]
+fn test_chapters_with_media_overlays_have_narration() {
+    let (_temp_dir, epub_path) = EpubBuilder::new("Narrated")
+        .opf_path("OEBPS/content.opf")
+        .version("3.0")
+        .manifest(r#"
+    <item id="c1" href="Text/chapter1.xhtml" media-type="application/xhtml+xml"
+          media-overlay="o1"/>
+    <item id="c2" href="Text/chapter2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="o1" href="Text/chapter1.smil" media-type="application/smil+xml"/>
+    <item id="a1" href="Audio/chapter 1.mp3" media-type="audio/mpeg"/>"#)
+        .spine(r#"<itemref idref="c1"/><itemref idref="c2"/>"#)
+        .file(
+            "OEBPS/Text/chapter1.xhtml",
+            r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<p><span id="s1">Call me Ishmael.</span> <span id="s2">Some years ago.</span></p>
+<p id="s3">Never mind how long precisely.</p>
+</body></html>"#,
+        )
+        .file(
+            "OEBPS/Text/chapter2.xhtml",
+            r#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p>Silence.</p></body></html>"#,
+        )
+        .file("OEBPS/Text/chapter1.smil", SMIL)
+        .file("OEBPS/Audio/chapter 1.mp3", "not really audio")
+        .file("OEBPS/Audio/silence.mp3", vec![0u8; 200_000])
+        .build("narrated.epub");
+
+    let epub = EpubReader::new(&epub_path).unwrap();
+    assert!(epub.has_narration(0));
+    assert!(!epub.has_narration(1));
+    assert!(epub.narration(1).unwrap().is_empty());
+
+    let clips = epub.narration(0).unwrap();
+    assert_eq!(clips.len(), 3);
+    assert_eq!(clips[0].document, "OEBPS/Text/chapter1.xhtml");
+    assert_eq!(clips[0].audio, "OEBPS/Audio/chapter 1.mp3");
+
+    let chapter = epub.get_chapter(0).unwrap();
+    assert!(chapter.anchor_line(&clips[2].fragment).is_some());
+
+    assert_eq!(epub.read_entry(&clips[0].audio).unwrap(), b"not really audio");
+
+    // An entry can't be bigger than the whole book could be
+    let book_size = std::fs::metadata(&epub_path).unwrap().len();
+    let epub = EpubReaderOptions::default().max_file_size(book_size).open(&epub_path).unwrap();
+    assert!(matches!(
+        epub.read_entry("OEBPS/Audio/silence.mp3"),
+        Err(EpubError::FileTooLarge { .. })
+    ));
+}
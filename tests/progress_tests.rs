@@ -0,0 +1,129 @@
+use tempfile::TempDir;
+use catatau::progress::{BookProgress, Highlight, Progress, ReadingPosition, book_key, file_hash, key_hash};
+
+#[test]
+fn test_read_chapters_are_saved_and_loaded() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("catatau").join("progress.json");
+    assert_eq!(Progress::load(&path), Progress::default());
+
+    let mut progress = Progress::default();
+    progress.book_mut("/books/middlemarch.epub").read_chapters.extend([0, 1, 4]);
+    progress.book_mut("/books/dubliners.epub");
+    progress.save(&path).unwrap();
+
+    let loaded = Progress::load(&path);
+    assert_eq!(loaded, progress);
+    let book = loaded.book("/books/middlemarch.epub").unwrap();
+    assert_eq!(book.read_chapters.iter().copied().collect::<Vec<_>>(), [0, 1, 4]);
+    assert!(loaded.book("/books/unknown.epub").is_none());
+
+    std::fs::write(&path, "{").unwrap();
+    assert_eq!(Progress::load(&path), Progress::default());
+}
+
+#[test]
+fn test_book_keys_are_absolute() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("book.epub");
+    std::fs::write(&path, b"").unwrap();
+
+    let relative = dir.path().join(".").join("book.epub");
+    assert_eq!(book_key(&relative), book_key(&path));
+    assert!(std::path::Path::new(&book_key(&path)).is_absolute());
+}
+
+#[test]
+fn test_reading_position_is_saved_with_the_file_hash() {
+    let dir = TempDir::new().unwrap();
+    let book = dir.path().join("book.epub");
+    std::fs::write(&book, b"first edition").unwrap();
+    let hash = file_hash(&book).unwrap();
+    assert_eq!(hash, file_hash(&book).unwrap());
+    std::fs::write(&book, b"second edition").unwrap();
+    assert_ne!(hash, file_hash(&book).unwrap());
+
+    // Read in pieces, a big file hashes as it would all at once
+    let bytes: Vec<u8> = (0..200_000u32).map(|n| (n % 251) as u8).collect();
+    std::fs::write(&book, &bytes).unwrap();
+    let whole = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    assert_eq!(file_hash(&book).unwrap(), format!("{whole:016x}"));
+    assert_eq!(key_hash("a"), "af63dc4c8601ec8c");
+
+    let path = dir.path().join("progress.json");
+    let mut progress = Progress::default();
+    progress.book_mut("/books/middlemarch.epub").position = Some(ReadingPosition {
+        chapter: 3,
+        line: 120,
+        lines: 400,
+        file_hash: hash,
+    });
+    progress.save(&path).unwrap();
+    assert_eq!(Progress::load(&path), progress);
+}
+
+#[test]
+fn test_highlights_are_saved_and_loaded() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("progress.json");
+    let mut progress = Progress::default();
+    progress.book_mut("/books/middlemarch.epub").highlights.push(Highlight {
+        chapter: 2,
+        start: 140,
+        end: 212,
+        excerpt: "Miss Brooke had that kind of beauty".to_string(),
+        note: None,
+    });
+    progress.book_mut("/books/middlemarch.epub").highlights.push(Highlight {
+        chapter: 5,
+        start: 10,
+        end: 90,
+        excerpt: "the quiet perpetual motion of a fatal wheel".to_string(),
+        note: Some("Compare the opening of chapter 2.\nAnd the finale.".to_string()),
+    });
+    progress.book_mut("/books/dubliners.epub");
+    progress.save(&path).unwrap();
+
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(saved.matches("highlights").count(), 1);
+    assert_eq!(saved.matches("\"note\"").count(), 1);
+    assert_eq!(Progress::load(&path), progress);
+}
+
+#[test]
+fn test_a_damaged_progress_file_is_kept_rather_than_saved_over() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("progress.json");
+    let damaged = r#"{"books": {"/books/middlemarch.epub": {"highlights": [{"chap"#;
+    std::fs::write(&path, damaged).unwrap();
+
+    let mut progress = Progress::load(&path);
+    assert_eq!(progress, Progress::default());
+    progress.book_mut("/books/dubliners.epub").read_chapters.insert(1);
+    progress.save(&path).unwrap();
+
+    let kept: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|file| file.to_string_lossy().contains("progress.json.unreadable-"))
+        .collect();
+    assert_eq!(kept.len(), 1, "{kept:?}");
+    assert_eq!(std::fs::read_to_string(&kept[0]).unwrap(), damaged);
+    assert_eq!(Progress::load(&path), progress);
+}
+
+#[test]
+fn test_percent_through_counts_the_position_in_its_chapter() {
+    let mut book = BookProgress::default();
+    assert_eq!(book.percent_through(4), None);
+    book.position = Some(ReadingPosition {
+        chapter: 1,
+        line: 50,
+        lines: 100,
+        file_hash: String::new(),
+    });
+    assert_eq!(book.percent_through(4), Some(37.5));
+    assert_eq!(book.percent_through(0), None);
+}
@@ -0,0 +1,100 @@
+#![cfg(unix)]
+
+use catatau::error::RemoteError;
+use catatau::remote::{RemoteServer, parse_request, response};
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+#[test]
+fn test_requests_are_parsed_and_answered() {
+    let line = r#"{"jsonrpc": "2.0", "id": 7, "method": "search", "params": {"query": "x"}}"#;
+    let (id, method, params) = parse_request(line).unwrap();
+    assert_eq!((id, method.as_str()), (Some(json!(7)), "search"));
+    assert_eq!(params["query"], "x");
+
+    let (id, _, params) = parse_request(r#"{"jsonrpc": "2.0", "method": "quit"}"#).unwrap();
+    assert_eq!((id, params), (None, Value::Null));
+    assert!(matches!(parse_request("{not json"), Err(RemoteError::Parse(_))));
+    assert!(matches!(parse_request(r#"{"id": 1}"#), Err(RemoteError::Parse(_))));
+
+    assert_eq!(
+        response(json!(1), Ok(json!({"chapter": 2}))),
+        json!({"jsonrpc": "2.0", "id": 1, "result": {"chapter": 2}})
+    );
+    let error = response(json!(2), Err(RemoteError::UnknownMethod("fly".to_string())));
+    assert_eq!(error["error"]["code"], -32601);
+}
+
+#[test]
+fn test_requests_reach_the_reader_through_the_socket() {
+    let temp_dir = TempDir::new().unwrap();
+    let socket = temp_dir.path().join("ctt.sock");
+    let server = RemoteServer::listen(&socket).unwrap();
+    let client = std::thread::spawn({
+        let socket = socket.clone();
+        move || {
+            let mut stream = UnixStream::connect(&socket).unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+            writeln!(stream, r#"{{"jsonrpc": "2.0", "method": "ping"}}"#).unwrap();
+            writeln!(stream, r#"{{"jsonrpc": "2.0", "id": 1, "method": "ping"}}"#).unwrap();
+            writeln!(stream, "nonsense").unwrap();
+            let answers: Vec<Value> = (0..2)
+                .map(|_| serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap())
+                .collect();
+            answers
+        }
+    });
+
+    let start = Instant::now();
+    let mut answered = 0;
+    while answered < 2 && start.elapsed() < Duration::from_secs(5) {
+        match server.next_call() {
+            Some(call) => {
+                assert_eq!(call.method, "ping");
+                call.answer(Ok(json!("pong")));
+                answered += 1;
+            }
+            None => std::thread::sleep(Duration::from_millis(10)),
+        }
+    }
+    let answers = client.join().unwrap();
+    assert_eq!(answers[0], json!({"jsonrpc": "2.0", "id": 1, "result": "pong"}));
+    assert_eq!(answers[1]["error"]["code"], -32700);
+
+    drop(server);
+    assert!(!socket.exists());
+}
+
+#[test]
+fn test_only_stale_sockets_are_replaced() {
+    let temp_dir = TempDir::new().unwrap();
+    let notes = temp_dir.path().join("notes.txt");
+    std::fs::write(&notes, "keep me").unwrap();
+    let error = RemoteServer::listen(&notes).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read_to_string(&notes).unwrap(), "keep me");
+
+    let socket = temp_dir.path().join("ctt.sock");
+    drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+    assert!(socket.exists());
+    let server = RemoteServer::listen(&socket).unwrap();
+    drop(server);
+}
+
+#[test]
+fn test_overlong_requests_are_refused() {
+    let temp_dir = TempDir::new().unwrap();
+    let socket = temp_dir.path().join("ctt.sock");
+    let _server = RemoteServer::listen(&socket).unwrap();
+    let mut stream = UnixStream::connect(&socket).unwrap();
+    let padding = "x".repeat(catatau::constants::MAX_REMOTE_REQUEST_BYTES);
+    // The reader may stop reading before the whole line is written
+    let _ = writeln!(stream, r#"{{"jsonrpc": "2.0", "id": 1, "method": "{padding}"}}"#);
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer).unwrap();
+    let answer: Value = serde_json::from_str(&answer).unwrap();
+    assert_eq!(answer["error"]["code"], -32700);
+}
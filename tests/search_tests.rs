@@ -0,0 +1,108 @@
+use catatau::search::{Query, SearchMode, find_matches, fuzzy_match};
+
+#[test]
+fn test_every_match_is_found_ignoring_case() {
+    let text = "The sea, the Sea! THE SEA";
+    let matches: Vec<&str> = find_matches(text, "the sea")
+        .into_iter()
+        .map(|range| &text[range])
+        .collect();
+    assert_eq!(matches, ["The sea", "the Sea", "THE SEA"]);
+
+    assert_eq!(find_matches("aaaa", "aa"), [0..2, 2..4]);
+    assert!(find_matches("anything", "").is_empty());
+    assert!(find_matches("short", "longer query").is_empty());
+}
+
+#[test]
+fn test_match_offsets_survive_case_changes() {
+    // Lowercasing İ gives two characters, and ẞ is three bytes to ß's two
+    let text = "İstanbul and STRAẞE";
+    let matches = find_matches(text, "straße");
+    assert_eq!(matches.len(), 1);
+    assert_eq!(&text[matches[0].clone()], "STRAẞE");
+    assert_eq!(&text[find_matches(text, "and")[0].clone()], "and");
+}
+
+#[test]
+fn test_search_ignores_accents_and_soft_hyphens() {
+    let found = |text: &str, query: &str| -> Vec<String> {
+        find_matches(text, query)
+            .into_iter()
+            .map(|range| text[range].to_string())
+            .collect()
+    };
+    assert_eq!(found("Un café, CAFÉ", "cafe"), ["café", "CAFÉ"]);
+    assert_eq!(found("a cafe", "café"), ["cafe"]);
+    // Decomposed accents and soft hyphens stay inside the match
+    assert_eq!(found("cafe\u{301} noir", "café"), ["cafe\u{301}"]);
+    assert_eq!(
+        found("extra\u{ad}ordi\u{ad}nary", "extraordinary"),
+        ["extra\u{ad}ordi\u{ad}nary"]
+    );
+    assert_eq!(found("the ﬁrst ﬂoor", "first"), ["ﬁrst"]);
+
+    let text = "Crème brûlée";
+    let fuzzy = fuzzy_match(text, "creme brulee").unwrap();
+    let matched: Vec<&str> = fuzzy.ranges.iter().map(|range| &text[range.clone()]).collect();
+    assert_eq!(matched, ["Crème", "brûlée"]);
+}
+
+#[test]
+fn test_fuzzy_match_finds_characters_in_order() {
+    let text = "The magic crystal glowed";
+    let found = fuzzy_match(text, "MgCry").unwrap();
+    let matched: Vec<&str> = found.ranges.iter().map(|range| &text[range.clone()]).collect();
+    assert_eq!(matched, ["m", "g", "cry"]);
+
+    assert!(fuzzy_match(text, "yrc").is_none());
+    assert!(fuzzy_match(text, "").is_none());
+    // Spaces in the query are ignored
+    assert!(fuzzy_match(text, "magic  glow").is_some());
+}
+
+#[test]
+fn test_fuzzy_match_ranks_tight_word_start_matches_first() {
+    let score = |text: &str| fuzzy_match(text, "cry").unwrap().score;
+    assert!(score("a crystal") > score("a cherry"));
+    assert!(score("a cherry") > score("cold and dry"));
+    assert!(score("the crystal") > score("secretly"));
+
+    assert_eq!(SearchMode::Substring.find("cold and dry", "cry"), None);
+    assert!(SearchMode::Fuzzy.find("cold and dry", "cry").is_some());
+    let found = SearchMode::Substring.find("a crystal", "CRY").unwrap();
+    assert_eq!(found.score, 0);
+    assert_eq!(found.ranges.len(), 1);
+    assert_eq!(found.ranges[0], 2..5);
+}
+
+#[test]
+fn test_query_syntax_is_parsed() {
+    let query = Query::parse(r#"red  dragon +castle -"the prologue" "old keep" - x"#);
+    assert_eq!(query.phrases, ["red dragon", "old keep", "- x"]);
+    assert_eq!(query.required, ["castle"]);
+    assert_eq!(query.excluded, ["the prologue"]);
+
+    // Plain queries stay one phrase, and hyphens inside words are kept
+    assert_eq!(Query::parse("magic crystal").phrases, ["magic crystal"]);
+    assert_eq!(Query::parse("well-known").phrases, ["well-known"]);
+    assert_eq!(Query::parse(r#"+"unfinished phrase"#).required, ["unfinished phrase"]);
+    assert_eq!(Query::parse("  "), Query::default());
+}
+
+#[test]
+fn test_query_matches_lines_and_chapters() {
+    let query = Query::parse(r#"dragon "red keep" +castle -prologue"#);
+    let line = "The dragon flew over the Red Keep";
+    let matched: Vec<&str> = query.find(line).into_iter().map(|range| &line[range]).collect();
+    assert_eq!(matched, ["dragon", "Red Keep"]);
+    assert!(query.find("The castle walls").is_empty());
+
+    assert!(query.allows_chapter("A castle.\nA dragon."));
+    assert!(!query.allows_chapter("A dragon."));
+    assert!(!query.allows_chapter("Prologue\nA castle and a dragon."));
+
+    // With only + terms, lines are matched against those
+    let query = Query::parse("+castle");
+    assert_eq!(query.find("the castle").len(), 1);
+}
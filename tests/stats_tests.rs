@@ -0,0 +1,72 @@
+use tempfile::TempDir;
+use catatau::stats::{ReadingStats, format_date, format_duration, parse_date};
+
+#[test]
+fn test_dates_round_trip() {
+    assert_eq!(format_date(0), "1970-01-01");
+    assert_eq!(format_date(11_016), "2000-02-29");
+    assert_eq!(format_date(20_742), "2026-10-16");
+    for day in [0, 59, 60, 365, 11_016, 20_742, 40_000] {
+        assert_eq!(parse_date(&format_date(day)), Some(day));
+    }
+    assert_eq!(parse_date("2026-13-01"), None);
+    assert_eq!(parse_date("yesterday"), None);
+}
+
+#[test]
+fn test_reading_time_totals() {
+    let mut stats = ReadingStats::default();
+    stats.record("Middlemarch", 100, 600);
+    stats.record("Middlemarch", 95, 300);
+    stats.record("Dubliners", 100, 120);
+    stats.record("Dubliners", 90, 60);
+
+    assert_eq!(stats.day_total(100), 720);
+    assert_eq!(stats.day_total(99), 0);
+    assert_eq!(stats.week_total(100), 1020);
+    assert_eq!(stats.book_totals(), [("Middlemarch", 900), ("Dubliners", 180)]);
+}
+
+#[test]
+fn test_reading_stats_are_saved_and_loaded() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("catatau").join("stats.json");
+    assert_eq!(ReadingStats::load(&path), ReadingStats::default());
+
+    let mut stats = ReadingStats::default();
+    stats.record("Middlemarch", 20_742, 1800);
+    stats.save(&path).unwrap();
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(saved.contains("2026-10-16"));
+    assert_eq!(ReadingStats::load(&path), stats);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert_eq!(ReadingStats::load(&path), ReadingStats::default());
+    assert!(!path.exists(), "the unreadable file is set aside");
+    let kept = std::fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|file| file.to_string_lossy().contains("stats.json.unreadable-"));
+    assert_eq!(std::fs::read_to_string(kept.unwrap()).unwrap(), "not json");
+}
+
+#[test]
+fn test_reading_time_is_formatted() {
+    assert_eq!(format_duration(30), "<1m");
+    assert_eq!(format_duration(12 * 60), "12m");
+    assert_eq!(format_duration(2 * 3600 + 5 * 60 + 40), "2h 05m");
+}
+
+#[test]
+fn test_reading_time_is_merged() {
+    let mut saved = ReadingStats::default();
+    saved.record("Middlemarch", 100, 600);
+    let mut session = ReadingStats::default();
+    session.record("Middlemarch", 100, 60);
+    session.record("Dubliners", 101, 120);
+
+    saved.merge(&session);
+    assert_eq!(saved.day_total(100), 660);
+    assert_eq!(saved.day_total(101), 120);
+    assert_eq!(saved.book_totals(), [("Middlemarch", 660), ("Dubliners", 120)]);
+}
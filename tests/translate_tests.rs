@@ -0,0 +1,31 @@
+use catatau::translate::{Backend, Translator, parse_deepl, parse_google};
+use serde_json::json;
+
+#[test]
+fn test_translator_backends_are_named_on_the_command_line() {
+    assert_eq!("argos".parse::<Backend>(), Ok(Backend::Argos));
+    assert_eq!("DeepL".parse::<Backend>(), Ok(Backend::DeepL));
+    assert_eq!("google".parse::<Backend>(), Ok(Backend::Google));
+    assert!("babelfish".parse::<Backend>().is_err());
+
+    let translator = Translator::new(Backend::DeepL, "pt-BR");
+    assert_eq!(translator.target, "pt-BR");
+}
+
+#[test]
+fn test_translations_are_read_from_api_responses() {
+    let deepl = json!({
+        "translations": [{ "detected_source_language": "EN", "text": "Bom dia" }]
+    });
+    assert_eq!(parse_deepl(&deepl).as_deref(), Some("Bom dia"));
+
+    let google = json!({
+        "data": {
+            "translations": [{ "translatedText": "Bonjour", "detectedSourceLanguage": "en" }]
+        }
+    });
+    assert_eq!(parse_google(&google).as_deref(), Some("Bonjour"));
+
+    assert_eq!(parse_deepl(&json!({ "message": "Wrong endpoint" })), None);
+    assert_eq!(parse_google(&json!({ "error": { "code": 403 } })), None);
+}
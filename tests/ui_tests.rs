@@ -1,49 +1,16 @@
-use std::fs::File;
-use std::io::Write;
+#![cfg(feature = "tui")]
+
+mod common;
+
+use common::EpubBuilder;
 use tempfile::TempDir;
-use zip::{ZipWriter, write::FileOptions, CompressionMethod};
 use catatau::{EpubReader, App};
 
 fn create_test_epub_with_content() -> (TempDir, std::path::PathBuf) {
-    let temp_dir = TempDir::new().unwrap();
-    let epub_path = temp_dir.path().join("test.epub");
-    let file = File::create(&epub_path).unwrap();
-    let mut zip = ZipWriter::new(file);
-
-    // Add mimetype
-    zip.start_file("mimetype", FileOptions::<()>::default().compression_method(CompressionMethod::Stored)).unwrap();
-    zip.write_all(b"application/epub+zip").unwrap();
-
-    // Add container.xml
-    zip.start_file("META-INF/container.xml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
-<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
-  <rootfiles>
-    <rootfile full-path="content.opf" media-type="application/oebps-package+xml"/>
-  </rootfiles>
-</container>"#).unwrap();
-
-    // Add OPF file
-    zip.start_file("content.opf", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
-<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="uuid_id" version="2.0">
-  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
-    <dc:title>Search Test Book</dc:title>
-    <dc:creator>Test Author</dc:creator>
-  </metadata>
-  <manifest>
-    <item id="chapter1" href="OEBPS/chapter1.xhtml" media-type="application/xhtml+xml"/>
-    <item id="chapter2" href="OEBPS/chapter2.xhtml" media-type="application/xhtml+xml"/>
-  </manifest>
-  <spine>
-    <itemref idref="chapter1"/>
-    <itemref idref="chapter2"/>
-  </spine>
-</package>"#).unwrap();
-
-    // Add chapter files with searchable content
-    zip.start_file("OEBPS/chapter1.xhtml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+    EpubBuilder::new("Search Test Book")
+        .metadata("<dc:creator>Test Author</dc:creator>")
+        // Chapter files with searchable content
+        .chapter("OEBPS/chapter1.xhtml", r#"<?xml version="1.0" encoding="UTF-8"?>
 <html xmlns="http://www.w3.org/1999/xhtml">
 <head><title>Chapter 1</title></head>
 <body>
@@ -54,10 +21,8 @@ fn create_test_epub_with_content() -> (TempDir, std::path::PathBuf) {
 <p>More content to make this chapter longer and more searchable.</p>
 <p>The adventure begins with a mysterious sound in the distance.</p>
 </body>
-</html>"#).unwrap();
-
-    zip.start_file("OEBPS/chapter2.xhtml", FileOptions::<()>::default()).unwrap();
-    zip.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
+</html>"#)
+        .chapter("OEBPS/chapter2.xhtml", r#"<?xml version="1.0" encoding="UTF-8"?>
 <html xmlns="http://www.w3.org/1999/xhtml">
 <head><title>Chapter 2</title></head>
 <body>
@@ -68,10 +33,8 @@ fn create_test_epub_with_content() -> (TempDir, std::path::PathBuf) {
 <p>Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris.</p>
 <p>The final destination becomes clear as they reach the mountaintop.</p>
 </body>
-</html>"#).unwrap();
-
-    zip.finish().unwrap();
-    (temp_dir, epub_path)
+</html>"#)
+        .build("test.epub")
 }
 
 #[test]
@@ -101,6 +64,81 @@ fn test_search_functionality() {
     assert!(results[1].context.contains("magic crystal"));
 }
 
+#[test]
+fn test_background_search_streams_every_chapter() {
+    use catatau::search::{BackgroundSearch, SearchMode};
+    use std::{sync::Arc, time::{Duration, Instant}};
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = Arc::new(EpubReader::new(&epub_path).expect("Failed to parse test EPUB"));
+
+    let mut search = BackgroundSearch::start(
+        Arc::clone(&epub),
+        "MAGIC crystal",
+        vec![0, 1],
+        10,
+        SearchMode::Substring,
+    );
+    let mut hits = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !search.is_finished() && Instant::now() < deadline {
+        hits.extend(search.poll());
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    hits.extend(search.poll());
+
+    assert!(search.is_finished());
+    assert_eq!(search.progress(), (2, 2));
+    let chapters: Vec<usize> = hits.iter().map(|hit| hit.chapter).collect();
+    assert_eq!(chapters, [0, 1]);
+    let lines = epub.get_chapter(0).unwrap().content.lines().count();
+    assert!(hits[0].line < lines);
+    assert!(hits[0].text.to_lowercase().contains("magic crystal"));
+}
+
+#[test]
+fn test_search_index_narrows_chapters() {
+    use catatau::search::SearchIndex;
+
+    let (temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+
+    let index = SearchIndex::build(&epub);
+    assert_eq!(index.candidates("MAGIC crystal"), Some(vec![0, 1]));
+    assert_eq!(index.candidates("extraordinary"), Some(vec![0]));
+    assert_eq!(index.candidates("guardian"), Some(vec![1]));
+    assert_eq!(index.candidates("unicorn"), Some(vec![]));
+    assert_eq!(index.candidates("ex"), None);
+
+    // The first open saves the index, and later ones read it back
+    let dir = temp_dir.path().join("index");
+    assert_eq!(SearchIndex::load_or_build(&epub, &dir), index);
+    let saved: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(saved.len(), 1);
+    let path = saved[0].as_ref().unwrap().path();
+    assert_eq!(SearchIndex::load(&path), Some(index.clone()));
+    assert_eq!(SearchIndex::load_or_build(&epub, &dir), index);
+
+    std::fs::write(&path, "not an index").unwrap();
+    assert_eq!(SearchIndex::load(&path), None);
+
+    // Indexes of other books go, the longest unused first, to make room
+    std::fs::remove_file(&path).unwrap();
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+    for n in 0..catatau::constants::MAX_SAVED_SEARCH_INDEXES {
+        let other = dir.join(format!("other-{n}.idx"));
+        std::fs::write(&other, "").unwrap();
+        let modified = old + std::time::Duration::from_secs(n as u64);
+        std::fs::File::options().write(true).open(&other).unwrap().set_modified(modified).unwrap();
+    }
+    SearchIndex::load_or_build(&epub, &dir);
+    assert!(path.exists());
+    assert!(!dir.join("other-0.idx").exists());
+    assert!(dir.join("other-1.idx").exists());
+    let saved = std::fs::read_dir(&dir).unwrap().count();
+    assert_eq!(saved, catatau::constants::MAX_SAVED_SEARCH_INDEXES);
+}
+
 #[test]
 fn test_search_case_insensitive() {
     let (_temp_dir, epub_path) = create_test_epub_with_content();
@@ -156,4 +194,977 @@ fn test_search_with_line_numbers() {
     assert!(first_result.line_number < epub.get_chapter_line_count(0));
     let chapter0 = epub.get_chapter(0).expect("Failed to get chapter 0");
     assert!(first_result.position < chapter0.content.len());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_app_resumes_where_the_book_was_left() {
+    use catatau::progress::{Progress, ReadingPosition, book_key, file_hash};
+
+    let (temp_dir, epub_path) = create_test_epub_with_content();
+    let state_dir = temp_dir.path().join("state");
+    let position = |file_hash: String| ReadingPosition {
+        chapter: 1,
+        line: 0,
+        lines: 10,
+        file_hash,
+    };
+    let mut progress = Progress::default();
+    progress.book_mut(&book_key(&epub_path)).position =
+        Some(position(file_hash(&epub_path).unwrap()));
+    progress.save(&state_dir.join("progress.json")).unwrap();
+
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.keep_state(state_dir.clone());
+    app.resume();
+    assert_eq!(app.current_chapter(), 1);
+
+    // A position saved for another version of the file is ignored
+    progress.book_mut(&book_key(&epub_path)).position = Some(position("0".repeat(16)));
+    progress.save(&state_dir.join("progress.json")).unwrap();
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.keep_state(state_dir);
+    app.resume();
+    assert_eq!(app.current_chapter(), 0);
+}
+
+// A book of one chapter per body, written over `path`
+fn write_epub(path: &std::path::Path, chapters: &[&str]) {
+    let book = chapters.iter().fold(EpubBuilder::new("Draft"), |book, body| book.body(body));
+    book.write(path);
+}
+
+#[test]
+fn test_reloading_keeps_the_place_in_the_changed_book() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("draft.epub");
+    let paragraphs = |word: &str| format!("<p>{word}</p>").repeat(40);
+    write_epub(&epub_path, &[&paragraphs("one"), &paragraphs("two"), &paragraphs("three")]);
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.go_to_chapter(2).unwrap();
+    app.go_to_book_percent(50);
+    let (chapter, offset) = (app.current_chapter(), app.scroll_offset());
+    assert!(offset > 0);
+
+    write_epub(&epub_path, &[&paragraphs("one"), &paragraphs("deux"), &paragraphs("three")]);
+    app.reload().unwrap();
+    assert_eq!((app.current_chapter(), app.scroll_offset()), (chapter, offset));
+    assert!(app.epub().get_chapter(1).unwrap().content.contains("deux"));
+
+    // A chapter that's gone leaves the reader in the last one there is
+    write_epub(&epub_path, &[&paragraphs("one")]);
+    app.go_to_chapter(3).unwrap();
+    app.reload().unwrap();
+    assert_eq!(app.current_chapter(), 0);
+
+    std::fs::write(&epub_path, "not a book").unwrap();
+    assert!(app.reload().is_err());
+    assert_eq!(app.epub().chapter_count(), 1);
+}
+
+#[test]
+fn test_source_view_shows_the_xhtml_read_since_the_last_reload() {
+    use catatau::DisplayOptions;
+    use crossterm::event::{KeyCode, KeyEvent};
+    use ratatui::backend::TestBackend;
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("draft.epub");
+    let lines = |word: &str| format!("<p>{word}</p>\n").repeat(30);
+    write_epub(&epub_path, &[&lines("first")]);
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let mut app = App::with_backend(epub, DisplayOptions::default(), TestBackend::new(60, 16)).unwrap();
+    let screen = |app: &mut App<TestBackend>| {
+        let buffer = app.render_to_buffer().unwrap();
+        let symbols: Vec<&str> = buffer.content.iter().map(|cell| cell.symbol()).collect();
+        symbols.concat()
+    };
+
+    app.handle_key(KeyEvent::from(KeyCode::Char('V'))).unwrap();
+    assert!(screen(&mut app).contains("<p>first</p>"));
+    // Scrolling stops at the last line of the XHTML, not of the text
+    app.handle_key(KeyEvent::from(KeyCode::Char('G'))).unwrap();
+    let bottom = app.scroll_offset();
+    assert!(bottom > 0);
+    app.handle_key(KeyEvent::from(KeyCode::Char('j'))).unwrap();
+    assert_eq!(app.scroll_offset(), bottom);
+
+    write_epub(&epub_path, &[&lines("second")]);
+    app.reload().unwrap();
+    let after = screen(&mut app);
+    assert!(after.contains("<p>second</p>") && !after.contains("first"), "{after}");
+}
+
+#[test]
+fn test_keys_update_the_reader_without_a_terminal() {
+    use catatau::Message;
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    let press = |app: &mut App, keys: &str| {
+        for c in keys.chars() {
+            app.update(Message::Key(KeyEvent::from(KeyCode::Char(c)))).unwrap();
+        }
+    };
+    app.update(Message::Resize(80, 10)).unwrap();
+
+    press(&mut app, "l");
+    assert_eq!(app.current_chapter(), 1);
+    press(&mut app, "3j");
+    assert_eq!(app.scroll_offset(), 3);
+    press(&mut app, "gg");
+    assert_eq!(app.scroll_offset(), 0);
+    press(&mut app, "h");
+    assert_eq!(app.current_chapter(), 0);
+    app.update(Message::Tick).unwrap();
+}
+
+#[test]
+fn test_the_whole_reader_draws_to_a_test_backend() {
+    use catatau::DisplayOptions;
+    use crossterm::event::{KeyCode, KeyEvent};
+    use ratatui::backend::TestBackend;
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let backend = TestBackend::new(60, 16);
+    let mut app = App::with_backend(epub, DisplayOptions::default(), backend).unwrap();
+    let screen = |app: &mut App<TestBackend>| {
+        let buffer = app.render_to_buffer().unwrap();
+        let width = buffer.area.width as usize;
+        let symbols: Vec<&str> = buffer.content.iter().map(|cell| cell.symbol()).collect();
+        symbols.chunks(width).map(|row| row.concat()).collect::<Vec<_>>().join("\n")
+    };
+
+    let first = screen(&mut app);
+    assert!(first.contains("The Beginning"), "{first}");
+    assert!(!first.contains("The Journey Continues"));
+
+    app.handle_key(KeyEvent::from(KeyCode::Char('l'))).unwrap();
+    let second = screen(&mut app);
+    assert!(second.contains("The Journey Continues"), "{second}");
+
+    app.handle_key(KeyEvent::from(KeyCode::Char('-'))).unwrap();
+    assert!(screen(&mut app).contains("Chapter 1"));
+}
+
+#[test]
+fn test_line_spacing_leaves_blank_rows_between_lines() {
+    use catatau::{DisplayOptions, LineSpacing};
+    use ratatui::backend::TestBackend;
+
+    let spacing: LineSpacing = "1.5".parse().unwrap();
+    assert_eq!((0..5).map(|index| spacing.row_of(index)).collect::<Vec<_>>(), [0, 1, 3, 4, 6]);
+    assert_eq!(spacing.lines_in(7), 5);
+    assert_eq!(spacing.lines_in(6), 4);
+    assert_eq!("double".parse::<LineSpacing>().unwrap().lines_in(7), 4);
+    assert_eq!("single".parse::<LineSpacing>().unwrap(), LineSpacing::SINGLE);
+    assert!("0.5".parse::<LineSpacing>().is_err());
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("spaced.epub");
+    let lines: String = (1..=12).map(|n| format!("line {n}<br/>")).collect();
+    write_epub(&epub_path, &[&format!("<p>{lines}</p>")]);
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let display = DisplayOptions {
+        line_spacing: "double".parse().unwrap(),
+        ..DisplayOptions::default()
+    };
+    let mut app = App::with_backend(epub, display, TestBackend::new(60, 20)).unwrap();
+    let buffer = app.render_to_buffer().unwrap();
+    let width = buffer.area.width as usize;
+    let symbols: Vec<&str> = buffer.content.iter().map(|cell| cell.symbol()).collect();
+    let rows: Vec<String> = symbols.chunks(width).map(|row| row.concat()).collect();
+    // The chapter's title, in the border above, is its first line too
+    let row_of = |text: &str| rows.iter().rposition(|row| row.contains(&format!("{text} ")));
+
+    let first = row_of("line 1").unwrap();
+    assert!(row_of("line 6").is_some());
+    assert!(!rows[first + 1].contains("line"));
+    // Twenty rows leave eleven for text, which fit six double-spaced lines
+    assert!(row_of("line 6").is_some());
+    assert!(row_of("line 7").is_none());
+}
+
+#[test]
+fn test_the_eink_profile_writes_progress_instead_of_drawing_a_bar() {
+    use catatau::{DisplayOptions, Profile};
+    use ratatui::backend::TestBackend;
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let footer = |profile: Profile| -> String {
+        let epub = EpubReader::new(&epub_path).unwrap();
+        let display = DisplayOptions {
+            profile,
+            ..DisplayOptions::default()
+        };
+        let mut app = App::with_backend(epub, display, TestBackend::new(60, 16)).unwrap();
+        let buffer = app.render_to_buffer().unwrap();
+        let width = buffer.area.width as usize;
+        let symbols: Vec<&str> = buffer.content.iter().map(|cell| cell.symbol()).collect();
+        let rows: Vec<String> = symbols.chunks(width).map(|row| row.concat()).collect();
+        rows.into_iter().find(|row| row.contains("Chapter 1/")).unwrap()
+    };
+
+    assert!(footer(Profile::Standard).contains('█'));
+    let eink = footer(Profile::Eink);
+    assert!(!eink.contains('█'), "{eink}");
+    assert!(eink.contains("%"), "{eink}");
+    assert_eq!("e-ink".parse::<Profile>(), Ok(Profile::Eink));
+}
+
+#[test]
+fn test_cached_lines_are_restyled_when_the_search_changes() {
+    use catatau::DisplayOptions;
+    use crossterm::event::{KeyCode, KeyEvent};
+    use ratatui::backend::TestBackend;
+    use ratatui::buffer::Buffer;
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("draft.epub");
+    let chapter = format!("{}<p>some magic here</p>", "<p>plain words</p>".repeat(3));
+    write_epub(&epub_path, &[&chapter, &"<p>more words</p>".repeat(40)]);
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let backend = TestBackend::new(60, 16);
+    let mut app = App::with_backend(epub, DisplayOptions::default(), backend).unwrap();
+    let text = |buffer: &Buffer| {
+        buffer.content.iter().map(|cell| cell.symbol()).collect::<String>()
+    };
+    // The styles of the first letters of "some" and "magic"
+    let styles = |buffer: &Buffer| {
+        let screen = text(buffer);
+        let at = |word: &str| {
+            let column = screen.find(word).unwrap();
+            buffer.content[screen[..column].chars().count()].style()
+        };
+        (at("some"), at("magic"))
+    };
+
+    let plain = app.render_to_buffer().unwrap();
+    let (some, magic) = styles(&plain);
+    assert_eq!(some, magic);
+
+    app.find_first("magic").unwrap();
+    let searched = app.render_to_buffer().unwrap();
+    let (some, magic) = styles(&searched);
+    assert_ne!(some, magic);
+
+    app.handle_key(KeyEvent::from(KeyCode::Esc)).unwrap();
+    app.handle_key(KeyEvent::from(KeyCode::Char('l'))).unwrap();
+    app.handle_key(KeyEvent::from(KeyCode::Char('h'))).unwrap();
+    let again = app.render_to_buffer().unwrap();
+    assert_eq!(text(&again), text(&plain));
+    assert_eq!(styles(&again), styles(&plain));
+}
+
+#[test]
+fn test_emphasis_is_styled_and_literal_asterisks_kept() {
+    use catatau::DisplayOptions;
+    use ratatui::backend::TestBackend;
+    use ratatui::style::Modifier;
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("draft.epub");
+    let chapter = "<p>Note that 2 * 3 * 4 is <em>quite</em> <strong>large</strong> here</p>";
+    write_epub(&epub_path, &[chapter]);
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let backend = TestBackend::new(60, 16);
+    let mut app = App::with_backend(epub, DisplayOptions::default(), backend).unwrap();
+
+    let buffer = app.render_to_buffer().unwrap();
+    let screen: String = buffer.content.iter().map(|cell| cell.symbol()).collect();
+    assert!(screen.contains("2 * 3 * 4 is quite large here"), "{screen}");
+    // The chapter's title in the header is taken from the same text
+    let modifiers = |word: &str| {
+        let column = screen.rfind(word).unwrap();
+        buffer.content[screen[..column].chars().count()].style().add_modifier
+    };
+    assert!(modifiers("quite").contains(Modifier::ITALIC));
+    assert!(modifiers("large").contains(Modifier::BOLD));
+    assert!(!modifiers("here").intersects(Modifier::ITALIC | Modifier::BOLD));
+    assert!(!modifiers("3 *").contains(Modifier::ITALIC));
+}
+
+#[test]
+fn test_next_chapter_is_loaded_near_the_end_of_one() {
+    use catatau::Message;
+    use crossterm::event::{KeyCode, KeyEvent};
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("draft.epub");
+    let paragraphs = |word: &str| format!("<p>{word}</p>").repeat(200);
+    write_epub(&epub_path, &[&paragraphs("one"), &paragraphs("two"), &paragraphs("three")]);
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.update(Message::Resize(80, 20)).unwrap();
+
+    app.update(Message::Tick).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!app.epub().is_chapter_cached(1));
+
+    app.update(Message::Key(KeyEvent::from(KeyCode::Char('G')))).unwrap();
+    app.update(Message::Tick).unwrap();
+    let start = Instant::now();
+    while !app.epub().is_chapter_cached(1) {
+        assert!(start.elapsed() < Duration::from_secs(5), "chapter 2 wasn't loaded");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!app.epub().is_chapter_cached(2));
+}
+
+#[test]
+fn test_keys_typed_are_recorded_to_replay() {
+    use catatau::DisplayOptions;
+    use catatau::keymap::parse_keys;
+    use ratatui::backend::TestBackend;
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let backend = TestBackend::new(60, 16);
+    let mut app = App::with_backend(epub, DisplayOptions::default(), backend).unwrap();
+    for key in parse_keys("<A-q>2jl<A-q>").unwrap() {
+        app.handle_key(key.into()).unwrap();
+    }
+    assert_eq!(app.current_chapter(), 1);
+    let buffer = app.render_to_buffer().unwrap();
+    let screen: String = buffer.content.iter().map(|cell| cell.symbol()).collect();
+    assert!(screen.contains("Recorded: 2jl "), "{screen}");
+}
+
+#[test]
+fn test_remote_calls_move_the_reader() {
+    use catatau::DisplayOptions;
+    use catatau::error::RemoteError;
+    use ratatui::backend::TestBackend;
+    use serde_json::{Value, json};
+
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("remote.epub");
+    let paragraphs = |word: &str| format!("<p>{word}</p>").repeat(40);
+    write_epub(&epub_path, &[&paragraphs("one"), &paragraphs("two"), &paragraphs("magic")]);
+    let epub = EpubReader::new(&epub_path).unwrap();
+    let backend = TestBackend::new(60, 16);
+    let mut app = App::with_backend(epub, DisplayOptions::default(), backend).unwrap();
+
+    let position = app.remote_call("get-position", &Value::Null).unwrap();
+    assert_eq!(position["title"], "Draft");
+    assert_eq!((position["chapter"].clone(), position["chapters"].clone()), (json!(1), json!(3)));
+
+    let position = app.remote_call("goto-chapter", &json!({"chapter": 2})).unwrap();
+    assert_eq!(position["chapter"], 2);
+    assert_eq!(app.current_chapter(), 1);
+
+    let position = app.remote_call("search", &json!({"query": "magic"})).unwrap();
+    assert_eq!(position["chapter"], 3);
+
+    app.remote_call("keys", &json!({"keys": "h3j"})).unwrap();
+    assert_eq!((app.current_chapter(), app.scroll_offset()), (1, 3));
+
+    let position = app.remote_call("goto-percent", &json!({"percent": 0})).unwrap();
+    assert_eq!((position["chapter"].clone(), position["percent"].clone()), (json!(1), json!(0)));
+
+    assert!(matches!(
+        app.remote_call("goto-chapter", &json!({"chapter": 9})),
+        Err(RemoteError::Failed(_))
+    ));
+    assert!(matches!(
+        app.remote_call("goto-percent", &json!({"percent": "half"})),
+        Err(RemoteError::InvalidParams(_))
+    ));
+    assert!(matches!(app.remote_call("fly", &Value::Null), Err(RemoteError::UnknownMethod(_))));
+}
+
+#[test]
+fn test_app_can_start_at_a_chapter_percentage_or_phrase() {
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+
+    app.go_to_chapter(2).unwrap();
+    assert_eq!(app.current_chapter(), 1);
+    assert!(app.go_to_chapter(3).unwrap_err().contains("the book has 2"));
+    assert!(app.go_to_chapter(0).is_err());
+
+    app.go_to_book_percent(0);
+    assert_eq!(app.current_chapter(), 0);
+    app.go_to_book_percent(100);
+    assert_eq!(app.current_chapter(), 1);
+
+    app.find_first("mysterious sound").unwrap();
+    assert_eq!(app.current_chapter(), 0);
+    app.find_first("ancient guardian").unwrap();
+    assert_eq!(app.current_chapter(), 1);
+    assert!(app.find_first("dragon").unwrap_err().contains("No match for \"dragon\""));
+}
+
+#[test]
+fn test_search_with_required_and_excluded_terms() {
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+
+    let chapters = |query: &str| -> Vec<usize> {
+        epub.search(query).iter().map(|result| result.chapter_index).collect()
+    };
+    assert_eq!(chapters("magic crystal"), [0, 1]);
+    assert_eq!(chapters("magic crystal +guardian"), [1]);
+    assert_eq!(chapters("magic crystal -guardian"), [0]);
+    assert_eq!(chapters(r#""magic crystal" +"ancient guardian" -forest"#), [1]);
+}
+
+#[test]
+fn test_search_results_for_the_command_line() {
+    use catatau::search::{results_json, results_text};
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+    let results = epub.search("ancient guardian");
+    assert_eq!(results.len(), 1);
+
+    let text = results_text(&results);
+    let line = results[0].line_number + 1;
+    let matching = format!("2:{line}:");
+    assert!(text.lines().any(|l| l.starts_with(&matching) && l.contains("ancient guardian")));
+    assert!(text.lines().any(|l| l.starts_with(&format!("2:{}-", line - 1))));
+
+    let json = results_json(&epub, "ancient guardian", &results);
+    assert_eq!(json["query"], "ancient guardian");
+    assert_eq!(json["matches"][0]["chapter"], 2);
+    assert_eq!(json["matches"][0]["line"], line);
+    assert!(json["matches"][0]["text"].as_str().unwrap().contains("ancient guardian"));
+
+    let both = epub.search("magic crystal");
+    assert_eq!(results_text(&both).lines().filter(|l| *l == "--").count(), 1);
+}
+
+#[test]
+fn test_highlights_are_exported_as_markdown() {
+    use catatau::{annotations, progress::Highlight};
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+    let highlight = |chapter, start, excerpt: &str, note: Option<&str>| Highlight {
+        chapter,
+        start,
+        end: start + 20,
+        excerpt: excerpt.to_string(),
+        note: note.map(str::to_string),
+    };
+    let highlights = [
+        highlight(0, 0, "The Beginning", None),
+        highlight(0, 40, "a unique phrase", Some("Remember this")),
+        highlight(1, 0, "the ancient guardian", None),
+    ];
+
+    let markdown = annotations::markdown(&epub, &highlights);
+    assert!(markdown.starts_with("# Search Test Book\n\nTest Author\n"));
+    assert_eq!(markdown.matches("\n## ").count(), 2);
+    assert!(markdown.contains("> a unique phrase\n\nRemember this\n"));
+    assert!(markdown.contains("*Chapter 1, 0% of the way through*"));
+    assert!(markdown.contains("*Chapter 2, 0% of the way through*"));
+    let chapters: Vec<&str> = markdown.split("\n## ").collect();
+    assert!(chapters[2].contains("> the ancient guardian"));
+}
+
+#[test]
+fn test_highlights_are_exported_for_readwise() {
+    use catatau::annotations::{self, Format};
+    use catatau::progress::Highlight;
+    use std::path::Path;
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+    let highlights = [
+        Highlight {
+            chapter: 0,
+            start: 0,
+            end: 10,
+            excerpt: "The Beginning".to_string(),
+            note: Some("Say \"hello\", then go".to_string()),
+        },
+        Highlight {
+            chapter: 1,
+            start: 0,
+            end: 10,
+            excerpt: "the ancient guardian".to_string(),
+            note: None,
+        },
+    ];
+
+    let csv = annotations::readwise_csv(&epub, &highlights);
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(rows[0], "Highlight,Title,Author,URL,Note,Location,Date");
+    assert_eq!(
+        rows[1],
+        "The Beginning,Search Test Book,Test Author,,\"Say \"\"hello\"\", then go\",1,"
+    );
+    assert_eq!(rows[2], "the ancient guardian,Search Test Book,Test Author,,,2,");
+
+    let json = annotations::readwise_json(&epub, &highlights);
+    assert_eq!(json["highlights"][1]["text"], "the ancient guardian");
+    assert_eq!(json["highlights"][1]["location"], 2);
+    assert_eq!(json["highlights"][0]["note"], "Say \"hello\", then go");
+    assert!(json["highlights"][1].get("note").is_none());
+
+    assert_eq!(Format::for_path(Path::new("notes.CSV")), Format::Csv);
+    assert_eq!(Format::for_path(Path::new("notes.json")), Format::Json);
+    assert_eq!(Format::for_path(Path::new("notes.txt")), Format::Markdown);
+}
+
+#[test]
+fn test_tagged_highlights_become_anki_cards() {
+    use catatau::annotations;
+    use catatau::progress::Highlight;
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+    let chapter = epub.get_chapter(0).unwrap().content.clone();
+    let offset = |text: &str| {
+        let at = chapter.find(text).unwrap();
+        chapter[..at].chars().filter(|c| !c.is_whitespace()).count()
+    };
+    let highlight = |text: &str, note: Option<&str>| {
+        let start = offset(text);
+        Highlight {
+            chapter: 0,
+            start,
+            end: start + text.chars().filter(|c| !c.is_whitespace()).count(),
+            excerpt: text.to_string(),
+            note: note.map(str::to_string),
+        }
+    };
+    let highlights = [
+        highlight("extraordinary", Some("#vocab extraordinary: very unusual")),
+        highlight("Lorem ipsum", Some("Placeholder text")),
+        highlight("a mysterious sound", Some("What does the adventure begin with? #qa")),
+        highlight("consectetur", None),
+    ];
+
+    let cards = annotations::anki_cards(&epub, &highlights);
+    let rows: Vec<&str> = cards.lines().collect();
+    assert_eq!(&rows[..3], ["#separator:tab", "#html:true", "#tags column:3"]);
+    assert_eq!(rows.len(), 5);
+
+    let vocab: Vec<&str> = rows[3].split('\t').collect();
+    assert_eq!(vocab[0], "extraordinary");
+    assert!(vocab[1].starts_with("very unusual<br><br>The protagonist discovers something "));
+    assert!(vocab[1].contains("<b>extraordinary</b> in the forest."));
+    assert!(vocab[1].contains("<i>Search Test Book, "));
+    assert_eq!(vocab[2], "catatau vocab");
+
+    let qa: Vec<&str> = rows[4].split('\t').collect();
+    assert_eq!(qa[0], "What does the adventure begin with?");
+    assert!(qa[1].starts_with("The adventure begins with a mysterious sound in the distance."));
+    assert_eq!(qa[2], "catatau qa");
+}
+
+#[test]
+fn test_app_starts_on_first_readable_chapter() {
+    let (_temp_dir, epub_path) = EpubBuilder::new("Cover Book")
+        .chapter("cover.xhtml", "<html><body><p>Cover</p></body></html>")
+        .chapter("one.xhtml", "<html><body><h1>One</h1><p>The story proper begins here, after the cover page has been skipped.</p></body></html>")
+        .build("cover.epub");
+
+    let epub = EpubReader::new(&epub_path).expect("Failed to parse test EPUB");
+    let app = App::new(epub);
+    assert_eq!(app.current_chapter(), 1);
+}
+
+#[test]
+fn test_rtl_lines_are_reordered_for_display() {
+    use catatau::bidi;
+    use ratatui::{layout::Alignment, text::Line};
+
+    assert!(bidi::is_rtl_language("he"));
+    assert!(bidi::is_rtl_language("ar-EG"));
+    assert!(bidi::is_rtl_language("az-Arab"));
+    assert!(!bidi::is_rtl_language("en-GB"));
+
+    assert_eq!(bidi::paragraph_is_rtl(["123", "שלום עולם"]), Some(true));
+    assert_eq!(bidi::paragraph_is_rtl(["Hello שלום"]), Some(false));
+    assert_eq!(bidi::paragraph_is_rtl(["42"]), None);
+
+    let line = bidi::reorder_line(Line::from("שלום (עולם)"), true);
+    let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+    assert_eq!(text, "(םלוע) םולש");
+    assert_eq!(line.alignment, Some(Alignment::Right));
+
+    // Hebrew inside an English sentence is reversed in place
+    let line = bidi::reorder_line(Line::from("The word שלום means peace"), false);
+    let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+    assert_eq!(text, "The word םולש means peace");
+    assert_eq!(line.alignment, Some(Alignment::Left));
+}
+
+#[test]
+fn test_themes_are_selectable_by_name() {
+    use catatau::Theme;
+
+    for name in Theme::NAMES {
+        let theme: Theme = name.parse().expect("built-in theme should parse");
+        assert_eq!(theme.name, name);
+    }
+    assert_eq!(Theme::default().name, "dark");
+    assert_eq!("Sepia".parse::<Theme>().unwrap().name, "sepia");
+
+    let error = "solarized".parse::<Theme>().unwrap_err();
+    assert!(error.contains("gruvbox"));
+}
+
+#[test]
+fn test_user_themes_load_from_toml() {
+    use catatau::Theme;
+    use ratatui::style::{Color, Modifier};
+
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("paper.toml"),
+        r##"
+base = "sepia"
+syntax = "InspiredGitHub"
+background = "#f6efe0"
+text = "black"
+heading1 = { fg = "red", modifiers = ["bold", "underlined"] }
+"##,
+    )
+    .unwrap();
+
+    let theme = Theme::load_from(Some(temp_dir.path()), "paper").unwrap();
+    assert_eq!(theme.name, "paper");
+    assert_eq!(theme.background, Color::Rgb(0xf6, 0xef, 0xe0));
+    assert_eq!(theme.text.fg, Some(Color::Black));
+    assert_eq!(theme.heading(1).fg, Some(Color::Red));
+    assert!(theme.heading(1).add_modifier.contains(Modifier::BOLD | Modifier::UNDERLINED));
+    assert_eq!(theme.syntax_theme, "InspiredGitHub");
+    // Elements not in the file keep the base theme's styles
+    assert_eq!(theme.selection, Theme::sepia().selection);
+
+    // Built-in names still work when there's no file for them
+    assert_eq!(Theme::load_from(Some(temp_dir.path()), "gruvbox").unwrap().name, "gruvbox");
+
+    let error = Theme::from_toml("broken", "txt = \"red\"").unwrap_err();
+    assert!(error.contains("txt"));
+    let error = Theme::from_toml("broken", "text = { fg = \"red\", modifiers = [\"loud\"] }")
+        .unwrap_err();
+    assert!(error.contains("loud"));
+}
+
+#[test]
+fn test_terminal_background_replies_are_parsed() {
+    use catatau::{Theme, background};
+
+    assert_eq!(
+        background::parse_response("\x1b]11;rgb:ffff/ffff/dddd\x07\x1b[?62;22c"),
+        Some((255, 255, 221))
+    );
+    assert_eq!(background::parse_response("\x1b]11;rgb:1e/1e/2e\x1b\\"), Some((30, 30, 46)));
+    assert_eq!(background::parse_response("\x1b]11;rgb:f/0/0\x07"), Some((255, 0, 0)));
+    // A terminal that only answered the device attributes query
+    assert_eq!(background::parse_response("\x1b[?1;2c"), None);
+    assert_eq!(background::parse_response("\x1b]11;rgb:ff/ff\x07"), None);
+
+    assert!(background::is_light((255, 255, 221)));
+    assert!(!background::is_light((30, 30, 46)));
+    assert!(Theme::light().is_light() && Theme::sepia().is_light());
+    assert!(!Theme::dark().is_light() && !Theme::gruvbox().is_light());
+}
+
+#[test]
+fn test_copied_text_is_sent_with_osc_52() {
+    use catatau::clipboard::osc52;
+
+    assert_eq!(osc52("hello"), "\x1b]52;c;aGVsbG8=\x07");
+    assert_eq!(osc52("café"), "\x1b]52;c;Y2Fmw6k=\x07");
+}
+
+#[test]
+fn test_monochrome_theme_has_no_colours() {
+    use catatau::Theme;
+    use ratatui::style::{Color, Modifier};
+
+    let theme = Theme::load("monochrome").unwrap();
+    let styles = [
+        theme.text, theme.muted, theme.accent, theme.secondary, theme.border, theme.code,
+        theme.highlight, theme.selection, theme.annotation, theme.warning, theme.danger,
+        theme.confirm, theme.special, theme.progress,
+    ];
+    for style in styles.iter().chain(&theme.headings) {
+        assert!(style.fg.is_none() && style.bg.is_none());
+        let allowed = Modifier::BOLD | Modifier::UNDERLINED | Modifier::REVERSED;
+        assert!(allowed.contains(style.add_modifier));
+    }
+    assert_eq!(theme.background, Color::Reset);
+    assert!(theme.highlight.add_modifier.contains(Modifier::REVERSED));
+    assert!(theme.syntax_theme.is_empty());
+}
+
+#[test]
+fn test_text_typed_into_the_search_is_kept_out_of_crash_reports() {
+    use catatau::crash;
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.handle_key(KeyEvent::from(KeyCode::Char('/'))).unwrap();
+    for c in "жёлудь".chars() {
+        app.handle_key(KeyEvent::from(KeyCode::Char(c))).unwrap();
+    }
+
+    // Other tests press keys too, so only what mustn't be there is certain
+    let keys = crash::snapshot().unwrap().keys;
+    assert!(!keys.iter().any(|key| "жёлудь".contains(key.as_str())));
+}
+
+// Type keys written as in a keymap, such as "3j" or "<C-o>"
+fn press(app: &mut App, keys: &str) {
+    for key in catatau::keymap::parse_keys(keys).unwrap() {
+        app.handle_key(key.into()).unwrap();
+    }
+}
+
+// A book of three long chapters, each a few screens of numbered lines
+fn create_long_epub() -> (TempDir, std::path::PathBuf) {
+    let chapter = |name: &str| {
+        (1..=60).map(|n| format!("<p>{name} line {n}</p>")).collect::<String>()
+    };
+    EpubBuilder::new("Long Book")
+        .body(&chapter("first"))
+        .body(&chapter("second"))
+        .body(&chapter("third"))
+        .build("long.epub")
+}
+
+fn open_long_epub() -> (TempDir, App) {
+    use catatau::Message;
+
+    let (temp_dir, epub_path) = create_long_epub();
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.update(Message::Resize(80, 20)).unwrap();
+    (temp_dir, app)
+}
+
+fn position(app: &App) -> (usize, usize) {
+    (app.current_chapter(), app.scroll_offset())
+}
+
+#[test]
+fn test_chapters_are_returned_to_where_they_were_left() {
+    let (_temp_dir, mut app) = open_long_epub();
+    press(&mut app, "5j");
+    press(&mut app, "l");
+    assert_eq!(position(&app), (1, 0));
+    press(&mut app, "3j");
+    press(&mut app, "h");
+    assert_eq!(position(&app), (0, 5));
+    press(&mut app, "l");
+    assert_eq!(position(&app), (1, 3));
+
+    // Going on past the last chapter leaves the place in it alone
+    press(&mut app, "l2j");
+    press(&mut app, "ll");
+    assert_eq!(position(&app), (2, 2));
+    press(&mut app, "hh");
+    assert_eq!(position(&app), (0, 5));
+    press(&mut app, "h");
+    assert_eq!(position(&app), (0, 5));
+}
+
+#[test]
+fn test_motions_in_a_book_with_nothing_to_read() {
+    let (_temp_dir, epub_path) = EpubBuilder::new("Blank").body("").build("blank.epub");
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.update(catatau::Message::Resize(80, 10)).unwrap();
+    let motions = [
+        "j", "10j", "G", "50G", "gg", "l", "3l", "h", "g$", "g0", "n", "N", "<C-o>", "<C-i>",
+    ];
+    for keys in motions {
+        press(&mut app, keys);
+        assert_eq!(position(&app), (0, 0), "after {keys}");
+    }
+}
+
+#[test]
+fn test_jumps_are_retraced_with_ctrl_o_and_ctrl_i() {
+    let (_temp_dir, mut app) = open_long_epub();
+    press(&mut app, "5j");
+    press(&mut app, "g$");
+    assert_eq!(position(&app), (2, 0));
+
+    press(&mut app, "<C-o>");
+    assert_eq!(position(&app), (0, 5));
+    press(&mut app, "<C-o>");
+    assert_eq!(position(&app), (0, 5), "there's nowhere further back to go");
+    press(&mut app, "<C-i>");
+    assert_eq!(position(&app), (2, 0));
+    press(&mut app, "<C-i>");
+    assert_eq!(position(&app), (2, 0), "there's nowhere further forward to go");
+
+    // A new jump forgets the way forward
+    press(&mut app, "<C-o>");
+    press(&mut app, "lg$");
+    press(&mut app, "<C-i>");
+    assert_eq!(position(&app), (2, 0));
+    press(&mut app, "<C-o>");
+    assert_eq!(position(&app), (1, 0));
+}
+
+#[test]
+fn test_counts_repeat_motions() {
+    let (_temp_dir, mut app) = open_long_epub();
+    press(&mut app, "10j");
+    assert_eq!(position(&app), (0, 10));
+    press(&mut app, "4k");
+    assert_eq!(position(&app), (0, 6));
+    press(&mut app, "2l");
+    assert_eq!(position(&app), (2, 0));
+    press(&mut app, "5l");
+    assert_eq!(app.current_chapter(), 2, "counts stop at the last chapter");
+    press(&mut app, "9h");
+    assert_eq!(position(&app), (0, 6));
+
+    press(&mut app, "50G");
+    let lines = app.epub().get_chapter(0).unwrap().content.lines().count();
+    let half = app.scroll_offset();
+    assert!(half > 0 && half < lines, "{half} of {lines}");
+    press(&mut app, "50G");
+    assert_eq!(app.scroll_offset(), half);
+    press(&mut app, "1000j");
+    assert!(app.scroll_offset() < lines);
+}
+
+#[test]
+fn test_g_sequences_reach_the_ends_of_the_chapter_and_book() {
+    let (_temp_dir, mut app) = open_long_epub();
+    press(&mut app, "l");
+    press(&mut app, "G");
+    let bottom = app.scroll_offset();
+    assert!(bottom > 0);
+    press(&mut app, "G");
+    assert_eq!(position(&app), (1, bottom));
+    press(&mut app, "gg");
+    assert_eq!(position(&app), (1, 0));
+
+    press(&mut app, "g$");
+    assert_eq!(position(&app), (2, 0));
+    press(&mut app, "g$");
+    assert_eq!(position(&app), (2, 0));
+    press(&mut app, "g0");
+    assert_eq!(position(&app), (0, 0));
+
+    // A sequence that isn't bound is dropped without taking the next key
+    press(&mut app, "gx");
+    press(&mut app, "j");
+    assert_eq!(position(&app), (0, 1));
+}
+
+#[test]
+fn test_ctrl_z_asks_the_run_loop_to_suspend() {
+    let (_temp_dir, mut app) = open_long_epub();
+    press(&mut app, "3j");
+    assert!(!app.suspend_requested());
+    press(&mut app, "<C-z>");
+    assert!(app.suspend_requested());
+    press(&mut app, "<C-z>");
+    assert!(app.suspend_requested());
+    assert_eq!(position(&app), (0, 3));
+}
+
+#[test]
+fn test_n_and_shift_n_step_through_matches_across_chapters() {
+    let (_temp_dir, epub_path) = create_test_epub_with_content();
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    press(&mut app, "n");
+    assert_eq!(app.status_message(), Some("No previous search"));
+
+    app.find_first("magic crystal").unwrap();
+    assert_eq!(app.current_chapter(), 0);
+    press(&mut app, "n");
+    assert_eq!(app.current_chapter(), 1);
+    press(&mut app, "n");
+    assert_eq!(app.current_chapter(), 0);
+    assert_eq!(app.status_message(), Some("Search wrapped to the start"));
+    press(&mut app, "N");
+    assert_eq!(app.current_chapter(), 1);
+    assert_eq!(app.status_message(), Some("Search wrapped to the end"));
+    press(&mut app, "2N");
+    assert_eq!(app.current_chapter(), 1);
+}
+
+#[test]
+fn test_r_reloads_the_book_from_disk() {
+    let temp_dir = TempDir::new().unwrap();
+    let epub_path = temp_dir.path().join("draft.epub");
+    let paragraphs = |word: &str| format!("<p>{word}</p>").repeat(40);
+    write_epub(&epub_path, &[&paragraphs("one"), &paragraphs("two")]);
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.update(catatau::Message::Resize(80, 10)).unwrap();
+    press(&mut app, "l5j");
+
+    write_epub(&epub_path, &[&paragraphs("one"), &paragraphs("deux")]);
+    press(&mut app, "R");
+    assert_eq!(app.status_message(), Some("Reloaded the book"));
+    assert_eq!(position(&app), (1, 5));
+    assert!(app.epub().get_chapter(1).unwrap().content.contains("deux"));
+    press(&mut app, "R");
+    assert_eq!(position(&app), (1, 5));
+
+    // A broken file is reported and the book already open kept
+    std::fs::write(&epub_path, "not a book").unwrap();
+    press(&mut app, "R");
+    assert!(app.status_message().is_some_and(|message| message != "Reloaded the book"));
+    assert_eq!(position(&app), (1, 5));
+    assert!(app.epub().get_chapter(1).unwrap().content.contains("deux"));
+}
+
+// A book of two chapters of long paragraphs, which wrap to more lines
+// the narrower the screen, open 100 columns wide
+fn open_wide_epub() -> (TempDir, App) {
+    let chapter = |name: &str| {
+        (1..=40).map(|n| format!("<p>{}</p>", format!("{name}{n} ").repeat(30))).collect::<String>()
+    };
+    let (temp_dir, epub_path) = EpubBuilder::new("Wide Book")
+        .body(&chapter("a"))
+        .body(&chapter("b"))
+        .build("wide.epub");
+    let mut app = App::new(EpubReader::new(&epub_path).unwrap());
+    app.update(catatau::Message::Resize(100, 20)).unwrap();
+    (temp_dir, app)
+}
+
+fn chapter_lines(app: &App) -> Vec<String> {
+    let chapter = app.epub().get_chapter(app.current_chapter()).unwrap();
+    chapter.content.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn test_chapters_are_returned_to_the_same_place_after_a_resize() {
+    use catatau::Message;
+
+    let (_temp_dir, mut app) = open_wide_epub();
+    let paragraph = chapter_lines(&app).iter().position(|line| line.starts_with("a11 ")).unwrap();
+    press(&mut app, &format!("{paragraph}j"));
+    press(&mut app, "l");
+
+    app.update(Message::Resize(50, 20)).unwrap();
+    press(&mut app, "h");
+    let top = &chapter_lines(&app)[app.scroll_offset()..];
+    assert!(top.iter().take(5).any(|line| line.contains("a11 ")), "{:?}", &top[..5]);
+}
+
+#[test]
+fn test_jumps_return_to_the_same_place_after_a_resize() {
+    use catatau::Message;
+
+    let (_temp_dir, mut app) = open_wide_epub();
+    let paragraph = chapter_lines(&app).iter().position(|line| line.starts_with("a11 ")).unwrap();
+    press(&mut app, &format!("{paragraph}j"));
+    press(&mut app, "g$");
+
+    app.update(Message::Resize(50, 20)).unwrap();
+    press(&mut app, "<C-o>");
+    let top = &chapter_lines(&app)[app.scroll_offset()..];
+    assert!(top.iter().take(5).any(|line| line.contains("a11 ")), "{:?}", &top[..5]);
+    press(&mut app, "<C-i>");
+    app.update(Message::Resize(100, 20)).unwrap();
+    press(&mut app, "<C-o>");
+    let top = &chapter_lines(&app)[app.scroll_offset()..];
+    assert!(top.iter().take(3).any(|line| line.contains("a11 ")), "{:?}", &top[..3]);
+}
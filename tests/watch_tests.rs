@@ -0,0 +1,38 @@
+use catatau::watch::BookWatcher;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+// Whether the watcher reports a change within a few seconds
+fn notices_change(watcher: &mut BookWatcher) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if watcher.changed() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+#[test]
+fn test_changes_to_the_book_are_noticed_once_settled() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("draft.epub");
+    std::fs::write(&path, "first draft").unwrap();
+    let mut watcher = BookWatcher::new(&path).unwrap();
+    assert!(!watcher.changed());
+
+    std::fs::write(temp_dir.path().join("notes.txt"), "not the book").unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(!watcher.changed());
+
+    std::fs::write(&path, "second draft").unwrap();
+    assert!(notices_change(&mut watcher));
+    assert!(!watcher.changed());
+
+    // Replaced rather than written into, as pandoc does
+    let new = temp_dir.path().join("draft.epub.tmp");
+    std::fs::write(&new, "third draft").unwrap();
+    std::fs::rename(&new, &path).unwrap();
+    assert!(notices_change(&mut watcher));
+}